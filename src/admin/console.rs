@@ -0,0 +1,227 @@
+use super::*;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// a longer line is not a command, and is rejected without buffering all of it
+const MAX_LINE_LEN: u64 = 1024;
+
+/// A line based admin console in the style of the memcache text protocol. Each
+/// line is a command:
+///
+/// ```text
+/// stats          the counters and gauges
+/// hist [prefix]  the latency percentiles, optionally of the histograms with
+///                names starting with the prefix
+/// quit           closes the connection
+/// ```
+///
+/// The reply to `stats` and `hist` is one `STAT <name> <value>` line for each
+/// metric, followed by `END`. Any other command is answered with `ERROR`. The
+/// percentiles are read from the latest metrics snapshot.
+pub async fn console(addr: String) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to listen for the admin console on {addr}: {e}");
+            return;
+        }
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(session(stream));
+            }
+            Err(e) => {
+                debug!("failed to accept an admin console connection: {e}");
+            }
+        }
+    }
+}
+
+async fn session(stream: TcpStream) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        let len = (&mut reader)
+            .take(MAX_LINE_LEN)
+            .read_line(&mut line)
+            .await?;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        if len as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+            write.write_all(b"CLIENT_ERROR line too long\r\n").await?;
+            return Ok(());
+        }
+
+        let mut words = line.split_whitespace();
+
+        let reply = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => continue,
+            (Some("stats"), None, _) => stats(),
+            (Some("hist"), prefix, None) => hist(prefix.unwrap_or_default()).await,
+            (Some("quit"), None, _) => return Ok(()),
+            _ => "ERROR\r\n".to_string(),
+        };
+
+        write.write_all(reply.as_bytes()).await?;
+    }
+}
+
+// the current value of each counter and gauge
+fn stats() -> String {
+    let mut lines = Vec::new();
+
+    for metric in &metriken::metrics() {
+        if metric.name().starts_with("log_") {
+            continue;
+        }
+
+        match metric.value() {
+            Some(Value::Counter(value)) => {
+                lines.push(format!("STAT {} {value}\r\n", metric.name()));
+            }
+            Some(Value::Gauge(value)) => {
+                lines.push(format!("STAT {} {value}\r\n", metric.name()));
+            }
+            _ => continue,
+        }
+    }
+
+    reply(lines)
+}
+
+// the percentiles of each histogram with a name starting with the prefix
+async fn hist(prefix: &str) -> String {
+    let mut lines = Vec::new();
+
+    let metrics_snapshot = METRICS_SNAPSHOT.read().await;
+
+    for metric in &metriken::metrics() {
+        let name = metric.name();
+
+        if !name.starts_with(prefix) || !matches!(metric.value(), Some(Value::Other(_))) {
+            continue;
+        }
+
+        for (label, _percentile, value) in metrics_snapshot.percentiles(name) {
+            lines.push(format!("STAT {name}/{label} {value}\r\n"));
+        }
+    }
+
+    reply(lines)
+}
+
+fn reply(mut lines: Vec<String>) -> String {
+    lines.sort();
+    lines.push("END\r\n".to_string());
+    lines.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // connects to a console session, returning the client side
+    async fn connect() -> BufReader<TcpStream> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap());
+        let (server, client) = tokio::join!(listener.accept(), client);
+
+        tokio::spawn(session(server.unwrap().0));
+
+        BufReader::new(client.unwrap())
+    }
+
+    // sends the command and reads the reply up to and including `END`
+    async fn command(client: &mut BufReader<TcpStream>, command: &str) -> Vec<String> {
+        client
+            .get_mut()
+            .write_all(command.as_bytes())
+            .await
+            .unwrap();
+
+        let mut lines = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            client.read_line(&mut line).await.unwrap();
+
+            let done = line == "END\r\n" || line == "ERROR\r\n" || line.is_empty();
+            lines.push(line);
+
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn stats() {
+        let mut client = connect().await;
+
+        REQUEST.increment();
+
+        let lines = command(&mut client, "stats\r\n").await;
+
+        assert_eq!(lines.last().unwrap(), "END\r\n");
+        assert!(lines[..lines.len() - 1]
+            .iter()
+            .all(|line| line.starts_with("STAT ") && line.ends_with("\r\n")));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with(&format!("STAT {REQUEST_COUNTER} "))));
+
+        // the lines are sorted by name
+        let mut sorted = lines.clone();
+        sorted.sort();
+        assert_eq!(lines[..lines.len() - 1], sorted[..lines.len() - 1]);
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let mut client = connect().await;
+
+        assert_eq!(command(&mut client, "bogus\r\n").await, vec!["ERROR\r\n"]);
+        assert_eq!(
+            command(&mut client, "stats all\r\n").await,
+            vec!["ERROR\r\n"]
+        );
+
+        // the session continues after an empty line and an error
+        client.get_mut().write_all(b"\r\n").await.unwrap();
+        assert_eq!(
+            command(&mut client, "stats\r\n").await.last().unwrap(),
+            "END\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn quit() {
+        let mut client = connect().await;
+
+        assert_eq!(command(&mut client, "quit\r\n").await, vec![""]);
+    }
+
+    #[tokio::test]
+    async fn line_too_long() {
+        let mut client = connect().await;
+
+        // exactly the limit without a newline, so that all of it is read before
+        // the session is closed
+        let line = "x".repeat(MAX_LINE_LEN as usize);
+        let mut reply = String::new();
+
+        client.get_mut().write_all(line.as_bytes()).await.unwrap();
+        client.read_line(&mut reply).await.unwrap();
+
+        assert_eq!(reply, "CLIENT_ERROR line too long\r\n");
+    }
+}
@@ -4,6 +4,10 @@ use ratelimit::Ratelimiter;
 use std::net::ToSocketAddrs;
 use std::sync::Arc;
 
+mod console;
+
+pub use console::console;
+
 /// The HTTP admin server.
 pub async fn http(config: Config, ratelimit: Option<Arc<Ratelimiter>>) {
     let admin = filters::admin(ratelimit);
@@ -113,9 +117,11 @@ pub mod handlers {
     use warp::http::StatusCode;
 
     /// Serves Prometheus / OpenMetrics text format metrics. All metrics have
-    /// type information, some have descriptions as well. Percentiles read from
-    /// heatmaps are exposed with a `percentile` label where the value
-    /// corresponds to the percentile in the range of 0.0 - 100.0.
+    /// type information, some have descriptions as well. Histograms are exposed
+    /// as summaries with a `quantile` label where the value corresponds to the
+    /// quantile in the range of 0.0 - 1.0. The quantiles are read from the
+    /// latest metrics snapshot, so serving a request does not read the
+    /// histograms directly.
     ///
     /// See: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
     ///
@@ -126,11 +132,15 @@ pub mod handlers {
     /// # TYPE some_gauge gauge
     /// # HELP some_gauge A signed 64bit gauge.
     /// some_gauge 0
-    /// # TYPE some_distribution{percentile="50.0"} gauge
-    /// some_distribution{percentile="50.0"} 0
+    /// # TYPE some_distribution summary
+    /// some_distribution{quantile="0.5"} 0
+    /// some_distribution{quantile="0.99"} 0
+    /// # TYPE command_latency summary
+    /// command_latency{command="get",quantile="0.5"} 0
     /// ```
     pub async fn prometheus_stats() -> Result<impl warp::Reply, Infallible> {
         let mut data = Vec::new();
+        let mut command_latencies = Vec::new();
 
         let metrics_snapshot = METRICS_SNAPSHOT.read().await;
 
@@ -166,27 +176,53 @@ pub mod handlers {
                         data.push(format!("# TYPE {name} gauge\n{name} {value}"));
                     }
                 }
+                // the per-command latencies are a single summary which uses a
+                // label for the command
+                Some(Value::Other(_)) if name.starts_with(COMMAND_LATENCY_PREFIX) => {
+                    let command = &name[COMMAND_LATENCY_PREFIX.len()..];
+
+                    for (_label, percentile, value) in metrics_snapshot.percentiles(name) {
+                        command_latencies.push(format!(
+                            "command_latency{{command=\"{command}\",quantile=\"{}\"}} {value} {timestamp}",
+                            percentile / 100.0,
+                        ));
+                    }
+                }
                 Some(Value::Other(_)) => {
                     let percentiles = metrics_snapshot.percentiles(metric.name());
 
+                    if percentiles.is_empty() {
+                        continue;
+                    }
+
+                    // the type and help are only given once for all of the
+                    // quantiles of a summary
+                    let mut summary = if let Some(description) = metric.description() {
+                        format!("# TYPE {name} summary\n# HELP {name} {description}")
+                    } else {
+                        format!("# TYPE {name} summary")
+                    };
+
                     for (_label, percentile, value) in percentiles {
-                        if let Some(description) = metric.description() {
-                            data.push(format!(
-                                "# TYPE {name} gauge\n# HELP {name} {description}\n{name}{{percentile=\"{:02}\"}} {value} {timestamp}",
-                                percentile,
-                            ));
-                        } else {
-                            data.push(format!(
-                                "# TYPE {name} gauge\n{name}{{percentile=\"{:02}\"}} {value} {timestamp}",
-                                percentile,
-                            ));
-                        }
+                        summary.push_str(&format!(
+                            "\n{name}{{quantile=\"{}\"}} {value} {timestamp}",
+                            percentile / 100.0,
+                        ));
                     }
+
+                    data.push(summary);
                 }
                 _ => continue,
             }
         }
 
+        if !command_latencies.is_empty() {
+            data.push(format!(
+                "# TYPE command_latency summary\n# HELP command_latency distribution of response latencies in nanoseconds for each command.\n{}",
+                command_latencies.join("\n")
+            ));
+        }
+
         data.sort();
         let mut content = data.join("\n");
         content += "\n";
@@ -300,3 +336,60 @@ pub async fn human_formatted_stats() -> Vec<String> {
 
     data
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    // the value of each sample line in the exposition which has the name,
+    // including its labels
+    fn values(content: &str, name: &str) -> Vec<u64> {
+        content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.strip_prefix(name))
+            .filter_map(|rest| rest.strip_prefix(' '))
+            .map(|rest| rest.split(' ').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn scrape() {
+        // the quantiles are for the latencies since the last snapshot
+        METRICS_SNAPSHOT.write().await.update();
+        for _ in 0..10 {
+            RESPONSE_LATENCY.increment(1_000_000).unwrap();
+        }
+        RESPONSE_OK.increment();
+        METRICS_SNAPSHOT.write().await.update();
+        let responses = RESPONSE_OK.value();
+
+        let (addr, server) = warp::serve(filters::admin(None)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let (head, content) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+
+        // the counters use underscores in place of the slashes in the names
+        assert!(content.contains("# TYPE client_response_ok counter\n"));
+        assert!(values(content, "client_response_ok")[0] >= responses);
+        assert_eq!(values(content, "client_response_timeout").len(), 1);
+
+        // the histograms are summaries with a line for each quantile
+        assert!(content.contains("# TYPE response_latency summary\n"));
+        for (_, percentile) in PERCENTILES {
+            let name = format!("response_latency{{quantile=\"{}\"}}", percentile / 100.0);
+            assert_eq!(values(content, &name).len(), 1, "{name}");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::*;
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::{Seed512, Xoshiro512PlusPlus};
+use std::time::Instant;
+
+// the connections are given a moment to be established before the throughput
+// is measured
+const CALIBRATION_WARMUP: Duration = Duration::from_secs(1);
+const CALIBRATION_DURATION: Duration = Duration::from_secs(5);
+
+// the recommendation leaves some headroom, as a connection which is saturated
+// adds queueing delay to every request
+const CALIBRATION_HEADROOM: f64 = 1.25;
+
+/// Briefly sends the workload in a closed loop over a single connection to
+/// each endpoint to measure the throughput of one connection. Returns the
+/// number of connections per endpoint which is recommended to sustain the
+/// ratelimit, or `None` if there is no ratelimit or nothing was measured.
+pub fn calibrate(config: &Config) -> Option<usize> {
+    let calibration = config.calibration();
+    let connections = calibration.target().endpoints().len();
+
+    output!(
+        "Calibration: measuring the throughput of {connections} connection(s) for {}s",
+        CALIBRATION_DURATION.as_secs()
+    );
+
+    // the calibration is not part of the test, so the counters and gauges are
+    // restored once it completes
+    let values = ValuesSnapshot::new();
+
+    let (client_sender, client_receiver) = bounded(calibration.workload().queue_depth());
+
+    // publishing is not calibrated, so those requests fail immediately
+    let (pubsub_sender, _) = bounded(1);
+
+    let generator = Generator::new(&calibration);
+    let client_runtime = launch_clients(&calibration, client_receiver)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // like the workload, the generator is seeded from the initial seed
+    let mut seed = [0; 64];
+    Xoshiro512PlusPlus::from_seed(calibration.general().initial_seed()).fill_bytes(&mut seed);
+
+    let generator_thread = {
+        let stop = stop.clone();
+
+        std::thread::spawn(move || {
+            let mut rng = Xoshiro512PlusPlus::from_seed(Seed512(seed));
+
+            while !stop.load(Ordering::Relaxed)
+                && !SHUTDOWN.load(Ordering::Relaxed)
+                && generator.generate(&client_sender, &pubsub_sender, &mut rng)
+            {}
+        })
+    };
+
+    std::thread::sleep(CALIBRATION_WARMUP);
+
+    let baseline = RESPONSE_OK.value();
+    let start = Instant::now();
+
+    std::thread::sleep(CALIBRATION_DURATION.saturating_sub(CALIBRATION_WARMUP));
+
+    let responses = RESPONSE_OK.value() - baseline;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    // stopping the clients closes the queue, so the generator cannot remain
+    // blocked on a client which is not able to connect
+    stop.store(true, Ordering::Relaxed);
+    client_runtime.shutdown_timeout(Duration::from_millis(100));
+    let _ = generator_thread.join();
+
+    // this also clears the gauges for the connections, which were dropped
+    // without being closed by the clients
+    values.restore();
+
+    if responses == 0 {
+        output!("Calibration: no responses were received, unable to estimate the connections");
+        return None;
+    }
+
+    let per_connection = responses as f64 / elapsed / connections as f64;
+
+    let ratelimit = config.workload().ratelimit();
+    let rate = match ratelimit.start() {
+        Some(rate) => rate.get(),
+        None => {
+            output!(
+                "Calibration: Per-Connection QPS: {:.2} (no ratelimit is set to estimate the connections for)",
+                per_connection
+            );
+            return None;
+        }
+    };
+
+    let total = (rate as f64 * CALIBRATION_HEADROOM / per_connection).ceil() as usize;
+    let poolsize = std::cmp::max(1, (total + connections - 1) / connections);
+
+    output!(
+        "Calibration: Per-Connection QPS: {:.2} Ratelimit: {rate}/s Recommended Connections: {} ({poolsize} per endpoint)",
+        per_connection,
+        poolsize * connections
+    );
+
+    if ratelimit.is_dynamic() {
+        output!("Calibration: the ratelimit changes during the test, the estimate is for the initial rate");
+    }
+
+    Some(poolsize)
+}
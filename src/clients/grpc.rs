@@ -0,0 +1,428 @@
+use super::*;
+use crate::net::Connector;
+use bytes::{BufMut, Bytes, BytesMut};
+use http_body_util::{BodyExt, Full};
+use hyper::client::conn::http2::SendRequest;
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::rt::Executor;
+use hyper::{Method, Request, StatusCode, Uri};
+use std::future::Future;
+
+// gRPC status codes which have a matching response metric
+const GRPC_OK: &str = "0";
+const GRPC_DEADLINE_EXCEEDED: &str = "4";
+const GRPC_RESOURCE_EXHAUSTED: &str = "8";
+
+#[derive(Clone)]
+struct Queue<T> {
+    tx: async_channel::Sender<T>,
+    rx: async_channel::Receiver<T>,
+}
+
+impl<T> Queue<T> {
+    pub fn new(size: usize) -> Self {
+        let (tx, rx) = async_channel::bounded::<T>(size);
+
+        Self { tx, rx }
+    }
+
+    pub async fn send(&self, item: T) -> std::result::Result<(), async_channel::SendError<T>> {
+        self.tx.send(item).await
+    }
+
+    pub async fn recv(&self) -> std::result::Result<T, async_channel::RecvError> {
+        self.rx.recv().await
+    }
+}
+
+// launch a pool manager and worker tasks. gRPC uses HTTP/2.0 so we follow the
+// same approach as the http2 client. Each request generated by the workload is
+// sent as a unary call to the configured method.
+pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
+    debug!("launching grpc protocol tasks");
+
+    // config validation ensures that the grpc section is present
+    let grpc = config.grpc().unwrap();
+
+    let message = match grpc.payload() {
+        Some(path) => match std::fs::read(path) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("error reading grpc payload: {path}\n{e}");
+                std::process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    // unary requests are a single length-prefixed message which is not
+    // compressed
+    let mut body = BytesMut::with_capacity(5 + message.len());
+    body.put_u8(0);
+    body.put_u32(message.len() as u32);
+    body.put_slice(&message);
+    let body = body.freeze();
+
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            let queue = Queue::new(1);
+            runtime.spawn(pool_manager(
+                endpoint.clone(),
+                config.clone(),
+                queue.clone(),
+            ));
+
+            for _ in 0..config.client().unwrap().concurrency() {
+                runtime.spawn(task(
+                    work_receiver.clone(),
+                    endpoint.clone(),
+                    config.clone(),
+                    queue.clone(),
+                    body.clone(),
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TokioExecutor;
+
+impl<F> Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, future: F) {
+        tokio::spawn(future);
+    }
+}
+
+async fn pool_manager(endpoint: String, config: Config, queue: Queue<SendRequest<Full<Bytes>>>) {
+    let connector = Connector::new(&config).expect("failed to init connector");
+    let mut backoff = Backoff::new(config.client().unwrap());
+    let mut sender = None;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if sender.is_none() {
+            CONNECT.increment();
+            let stream = match timeout(
+                config.client().unwrap().connect_timeout(),
+                connector.connect(&endpoint),
+            )
+            .await
+            {
+                Ok(Ok(s)) => s,
+                Ok(Err(_)) => {
+                    CONNECT_EX.increment();
+                    record_error(ErrorCategory::Connection);
+                    if backoff.wait().await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(_) => {
+                    CONNECT_TIMEOUT.increment();
+                    record_error(ErrorCategory::Connection);
+                    if backoff.wait().await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            let (s, conn) =
+                match hyper::client::conn::http2::handshake(TokioExecutor {}, stream).await {
+                    Ok((s, c)) => (s, c),
+                    Err(_e) => {
+                        CONNECT_EX.increment();
+                        record_error(ErrorCategory::Connection);
+                        if backoff.wait().await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+
+            SESSION.increment();
+            backoff.reset();
+
+            sender = Some(s);
+
+            tokio::task::spawn(async move {
+                if let Err(e) = conn.await {
+                    debug!("connection failed: {e}");
+                    record_error(ErrorCategory::Connection);
+                }
+            });
+        }
+
+        let mut s = sender.take().unwrap();
+
+        if let Err(_e) = s.ready().await {
+            continue;
+        }
+
+        if queue.send(s.clone()).await.is_err() {
+            return;
+        }
+
+        sender = Some(s);
+    }
+}
+
+// a task for grpc
+async fn task(
+    work_receiver: Receiver<WorkItem>,
+    endpoint: String,
+    config: Config,
+    queue: Queue<SendRequest<Full<Bytes>>>,
+    body: Bytes,
+) -> Result<()> {
+    let method = config.grpc().unwrap().method();
+    let url: Uri = if config.tls().is_none() {
+        format!("http://{endpoint}{method}").parse().unwrap()
+    } else {
+        format!("https://{endpoint}{method}").parse().unwrap()
+    };
+
+    let mut sender = None;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if sender.is_none() {
+            let s = queue
+                .recv()
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            sender = Some(s);
+        }
+
+        let mut s = sender.take().unwrap();
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
+
+        let request = match &work_item {
+            WorkItem::Request { .. } => Request::builder()
+                .method(Method::POST)
+                .uri(url.clone())
+                .header(hyper::header::CONTENT_TYPE, "application/grpc")
+                .header(hyper::header::TE, "trailers")
+                .header(hyper::header::USER_AGENT, "rpc-perf/5.0.0-alpha (grpc)")
+                .body(Full::new(body.clone()))
+                .expect("failed to build request"),
+            WorkItem::Reconnect => {
+                SESSION_CLOSED_CLIENT.increment();
+                REQUEST_RECONNECT.increment();
+                continue;
+            }
+        };
+
+        REQUEST_OK.increment();
+
+        // send request and read the full response, as the status for the call
+        // is usually sent in the trailers
+        let start = Instant::now();
+        let response = timeout(config.client().unwrap().request_timeout(), async {
+            let response = s.send_request(request).await?;
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await?;
+            Ok::<_, hyper::Error>((parts, body))
+        })
+        .await;
+        let stop = Instant::now();
+
+        match response {
+            Ok(Ok((parts, body))) => {
+                // a trailers-only response carries the status in the headers
+                let status = if parts.status != StatusCode::OK {
+                    None
+                } else {
+                    grpc_status(body.trailers()).or_else(|| grpc_status(Some(&parts.headers)))
+                };
+
+                record_response_size(body.to_bytes().len());
+
+                match status.as_ref().and_then(|s| s.to_str().ok()) {
+                    Some(GRPC_OK) => {
+                        RESPONSE_OK.increment();
+
+                        let latency = stop.duration_since(start).as_nanos() as u64;
+
+                        record_latency(histograms, latency);
+                        record_corrected_latency(intended, stop);
+                    }
+                    Some(GRPC_DEADLINE_EXCEEDED) => {
+                        RESPONSE_BACKEND_TIMEOUT.increment();
+                    }
+                    Some(GRPC_RESOURCE_EXHAUSTED) => {
+                        RESPONSE_RATELIMITED.increment();
+                    }
+                    _ => {
+                        RESPONSE_EX.increment();
+                        record_error(ErrorCategory::Server);
+                    }
+                }
+            }
+            Ok(Err(_e)) => {
+                RESPONSE_EX.increment();
+                record_error(ErrorCategory::Connection);
+                SESSION_CLOSED_CLIENT.increment();
+                continue;
+            }
+            Err(_) => {
+                RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
+                SESSION_CLOSED_CLIENT.increment();
+                continue;
+            }
+        }
+
+        if let Err(_e) = s.ready().await {
+            continue;
+        }
+
+        sender = Some(s);
+    }
+
+    Ok(())
+}
+
+fn grpc_status(headers: Option<&HeaderMap>) -> Option<HeaderValue> {
+    headers
+        .and_then(|headers| headers.get("grpc-status"))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // a call received by the server, with its path, content type and message
+    type Call = (String, Option<HeaderValue>, Bytes);
+
+    // replies to each call with a message and then the next status in the
+    // trailers
+    async fn serve(listener: TcpListener, statuses: &'static [&'static str]) -> Vec<Call> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = h2::server::handshake(stream).await.unwrap();
+        let (calls, mut received) = tokio::sync::mpsc::unbounded_channel();
+
+        for status in statuses {
+            let (request, mut respond) = connection.accept().await.unwrap().unwrap();
+            let calls = calls.clone();
+
+            tokio::spawn(async move {
+                let (parts, mut body) = request.into_parts();
+
+                let mut message = BytesMut::new();
+                while let Some(data) = body.data().await {
+                    let data = data.unwrap();
+                    let _ = body.flow_control().release_capacity(data.len());
+                    message.extend_from_slice(&data);
+                }
+
+                let response = hyper::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(hyper::header::CONTENT_TYPE, "application/grpc")
+                    .body(())
+                    .unwrap();
+                let mut send = respond.send_response(response, false).unwrap();
+                send.send_data(Bytes::from_static(&[0, 0, 0, 0, 0]), false)
+                    .unwrap();
+
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static(status));
+                send.send_trailers(trailers).unwrap();
+
+                let _ = calls.send((
+                    parts.uri.path().to_string(),
+                    parts.headers.get(hyper::header::CONTENT_TYPE).cloned(),
+                    message.freeze(),
+                ));
+            });
+        }
+
+        // the connection is driven until the client goes away, so that the
+        // replies are sent
+        tokio::spawn(async move { while connection.accept().await.is_some() {} });
+
+        let mut result = Vec::new();
+        for _ in statuses {
+            result.push(received.recv().await.unwrap());
+        }
+        result
+    }
+
+    #[tokio::test]
+    async fn unary_calls() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config(
+            "grpc",
+            &endpoint,
+            r#"
+            concurrency = 1
+
+            [grpc]
+            method = "/test.Echo/Call"
+            "#,
+        );
+
+        let ok = RESPONSE_OK.value();
+        let ratelimited = RESPONSE_RATELIMITED.value();
+        let backend_timeout = RESPONSE_BACKEND_TIMEOUT.value();
+        let server_errors = ERROR_SERVER.value();
+
+        let server = tokio::spawn(serve(
+            listener,
+            &[
+                GRPC_OK,
+                GRPC_RESOURCE_EXHAUSTED,
+                GRPC_DEADLINE_EXCEEDED,
+                "13",
+            ],
+        ));
+
+        let (sender, receiver) = async_channel::bounded(16);
+        for _ in 0..4 {
+            sender.send(testing::get("key")).await.unwrap();
+        }
+
+        let queue = Queue::new(1);
+        tokio::spawn(pool_manager(
+            endpoint.clone(),
+            config.clone(),
+            queue.clone(),
+        ));
+        tokio::spawn(task(
+            receiver,
+            endpoint,
+            config,
+            queue,
+            Bytes::from_static(b"\x00\x00\x00\x00\x03abc"),
+        ));
+
+        for (path, content_type, message) in server.await.unwrap() {
+            assert_eq!(path, "/test.Echo/Call");
+            assert_eq!(content_type.unwrap(), "application/grpc");
+            assert_eq!(&message[..], b"\x00\x00\x00\x00\x03abc");
+        }
+
+        // each status is recorded as its own outcome
+        testing::eventually(|| RESPONSE_OK.value() > ok).await;
+        testing::eventually(|| RESPONSE_RATELIMITED.value() > ratelimited).await;
+        testing::eventually(|| RESPONSE_BACKEND_TIMEOUT.value() > backend_timeout).await;
+        testing::eventually(|| ERROR_SERVER.value() > server_errors).await;
+    }
+}
@@ -1,9 +1,9 @@
 use super::*;
 use crate::net::Connector;
 use bytes::Bytes;
-use http_body_util::Empty;
+use http_body_util::{BodyExt, Full};
 use hyper::header::{HeaderName, HeaderValue};
-use hyper::{Request, Uri};
+use hyper::{Method, Request, Uri};
 
 /// Launch tasks with one conncetion per task as http/1.1 is not mux'd
 pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
@@ -13,8 +13,11 @@ pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiv
         error!("HTTP/1.1 does not support multiplexing sessions onto single streams. Ignoring the concurrency parameter.");
     }
 
-    for _ in 0..config.client().unwrap().poolsize() {
-        for endpoint in config.target().endpoints() {
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
             runtime.spawn(task(
                 work_receiver.clone(),
                 endpoint.clone(),
@@ -28,10 +31,16 @@ pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiv
 #[allow(clippy::slow_vector_initialization)]
 async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Config) -> Result<()> {
     let connector = Connector::new(&config)?;
+    let keepalive = config.client().unwrap().http_keepalive();
+    let mut backoff = Backoff::new(config.client().unwrap());
+    let ratelimit = ConnectionRatelimit::new(config.client().unwrap());
+    let mut lifetime = Lifetime::new(config.client().unwrap());
     let mut session = None;
     let mut session_requests = 0;
     let mut session_start = Instant::now();
 
+    wait_for_ramp().await;
+
     while RUNNING.load(Ordering::Relaxed) {
         if session.is_none() {
             if session_requests != 0 {
@@ -40,6 +49,7 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 let _ = SESSION_LIFECYCLE_REQUESTS.increment(lifecycle_ns);
             }
             CONNECT.increment();
+            let start = Instant::now();
             let stream = match timeout(
                 config.client().unwrap().connect_timeout(),
                 connector.connect(&endpoint),
@@ -49,12 +59,14 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 Ok(Ok(s)) => s,
                 Ok(Err(_)) => {
                     CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
                 Err(_) => {
                     CONNECT_TIMEOUT.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
             };
@@ -62,11 +74,15 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
             let (s, conn) = match hyper::client::conn::http1::handshake(stream).await {
                 Ok((s, c)) => {
                     CONNECT_OK.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
                     (s, c)
                 }
                 Err(_e) => {
                     CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
             };
@@ -87,34 +103,53 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
         let mut s = session.take().unwrap();
 
+        // replace the session once it reaches the end of its lifetime
+        if lifetime.expired() {
+            SESSION_CLOSED_CLIENT.increment();
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
         let work_item = work_receiver
             .recv()
             .await
             .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
 
         REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
 
         // compose request into buffer
         let request = match &work_item {
-            WorkItem::Request { request, sequence } => match request {
+            WorkItem::Request {
+                request, sequence, ..
+            } => match request {
                 ClientRequest::Get(r) => {
-                    let key = unsafe { std::str::from_utf8_unchecked(&r.key) };
-                    let url: Uri = if config.tls().is_none() {
-                        format!("http://{endpoint}/{key}").parse().unwrap()
-                    } else {
-                        format!("https://{endpoint}/{key}").parse().unwrap()
-                    };
-                    let authority = url.authority().unwrap().clone();
-
-                    Request::builder()
-                        .uri(url)
-                        .header(hyper::header::HOST, authority.as_str())
-                        .header(
-                            hyper::header::USER_AGENT,
-                            &format!("rpc-perf/5.0.0-alpha (request; seq:{sequence})"),
-                        )
-                        .body(Empty::<Bytes>::new())
-                        .expect("failed to build request")
+                    GET.increment();
+                    compose(
+                        &config,
+                        &endpoint,
+                        *sequence,
+                        keepalive,
+                        Method::GET,
+                        &r.key,
+                        Bytes::new(),
+                    )
+                }
+                ClientRequest::Set(r) => {
+                    SET.increment();
+                    compose(
+                        &config,
+                        &endpoint,
+                        *sequence,
+                        keepalive,
+                        Method::POST,
+                        &r.key,
+                        Bytes::from(r.value.clone()),
+                    )
                 }
                 _ => {
                     // skip any requests that aren't supported and preserve the
@@ -135,24 +170,36 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
         };
 
         REQUEST_OK.increment();
+        lifetime.request();
 
         // send request
         let start = Instant::now();
-        let response = timeout(
-            config.client().unwrap().request_timeout(),
-            s.send_request(request),
-        )
+        let response = timeout(config.client().unwrap().request_timeout(), async {
+            let response = s.send_request(request).await?;
+
+            // read the entire body so that the connection may be reused
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await?.to_bytes();
+
+            Ok::<_, hyper::Error>((parts, body.len()))
+        })
         .await;
         let stop = Instant::now();
 
         match response {
-            Ok(Ok(response)) => {
+            Ok(Ok((response, size))) => {
+                // only the body is counted, as the headers are parsed by hyper
+                record_response_size(size);
+
                 // validate response
                 match &work_item {
                     WorkItem::Request { request, .. } => match request {
                         ClientRequest::Get { .. } => {
                             GET_OK.increment();
                         }
+                        ClientRequest::Set { .. } => {
+                            SET_STORED.increment();
+                        }
                         _ => {
                             error!("unexpected request");
                             unimplemented!();
@@ -168,10 +215,19 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
                 let latency = stop.duration_since(start).as_nanos() as u64;
 
-                let _ = RESPONSE_LATENCY.increment(latency);
+                record_latency(histograms, latency);
+                record_corrected_latency(intended, stop);
+
+                match response.status.as_u16() {
+                    100..=199 => HTTP_RESPONSE_1XX.increment(),
+                    200..=299 => HTTP_RESPONSE_2XX.increment(),
+                    300..=399 => HTTP_RESPONSE_3XX.increment(),
+                    400..=499 => HTTP_RESPONSE_4XX.increment(),
+                    _ => HTTP_RESPONSE_5XX.increment(),
+                };
 
                 if let Some(header) = response
-                    .headers()
+                    .headers
                     .get(HeaderName::from_bytes(b"Connection").unwrap())
                 {
                     if header == HeaderValue::from_static("close") {
@@ -181,10 +237,13 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
                 session_requests += 1;
 
-                // if we get an error when checking if the session is ready for
-                // another request, we update the connection gauge and allow the
-                // session to be dropped
-                if let Err(_e) = s.ready().await {
+                // if keepalive is disabled, or we get an error when checking if
+                // the session is ready for another request, we update the
+                // connection gauge and allow the session to be dropped
+                if !keepalive {
+                    SESSION_CLOSED_CLIENT.increment();
+                    CONNECT_CURR.decrement();
+                } else if let Err(_e) = s.ready().await {
                     CONNECT_CURR.decrement();
                 } else {
                     // preserve the session for reuse
@@ -195,6 +254,7 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 // an actual error was returned, do the necessary bookkeeping
                 // and allow the session to be dropped
                 RESPONSE_EX.increment();
+                record_error(ErrorCategory::Connection);
 
                 // record execption
                 match work_item {
@@ -202,6 +262,9 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                         ClientRequest::Get { .. } => {
                             GET_EX.increment();
                         }
+                        ClientRequest::Set { .. } => {
+                            SET_EX.increment();
+                        }
                         _ => {
                             error!("unexpected request");
                             unimplemented!();
@@ -219,6 +282,7 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 // increment timeout related stats and allow the session to be
                 // dropped
                 RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
                 SESSION_CLOSED_CLIENT.increment();
                 CONNECT_CURR.decrement();
             }
@@ -227,3 +291,164 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
     Ok(())
 }
+
+fn compose(
+    config: &Config,
+    endpoint: &str,
+    sequence: u64,
+    keepalive: bool,
+    method: Method,
+    key: &[u8],
+    body: Bytes,
+) -> Request<Full<Bytes>> {
+    let key = unsafe { std::str::from_utf8_unchecked(key) };
+    let url: Uri = if config.tls().is_none() {
+        format!("http://{endpoint}/{key}").parse().unwrap()
+    } else {
+        format!("https://{endpoint}/{key}").parse().unwrap()
+    };
+    let authority = url.authority().unwrap().clone();
+
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(url)
+        .header(hyper::header::HOST, authority.as_str())
+        .header(
+            hyper::header::USER_AGENT,
+            &format!("rpc-perf/5.0.0-alpha (request; seq:{sequence})"),
+        );
+
+    if !keepalive {
+        builder = builder.header(hyper::header::CONNECTION, "close");
+    }
+
+    builder
+        .body(Full::new(body))
+        .expect("failed to build request")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::BufReader;
+    use tokio::net::{TcpListener, TcpStream};
+
+    // reads the head of the next request and skips over its body, returning
+    // `None` once the connection is closed by the client
+    async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<String> {
+        let mut head = String::new();
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                return None;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            head.push_str(&line.to_ascii_lowercase());
+        }
+
+        let len = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map(|len| len.trim().parse().unwrap())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await.unwrap();
+
+        Some(head)
+    }
+
+    async fn respond(stream: &mut BufReader<TcpStream>, status: u16, close: bool) {
+        let connection = if close { "Connection: close\r\n" } else { "" };
+        let response =
+            format!("HTTP/1.1 {status} Status\r\nContent-Length: 2\r\n{connection}\r\nok");
+        stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .unwrap();
+    }
+
+    fn set(key: &str) -> WorkItem {
+        testing::request(ClientRequest::Set(workload::client::Set {
+            key: Arc::from(key.as_bytes()),
+            value: b"value".to_vec(),
+            ttl: None,
+        }))
+    }
+
+    #[tokio::test]
+    async fn status_codes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("http1", &endpoint, "");
+
+        let counts = || {
+            [
+                HTTP_RESPONSE_2XX.value(),
+                HTTP_RESPONSE_3XX.value(),
+                HTTP_RESPONSE_4XX.value(),
+                HTTP_RESPONSE_5XX.value(),
+            ]
+        };
+        let before = counts();
+
+        let (sender, receiver) = async_channel::bounded(16);
+        sender.send(testing::get("a")).await.unwrap();
+        sender.send(set("b")).await.unwrap();
+        sender.send(testing::get("c")).await.unwrap();
+        sender.send(testing::get("d")).await.unwrap();
+        tokio::spawn(task(receiver, endpoint, config));
+
+        let mut stream = BufReader::new(listener.accept().await.unwrap().0);
+
+        // the connection is kept alive, so each request arrives on it
+        for (status, line) in [
+            (200, "get /a http/1.1"),
+            (301, "post /b http/1.1"),
+            (404, "get /c http/1.1"),
+            (503, "get /d http/1.1"),
+        ] {
+            let head = read_request(&mut stream).await.unwrap();
+            assert!(head.starts_with(line), "{head}");
+            assert!(!head.contains("connection: close"));
+            respond(&mut stream, status, false).await;
+        }
+
+        // each response is counted by the class of its status code
+        testing::eventually(|| {
+            counts()
+                .iter()
+                .zip(before.iter())
+                .all(|(count, before)| count > before)
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn without_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("http1", &endpoint, "http_keepalive = false");
+
+        let (sender, receiver) = async_channel::bounded(16);
+        sender.send(testing::get("a")).await.unwrap();
+        sender.send(testing::get("b")).await.unwrap();
+        tokio::spawn(task(receiver, endpoint, config));
+
+        // each request asks for the connection to be closed, and is sent on a
+        // new connection
+        for _ in 0..2 {
+            let mut stream = BufReader::new(listener.accept().await.unwrap().0);
+
+            let head = read_request(&mut stream).await.unwrap();
+            assert!(head.contains("connection: close"), "{head}");
+            respond(&mut stream, 200, true).await;
+
+            assert!(read_request(&mut stream).await.is_none());
+        }
+    }
+}
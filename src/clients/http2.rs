@@ -35,10 +35,13 @@ impl<T> Queue<T> {
 pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
     debug!("launching http2 protocol tasks");
 
-    for _ in 0..config.client().unwrap().poolsize() {
-        for endpoint in config.target().endpoints() {
-            // for each endpoint have poolsize # of pool_managers, each managing
-            // a single TCP stream
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            // for each endpoint have its share of the pool as pool_managers,
+            // each managing a single TCP stream
 
             let queue = Queue::new(1);
             runtime.spawn(pool_manager(
@@ -76,6 +79,7 @@ where
 
 async fn pool_manager(endpoint: String, config: Config, queue: Queue<SendRequest<Empty<Bytes>>>) {
     let connector = Connector::new(&config).expect("failed to init connector");
+    let mut backoff = Backoff::new(config.client().unwrap());
     let mut sender = None;
 
     while RUNNING.load(Ordering::Relaxed) {
@@ -90,12 +94,18 @@ async fn pool_manager(endpoint: String, config: Config, queue: Queue<SendRequest
                 Ok(Ok(s)) => s,
                 Ok(Err(_)) => {
                     CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    if backoff.wait().await.is_err() {
+                        return;
+                    }
                     continue;
                 }
                 Err(_) => {
                     CONNECT_TIMEOUT.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    if backoff.wait().await.is_err() {
+                        return;
+                    }
                     continue;
                 }
             };
@@ -105,12 +115,16 @@ async fn pool_manager(endpoint: String, config: Config, queue: Queue<SendRequest
                     Ok((s, c)) => (s, c),
                     Err(_e) => {
                         CONNECT_EX.increment();
-                        sleep(Duration::from_millis(100)).await;
+                        record_error(ErrorCategory::Connection);
+                        if backoff.wait().await.is_err() {
+                            return;
+                        }
                         continue;
                     }
                 };
 
             SESSION.increment();
+            backoff.reset();
 
             sender = Some(s);
 
@@ -163,10 +177,14 @@ async fn task(
             .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
 
         REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
 
         // compose request into buffer
         let request = match &work_item {
-            WorkItem::Request { request, sequence } => match request {
+            WorkItem::Request {
+                request, sequence, ..
+            } => match request {
                 ClientRequest::Get(r) => {
                     let key = unsafe { std::str::from_utf8_unchecked(&r.key) };
                     let url: Uri = if config.tls().is_none() {
@@ -233,7 +251,8 @@ async fn task(
 
                 let latency = stop.duration_since(start).as_nanos() as u64;
 
-                let _ = RESPONSE_LATENCY.increment(latency);
+                record_latency(histograms, latency);
+                record_corrected_latency(intended, stop);
 
                 if let Some(header) = response
                     .headers()
@@ -245,6 +264,8 @@ async fn task(
                 }
             }
             Ok(Err(_e)) => {
+                record_error(ErrorCategory::Connection);
+
                 // record execption
                 match work_item {
                     WorkItem::Request { request, .. } => match request {
@@ -266,6 +287,7 @@ async fn task(
             }
             Err(_) => {
                 RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
                 SESSION_CLOSED_CLIENT.increment();
                 continue;
             }
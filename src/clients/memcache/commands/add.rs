@@ -9,7 +9,7 @@ impl From<&workload::client::Add> for RequestWithValidator {
                 (*other.key).to_owned().into_boxed_slice(),
                 (*other.value).to_owned().into_boxed_slice(),
                 0,
-                Ttl::none(),
+                ttl(other.ttl),
                 false,
             ),
             validator: Box::new(validate_response),
@@ -8,12 +8,19 @@ impl From<&workload::client::Get> for RequestWithValidator {
             request: Request::get(
                 vec![(*other.key).to_owned().into_boxed_slice()].into_boxed_slice(),
             ),
-            validator: Box::new(validate_response),
+            validator: if other.verify {
+                let key = other.key.clone();
+                Box::new(move |response| validate_response(response, Some(&key[..])))
+            } else {
+                Box::new(|response| validate_response(response, None))
+            },
         }
     }
 }
 
-pub fn validate_response(response: Response) -> std::result::Result<(), ()> {
+/// Validates a get response, checking the value against the expected value
+/// when a key to verify is given.
+pub fn validate_response(response: Response, verify: Option<&[u8]>) -> std::result::Result<(), ()> {
     match response {
         Response::Values(values) => {
             if values.values().is_empty() {
@@ -22,6 +29,14 @@ pub fn validate_response(response: Response) -> std::result::Result<(), ()> {
             } else {
                 RESPONSE_HIT.increment();
                 GET_KEY_HIT.increment();
+
+                if let Some(key) = verify {
+                    for value in values.values() {
+                        if let Some(value) = value.value() {
+                            workload::verify_value(key, value);
+                        }
+                    }
+                }
             }
             Ok(())
         }
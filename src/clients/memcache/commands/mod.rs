@@ -5,3 +5,69 @@ mod delete;
 mod get;
 mod replace;
 mod set;
+
+/// Converts the TTL from the workload into a memcache expiration time using
+/// the same conversion as the binary protocol, so TTLs beyond 30 days are sent
+/// as the unix time they end at and a TTL of zero expires the item immediately.
+pub fn ttl(ttl: Option<Duration>) -> Ttl {
+    match ttl {
+        None => Ttl::none(),
+        Some(_) => Ttl::new(expiry(ttl, SystemTime::now()) as i64, TimeType::Memcache),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // the expiration time from the command line of a composed set request
+    fn exptime(ttl: Option<Duration>) -> i64 {
+        let set = workload::client::Set {
+            key: Arc::from(&b"key"[..]),
+            value: b"value".to_vec(),
+            ttl,
+        };
+
+        let mut buffer = Buffer::new(1024);
+        RequestWithValidator::from(&set)
+            .request
+            .compose(&mut buffer);
+
+        let request: &[u8] = buffer.borrow();
+        let request = std::str::from_utf8(request).unwrap();
+        let line = request.split("\r\n").next().unwrap();
+
+        // set <key> <flags> <exptime> <bytes>
+        line.split(' ').nth(3).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn exptime_none() {
+        assert_eq!(exptime(None), 0);
+    }
+
+    #[test]
+    fn exptime_zero() {
+        // any expiration time in the past expires the item immediately
+        let exptime = exptime(Some(Duration::ZERO));
+        assert!(exptime < 0 || exptime == EXPIRE_IMMEDIATELY as i64);
+    }
+
+    #[test]
+    fn exptime_relative() {
+        assert_eq!(exptime(Some(Duration::from_secs(60))), 60);
+        assert_eq!(exptime(Some(Duration::from_millis(10))), 1);
+    }
+
+    #[test]
+    fn exptime_beyond_30_days() {
+        let ttl = Duration::from_secs(MAX_RELATIVE_EXPIRY + 60);
+
+        let before = expiry(Some(ttl), SystemTime::now()) as i64;
+        let exptime = exptime(Some(ttl));
+        let after = expiry(Some(ttl), SystemTime::now()) as i64;
+
+        assert!(exptime >= before && exptime <= after);
+    }
+}
@@ -10,7 +10,7 @@ impl From<&workload::client::Set> for RequestWithValidator {
                 (*other.key).to_owned().into_boxed_slice(),
                 (*other.value).to_owned().into_boxed_slice(),
                 0,
-                Ttl::none(),
+                ttl(other.ttl),
                 false,
             ),
             validator: Box::new(validate_response),
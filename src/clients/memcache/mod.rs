@@ -1,10 +1,18 @@
 use super::*;
 use crate::net::Connector;
-use protocol_memcache::{Compose, Parse, Request, Response, Ttl};
+use protocol_memcache::{Compose, Parse, Request, Response, TimeType, Ttl};
 use session::{Buf, BufMut, Buffer};
 use std::borrow::{Borrow, BorrowMut};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 mod commands;
+mod udp;
+
+// expiration times beyond 30 days are treated as unix timestamps
+const MAX_RELATIVE_EXPIRY: u64 = 60 * 60 * 24 * 30;
+
+// a timestamp in the past, which causes the item to expire immediately
+const EXPIRE_IMMEDIATELY: u32 = MAX_RELATIVE_EXPIRY as u32 + 1;
 
 struct RequestWithValidator {
     request: Request,
@@ -16,13 +24,27 @@ pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiv
     debug!("launching memcache protocol tasks");
 
     // create one task per connection
-    for _ in 0..config.client().unwrap().poolsize() {
-        for endpoint in config.target().endpoints() {
-            runtime.spawn(task(
-                work_receiver.clone(),
-                endpoint.clone(),
-                config.clone(),
-            ));
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            match config.memcache().map(|memcache| memcache.transport()) {
+                Some(MemcacheTransport::Udp) => {
+                    runtime.spawn(udp::task(
+                        work_receiver.clone(),
+                        endpoint.clone(),
+                        config.clone(),
+                    ));
+                }
+                _ => {
+                    runtime.spawn(task(
+                        work_receiver.clone(),
+                        endpoint.clone(),
+                        config.clone(),
+                    ));
+                }
+            }
         }
     }
 }
@@ -34,15 +56,21 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
     // we would not be creating a memcache client task if we didn't have a
     // client config, so this unwrap will succeed.
     let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
 
     let mut stream = None;
     let parser = protocol_memcache::ResponseParser {};
     let mut read_buffer = Buffer::new(client_config.read_buffer_size());
     let mut write_buffer = Buffer::new(client_config.write_buffer_size());
 
+    wait_for_ramp().await;
+
     while RUNNING.load(Ordering::Relaxed) {
         if stream.is_none() {
             CONNECT.increment();
+            let start = Instant::now();
             stream = match timeout(
                 client_config.connect_timeout(),
                 connector.connect(&endpoint),
@@ -52,16 +80,21 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 Ok(Ok(s)) => {
                     CONNECT_OK.increment();
                     CONNECT_CURR.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
                     Some(s)
                 }
                 Ok(Err(_)) => {
                     CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
                 Err(_) => {
                     CONNECT_TIMEOUT.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
             }
@@ -69,34 +102,68 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
         let mut s = stream.take().unwrap();
 
-        let work_item = work_receiver
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
+        // wait for a work item and then opportunistically fill the pipeline
+        // with any other work items which are already queued
+        let mut work_items = vec![work_receiver
             .recv()
             .await
-            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
-
-        REQUEST.increment();
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?];
 
-        // check if we should reconnect
-        if work_item == WorkItem::Reconnect {
-            CONNECT_CURR.decrement();
-            continue;
+        while work_items.len() < client_config.pipeline_depth() {
+            match work_receiver.try_recv() {
+                Ok(work_item) => work_items.push(work_item),
+                Err(_) => break,
+            }
         }
 
-        let request = RequestWithValidator::try_from(&work_item);
+        let mut reconnect = false;
+        let mut requests = Vec::with_capacity(work_items.len());
 
-        // skip unsupported work items
-        if request.is_err() {
-            stream = Some(s);
-            continue;
+        for work_item in &work_items {
+            REQUEST.increment();
+
+            // check if we should reconnect, this happens once any requests
+            // in the pipeline have completed
+            if *work_item == WorkItem::Reconnect {
+                reconnect = true;
+                continue;
+            }
+
+            // skip unsupported work items
+            if let Ok(request) = RequestWithValidator::try_from(work_item) {
+                // compose request
+                REQUEST_OK.increment();
+                lifetime.request();
+                request.request.compose(&mut write_buffer);
+                requests.push((
+                    request,
+                    latency_histograms(work_item),
+                    intended_start(work_item),
+                ));
+            }
         }
 
-        let request = request.unwrap();
+        if requests.is_empty() {
+            if reconnect {
+                CONNECT_CURR.decrement();
+            } else {
+                stream = Some(s);
+            }
+            continue;
+        }
 
-        // compose request
-        REQUEST_OK.increment();
-        request.request.compose(&mut write_buffer);
+        let _ = PIPELINE_DEPTH.increment(requests.len() as _);
 
-        // send request
+        // send requests
         let start = Instant::now();
         s.write_all(write_buffer.borrow()).await?;
 
@@ -104,89 +171,141 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
         write_buffer.clear();
         read_buffer.clear();
 
-        // read until response or timeout
-        let response = loop {
-            let remaining_time = client_config
-                .request_timeout()
-                .as_millis()
-                .saturating_sub(start.elapsed().as_millis());
-            if remaining_time == 0 {
-                break Err(ResponseError::Timeout);
-            }
+        let mut outstanding = requests.into_iter();
+        let mut failure = None;
 
-            match timeout(
-                Duration::from_millis(remaining_time as _),
-                s.read(read_buffer.borrow_mut()),
-            )
-            .await
-            {
-                Ok(Ok(n)) => {
-                    unsafe {
-                        read_buffer.advance_mut(n);
-                    }
-                    match parser.parse(read_buffer.borrow()) {
-                        Ok(resp) => {
-                            let consumed = resp.consumed();
-                            let resp = resp.into_inner();
+        // responses arrive in the same order as the requests were sent
+        for (request, histograms, intended) in outstanding.by_ref() {
+            // read until response or timeout
+            let response = loop {
+                // responses may be coalesced, so we try to parse any data
+                // which is already buffered before reading more
+                match parser.parse(read_buffer.borrow()) {
+                    Ok(resp) => {
+                        let consumed = resp.consumed();
+                        let resp = resp.into_inner();
 
-                            read_buffer.advance(consumed);
+                        read_buffer.advance(consumed);
+                        record_response_size(consumed);
 
-                            break Ok(resp);
-                        }
-                        Err(e) => match e.kind() {
-                            ErrorKind::WouldBlock => {}
-                            _ => {
-                                break Err(ResponseError::Exception);
-                            }
-                        },
+                        break Ok(resp);
                     }
+                    Err(e) => match e.kind() {
+                        ErrorKind::WouldBlock => {}
+                        _ => {
+                            record_error(ErrorCategory::Protocol);
+                            break Err(ResponseError::Exception);
+                        }
+                    },
                 }
-                Ok(Err(_)) => {
-                    break Err(ResponseError::Exception);
-                }
-                Err(_) => {
+
+                let remaining_time = client_config
+                    .request_timeout()
+                    .as_millis()
+                    .saturating_sub(start.elapsed().as_millis());
+                if remaining_time == 0 {
                     break Err(ResponseError::Timeout);
                 }
-            }
-        };
 
-        let stop = Instant::now();
+                match timeout(
+                    Duration::from_millis(remaining_time as _),
+                    s.read(read_buffer.borrow_mut()),
+                )
+                .await
+                {
+                    Ok(Ok(0)) => {
+                        // the connection was closed by the server
+                        record_error(ErrorCategory::Connection);
+                        break Err(ResponseError::Exception);
+                    }
+                    Ok(Ok(n)) => unsafe {
+                        read_buffer.advance_mut(n);
+                    },
+                    Ok(Err(_)) => {
+                        record_error(ErrorCategory::Connection);
+                        break Err(ResponseError::Exception);
+                    }
+                    Err(_) => {
+                        break Err(ResponseError::Timeout);
+                    }
+                }
+            };
+
+            let stop = Instant::now();
 
-        match response {
-            Ok(response) => {
-                let latency_ns = stop.duration_since(start).as_nanos() as u64;
+            match response {
+                Ok(response) => {
+                    let latency_ns = stop.duration_since(start).as_nanos() as u64;
+
+                    // error replies are the server refusing the request, any
+                    // other invalid response is unexpected for the request
+                    let category = match response {
+                        Response::Error(_)
+                        | Response::ClientError(_)
+                        | Response::ServerError(_) => ErrorCategory::Server,
+                        _ => ErrorCategory::Protocol,
+                    };
+
+                    // check if the response is valid
+                    if (request.validator)(response).is_err() {
+                        // increment error stats, connection will be dropped
+                        record_error(category);
+                        RESPONSE_EX.increment();
+                        failure = Some(ResponseError::Exception);
+                        break;
+                    }
 
-                // check if the response is valid
-                if (request.validator)(response).is_err() {
-                    // increment error stats, connection will be dropped
-                    RESPONSE_EX.increment();
-                    CONNECT_CURR.increment();
-                } else {
                     // increment success stats and latency
                     RESPONSE_OK.increment();
 
-                    let _ = RESPONSE_LATENCY.increment(latency_ns);
+                    record_latency(histograms, latency_ns);
+                    record_corrected_latency(intended, stop);
+                }
+                Err(ResponseError::Exception) => {
+                    // use validate response to record the exception
+                    let _ = (request.validator)(Response::error());
 
-                    // preserve the connection for the next request
-                    stream = Some(s);
+                    RESPONSE_EX.increment();
+                    failure = Some(ResponseError::Exception);
+                    break;
+                }
+                Err(ResponseError::Timeout) => {
+                    RESPONSE_TIMEOUT.increment();
+                    record_error(ErrorCategory::Timeout);
+                    failure = Some(ResponseError::Timeout);
+                    break;
+                }
+                Err(ResponseError::Ratelimited) | Err(ResponseError::BackendTimeout) => {
+                    unimplemented!();
                 }
             }
-            Err(ResponseError::Exception) => {
-                // use validate response to record the exception
-                let _ = (request.validator)(Response::error());
+        }
 
-                // increment error stats and allow connection to be dropped
-                RESPONSE_EX.increment();
-                CONNECT_CURR.decrement();
-            }
-            Err(ResponseError::Timeout) => {
-                // increment error stats and allow connection to be dropped
-                RESPONSE_TIMEOUT.increment();
-                CONNECT_CURR.decrement();
+        // the connection is dropped on failure, so any requests which remain
+        // in the pipeline will never receive a response
+        match failure {
+            Some(ResponseError::Timeout) => {
+                for _ in outstanding {
+                    RESPONSE_TIMEOUT.increment();
+                    record_error(ErrorCategory::Timeout);
+                }
             }
-            Err(ResponseError::Ratelimited) | Err(ResponseError::BackendTimeout) => {
-                unimplemented!();
+            Some(_) => {
+                for (request, _, _) in outstanding {
+                    let _ = (request.validator)(Response::error());
+                    record_error(ErrorCategory::Connection);
+                    RESPONSE_EX.increment();
+                }
             }
+            None => {}
+        }
+
+        if failure.is_some() || reconnect {
+            // allow the connection to be dropped
+            CONNECT_CURR.decrement();
+        } else {
+            // preserve the connection for the next request
+            stream = Some(s);
         }
     }
 
@@ -216,3 +335,256 @@ impl TryFrom<&WorkItem> for RequestWithValidator {
         }
     }
 }
+
+/// The memcache expiration time for a TTL, which is shared by the text and
+/// binary protocols. Having no TTL means the item never expires, and non-zero
+/// TTLs are never rounded down to zero as they have a resolution of one second.
+/// TTLs beyond 30 days are sent as the unix time they end at.
+pub fn expiry(ttl: Option<Duration>, now: SystemTime) -> u32 {
+    let ttl = match ttl {
+        None => return 0,
+        Some(ttl) if ttl.is_zero() => return EXPIRE_IMMEDIATELY,
+        Some(ttl) => std::cmp::max(1, ttl.as_secs()),
+    };
+
+    if ttl <= MAX_RELATIVE_EXPIRY {
+        return ttl as u32;
+    }
+
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default();
+
+    // the expiration time is limited by the width of the field
+    std::cmp::min(now.saturating_add(ttl), u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::io::BufReader;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn expiry_is_relative_up_to_30_days() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(expiry(None, now), 0);
+        assert_eq!(expiry(Some(Duration::ZERO), now), EXPIRE_IMMEDIATELY);
+        assert_eq!(expiry(Some(Duration::from_millis(10)), now), 1);
+        assert_eq!(
+            expiry(Some(Duration::from_secs(MAX_RELATIVE_EXPIRY)), now),
+            MAX_RELATIVE_EXPIRY as u32
+        );
+    }
+
+    #[test]
+    fn expiry_is_absolute_beyond_30_days() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ttl = Duration::from_secs(MAX_RELATIVE_EXPIRY + 1);
+
+        assert_eq!(
+            expiry(Some(ttl), now),
+            1_700_000_000 + MAX_RELATIVE_EXPIRY as u32 + 1
+        );
+        assert_eq!(expiry(Some(Duration::from_secs(u64::MAX)), now), u32::MAX);
+    }
+
+    #[tokio::test]
+    async fn command_latencies() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("memcache", &endpoint, "");
+
+        let before = [testing::count(&GET_LATENCY), testing::count(&SET_LATENCY)];
+
+        let (sender, receiver) = async_channel::bounded(16);
+        for i in 0..4 {
+            sender.send(testing::get(&format!("key{i}"))).await.unwrap();
+            sender
+                .send(testing::request(ClientRequest::Set(
+                    workload::client::Set {
+                        key: Arc::from(format!("key{i}").as_bytes()),
+                        value: b"value".to_vec(),
+                        ttl: None,
+                    },
+                )))
+                .await
+                .unwrap();
+        }
+        tokio::spawn(task(receiver, endpoint, config));
+
+        let mut stream = BufReader::new(listener.accept().await.unwrap().0);
+        for _ in 0..8 {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+
+            let response: &[u8] = if line.starts_with("get ") {
+                b"END\r\n"
+            } else {
+                assert!(line.starts_with("set "), "{line}");
+                let mut value = String::new();
+                stream.read_line(&mut value).await.unwrap();
+                assert_eq!(value, "value\r\n");
+                b"STORED\r\n"
+            };
+            stream.get_mut().write_all(response).await.unwrap();
+        }
+
+        // the latencies of each command are recorded in their own histogram
+        testing::eventually(|| {
+            testing::count(&GET_LATENCY) >= before[0] + 4
+                && testing::count(&SET_LATENCY) >= before[1] + 4
+        })
+        .await;
+    }
+
+    // a store for the set and get commands on a single connection, which
+    // corrupts the values it returns when it is buggy
+    async fn store(stream: TcpStream, buggy: bool) {
+        let mut stream = BufReader::new(stream);
+        let mut items: HashMap<String, Vec<u8>> = HashMap::new();
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                return;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            let mut response = Vec::new();
+            match fields[0] {
+                "set" => {
+                    let mut value = vec![0; fields[4].parse::<usize>().unwrap() + 2];
+                    stream.read_exact(&mut value).await.unwrap();
+                    value.truncate(value.len() - 2);
+                    items.insert(fields[1].to_string(), value);
+                    response.extend_from_slice(b"STORED\r\n");
+                }
+                "get" => {
+                    if let Some(value) = items.get(fields[1]) {
+                        let mut value = value.clone();
+                        if buggy {
+                            value[0] ^= 0xff;
+                        }
+                        response.extend_from_slice(
+                            format!("VALUE {} 0 {}\r\n", fields[1], value.len()).as_bytes(),
+                        );
+                        response.extend_from_slice(&value);
+                        response.extend_from_slice(b"\r\n");
+                    }
+                    response.extend_from_slice(b"END\r\n");
+                }
+                command => panic!("unexpected command: {command}"),
+            }
+            stream.get_mut().write_all(&response).await.unwrap();
+        }
+    }
+
+    // writes and then reads back the keys with verification
+    async fn write_and_verify(buggy: bool, keys: usize) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("memcache", &endpoint, "");
+
+        let (sender, receiver) = async_channel::bounded(16);
+        for i in 0..keys {
+            let key: Arc<[u8]> = Arc::from(format!("key{i}").as_bytes());
+            sender
+                .send(testing::request(ClientRequest::Set(
+                    workload::client::Set {
+                        key: key.clone(),
+                        value: workload::verified_value(&key, 32),
+                        ttl: None,
+                    },
+                )))
+                .await
+                .unwrap();
+            sender
+                .send(testing::request(ClientRequest::Get(
+                    workload::client::Get { key, verify: true },
+                )))
+                .await
+                .unwrap();
+        }
+        tokio::spawn(task(receiver, endpoint, config));
+
+        store(listener.accept().await.unwrap().0, buggy).await;
+    }
+
+    #[tokio::test]
+    async fn verify() {
+        // the counters are only changed by this test, so the stores are run
+        // one after the other
+        let verified = GET_VERIFIED.value();
+        let mismatch = GET_MISMATCH.value();
+
+        tokio::spawn(write_and_verify(false, 4));
+        testing::eventually(|| GET_VERIFIED.value() == verified + 4).await;
+        assert_eq!(GET_MISMATCH.value(), mismatch);
+
+        tokio::spawn(write_and_verify(true, 4));
+        testing::eventually(|| GET_MISMATCH.value() == mismatch + 4).await;
+        assert_eq!(GET_VERIFIED.value(), verified + 4);
+    }
+
+    // sends a get to a server which replies with the response, or which never
+    // replies if there is none, and waits for the error counter to increase
+    async fn error(response: Option<&'static [u8]>, counter: &'static metriken::Counter) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("memcache", &endpoint, "");
+        let before = counter.value();
+
+        let (sender, receiver) = async_channel::bounded(16);
+        sender.send(testing::get("key")).await.unwrap();
+        tokio::spawn(task(receiver, endpoint, config));
+
+        let mut stream = BufReader::new(listener.accept().await.unwrap().0);
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "get key\r\n");
+
+        if let Some(response) = response {
+            stream.get_mut().write_all(response).await.unwrap();
+        }
+
+        testing::eventually(|| counter.value() > before).await;
+    }
+
+    #[tokio::test]
+    async fn error_server() {
+        error(Some(b"SERVER_ERROR out of memory\r\n"), &ERROR_SERVER).await;
+    }
+
+    #[tokio::test]
+    async fn error_protocol() {
+        // a valid response, but not one for a get
+        error(Some(b"STORED\r\n"), &ERROR_PROTOCOL).await;
+    }
+
+    #[tokio::test]
+    async fn error_timeout() {
+        error(None, &ERROR_TIMEOUT).await;
+    }
+
+    #[tokio::test]
+    async fn error_connection() {
+        // the port of a closed listener refuses the connection
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let config = testing::config("memcache", &endpoint, "");
+        let before = ERROR_CONNECTION.value();
+
+        let (sender, receiver) = async_channel::bounded(16);
+        sender.send(testing::get("key")).await.unwrap();
+        tokio::spawn(task(receiver, endpoint, config));
+
+        testing::eventually(|| ERROR_CONNECTION.value() > before).await;
+    }
+}
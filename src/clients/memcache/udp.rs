@@ -0,0 +1,262 @@
+use super::*;
+use tokio::net::UdpSocket;
+
+// the frame header which precedes the payload of each datagram is the request
+// id, the sequence number, the number of datagrams, and a reserved field
+const HEADER_LEN: usize = 8;
+
+// memcached only accepts requests which fit in a single datagram
+const MAX_REQUEST_LEN: usize = 1400;
+
+// large enough for any datagram
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// Sends the requests over UDP with one socket per task. Each request is a
+/// single datagram and the response is reassembled from the datagrams which
+/// carry the request id, which must arrive in order. The socket is kept on
+/// failures, as the request id identifies any late responses.
+pub async fn task(
+    work_receiver: Receiver<WorkItem>,
+    endpoint: String,
+    config: Config,
+) -> Result<()> {
+    // we would not be creating a memcache client task if we didn't have a
+    // client config, so this unwrap will succeed.
+    let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+
+    let mut socket = None;
+    let parser = protocol_memcache::ResponseParser {};
+    let mut write_buffer = Buffer::new(client_config.write_buffer_size());
+    let mut read_buffer = vec![0; MAX_DATAGRAM_LEN];
+    let mut request_id: u16 = 0;
+
+    wait_for_ramp().await;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if socket.is_none() {
+            CONNECT.increment();
+            socket = match timeout(client_config.connect_timeout(), bind(&endpoint)).await {
+                Ok(Ok(s)) => {
+                    CONNECT_OK.increment();
+                    CONNECT_CURR.increment();
+                    backoff.reset();
+                    Some(s)
+                }
+                Ok(Err(_)) => {
+                    CONNECT_EX.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+                Err(_) => {
+                    CONNECT_TIMEOUT.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+            }
+        }
+
+        let s = socket.take().unwrap();
+
+        ratelimit.wait().await;
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+
+        // a reconnect binds a new socket, and so a new local port
+        if work_item == WorkItem::Reconnect {
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        // skip unsupported work items
+        let request = match RequestWithValidator::try_from(&work_item) {
+            Ok(request) => request,
+            Err(_) => {
+                socket = Some(s);
+                continue;
+            }
+        };
+
+        request_id = request_id.wrapping_add(1);
+
+        let mut header = [0; HEADER_LEN];
+        header[0..2].copy_from_slice(&request_id.to_be_bytes());
+        header[4..6].copy_from_slice(&1_u16.to_be_bytes());
+
+        write_buffer.put_slice(&header);
+        request.request.compose(&mut write_buffer);
+
+        if write_buffer.remaining() > MAX_REQUEST_LEN {
+            REQUEST_UNSUPPORTED.increment();
+            write_buffer.clear();
+            socket = Some(s);
+            continue;
+        }
+
+        REQUEST_OK.increment();
+
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
+
+        // send request
+        let start = Instant::now();
+        let sent = s.send(write_buffer.borrow()).await;
+        write_buffer.clear();
+
+        let response = match sent {
+            Ok(_) => {
+                let deadline = start + client_config.request_timeout();
+                receive(&s, &mut read_buffer, request_id, deadline)
+                    .await
+                    .and_then(|payload| match parser.parse(&payload) {
+                        Ok(response) => {
+                            record_response_size(payload.len());
+                            Ok(response.into_inner())
+                        }
+                        Err(_) => {
+                            // the response is incomplete or invalid once
+                            // all of the datagrams are reassembled
+                            record_error(ErrorCategory::Protocol);
+                            Err(ResponseError::Exception)
+                        }
+                    })
+            }
+            Err(_) => {
+                record_error(ErrorCategory::Connection);
+                Err(ResponseError::Exception)
+            }
+        };
+
+        let stop = Instant::now();
+
+        match response {
+            Ok(response) => {
+                let latency_ns = stop.duration_since(start).as_nanos() as u64;
+
+                // error replies are the server refusing the request, any
+                // other invalid response is unexpected for the request
+                let category = match response {
+                    Response::Error(_) | Response::ClientError(_) | Response::ServerError(_) => {
+                        ErrorCategory::Server
+                    }
+                    _ => ErrorCategory::Protocol,
+                };
+
+                // check if the response is valid
+                if (request.validator)(response).is_err() {
+                    record_error(category);
+                    RESPONSE_EX.increment();
+                } else {
+                    // increment success stats and latency
+                    RESPONSE_OK.increment();
+
+                    record_latency(histograms, latency_ns);
+                    record_corrected_latency(intended, stop);
+                }
+            }
+            Err(ResponseError::Timeout) => {
+                RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
+            }
+            // memcache has no ratelimited or backend timeout replies, so any
+            // other error is an exception
+            Err(_) => {
+                // use validate response to record the exception
+                let _ = (request.validator)(Response::error());
+
+                RESPONSE_EX.increment();
+            }
+        }
+
+        socket = Some(s);
+    }
+
+    Ok(())
+}
+
+// binds a socket to an ephemeral port and connects it to the endpoint, so
+// that only datagrams from the endpoint are received
+async fn bind(endpoint: &str) -> Result<UdpSocket> {
+    let addr = tokio::net::lookup_host(endpoint)
+        .await?
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::Other, "endpoint did not resolve"))?;
+
+    let socket = if addr.is_ipv4() {
+        UdpSocket::bind("0.0.0.0:0").await?
+    } else {
+        UdpSocket::bind("[::]:0").await?
+    };
+
+    socket.connect(addr).await?;
+
+    Ok(socket)
+}
+
+// receives the datagrams of the response and returns their payloads joined in
+// order. Datagrams for other request ids are late responses to requests which
+// timed out and are discarded. A lost datagram is a timeout.
+async fn receive(
+    socket: &UdpSocket,
+    buffer: &mut [u8],
+    request_id: u16,
+    deadline: Instant,
+) -> std::result::Result<Vec<u8>, ResponseError> {
+    let mut payload = Vec::new();
+    let mut sequence: u16 = 0;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(ResponseError::Timeout);
+        }
+
+        let len = match timeout(remaining, socket.recv(buffer)).await {
+            Ok(Ok(len)) => len,
+            Ok(Err(_)) => {
+                record_error(ErrorCategory::Connection);
+                return Err(ResponseError::Exception);
+            }
+            Err(_) => {
+                return Err(ResponseError::Timeout);
+            }
+        };
+
+        let datagram = &buffer[..len];
+
+        if datagram.len() < HEADER_LEN {
+            record_error(ErrorCategory::Protocol);
+            return Err(ResponseError::Exception);
+        }
+
+        let id = u16::from_be_bytes([datagram[0], datagram[1]]);
+        let number = u16::from_be_bytes([datagram[2], datagram[3]]);
+        let count = u16::from_be_bytes([datagram[4], datagram[5]]);
+
+        if id != request_id {
+            continue;
+        }
+
+        // the datagrams of a response must arrive in order
+        if number != sequence || number >= count {
+            record_error(ErrorCategory::Protocol);
+            return Err(ResponseError::Exception);
+        }
+
+        payload.extend_from_slice(&datagram[HEADER_LEN..]);
+        sequence += 1;
+
+        if sequence == count {
+            return Ok(payload);
+        }
+    }
+}
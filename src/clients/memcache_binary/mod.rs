@@ -0,0 +1,542 @@
+use super::*;
+use crate::net::Connector;
+use session::{Buf, BufMut, Buffer};
+use std::borrow::{Borrow, BorrowMut};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod protocol;
+
+use protocol::{Opcode, Request, Response, Status};
+
+/// Launch tasks with one conncetion per task as memcache protocol is not mux-enabled.
+pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
+    debug!("launching memcache binary protocol tasks");
+
+    // create one task per connection
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            runtime.spawn(task(
+                work_receiver.clone(),
+                endpoint.clone(),
+                config.clone(),
+            ));
+        }
+    }
+}
+
+/// A connection along with the state which is tracked for it.
+struct Session {
+    stream: net::Stream,
+    read_buffer: Buffer,
+    write_buffer: Vec<u8>,
+    // the opaque value is echoed back by the server and lets us detect any
+    // responses which do not belong to the current request
+    opaque: u32,
+    // the most recently observed CAS value for keys used in CAS requests
+    cas_values: HashMap<Arc<[u8]>, u64>,
+    // the latency histograms for the work item currently being sent
+    histograms: LatencyHistograms,
+    // when the work item currently being sent was intended to be sent
+    intended: Option<Instant>,
+}
+
+impl Session {
+    /// Sends a request and reads its response, recording the response stats.
+    async fn send(
+        &mut self,
+        mut request: Request,
+        request_timeout: Duration,
+    ) -> std::result::Result<Response, ResponseError> {
+        self.opaque = self.opaque.wrapping_add(1);
+        request.set_opaque(self.opaque);
+
+        // compose request
+        request.compose(&mut self.write_buffer);
+
+        // send request
+        let start = Instant::now();
+        let result = self.stream.write_all(&self.write_buffer).await;
+
+        // clear the buffers
+        self.write_buffer.clear();
+        self.read_buffer.clear();
+
+        let response = match result {
+            Ok(()) => self.recv(start, request_timeout).await,
+            Err(_) => {
+                record_error(ErrorCategory::Connection);
+                Err(ResponseError::Exception)
+            }
+        };
+
+        let stop = Instant::now();
+
+        match response {
+            Ok(response) => {
+                // check if the response is valid
+                if validate_response(&request, &response).is_err() {
+                    RESPONSE_EX.increment();
+                    return Err(ResponseError::Exception);
+                }
+
+                // increment success stats and latency
+                RESPONSE_OK.increment();
+
+                let latency_ns = stop.duration_since(start).as_nanos() as u64;
+                record_latency(self.histograms, latency_ns);
+                record_corrected_latency(self.intended, stop);
+
+                Ok(response)
+            }
+            Err(ResponseError::Timeout) => {
+                RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
+                Err(ResponseError::Timeout)
+            }
+            Err(e) => {
+                record_exception(&request);
+                RESPONSE_EX.increment();
+                Err(e)
+            }
+        }
+    }
+
+    /// Read until response or timeout.
+    async fn recv(
+        &mut self,
+        start: Instant,
+        request_timeout: Duration,
+    ) -> std::result::Result<Response, ResponseError> {
+        loop {
+            let remaining_time = request_timeout
+                .as_millis()
+                .saturating_sub(start.elapsed().as_millis());
+            if remaining_time == 0 {
+                return Err(ResponseError::Timeout);
+            }
+
+            match timeout(
+                Duration::from_millis(remaining_time as _),
+                self.stream.read(self.read_buffer.borrow_mut()),
+            )
+            .await
+            {
+                Ok(Ok(0)) => {
+                    // the connection was closed by the server
+                    record_error(ErrorCategory::Connection);
+                    return Err(ResponseError::Exception);
+                }
+                Ok(Ok(n)) => {
+                    unsafe {
+                        self.read_buffer.advance_mut(n);
+                    }
+                    match Response::parse(self.read_buffer.borrow()) {
+                        Ok((response, consumed)) => {
+                            self.read_buffer.advance(consumed);
+                            record_response_size(consumed);
+
+                            return Ok(response);
+                        }
+                        Err(e) => match e.kind() {
+                            ErrorKind::WouldBlock => {}
+                            _ => {
+                                record_error(ErrorCategory::Protocol);
+                                return Err(ResponseError::Exception);
+                            }
+                        },
+                    }
+                }
+                Ok(Err(_)) => {
+                    record_error(ErrorCategory::Connection);
+                    return Err(ResponseError::Exception);
+                }
+                Err(_) => {
+                    return Err(ResponseError::Timeout);
+                }
+            }
+        }
+    }
+
+    /// Performs a compare-and-swap. If the CAS value for the key is not known,
+    /// it is first read with a `get`.
+    async fn cas(
+        &mut self,
+        request: &workload::client::Cas,
+        request_timeout: Duration,
+    ) -> std::result::Result<(), ResponseError> {
+        CAS.increment();
+
+        let cas = match self.cas_values.get(&request.key) {
+            Some(cas) => *cas,
+            None => {
+                GET.increment();
+                let response = self
+                    .send(
+                        Request::get((*request.key).to_owned().into_boxed_slice()),
+                        request_timeout,
+                    )
+                    .await?;
+
+                if response.status != Status::NoError {
+                    // there is nothing to swap if the key does not exist
+                    CAS_NOT_FOUND.increment();
+                    return Ok(());
+                }
+
+                response.cas
+            }
+        };
+
+        let response = self
+            .send(
+                Request::cas(
+                    (*request.key).to_owned().into_boxed_slice(),
+                    request.value.clone().into_boxed_slice(),
+                    request.ttl,
+                    cas,
+                ),
+                request_timeout,
+            )
+            .await?;
+
+        // a successful swap returns the new CAS value, otherwise the value we
+        // had is stale and has to be read again
+        if response.status == Status::NoError {
+            self.cas_values.insert(request.key.clone(), response.cas);
+        } else {
+            self.cas_values.remove(&request.key);
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::slow_vector_initialization)]
+async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Config) -> Result<()> {
+    let connector = Connector::new(&config)?;
+
+    // we would not be creating a memcache client task if we didn't have a
+    // client config, so this unwrap will succeed.
+    let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
+
+    let mut session = None;
+
+    wait_for_ramp().await;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if session.is_none() {
+            CONNECT.increment();
+            let start = Instant::now();
+            session = match timeout(
+                client_config.connect_timeout(),
+                connector.connect(&endpoint),
+            )
+            .await
+            {
+                Ok(Ok(stream)) => {
+                    CONNECT_OK.increment();
+                    CONNECT_CURR.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
+                    Some(Session {
+                        stream,
+                        read_buffer: Buffer::new(client_config.read_buffer_size()),
+                        write_buffer: Vec::with_capacity(client_config.write_buffer_size()),
+                        opaque: 0,
+                        cas_values: HashMap::new(),
+                        histograms: LatencyHistograms::default(),
+                        intended: None,
+                    })
+                }
+                Ok(Err(_)) => {
+                    CONNECT_EX.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+                Err(_) => {
+                    CONNECT_TIMEOUT.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+            }
+        }
+
+        let mut s = session.take().unwrap();
+
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+
+        // check if we should reconnect
+        if work_item == WorkItem::Reconnect {
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        s.histograms = latency_histograms(&work_item);
+        s.intended = intended_start(&work_item);
+
+        let result = match &work_item {
+            WorkItem::Request {
+                request: ClientRequest::Cas(r),
+                ..
+            } => {
+                REQUEST_OK.increment();
+                lifetime.request();
+                s.cas(r, client_config.request_timeout()).await
+            }
+            _ => match Request::try_from(&work_item) {
+                Ok(request) => {
+                    REQUEST_OK.increment();
+                    lifetime.request();
+                    s.send(request, client_config.request_timeout())
+                        .await
+                        .map(|_| ())
+                }
+                Err(_) => {
+                    // skip unsupported work items
+                    session = Some(s);
+                    continue;
+                }
+            },
+        };
+
+        if result.is_ok() {
+            // preserve the connection for the next request
+            session = Some(s);
+        } else {
+            // allow the connection to be dropped
+            CONNECT_CURR.decrement();
+        }
+    }
+
+    Ok(())
+}
+
+impl TryFrom<&WorkItem> for Request {
+    type Error = ();
+    fn try_from(other: &WorkItem) -> std::result::Result<Request, ()> {
+        match other {
+            WorkItem::Request { request, .. } => match request {
+                ClientRequest::Add(r) => {
+                    ADD.increment();
+                    Ok(Request::add(
+                        (*r.key).to_owned().into_boxed_slice(),
+                        (*r.value).to_owned().into_boxed_slice(),
+                        r.ttl,
+                    ))
+                }
+                ClientRequest::Get(r) => {
+                    GET.increment();
+                    Ok(Request::get((*r.key).to_owned().into_boxed_slice()))
+                }
+                ClientRequest::Delete(r) => {
+                    DELETE.increment();
+                    Ok(Request::delete((*r.key).to_owned().into_boxed_slice()))
+                }
+                ClientRequest::Replace(r) => {
+                    REPLACE.increment();
+                    Ok(Request::replace(
+                        (*r.key).to_owned().into_boxed_slice(),
+                        (*r.value).to_owned().into_boxed_slice(),
+                        r.ttl,
+                    ))
+                }
+                ClientRequest::Set(r) => {
+                    SET.increment();
+                    Ok(Request::set(
+                        (*r.key).to_owned().into_boxed_slice(),
+                        (*r.value).to_owned().into_boxed_slice(),
+                        r.ttl,
+                    ))
+                }
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+fn validate_response(request: &Request, response: &Response) -> std::result::Result<(), ()> {
+    if response.opcode != request.opcode() || response.opaque != request.opaque() {
+        record_error(ErrorCategory::Protocol);
+        record_exception(request);
+        return Err(());
+    }
+
+    match (request.opcode(), response.status) {
+        (Opcode::Set, Status::NoError) if request.cas() != 0 => {
+            CAS_STORED.increment();
+        }
+        (Opcode::Set, Status::KeyExists) if request.cas() != 0 => {
+            CAS_EXISTS.increment();
+        }
+        (Opcode::Set, Status::KeyNotFound) if request.cas() != 0 => {
+            CAS_NOT_FOUND.increment();
+        }
+        (Opcode::Get, Status::NoError) => {
+            RESPONSE_HIT.increment();
+            GET_KEY_HIT.increment();
+        }
+        (Opcode::Get, Status::KeyNotFound) => {
+            RESPONSE_MISS.increment();
+            GET_KEY_MISS.increment();
+        }
+        (Opcode::Set, Status::NoError) => {
+            SET_STORED.increment();
+        }
+        (Opcode::Set, Status::ItemNotStored) => {
+            SET_NOT_STORED.increment();
+        }
+        (Opcode::Add, Status::NoError) => {
+            ADD_STORED.increment();
+        }
+        (Opcode::Add, Status::KeyExists) | (Opcode::Add, Status::ItemNotStored) => {
+            ADD_NOT_STORED.increment();
+        }
+        (Opcode::Replace, Status::NoError) => {
+            REPLACE_STORED.increment();
+        }
+        (Opcode::Replace, Status::KeyNotFound) | (Opcode::Replace, Status::ItemNotStored) => {
+            REPLACE_NOT_STORED.increment();
+        }
+        (Opcode::Delete, Status::NoError) => {
+            DELETE_DELETED.increment();
+        }
+        (Opcode::Delete, Status::KeyNotFound) => {
+            DELETE_NOT_FOUND.increment();
+        }
+        _ => {
+            // any other status is an error reported by the server
+            record_error(ErrorCategory::Server);
+            record_exception(request);
+            return Err(());
+        }
+    }
+
+    Ok(())
+}
+
+fn record_exception(request: &Request) {
+    match request.opcode() {
+        Opcode::Set if request.cas() != 0 => CAS_EX.increment(),
+        Opcode::Get => GET_EX.increment(),
+        Opcode::Set => SET_EX.increment(),
+        Opcode::Add => ADD_EX.increment(),
+        Opcode::Replace => REPLACE_EX.increment(),
+        Opcode::Delete => DELETE_EX.increment(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests() -> Vec<Request> {
+        let key = || b"key".to_vec().into_boxed_slice();
+        let value = || b"value".to_vec().into_boxed_slice();
+
+        vec![
+            Request::get(key()),
+            Request::set(key(), value(), None),
+            Request::add(key(), value(), None),
+            Request::replace(key(), value(), None),
+            Request::delete(key()),
+            Request::cas(key(), value(), None, 42),
+        ]
+    }
+
+    fn response(request: &Request, status: Status) -> Response {
+        Response {
+            opcode: request.opcode(),
+            status,
+            opaque: request.opaque(),
+            cas: 0,
+        }
+    }
+
+    #[test]
+    fn server_errors() {
+        for request in requests() {
+            assert!(validate_response(&request, &response(&request, Status::NoError)).is_ok());
+
+            // these statuses are errors for every request
+            for status in [
+                Status::ValueTooLarge,
+                Status::InvalidArguments,
+                Status::Other(0x0081),
+                Status::Other(0x0082),
+            ] {
+                assert!(
+                    validate_response(&request, &response(&request, status)).is_err(),
+                    "{:?} {status:?}",
+                    request.opcode()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn expected_statuses() {
+        let requests = requests();
+
+        // the statuses which are an expected outcome rather than an error
+        let validate =
+            |request: &Request, status| validate_response(request, &response(request, status));
+
+        // get
+        assert!(validate(&requests[0], Status::KeyNotFound).is_ok());
+        assert!(validate(&requests[0], Status::KeyExists).is_err());
+        // set
+        assert!(validate(&requests[1], Status::ItemNotStored).is_ok());
+        assert!(validate(&requests[1], Status::KeyExists).is_err());
+        // add
+        assert!(validate(&requests[2], Status::KeyExists).is_ok());
+        assert!(validate(&requests[2], Status::KeyNotFound).is_err());
+        // replace
+        assert!(validate(&requests[3], Status::KeyNotFound).is_ok());
+        assert!(validate(&requests[3], Status::KeyExists).is_err());
+        // delete
+        assert!(validate(&requests[4], Status::KeyNotFound).is_ok());
+        assert!(validate(&requests[4], Status::KeyExists).is_err());
+        // cas
+        assert!(validate(&requests[5], Status::KeyExists).is_ok());
+        assert!(validate(&requests[5], Status::KeyNotFound).is_ok());
+    }
+
+    #[test]
+    fn mismatched_response() {
+        let mut request = Request::get(b"key".to_vec().into_boxed_slice());
+        request.set_opaque(1);
+
+        let mut other = response(&request, Status::NoError);
+        other.opaque = 2;
+        assert!(validate_response(&request, &other).is_err());
+
+        let mut other = response(&request, Status::NoError);
+        other.opcode = Opcode::Set;
+        assert!(validate_response(&request, &other).is_err());
+    }
+}
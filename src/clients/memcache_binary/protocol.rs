@@ -0,0 +1,444 @@
+//! A minimal implementation of the memcache binary protocol covering the
+//! opcodes which are used by the workload generator.
+
+use crate::clients::memcache::expiry;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, SystemTime};
+
+const HEADER_LEN: usize = 24;
+
+const MAGIC_REQUEST: u8 = 0x80;
+const MAGIC_RESPONSE: u8 = 0x81;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Get,
+    Set,
+    Add,
+    Replace,
+    Delete,
+}
+
+impl Opcode {
+    fn as_u8(&self) -> u8 {
+        match self {
+            Self::Get => 0x00,
+            Self::Set => 0x01,
+            Self::Add => 0x02,
+            Self::Replace => 0x03,
+            Self::Delete => 0x04,
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = Error;
+
+    fn try_from(other: u8) -> Result<Self, Error> {
+        match other {
+            0x00 => Ok(Self::Get),
+            0x01 => Ok(Self::Set),
+            0x02 => Ok(Self::Add),
+            0x03 => Ok(Self::Replace),
+            0x04 => Ok(Self::Delete),
+            _ => Err(Error::new(ErrorKind::InvalidData, "unknown opcode")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    NoError,
+    KeyNotFound,
+    KeyExists,
+    ValueTooLarge,
+    InvalidArguments,
+    ItemNotStored,
+    Other(u16),
+}
+
+impl From<u16> for Status {
+    fn from(other: u16) -> Self {
+        match other {
+            0x0000 => Self::NoError,
+            0x0001 => Self::KeyNotFound,
+            0x0002 => Self::KeyExists,
+            0x0003 => Self::ValueTooLarge,
+            0x0004 => Self::InvalidArguments,
+            0x0005 => Self::ItemNotStored,
+            status => Self::Other(status),
+        }
+    }
+}
+
+pub struct Request {
+    opcode: Opcode,
+    opaque: u32,
+    cas: u64,
+    key: Box<[u8]>,
+    value: Box<[u8]>,
+    flags: u32,
+    expiry: u32,
+}
+
+impl Request {
+    pub fn get(key: Box<[u8]>) -> Self {
+        Self::new(Opcode::Get, key, Vec::new().into_boxed_slice(), None)
+    }
+
+    pub fn delete(key: Box<[u8]>) -> Self {
+        Self::new(Opcode::Delete, key, Vec::new().into_boxed_slice(), None)
+    }
+
+    pub fn set(key: Box<[u8]>, value: Box<[u8]>, ttl: Option<Duration>) -> Self {
+        Self::new(Opcode::Set, key, value, ttl)
+    }
+
+    pub fn add(key: Box<[u8]>, value: Box<[u8]>, ttl: Option<Duration>) -> Self {
+        Self::new(Opcode::Add, key, value, ttl)
+    }
+
+    pub fn replace(key: Box<[u8]>, value: Box<[u8]>, ttl: Option<Duration>) -> Self {
+        Self::new(Opcode::Replace, key, value, ttl)
+    }
+
+    /// A set which only succeeds if the item's CAS value matches the provided
+    /// CAS value.
+    pub fn cas(key: Box<[u8]>, value: Box<[u8]>, ttl: Option<Duration>, cas: u64) -> Self {
+        let mut request = Self::new(Opcode::Set, key, value, ttl);
+        request.cas = cas;
+        request
+    }
+
+    fn new(opcode: Opcode, key: Box<[u8]>, value: Box<[u8]>, ttl: Option<Duration>) -> Self {
+        Self {
+            opcode,
+            opaque: 0,
+            cas: 0,
+            key,
+            value,
+            flags: 0,
+            expiry: expiry(ttl, SystemTime::now()),
+        }
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        self.opcode
+    }
+
+    pub fn opaque(&self) -> u32 {
+        self.opaque
+    }
+
+    pub fn cas(&self) -> u64 {
+        self.cas
+    }
+
+    /// Sets the opaque value which the server echoes back in the response.
+    pub fn set_opaque(&mut self, opaque: u32) {
+        self.opaque = opaque;
+    }
+
+    /// Encodes the request onto the end of the buffer.
+    pub fn compose(&self, buffer: &mut Vec<u8>) {
+        let extras_len = match self.opcode {
+            Opcode::Set | Opcode::Add | Opcode::Replace => 8,
+            Opcode::Get | Opcode::Delete => 0,
+        };
+
+        let body_len = extras_len + self.key.len() + self.value.len();
+
+        buffer.reserve(HEADER_LEN + body_len);
+
+        buffer.push(MAGIC_REQUEST);
+        buffer.push(self.opcode.as_u8());
+        buffer.extend_from_slice(&(self.key.len() as u16).to_be_bytes());
+        buffer.push(extras_len as u8);
+        // data type
+        buffer.push(0);
+        // vbucket id
+        buffer.extend_from_slice(&0_u16.to_be_bytes());
+        buffer.extend_from_slice(&(body_len as u32).to_be_bytes());
+        buffer.extend_from_slice(&self.opaque.to_be_bytes());
+        buffer.extend_from_slice(&self.cas.to_be_bytes());
+
+        if extras_len > 0 {
+            buffer.extend_from_slice(&self.flags.to_be_bytes());
+            buffer.extend_from_slice(&self.expiry.to_be_bytes());
+        }
+
+        buffer.extend_from_slice(&self.key);
+        buffer.extend_from_slice(&self.value);
+    }
+}
+
+pub struct Response {
+    pub opcode: Opcode,
+    pub status: Status,
+    pub opaque: u32,
+    pub cas: u64,
+}
+
+impl Response {
+    /// Attempts to decode a response from the start of the buffer, returning
+    /// the response and the number of bytes consumed. An error with the kind
+    /// `WouldBlock` indicates that more data is needed.
+    pub fn parse(buffer: &[u8]) -> Result<(Self, usize), Error> {
+        if buffer.len() < HEADER_LEN {
+            return Err(Error::from(ErrorKind::WouldBlock));
+        }
+
+        if buffer[0] != MAGIC_RESPONSE {
+            return Err(Error::new(ErrorKind::InvalidData, "bad magic"));
+        }
+
+        let opcode = Opcode::try_from(buffer[1])?;
+        let key_len = u16::from_be_bytes([buffer[2], buffer[3]]) as usize;
+        let extras_len = buffer[4] as usize;
+        let status = Status::from(u16::from_be_bytes([buffer[6], buffer[7]]));
+        let body_len = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]) as usize;
+        let opaque = u32::from_be_bytes([buffer[12], buffer[13], buffer[14], buffer[15]]);
+        let cas = u64::from_be_bytes([
+            buffer[16], buffer[17], buffer[18], buffer[19], buffer[20], buffer[21], buffer[22],
+            buffer[23],
+        ]);
+
+        if extras_len + key_len > body_len {
+            return Err(Error::new(ErrorKind::InvalidData, "bad body length"));
+        }
+
+        if buffer.len() < HEADER_LEN + body_len {
+            return Err(Error::from(ErrorKind::WouldBlock));
+        }
+
+        // the value is not used, so we skip over the body entirely
+        Ok((
+            Self {
+                opcode,
+                status,
+                opaque,
+                cas,
+            },
+            HEADER_LEN + body_len,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Header {
+        magic: u8,
+        opcode: u8,
+        key_len: usize,
+        extras_len: usize,
+        body_len: usize,
+        opaque: u32,
+        cas: u64,
+    }
+
+    fn header(buffer: &[u8]) -> Header {
+        Header {
+            magic: buffer[0],
+            opcode: buffer[1],
+            key_len: u16::from_be_bytes([buffer[2], buffer[3]]) as usize,
+            extras_len: buffer[4] as usize,
+            body_len: u32::from_be_bytes(buffer[8..12].try_into().unwrap()) as usize,
+            opaque: u32::from_be_bytes(buffer[12..16].try_into().unwrap()),
+            cas: u64::from_be_bytes(buffer[16..24].try_into().unwrap()),
+        }
+    }
+
+    fn compose(request: &Request) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        request.compose(&mut buffer);
+        buffer
+    }
+
+    // the response a server would send for the request, with a value in the
+    // body for a successful get
+    fn respond(request: &[u8], status: u16, cas: u64) -> Vec<u8> {
+        let value: &[u8] = if request[1] == 0x00 && status == 0 {
+            b"value"
+        } else {
+            b""
+        };
+
+        let mut response = Vec::new();
+        response.push(MAGIC_RESPONSE);
+        response.push(request[1]);
+        // key length
+        response.extend_from_slice(&0_u16.to_be_bytes());
+        // extras length, which holds the flags for a get
+        let extras_len = if value.is_empty() { 0 } else { 4 };
+        response.push(extras_len);
+        // data type
+        response.push(0);
+        response.extend_from_slice(&status.to_be_bytes());
+        response.extend_from_slice(&(extras_len as u32 + value.len() as u32).to_be_bytes());
+        // the opaque value is echoed back
+        response.extend_from_slice(&request[12..16]);
+        response.extend_from_slice(&cas.to_be_bytes());
+        response.extend_from_slice(&vec![0; extras_len as usize]);
+        response.extend_from_slice(value);
+        response
+    }
+
+    fn requests() -> Vec<Request> {
+        let key = || b"key".to_vec().into_boxed_slice();
+        let value = || b"value".to_vec().into_boxed_slice();
+        let ttl = Some(Duration::from_secs(60));
+
+        vec![
+            Request::get(key()),
+            Request::set(key(), value(), ttl),
+            Request::add(key(), value(), ttl),
+            Request::replace(key(), value(), ttl),
+            Request::delete(key()),
+            Request::cas(key(), value(), ttl, 42),
+        ]
+    }
+
+    #[test]
+    fn compose_header() {
+        for (opaque, mut request) in requests().into_iter().enumerate() {
+            request.set_opaque(opaque as u32);
+            let buffer = compose(&request);
+            let header = header(&buffer);
+
+            // only the storage commands have extras and a value
+            let (opcode, extras_len, value_len) = match request.opcode() {
+                Opcode::Get => (0x00, 0, 0),
+                Opcode::Set => (0x01, 8, 5),
+                Opcode::Add => (0x02, 8, 5),
+                Opcode::Replace => (0x03, 8, 5),
+                Opcode::Delete => (0x04, 0, 0),
+            };
+
+            assert_eq!(header.magic, MAGIC_REQUEST);
+            assert_eq!(header.opcode, opcode);
+            assert_eq!(header.key_len, 3);
+            assert_eq!(header.extras_len, extras_len);
+            assert_eq!(header.body_len, extras_len + 3 + value_len);
+            assert_eq!(header.opaque, opaque as u32);
+            assert_eq!(header.cas, request.cas());
+            assert_eq!(buffer.len(), HEADER_LEN + header.body_len);
+
+            // the key follows the extras, and the value follows the key
+            let key = HEADER_LEN + extras_len;
+            assert_eq!(&buffer[key..key + 3], b"key");
+            assert_eq!(&buffer[key + 3..], &b"value"[..value_len]);
+
+            if extras_len > 0 {
+                // flags, then the expiration time
+                assert_eq!(buffer[HEADER_LEN..HEADER_LEN + 4], [0; 4]);
+                assert_eq!(
+                    u32::from_be_bytes(buffer[HEADER_LEN + 4..HEADER_LEN + 8].try_into().unwrap()),
+                    60
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn compose_cas() {
+        let request = Request::cas(
+            b"key".to_vec().into_boxed_slice(),
+            b"value".to_vec().into_boxed_slice(),
+            None,
+            0x0102_0304_0506_0708,
+        );
+        let buffer = compose(&request);
+
+        // a cas is a set with the cas value in the header
+        assert_eq!(header(&buffer).opcode, 0x01);
+        assert_eq!(header(&buffer).cas, 0x0102_0304_0506_0708);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (opaque, mut request) in requests().into_iter().enumerate() {
+            request.set_opaque(opaque as u32 + 1000);
+            let response = respond(&compose(&request), 0, 7);
+
+            let (parsed, consumed) = Response::parse(&response).unwrap();
+
+            assert_eq!(parsed.opcode, request.opcode());
+            assert_eq!(parsed.status, Status::NoError);
+            assert_eq!(parsed.opaque, opaque as u32 + 1000);
+            assert_eq!(parsed.cas, 7);
+            assert_eq!(consumed, response.len());
+        }
+    }
+
+    #[test]
+    fn parse_coalesced() {
+        let requests = requests();
+        let mut buffer = Vec::new();
+        for request in &requests {
+            buffer.extend_from_slice(&respond(&compose(request), 0, 0));
+        }
+
+        let mut offset = 0;
+        for request in &requests {
+            let (parsed, consumed) = Response::parse(&buffer[offset..]).unwrap();
+            assert_eq!(parsed.opcode, request.opcode());
+            offset += consumed;
+        }
+
+        assert_eq!(offset, buffer.len());
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let response = respond(&compose(&Request::get(b"key".to_vec().into())), 0, 0);
+
+        // a truncated header or body needs more data, and nothing is consumed
+        // until the whole response has arrived
+        for len in 0..response.len() {
+            let error = Response::parse(&response[..len]).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::WouldBlock, "length {len}");
+        }
+
+        assert_eq!(Response::parse(&response).unwrap().1, response.len());
+    }
+
+    #[test]
+    fn parse_bad_magic() {
+        let mut response = respond(&compose(&Request::delete(b"key".to_vec().into())), 0, 0);
+        response[0] = MAGIC_REQUEST;
+
+        let error = Response::parse(&response).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_bad_lengths() {
+        let mut response = respond(&compose(&Request::delete(b"key".to_vec().into())), 0, 0);
+        // a key which is longer than the body
+        response[3] = 1;
+
+        let error = Response::parse(&response).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_status() {
+        let request = compose(&Request::get(b"key".to_vec().into()));
+
+        for (code, status) in [
+            (0x0000, Status::NoError),
+            (0x0001, Status::KeyNotFound),
+            (0x0002, Status::KeyExists),
+            (0x0003, Status::ValueTooLarge),
+            (0x0004, Status::InvalidArguments),
+            (0x0005, Status::ItemNotStored),
+            (0x0081, Status::Other(0x0081)),
+            (0x0082, Status::Other(0x0082)),
+        ] {
+            let response = respond(&request, code, 0);
+            assert_eq!(Response::parse(&response).unwrap().0.status, status);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use crate::*;
 
 use ::momento::{MomentoError, MomentoErrorCode};
 use async_channel::Receiver;
+use metriken::AtomicHistogram;
+use ratelimit::Ratelimiter;
 use tokio::io::*;
 use tokio::runtime::Runtime;
 use tokio::time::{timeout, Duration};
@@ -11,12 +13,22 @@ use tokio::time::{timeout, Duration};
 use std::io::{Error, ErrorKind, Result};
 use std::time::Instant;
 
+mod grpc;
 mod http1;
 mod http2;
 mod memcache;
+mod memcache_binary;
 mod momento;
 mod ping;
+mod raw;
 mod redis;
+mod thrift;
+
+// the number of client tasks which may open their first connection, which the
+// connection ramp increases over time
+static RAMP_TARGET: AtomicUsize = AtomicUsize::new(usize::MAX);
+// the number of client tasks which have taken their place in the ramp
+static RAMP_SLOTS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn launch_clients(config: &Config, work_receiver: Receiver<WorkItem>) -> Option<Runtime> {
     debug!("Launching clients...");
@@ -30,7 +42,23 @@ pub fn launch_clients(config: &Config, work_receiver: Receiver<WorkItem>) -> Opt
         .build()
         .expect("failed to initialize tokio runtime");
 
+    RAMP_SLOTS.store(0, Ordering::Relaxed);
+
+    match config.client().unwrap().connection_ramp() {
+        Some(ramp) => {
+            RAMP_TARGET.store(0, Ordering::Relaxed);
+            let total = config.client().unwrap().poolsize() * config.target().endpoints().len();
+            client_rt.spawn(ramp_connections(ramp, total));
+        }
+        None => {
+            RAMP_TARGET.store(usize::MAX, Ordering::Relaxed);
+        }
+    }
+
     match config.general().protocol() {
+        Protocol::Grpc => {
+            clients::grpc::launch_tasks(&mut client_rt, config.clone(), work_receiver)
+        }
         Protocol::Http1 => {
             clients::http1::launch_tasks(&mut client_rt, config.clone(), work_receiver)
         }
@@ -40,15 +68,22 @@ pub fn launch_clients(config: &Config, work_receiver: Receiver<WorkItem>) -> Opt
         Protocol::Memcache => {
             clients::memcache::launch_tasks(&mut client_rt, config.clone(), work_receiver)
         }
+        Protocol::MemcacheBinary => {
+            clients::memcache_binary::launch_tasks(&mut client_rt, config.clone(), work_receiver)
+        }
         Protocol::Momento => {
             clients::momento::launch_tasks(&mut client_rt, config.clone(), work_receiver)
         }
         Protocol::Ping => {
             clients::ping::launch_tasks(&mut client_rt, config.clone(), work_receiver)
         }
+        Protocol::Raw => clients::raw::launch_tasks(&mut client_rt, config.clone(), work_receiver),
         Protocol::Resp => {
             clients::redis::launch_tasks(&mut client_rt, config.clone(), work_receiver)
         }
+        Protocol::Thrift => {
+            clients::thrift::launch_tasks(&mut client_rt, config.clone(), work_receiver)
+        }
         Protocol::Kafka => {
             error!("keyspace is not supported for the kafka protocol");
             std::process::exit(1);
@@ -62,6 +97,394 @@ pub fn launch_clients(config: &Config, work_receiver: Receiver<WorkItem>) -> Opt
     Some(client_rt)
 }
 
+/// The histograms which the latency of a successful response is recorded in,
+/// in addition to the aggregate latency histogram.
+#[derive(Clone, Copy, Default)]
+pub struct LatencyHistograms {
+    command: Option<&'static AtomicHistogram>,
+    class: Option<&'static AtomicHistogram>,
+}
+
+impl LatencyHistograms {
+    /// Records the latency for the command only.
+    pub fn command(histogram: &'static AtomicHistogram) -> Self {
+        Self {
+            command: Some(histogram),
+            class: None,
+        }
+    }
+}
+
+/// Returns the latency histograms for the command and the value size class of
+/// a work item. Determined before the request is sent, so that the work item
+/// can be consumed.
+pub fn latency_histograms(work_item: &WorkItem) -> LatencyHistograms {
+    match work_item {
+        WorkItem::Request { request, class, .. } => LatencyHistograms {
+            command: command_latency(request),
+            class: workload::value_class_latency(*class),
+        },
+        WorkItem::Reconnect => LatencyHistograms::default(),
+    }
+}
+
+// the latency histogram for the command of a request
+fn command_latency(request: &ClientRequest) -> Option<&'static AtomicHistogram> {
+    let histogram: &'static AtomicHistogram = match request {
+        ClientRequest::Ping(_) => &PING_LATENCY,
+        ClientRequest::Add(_) => &ADD_LATENCY,
+        ClientRequest::Get(_) => &GET_LATENCY,
+        ClientRequest::Delete(_) => &DELETE_LATENCY,
+        ClientRequest::MultiGet(_) => &MULTI_GET_LATENCY,
+        ClientRequest::MultiSet(_) => &MULTI_SET_LATENCY,
+        ClientRequest::Replace(_) => &REPLACE_LATENCY,
+        ClientRequest::Set(_) => &SET_LATENCY,
+        ClientRequest::Cas(_) => &CAS_LATENCY,
+        ClientRequest::Scan(_) => &SCAN_LATENCY,
+        ClientRequest::HashExists(_) => &HASH_EXISTS_LATENCY,
+        ClientRequest::HashDelete(_) => &HASH_DELETE_LATENCY,
+        ClientRequest::HashGet(_) => &HASH_GET_LATENCY,
+        ClientRequest::HashGetAll(_) => &HASH_GET_ALL_LATENCY,
+        ClientRequest::HashIncrement(_) => &HASH_INCR_LATENCY,
+        ClientRequest::HashSet(_) => &HASH_SET_LATENCY,
+        ClientRequest::ListFetch(_) => &LIST_FETCH_LATENCY,
+        ClientRequest::ListLength(_) => &LIST_LENGTH_LATENCY,
+        ClientRequest::ListPopBack(_) => &LIST_POP_BACK_LATENCY,
+        ClientRequest::ListPopFront(_) => &LIST_POP_FRONT_LATENCY,
+        ClientRequest::ListPushBack(_) => &LIST_PUSH_BACK_LATENCY,
+        ClientRequest::ListPushFront(_) => &LIST_PUSH_FRONT_LATENCY,
+        ClientRequest::ListRange(_) => &LIST_RANGE_LATENCY,
+        ClientRequest::ListRemove(_) => &LIST_REMOVE_LATENCY,
+        ClientRequest::ListStore(_) => &LIST_STORE_LATENCY,
+        ClientRequest::SetAdd(_) => &SET_ADD_LATENCY,
+        ClientRequest::SetMembers(_) => &SET_MEMBERS_LATENCY,
+        ClientRequest::SetRemove(_) => &SET_REMOVE_LATENCY,
+        ClientRequest::SortedSetAdd(_) => &SORTED_SET_ADD_LATENCY,
+        ClientRequest::SortedSetIncrement(_) => &SORTED_SET_INCR_LATENCY,
+        ClientRequest::SortedSetRange(_) => &SORTED_SET_RANGE_LATENCY,
+        ClientRequest::SortedSetRank(_) => &SORTED_SET_RANK_LATENCY,
+        ClientRequest::SortedSetRemove(_) => &SORTED_SET_REMOVE_LATENCY,
+        ClientRequest::SortedSetScore(_) => &SORTED_SET_SCORE_LATENCY,
+        ClientRequest::Reconnect => return None,
+    };
+
+    Some(histogram)
+}
+
+/// Records the latency of a successful response in the aggregate, the
+/// per-command, and the per-class latency histograms.
+pub fn record_latency(histograms: LatencyHistograms, latency_ns: u64) {
+    // the latencies of the preload are not part of the results
+    if workload::PRELOADING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let _ = RESPONSE_LATENCY.increment(latency_ns);
+
+    for histogram in [histograms.command, histograms.class].into_iter().flatten() {
+        let _ = histogram.increment(latency_ns);
+    }
+}
+
+/// Returns when the request of a work item was intended to be sent, if the
+/// corrected latency is recorded. Determined before the request is sent, so
+/// that the work item can be consumed.
+pub fn intended_start(work_item: &WorkItem) -> Option<Instant> {
+    match work_item {
+        WorkItem::Request { intended, .. } => *intended,
+        WorkItem::Reconnect => None,
+    }
+}
+
+/// Records the latency from when the request was intended to be sent, which
+/// includes the time it waited before being sent.
+pub fn record_corrected_latency(intended: Option<Instant>, stop: Instant) {
+    if let Some(intended) = intended {
+        let latency_ns = stop.saturating_duration_since(intended).as_nanos() as u64;
+        let _ = RESPONSE_LATENCY_CORRECTED.increment(latency_ns);
+    }
+}
+
+/// Exponential backoff between connection attempts.
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    jitter: f64,
+    max_retries: Option<usize>,
+    attempts: usize,
+}
+
+impl Backoff {
+    pub fn new(config: &config::Client) -> Self {
+        Self {
+            initial: config.reconnect_backoff_initial(),
+            max: config.reconnect_backoff_max(),
+            jitter: config.reconnect_backoff_jitter(),
+            max_retries: config.reconnect_max_retries(),
+            attempts: 0,
+        }
+    }
+
+    /// Called once a connection is established so that the next failure
+    /// starts with the initial delay.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Waits before the next connection attempt. Returns an error if the
+    /// maximum number of retries has been exhausted, in which case the
+    /// connection should be abandoned.
+    pub async fn wait(&mut self) -> Result<()> {
+        if let Some(max_retries) = self.max_retries {
+            if self.attempts >= max_retries {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "exceeded the maximum number of reconnect attempts",
+                ));
+            }
+        }
+
+        let delay = self.delay(rand::random::<f64>());
+
+        self.attempts += 1;
+
+        CONNECT_BACKOFF.increment();
+
+        tokio::time::sleep(delay).await;
+
+        Ok(())
+    }
+
+    /// The delay before the next attempt, which doubles with each attempt up
+    /// to the maximum. The random value in `[0, 1)` picks where the delay
+    /// falls within the jitter.
+    fn delay(&self, random: f64) -> Duration {
+        let delay = self
+            .initial
+            .saturating_mul(1 << std::cmp::min(self.attempts, 31) as u32)
+            .min(self.max);
+
+        // the jitter spreads the delay evenly over +/- the jitter fraction
+        delay.mul_f64(1.0 + self.jitter * (2.0 * random - 1.0))
+    }
+}
+
+/// Allows `step` more client tasks to connect each interval until all of the
+/// connections are allowed.
+async fn ramp_connections(ramp: config::ConnectionRamp, total: usize) {
+    let mut target = 0;
+
+    while RUNNING.load(Ordering::Relaxed) && target < total {
+        target = std::cmp::min(target + ramp.step(), total);
+
+        RAMP_TARGET.store(target, Ordering::Relaxed);
+        CONNECT_TARGET.set(target as i64);
+
+        if target < total {
+            tokio::time::sleep(ramp.interval()).await;
+        }
+    }
+}
+
+/// Waits until the connection ramp allows the task to open its first
+/// connection. Later connection attempts, including the retries for a first
+/// connection which fails, are only delayed by the backoff.
+pub async fn wait_for_ramp() {
+    let slot = RAMP_SLOTS.fetch_add(1, Ordering::Relaxed);
+
+    while RUNNING.load(Ordering::Relaxed) && RAMP_TARGET.load(Ordering::Relaxed) <= slot {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Limits the rate of requests on a single connection. This is layered under
+/// the workload ratelimit, which limits the rate across all connections.
+pub struct ConnectionRatelimit {
+    ratelimiter: Option<Ratelimiter>,
+}
+
+impl ConnectionRatelimit {
+    pub fn new(config: &config::Client) -> Self {
+        let ratelimiter = config.per_connection_rate().map(|rate| {
+            let rate = rate.get();
+            let amount = (rate as f64 / 1_000_000.0).ceil() as u64;
+
+            // even though we might not have nanosecond level clock resolution,
+            // by using a nanosecond level duration, we achieve more accurate
+            // ratelimits.
+            let interval = Duration::from_nanos(1_000_000_000 / (rate / amount));
+
+            Ratelimiter::builder(amount, interval)
+                .max_tokens(amount)
+                .build()
+                .expect("failed to initialize ratelimiter")
+        });
+
+        Self { ratelimiter }
+    }
+
+    /// Waits until the connection may send another request. The wait happens
+    /// before a work item is taken from the queue, so any other connection
+    /// which is permitted to send may take it instead.
+    pub async fn wait(&self) {
+        let ratelimiter = match &self.ratelimiter {
+            Some(ratelimiter) => ratelimiter,
+            None => return,
+        };
+
+        let mut delayed = false;
+
+        while let Err(delay) = ratelimiter.try_wait() {
+            if !delayed {
+                RATELIMIT_CONNECTION_DELAYED.increment();
+                delayed = true;
+            }
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Tracks the lifetime of a connection, so that it can be replaced once it
+/// has served the configured number of requests or been open long enough.
+pub struct Lifetime {
+    requests: Option<usize>,
+    duration: Option<Duration>,
+    served: usize,
+    opened: Instant,
+}
+
+impl Lifetime {
+    pub fn new(config: &config::Client) -> Self {
+        let lifetime = config.connection_lifetime();
+
+        Self {
+            requests: lifetime.and_then(|lifetime| lifetime.requests()),
+            duration: lifetime.and_then(|lifetime| lifetime.duration()),
+            served: 0,
+            opened: Instant::now(),
+        }
+    }
+
+    /// Starts the lifetime of a newly opened connection.
+    pub fn open(&mut self) {
+        self.served = 0;
+        self.opened = Instant::now();
+    }
+
+    /// Counts a request which was sent on the connection.
+    pub fn request(&mut self) {
+        self.served += 1;
+    }
+
+    /// Returns true once the connection has reached the end of its lifetime.
+    pub fn expired(&self) -> bool {
+        self.requests
+            .is_some_and(|requests| self.served >= requests)
+            || self
+                .duration
+                .is_some_and(|duration| self.opened.elapsed() >= duration)
+    }
+}
+
+/// The most times a request is sent, including the first attempt, when the
+/// failed requests for its command are retried.
+pub fn attempts(config: &config::Client, request: &ClientRequest) -> usize {
+    match (config.retry(), verb(request)) {
+        (Some(retry), Some(verb)) if retry.retries(verb) => retry.max_attempts(),
+        _ => 1,
+    }
+}
+
+// the command of a request, for the requests which are generated from one
+fn verb(request: &ClientRequest) -> Option<config::Verb> {
+    use config::Verb;
+
+    let verb = match request {
+        ClientRequest::Ping(_) => Verb::Ping,
+        ClientRequest::Add(_) => Verb::Add,
+        ClientRequest::Get(_) | ClientRequest::MultiGet(_) => Verb::Get,
+        ClientRequest::Delete(_) => Verb::Delete,
+        ClientRequest::Set(_) | ClientRequest::MultiSet(_) => Verb::Set,
+        ClientRequest::Replace(_) => Verb::Replace,
+        ClientRequest::Cas(_) => Verb::Cas,
+        ClientRequest::Scan(_) => Verb::Scan,
+        ClientRequest::HashExists(_) => Verb::HashExists,
+        ClientRequest::HashDelete(_) => Verb::HashDelete,
+        ClientRequest::HashGet(_) => Verb::HashGet,
+        ClientRequest::HashGetAll(_) => Verb::HashGetAll,
+        ClientRequest::HashIncrement(_) => Verb::HashIncrement,
+        ClientRequest::HashSet(_) => Verb::HashSet,
+        ClientRequest::ListFetch(_) => Verb::ListFetch,
+        ClientRequest::ListLength(_) => Verb::ListLength,
+        ClientRequest::ListPopBack(_) => Verb::ListPopBack,
+        ClientRequest::ListPopFront(_) => Verb::ListPopFront,
+        ClientRequest::ListPushBack(_) => Verb::ListPushBack,
+        ClientRequest::ListPushFront(_) => Verb::ListPushFront,
+        ClientRequest::ListRemove(_) => Verb::ListRemove,
+        ClientRequest::SetAdd(_) => Verb::SetAdd,
+        ClientRequest::SetMembers(_) => Verb::SetMembers,
+        ClientRequest::SetRemove(_) => Verb::SetRemove,
+        ClientRequest::SortedSetAdd(_) => Verb::SortedSetAdd,
+        ClientRequest::SortedSetIncrement(_) => Verb::SortedSetIncrement,
+        ClientRequest::SortedSetRange(_) => Verb::SortedSetRange,
+        ClientRequest::SortedSetRank(_) => Verb::SortedSetRank,
+        ClientRequest::SortedSetRemove(_) => Verb::SortedSetRemove,
+        ClientRequest::SortedSetScore(_) => Verb::SortedSetScore,
+        ClientRequest::ListRange(_) | ClientRequest::ListStore(_) | ClientRequest::Reconnect => {
+            return None
+        }
+    };
+
+    Some(verb)
+}
+
+/// Records the size of a response which was received, in bytes. The size is
+/// what was read for the response, so it includes the framing of the
+/// protocol.
+pub fn record_response_size(bytes: usize) {
+    RESPONSE_BYTES.add(bytes as u64);
+    let _ = RESPONSE_SIZE.increment(bytes as u64);
+}
+
+/// Records how long it took to open a connection, from when the attempt
+/// started, and logs the connect event.
+pub fn record_connect(endpoint: &str, start: Instant) {
+    let latency = start.elapsed().as_nanos() as u64;
+    let _ = CONNECT_LATENCY.increment(latency);
+
+    debug!(
+        event = "connect",
+        endpoint = endpoint,
+        latency_us = latency / 1000;
+        "connected to {endpoint}"
+    );
+}
+
+/// The categories of errors, which are each counted separately so that
+/// failures on the client side can be told apart from those on the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// A connection attempt failed, or the connection failed while a request
+    /// was outstanding.
+    Connection,
+    /// The response could not be parsed or was unexpected for the request.
+    Protocol,
+    /// The server replied with an error, such as `-ERR` for resp.
+    Server,
+    /// The response was not received in time.
+    Timeout,
+}
+
+pub fn record_error(category: ErrorCategory) {
+    match category {
+        ErrorCategory::Connection => ERROR_CONNECTION.increment(),
+        ErrorCategory::Protocol => ERROR_PROTOCOL.increment(),
+        ErrorCategory::Server => ERROR_SERVER.increment(),
+        ErrorCategory::Timeout => ERROR_TIMEOUT.increment(),
+    };
+}
+
 pub enum ResponseError {
     /// Some exception while reading the response
     Exception,
@@ -78,7 +501,195 @@ impl From<MomentoError> for ResponseError {
         match other.error_code {
             MomentoErrorCode::LimitExceededError { .. } => ResponseError::Ratelimited,
             MomentoErrorCode::TimeoutError { .. } => ResponseError::BackendTimeout,
-            _ => ResponseError::Exception,
+            MomentoErrorCode::ConnectionError { .. } => {
+                record_error(ErrorCategory::Connection);
+                ResponseError::Exception
+            }
+            _ => {
+                record_error(ErrorCategory::Server);
+                ResponseError::Exception
+            }
+        }
+    }
+}
+
+/// Helpers for the tests which run a client task against a local server.
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use std::sync::Arc;
+
+    /// The config for a client of the protocol with a single connection to
+    /// the endpoint. The extra settings are added to the `[client]` section,
+    /// and may be followed by other sections.
+    pub fn config(protocol: &str, endpoint: &str, extra: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [general]
+            protocol = "{protocol}"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            initial_seed = "0"
+
+            [debug]
+
+            [target]
+            endpoints = ["{endpoint}"]
+
+            [client]
+            threads = 1
+            poolsize = 1
+            connect_timeout = 1000
+            request_timeout = 1000
+            reconnect_backoff_initial = 10
+            {extra}
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+            start = 1000
+            "#
+        ))
+        .unwrap()
+    }
+
+    pub fn request(request: ClientRequest) -> WorkItem {
+        WorkItem::Request {
+            request,
+            sequence: 0,
+            intended: None,
+            class: None,
+        }
+    }
+
+    pub fn get(key: &str) -> WorkItem {
+        request(ClientRequest::Get(workload::client::Get {
+            key: Arc::from(key.as_bytes()),
+            verify: false,
+        }))
+    }
+
+    /// The number of latencies recorded in the histogram.
+    pub fn count(histogram: &AtomicHistogram) -> u64 {
+        histogram
+            .load()
+            .map(|histogram| histogram.into_iter().map(|bucket| bucket.count()).sum())
+            .unwrap_or(0)
+    }
+
+    /// Waits for the condition to hold, which is checked periodically as the
+    /// client tasks record their results in the background.
+    pub async fn eventually(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        while !condition() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for the condition"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff(jitter: f64, max_retries: Option<usize>) -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(100),
+            jitter,
+            max_retries,
+            attempts: 0,
+        }
+    }
+
+    // the delay in milliseconds, rounded to hide the floating point jitter
+    fn millis(delay: Duration) -> u64 {
+        (delay.as_secs_f64() * 1000.0).round() as u64
+    }
+
+    #[test]
+    fn backoff_delays() {
+        let mut backoff = backoff(0.0, None);
+
+        // the delay doubles with each attempt until it reaches the maximum
+        let mut delays = Vec::new();
+        for _ in 0..6 {
+            delays.push(millis(backoff.delay(0.5)));
+            backoff.attempts += 1;
+        }
+        assert_eq!(delays, [10, 20, 40, 80, 100, 100]);
+
+        // an established connection starts over from the initial delay
+        backoff.reset();
+        assert_eq!(millis(backoff.delay(0.5)), 10);
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        let mut backoff = backoff(0.5, None);
+        backoff.attempts = 2;
+
+        assert_eq!(millis(backoff.delay(0.0)), 20);
+        assert_eq!(millis(backoff.delay(0.5)), 40);
+        assert_eq!(millis(backoff.delay(0.999)), 60);
+    }
+
+    #[tokio::test]
+    async fn backoff_waits() {
+        let mut backoff = backoff(0.0, Some(4));
+
+        // each wait is longer than the one before
+        for expected in [10, 20, 40, 80] {
+            let start = Instant::now();
+            backoff.wait().await.unwrap();
+            assert!(millis(start.elapsed()) >= expected);
+        }
+
+        // until the retries are exhausted
+        assert!(backoff.wait().await.is_err());
+    }
+
+    fn retry_attempts(retry: &str, request: ClientRequest) -> usize {
+        let config = testing::config("resp", "127.0.0.1:6379", retry);
+        attempts(config.client().unwrap(), &request)
+    }
+
+    fn set() -> ClientRequest {
+        ClientRequest::Set(workload::client::Set {
+            key: b"key"[..].into(),
+            value: b"value".to_vec(),
+            ttl: None,
+        })
+    }
+
+    #[test]
+    fn retry_commands() {
+        let get = || {
+            ClientRequest::Get(workload::client::Get {
+                key: b"key"[..].into(),
+                verify: false,
+            })
+        };
+
+        // without a retry policy each request is sent once
+        assert_eq!(retry_attempts("", get()), 1);
+
+        // by default only the commands which read are retried
+        let retry = "[client.retry]\nmax_attempts = 3";
+        assert_eq!(retry_attempts(retry, get()), 3);
+        assert_eq!(retry_attempts(retry, set()), 1);
+        assert_eq!(retry_attempts(retry, ClientRequest::Reconnect), 1);
+
+        // a write is only retried when it is listed
+        let retry =
+            "[client.retry]\nmax_attempts = 2\ncommands = [\"set\"]\nallow_non_idempotent = true";
+        assert_eq!(retry_attempts(retry, set()), 2);
+        assert_eq!(retry_attempts(retry, get()), 1);
+    }
+}
@@ -72,6 +72,8 @@ async fn task(
             .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
 
         REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
         let start = Instant::now();
         let result = match work_item {
             WorkItem::Request { request, .. } => match request {
@@ -178,13 +180,15 @@ async fn task(
 
                 let latency = stop.duration_since(start).as_nanos() as u64;
 
-                let _ = RESPONSE_LATENCY.increment(latency);
+                record_latency(histograms, latency);
+                record_corrected_latency(intended, stop);
             }
             Err(ResponseError::Exception) => {
                 RESPONSE_EX.increment();
             }
             Err(ResponseError::Timeout) => {
                 RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
             }
             Err(ResponseError::Ratelimited) => {
                 RESPONSE_RATELIMITED.increment();
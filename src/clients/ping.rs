@@ -10,8 +10,11 @@ pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiv
 
     // create one task per "connection"
     // note: these may be channels instead of connections for multiplexed protocols
-    for _ in 0..config.client().unwrap().poolsize() {
-        for endpoint in config.target().endpoints() {
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
             runtime.spawn(task(
                 work_receiver.clone(),
                 endpoint.clone(),
@@ -29,15 +32,28 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
     // this unwrap will succeed because we wouldn't be creating these tasks if
     // there wasn't a client config.
     let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
+
+    let mode = config.ping().map(|ping| ping.mode()).unwrap_or_default();
+    let payload = config
+        .ping()
+        .and_then(|ping| ping.payload())
+        .unwrap_or("PING");
+    let mut token: u64 = 0;
 
     let mut stream = None;
     let parser = protocol_ping::ResponseParser::new();
     let mut read_buffer = Buffer::new(client_config.read_buffer_size());
     let mut write_buffer = Buffer::new(client_config.write_buffer_size());
 
+    wait_for_ramp().await;
+
     while RUNNING.load(Ordering::Relaxed) {
         if stream.is_none() {
             CONNECT.increment();
+            let start = Instant::now();
             stream = match timeout(
                 client_config.connect_timeout(),
                 connector.connect(&endpoint),
@@ -47,16 +63,21 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 Ok(Ok(s)) => {
                     CONNECT_OK.increment();
                     CONNECT_CURR.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
                     Some(s)
                 }
                 Ok(Err(_)) => {
                     CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
                 Err(_) => {
                     CONNECT_TIMEOUT.increment();
-                    sleep(Duration::from_millis(100)).await;
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
                     continue;
                 }
             }
@@ -64,19 +85,48 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
         let mut s = stream.take().unwrap();
 
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
         let work_item = work_receiver
             .recv()
             .await
             .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
 
         REQUEST.increment();
+        let intended = intended_start(&work_item);
 
-        // compose request into buffer
-        match &work_item {
+        // compose request into buffer, for the line based modes we keep the
+        // expected response line
+        let expected: Option<Vec<u8>> = match &work_item {
             WorkItem::Request { request, .. } => match request {
-                ClientRequest::Ping(_) => {
-                    Request::Ping.compose(&mut write_buffer);
-                }
+                ClientRequest::Ping(_) => match mode {
+                    PingMode::Ping => {
+                        Request::Ping.compose(&mut write_buffer);
+                        None
+                    }
+                    PingMode::Custom => {
+                        write_buffer.put_slice(payload.as_bytes());
+                        write_buffer.put_slice(b"\r\n");
+                        config
+                            .ping()
+                            .and_then(|ping| ping.response())
+                            .map(|response| response.as_bytes().to_vec())
+                    }
+                    PingMode::Pingpong => {
+                        token += 1;
+                        let line = format!("{payload} {token}").into_bytes();
+                        write_buffer.put_slice(&line);
+                        write_buffer.put_slice(b"\r\n");
+                        Some(line)
+                    }
+                },
                 _ => {
                     REQUEST_UNSUPPORTED.increment();
                     stream = Some(s);
@@ -87,9 +137,10 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 REQUEST_RECONNECT.increment();
                 continue;
             }
-        }
+        };
 
         REQUEST_OK.increment();
+        lifetime.request();
 
         // send request
         let start = Instant::now();
@@ -105,18 +156,18 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
             )
             .await
             {
+                Ok(Ok(0)) => {
+                    // the connection was closed by the server
+                    record_error(ErrorCategory::Connection);
+                    break Err(ResponseError::Exception);
+                }
                 Ok(Ok(n)) => {
                     unsafe {
                         read_buffer.advance_mut(n);
                     }
-                    match parser.parse(read_buffer.borrow()) {
-                        Ok(resp) => {
-                            let consumed = resp.consumed();
-                            let resp = resp.into_inner();
-
-                            read_buffer.advance(consumed);
-
-                            break Ok(resp);
+                    match parse(&parser, &mut read_buffer, expected.as_deref()) {
+                        Ok(matched) => {
+                            break Ok(matched);
                         }
                         Err(e) => match e.kind() {
                             ErrorKind::WouldBlock => {
@@ -127,12 +178,14 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                                 }
                             }
                             _ => {
+                                record_error(ErrorCategory::Protocol);
                                 break Err(ResponseError::Exception);
                             }
                         },
                     }
                 }
                 Ok(Err(_)) => {
+                    record_error(ErrorCategory::Connection);
                     break Err(ResponseError::Exception);
                 }
                 Err(_) => {
@@ -148,11 +201,20 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
                 // validate response
                 match work_item {
                     WorkItem::Request { request, .. } => match request {
-                        ClientRequest::Ping(_) => match response {
-                            Response::Pong => {
+                        ClientRequest::Ping(_) => {
+                            if response {
                                 PING_OK.increment();
+                            } else {
+                                // the framing is intact, so the connection
+                                // can still be reused
+                                PING_EX.increment();
+                                PING_MISMATCH.increment();
+                                record_error(ErrorCategory::Protocol);
+                                RESPONSE_EX.increment();
+                                stream = Some(s);
+                                continue;
                             }
-                        },
+                        }
                         _ => {
                             error!("unexpected request");
                             unimplemented!();
@@ -171,7 +233,8 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
                 let latency = stop.duration_since(start).as_nanos() as u64;
 
-                let _ = RESPONSE_LATENCY.increment(latency);
+                record_latency(LatencyHistograms::command(&PING_LATENCY), latency);
+                record_corrected_latency(intended, stop);
             }
             Err(ResponseError::Exception) => {
                 // record execption
@@ -195,6 +258,7 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
             }
             Err(ResponseError::Timeout) => {
                 RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
                 CONNECT_CURR.sub(1);
             }
             Err(ResponseError::Ratelimited) | Err(ResponseError::BackendTimeout) => {
@@ -205,3 +269,145 @@ async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Confi
 
     Ok(())
 }
+
+/// Parses a response from the buffer. Returns whether the response was the
+/// expected one, or a `WouldBlock` error if the response is incomplete. Without
+/// an expected line, the response is parsed with the ping protocol.
+fn parse(
+    parser: &protocol_ping::ResponseParser,
+    buffer: &mut Buffer,
+    expected: Option<&[u8]>,
+) -> Result<bool> {
+    match expected {
+        None => {
+            let response = parser.parse(buffer.borrow())?;
+            let consumed = response.consumed();
+            let matched = matches!(response.into_inner(), Response::Pong);
+
+            buffer.advance(consumed);
+            record_response_size(consumed);
+
+            Ok(matched)
+        }
+        Some(expected) => {
+            let data: &[u8] = buffer.borrow();
+            let end = data
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .ok_or_else(|| Error::from(ErrorKind::WouldBlock))?;
+            let matched = &data[..end] == expected;
+
+            buffer.advance(end + 2);
+            record_response_size(end + 2);
+
+            Ok(matched)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn ping() -> WorkItem {
+        testing::request(ClientRequest::Ping(workload::client::Ping {}))
+    }
+
+    async fn serve(stream: TcpStream, requests: usize) {
+        let mut stream = BufReader::new(stream);
+
+        for _ in 0..requests {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "PING\r\n");
+            stream.get_mut().write_all(b"PONG\r\n").await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = testing::config("ping", &addr.to_string(), "");
+
+        let (sender, receiver) = async_channel::bounded(16);
+        for _ in 0..5 {
+            sender.send(ping()).await.unwrap();
+        }
+        tokio::spawn(task(receiver, addr.to_string(), config));
+
+        // the server stops listening and closes the connection after two
+        // requests, so the third request fails
+        let stream = listener.accept().await.unwrap().0;
+        drop(listener);
+        let backoff = CONNECT_BACKOFF.value();
+        serve(stream, 2).await;
+
+        // the refused connection attempts back off until the server listens
+        // again, after 10 + 20 + 40ms
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let listener = TcpListener::bind(addr).await.unwrap();
+        assert!(CONNECT_BACKOFF.value() >= backoff + 3);
+
+        // then the remaining requests are sent on a new connection
+        let stream = listener.accept().await.unwrap().0;
+        serve(stream, 2).await;
+    }
+
+    // the latency at the percentile of the responses recorded since the
+    // histogram was loaded before
+    fn percentile(
+        histogram: &AtomicHistogram,
+        before: &Option<histogram::Histogram>,
+        percentile: f64,
+    ) -> u64 {
+        let current = histogram.load().unwrap();
+        let delta = match before {
+            Some(before) => current.wrapping_sub(before).unwrap(),
+            None => current,
+        };
+
+        delta.percentiles(&[percentile]).unwrap().unwrap()[0]
+            .1
+            .end()
+    }
+
+    #[tokio::test]
+    async fn corrected_latency() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = testing::config("ping", &addr.to_string(), "");
+
+        let raw = PING_LATENCY.load();
+        let corrected = RESPONSE_LATENCY_CORRECTED.load();
+        let count = testing::count(&RESPONSE_LATENCY_CORRECTED);
+
+        // the requests are due now, but the client stalls before sending them
+        let (sender, receiver) = async_channel::bounded(16);
+        let intended = Instant::now();
+        for _ in 0..5 {
+            sender
+                .send(WorkItem::Request {
+                    request: ClientRequest::Ping(workload::client::Ping {}),
+                    sequence: 0,
+                    intended: Some(intended),
+                    class: None,
+                })
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        tokio::spawn(task(receiver, addr.to_string(), config));
+        let stream = listener.accept().await.unwrap().0;
+        serve(stream, 5).await;
+        testing::eventually(|| testing::count(&RESPONSE_LATENCY_CORRECTED) >= count + 5).await;
+
+        // the stall is only included in the corrected latency
+        let stall = Duration::from_millis(200).as_nanos() as u64;
+        assert!(percentile(&RESPONSE_LATENCY_CORRECTED, &corrected, 0.0) >= stall);
+        assert!(percentile(&PING_LATENCY, &raw, 100.0) < stall);
+    }
+}
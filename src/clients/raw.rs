@@ -0,0 +1,316 @@
+use super::*;
+use crate::net::Connector;
+use rand::distributions::{Alphanumeric, DistString};
+use session::{Buf, BufMut, Buffer};
+use std::borrow::{Borrow, BorrowMut};
+
+/// Launch tasks with one connection per task, as each connection has a single
+/// command outstanding. Each request generated by the workload is sent as the
+/// command from the template, filled in with the key and value of the request.
+pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
+    debug!("launching raw protocol tasks");
+
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            runtime.spawn(task(
+                work_receiver.clone(),
+                endpoint.clone(),
+                config.clone(),
+            ));
+        }
+    }
+}
+
+// a task for servers with a line based or other custom protocol, where each
+// response ends with the terminator
+async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Config) -> Result<()> {
+    let connector = Connector::new(&config)?;
+
+    // this unwrap will succeed because we wouldn't be creating these tasks if
+    // there wasn't a client config.
+    let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
+
+    // config validation ensures that the raw section is present
+    let raw = config.raw().unwrap();
+    let template = raw.template();
+    let terminator = raw.terminator();
+
+    let mut stream = None;
+    let mut read_buffer = Buffer::new(client_config.read_buffer_size());
+    let mut write_buffer = Buffer::new(client_config.write_buffer_size());
+
+    wait_for_ramp().await;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if stream.is_none() {
+            CONNECT.increment();
+            let start = Instant::now();
+            stream = match timeout(
+                client_config.connect_timeout(),
+                connector.connect(&endpoint),
+            )
+            .await
+            {
+                Ok(Ok(s)) => {
+                    CONNECT_OK.increment();
+                    CONNECT_CURR.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
+                    read_buffer.clear();
+                    Some(s)
+                }
+                Ok(Err(_)) => {
+                    CONNECT_EX.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+                Err(_) => {
+                    CONNECT_TIMEOUT.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+            }
+        }
+
+        let mut s = stream.take().unwrap();
+
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
+
+        match &work_item {
+            WorkItem::Request { request, .. } => match compose(&template, request) {
+                Some(command) => write_buffer.put_slice(&command),
+                None => {
+                    REQUEST_UNSUPPORTED.increment();
+                    stream = Some(s);
+                    continue;
+                }
+            },
+            WorkItem::Reconnect => {
+                REQUEST_RECONNECT.increment();
+                CONNECT_CURR.decrement();
+                continue;
+            }
+        }
+
+        REQUEST_OK.increment();
+        lifetime.request();
+
+        // send request
+        let start = Instant::now();
+        s.write_all(write_buffer.borrow()).await?;
+        write_buffer.clear();
+
+        // read until the response is complete or the request times out
+        let deadline = start + client_config.request_timeout();
+        let response = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                break Err(ResponseError::Timeout);
+            }
+
+            match timeout(remaining, s.read(read_buffer.borrow_mut())).await {
+                Ok(Ok(0)) => {
+                    record_error(ErrorCategory::Connection);
+                    break Err(ResponseError::Exception);
+                }
+                Ok(Ok(n)) => {
+                    unsafe {
+                        read_buffer.advance_mut(n);
+                    }
+                    if let Some(consumed) = parse(read_buffer.borrow(), terminator) {
+                        read_buffer.advance(consumed);
+                        record_response_size(consumed);
+                        break Ok(());
+                    }
+                }
+                Ok(Err(_)) => {
+                    record_error(ErrorCategory::Connection);
+                    break Err(ResponseError::Exception);
+                }
+                Err(_) => {
+                    break Err(ResponseError::Timeout);
+                }
+            }
+        };
+
+        let stop = Instant::now();
+
+        match response {
+            Ok(()) => {
+                // preserve the connection for reuse
+                stream = Some(s);
+
+                RESPONSE_OK.increment();
+
+                let latency = stop.duration_since(start).as_nanos() as u64;
+
+                record_latency(histograms, latency);
+                record_corrected_latency(intended, stop);
+            }
+            Err(ResponseError::Timeout) => {
+                RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
+                CONNECT_CURR.decrement();
+            }
+            // a raw response is only matched by its terminator, so any error
+            // other than a timeout is an exception
+            Err(_) => {
+                RESPONSE_EX.increment();
+                CONNECT_CURR.decrement();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// fills in the template with the key and value of the request. The key and
+// value are copied as bytes, so they need not be utf-8. Returns `None` for the
+// requests which do not have a single key.
+fn compose(template: &[TemplatePart], request: &ClientRequest) -> Option<Vec<u8>> {
+    let (key, value): (&[u8], &[u8]) = match request {
+        ClientRequest::Get(r) => (&r.key, &[]),
+        ClientRequest::Delete(r) => (&r.key, &[]),
+        ClientRequest::Add(r) => (&r.key, &r.value),
+        ClientRequest::Replace(r) => (&r.key, &r.value),
+        ClientRequest::Set(r) => (&r.key, &r.value),
+        _ => return None,
+    };
+
+    let mut command = Vec::new();
+
+    for part in template {
+        match part {
+            TemplatePart::Literal(bytes) => command.extend_from_slice(bytes),
+            TemplatePart::Key => command.extend_from_slice(key),
+            TemplatePart::Value => command.extend_from_slice(value),
+            TemplatePart::Vlen => command.extend_from_slice(value.len().to_string().as_bytes()),
+            TemplatePart::Rand(len) => {
+                let random = Alphanumeric.sample_string(&mut rand::thread_rng(), *len);
+                command.extend_from_slice(random.as_bytes());
+            }
+        }
+    }
+
+    Some(command)
+}
+
+// returns the length of the response, including the terminator, once the
+// buffer holds all of it
+fn parse(buffer: &[u8], terminator: &[u8]) -> Option<usize> {
+    buffer
+        .windows(terminator.len())
+        .position(|window| window == terminator)
+        .map(|position| position + terminator.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workload::client::{Delete, Get, Ping, Set};
+
+    fn set(key: &[u8], value: &[u8]) -> ClientRequest {
+        ClientRequest::Set(Set {
+            key: key.into(),
+            value: value.to_vec(),
+            ttl: None,
+        })
+    }
+
+    #[test]
+    fn compose_key() {
+        let template = [
+            TemplatePart::Literal(b"get ".to_vec()),
+            TemplatePart::Key,
+            TemplatePart::Literal(b"\r\n".to_vec()),
+        ];
+
+        let get = ClientRequest::Get(Get {
+            key: b"abc".as_slice().into(),
+            verify: false,
+        });
+        assert_eq!(compose(&template, &get), Some(b"get abc\r\n".to_vec()));
+
+        let delete = ClientRequest::Delete(Delete {
+            key: b"abc".as_slice().into(),
+        });
+        assert_eq!(compose(&template, &delete), Some(b"get abc\r\n".to_vec()));
+    }
+
+    #[test]
+    fn compose_value() {
+        let template = [
+            TemplatePart::Literal(b"set ".to_vec()),
+            TemplatePart::Key,
+            TemplatePart::Literal(b" ".to_vec()),
+            TemplatePart::Vlen,
+            TemplatePart::Literal(b"\r\n".to_vec()),
+            TemplatePart::Value,
+            TemplatePart::Literal(b"\r\n".to_vec()),
+        ];
+
+        assert_eq!(
+            compose(&template, &set(b"abc", b"\x00\xff value")),
+            Some(b"set abc 8\r\n\x00\xff value\r\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn compose_rand() {
+        let template = [TemplatePart::Key, TemplatePart::Rand(16)];
+
+        let command = compose(&template, &set(b"abc", b"")).unwrap();
+
+        assert_eq!(command.len(), 3 + 16);
+        assert!(command.starts_with(b"abc"));
+        assert!(command[3..].iter().all(|b| b.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn compose_unsupported() {
+        let template = [TemplatePart::Key];
+
+        assert_eq!(compose(&template, &ClientRequest::Ping(Ping {})), None);
+    }
+
+    #[test]
+    fn parse_terminator() {
+        assert_eq!(parse(b"+OK\r\n", b"\r\n"), Some(5));
+        // only the first response is consumed
+        assert_eq!(parse(b"+OK\r\n+OK\r\n", b"\r\n"), Some(5));
+        assert_eq!(parse(b"VALUE abc\r\nEND\r\n", b"END\r\n"), Some(16));
+    }
+
+    #[test]
+    fn parse_incomplete() {
+        assert_eq!(parse(b"", b"\r\n"), None);
+        assert_eq!(parse(b"+OK\r", b"\r\n"), None);
+        assert_eq!(parse(b"VALUE abc\r\n", b"END\r\n"), None);
+    }
+}
@@ -0,0 +1,358 @@
+//! Support for routing requests to the nodes of a Redis Cluster based on the
+//! hash slot of each key.
+
+use super::*;
+use ::redis::Value;
+use std::cell::Cell;
+
+const SLOTS: usize = 16384;
+
+tokio::task_local! {
+    // the redirection in the error reply to a request, which is set by the
+    // commands while a request is sent with `send()`
+    pub static REDIRECT: Cell<Option<Redirect>>;
+}
+
+/// A `MOVED <slot> <addr>` or `ASK <slot> <addr>` error reply, which sends the
+/// request to another node.
+pub struct Redirect {
+    /// Whether the slot has moved to the node for good. Otherwise the node is
+    /// importing the slot and only handles this request, after `ASKING`.
+    pub moved: bool,
+    pub slot: u16,
+    pub node: Arc<str>,
+}
+
+impl Redirect {
+    /// Parses the code and detail of an error reply, returning `None` if the
+    /// error is not a redirection.
+    pub fn parse(code: Option<&str>, detail: Option<&str>) -> Option<Self> {
+        let moved = match code? {
+            "MOVED" => true,
+            "ASK" => false,
+            _ => return None,
+        };
+
+        let mut words = detail?.split_whitespace();
+
+        let slot = words
+            .next()?
+            .parse::<u16>()
+            .ok()
+            .filter(|slot| (*slot as usize) < SLOTS)?;
+
+        let (host, port) = words.next()?.rsplit_once(':')?;
+        let port = port.parse::<u16>().ok()?;
+
+        Some(Self {
+            moved,
+            slot,
+            node: address(host, port).into(),
+        })
+    }
+}
+
+/// Maps each hash slot to the address of the node which owns it.
+pub struct SlotMap {
+    nodes: Vec<Option<Arc<str>>>,
+}
+
+impl SlotMap {
+    /// Uses `CLUSTER SLOTS` to discover the node which owns each slot.
+    pub async fn discover(
+        connection: &mut Connection<net::Stream>,
+        config: &Config,
+    ) -> Option<Self> {
+        let value = match timeout(
+            config.client().unwrap().request_timeout(),
+            ::redis::cmd("CLUSTER")
+                .arg("SLOTS")
+                .query_async::<_, Value>(connection),
+        )
+        .await
+        {
+            Ok(Ok(value)) => value,
+            Ok(Err(e)) => {
+                error!("failed to discover cluster slots: {e}");
+                return None;
+            }
+            Err(_) => {
+                error!("timeout discovering cluster slots");
+                return None;
+            }
+        };
+
+        let slots = Self::from_slots(value);
+
+        if slots.is_none() {
+            error!("cluster slots are not valid or not fully covered");
+        }
+
+        slots
+    }
+
+    // builds the map from the reply to `CLUSTER SLOTS`, which must cover
+    // every slot
+    fn from_slots(value: Value) -> Option<Self> {
+        let mut nodes = vec![None; SLOTS];
+
+        // each range is: [start, end, [ip, port, ..], replicas..]
+        let Value::Bulk(ranges) = value else {
+            return None;
+        };
+
+        for range in ranges {
+            let Value::Bulk(range) = range else {
+                return None;
+            };
+
+            let (Some(Value::Int(start)), Some(Value::Int(end)), Some(Value::Bulk(primary))) =
+                (range.first(), range.get(1), range.get(2))
+            else {
+                return None;
+            };
+
+            let (Some(Value::Data(ip)), Some(Value::Int(port))) = (primary.first(), primary.get(1))
+            else {
+                return None;
+            };
+
+            let port = u16::try_from(*port).ok()?;
+            let address: Arc<str> = address(&String::from_utf8_lossy(ip), port).into();
+
+            for slot in (*start as usize)..=std::cmp::min(*end as usize, SLOTS - 1) {
+                nodes[slot] = Some(address.clone());
+            }
+        }
+
+        if nodes.iter().any(|node| node.is_none()) {
+            return None;
+        }
+
+        Some(Self { nodes })
+    }
+
+    /// Assigns the slot to the node, after a `MOVED` redirection.
+    pub fn assign(&mut self, slot: u16, node: Arc<str>) {
+        self.nodes[slot as usize] = Some(node);
+    }
+
+    /// Returns the address of the node which should receive the request. Any
+    /// requests without a key are sent to the owner of the first slot.
+    pub fn route(&self, request: &ClientRequest) -> Arc<str> {
+        let slot = key(request).map(slot).unwrap_or(0);
+
+        // discovery ensures that all slots are covered
+        self.nodes[slot as usize].clone().unwrap()
+    }
+}
+
+/// Sends the request to a node, preceded by `ASKING` after an `ASK`
+/// redirection. Returns the result along with any redirection in the reply,
+/// or `None` if the request is not supported.
+pub async fn send(
+    con: &mut Connection<net::Stream>,
+    config: &Config,
+    request: ClientRequest,
+    asking: bool,
+) -> Option<(std::result::Result<(), ResponseError>, Option<Redirect>)> {
+    if asking {
+        match timeout(
+            config.client().unwrap().request_timeout(),
+            ::redis::cmd("ASKING").query_async::<_, ()>(con),
+        )
+        .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => {
+                record_error(ErrorCategory::Server);
+                return Some((Err(ResponseError::Exception), None));
+            }
+            Err(_) => {
+                return Some((Err(ResponseError::Timeout), None));
+            }
+        }
+    }
+
+    let (result, redirect) = REDIRECT
+        .scope(Cell::new(None), async {
+            let result = super::send(con, config, request).await;
+            (result, REDIRECT.with(|redirect| redirect.take()))
+        })
+        .await;
+
+    result.map(|result| (result, redirect))
+}
+
+// the address of a node, with an IPv6 host in brackets so that it can be
+// connected to
+fn address(host: &str, port: u16) -> String {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// Calculates the hash slot for a key, respecting any hash tags.
+pub fn slot(key: &[u8]) -> u16 {
+    // if the key contains a non-empty `{...}` only that section is hashed
+    let key = match key.iter().position(|b| *b == b'{') {
+        Some(open) => match key[(open + 1)..].iter().position(|b| *b == b'}') {
+            Some(len) if len > 0 => &key[(open + 1)..(open + 1 + len)],
+            _ => key,
+        },
+        None => key,
+    };
+
+    crc16(key) % SLOTS as u16
+}
+
+/// CRC16 using the XMODEM polynomial as specified by Redis Cluster.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+fn key(request: &ClientRequest) -> Option<&[u8]> {
+    let key = match request {
+        ClientRequest::Ping(_) | ClientRequest::Scan(_) | ClientRequest::Reconnect => {
+            return None;
+        }
+        ClientRequest::MultiGet(r) => {
+            return r.keys.first().map(|k| k.as_ref());
+        }
+        ClientRequest::MultiSet(r) => {
+            return r.pairs.first().map(|(k, _)| k.as_ref());
+        }
+        ClientRequest::Add(r) => &r.key,
+        ClientRequest::Get(r) => &r.key,
+        ClientRequest::Delete(r) => &r.key,
+        ClientRequest::Replace(r) => &r.key,
+        ClientRequest::Set(r) => &r.key,
+        ClientRequest::Cas(r) => &r.key,
+        ClientRequest::HashExists(r) => &r.key,
+        ClientRequest::HashDelete(r) => &r.key,
+        ClientRequest::HashGet(r) => &r.key,
+        ClientRequest::HashGetAll(r) => &r.key,
+        ClientRequest::HashIncrement(r) => &r.key,
+        ClientRequest::HashSet(r) => &r.key,
+        ClientRequest::ListFetch(r) => &r.key,
+        ClientRequest::ListLength(r) => &r.key,
+        ClientRequest::ListPopBack(r) => &r.key,
+        ClientRequest::ListPopFront(r) => &r.key,
+        ClientRequest::ListPushBack(r) => &r.key,
+        ClientRequest::ListPushFront(r) => &r.key,
+        ClientRequest::ListRange(r) => &r.key,
+        ClientRequest::ListRemove(r) => &r.key,
+        ClientRequest::ListStore(r) => &r.key,
+        ClientRequest::SetAdd(r) => &r.key,
+        ClientRequest::SetMembers(r) => &r.key,
+        ClientRequest::SetRemove(r) => &r.key,
+        ClientRequest::SortedSetAdd(r) => &r.key,
+        ClientRequest::SortedSetIncrement(r) => &r.key,
+        ClientRequest::SortedSetRange(r) => &r.key,
+        ClientRequest::SortedSetRank(r) => &r.key,
+        ClientRequest::SortedSetRemove(r) => &r.key,
+        ClientRequest::SortedSetScore(r) => &r.key,
+    };
+
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_crc16() {
+        // the test vector from the Redis Cluster specification
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(slot(b"123456789"), 0x31C3 & 16383);
+        assert_eq!(slot(b""), 0);
+    }
+
+    #[test]
+    fn slot_hash_tags() {
+        assert_eq!(slot(b"{user1000}.following"), slot(b"user1000"));
+        assert_eq!(slot(b"{user1000}.followers"), slot(b"user1000"));
+        // only the first tag is used
+        assert_eq!(slot(b"foo{bar}{zap}"), slot(b"bar"));
+        // an empty tag hashes the whole key
+        assert_eq!(slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % SLOTS as u16);
+        assert_eq!(slot(b"foo{{bar}}zap"), slot(b"{bar"));
+        // an unterminated tag hashes the whole key
+        assert_eq!(slot(b"foo{bar"), crc16(b"foo{bar") % SLOTS as u16);
+    }
+
+    fn range(start: i64, end: i64, ip: &str, port: i64) -> Value {
+        Value::Bulk(vec![
+            Value::Int(start),
+            Value::Int(end),
+            Value::Bulk(vec![Value::Data(ip.as_bytes().to_vec()), Value::Int(port)]),
+        ])
+    }
+
+    #[test]
+    fn from_slots() {
+        let slots = SlotMap::from_slots(Value::Bulk(vec![
+            range(0, 8191, "10.0.0.1", 6379),
+            range(8192, 16383, "::1", 6380),
+        ]))
+        .unwrap();
+
+        assert_eq!(slots.nodes[0].as_deref(), Some("10.0.0.1:6379"));
+        assert_eq!(slots.nodes[8191].as_deref(), Some("10.0.0.1:6379"));
+        assert_eq!(slots.nodes[8192].as_deref(), Some("[::1]:6380"));
+        assert_eq!(slots.nodes[16383].as_deref(), Some("[::1]:6380"));
+    }
+
+    #[test]
+    fn from_slots_requires_every_slot() {
+        assert!(SlotMap::from_slots(Value::Bulk(vec![range(0, 8191, "10.0.0.1", 6379)])).is_none());
+        assert!(SlotMap::from_slots(Value::Nil).is_none());
+        assert!(SlotMap::from_slots(Value::Bulk(vec![Value::Int(0)])).is_none());
+    }
+
+    #[test]
+    fn assign() {
+        let mut slots =
+            SlotMap::from_slots(Value::Bulk(vec![range(0, 16383, "10.0.0.1", 6379)])).unwrap();
+
+        slots.assign(3999, "10.0.0.2:6379".into());
+
+        assert_eq!(slots.nodes[3998].as_deref(), Some("10.0.0.1:6379"));
+        assert_eq!(slots.nodes[3999].as_deref(), Some("10.0.0.2:6379"));
+    }
+
+    #[test]
+    fn redirect() {
+        let moved = Redirect::parse(Some("MOVED"), Some("3999 127.0.0.1:6381")).unwrap();
+        assert!(moved.moved);
+        assert_eq!(moved.slot, 3999);
+        assert_eq!(&*moved.node, "127.0.0.1:6381");
+
+        let ask = Redirect::parse(Some("ASK"), Some("3999 ::1:6381")).unwrap();
+        assert!(!ask.moved);
+        assert_eq!(&*ask.node, "[::1]:6381");
+
+        assert!(Redirect::parse(Some("WRONGTYPE"), Some("Operation against a key")).is_none());
+        assert!(Redirect::parse(Some("MOVED"), Some("16384 127.0.0.1:6381")).is_none());
+        assert!(Redirect::parse(Some("MOVED"), Some("3999")).is_none());
+        assert!(Redirect::parse(None, None).is_none());
+    }
+}
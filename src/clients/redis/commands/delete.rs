@@ -3,7 +3,7 @@ use super::*;
 /// Delete a key from the cache. This will delete an entire Hash/Set/SortedSet
 /// if used in the same keyspace.
 pub async fn delete(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::Delete,
 ) -> std::result::Result<(), ResponseError> {
@@ -18,10 +18,7 @@ pub async fn delete(
             DELETE_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            DELETE_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &DELETE_EX)),
         Err(_) => {
             DELETE_TIMEOUT.increment();
             Err(ResponseError::Timeout)
@@ -2,7 +2,7 @@ use super::*;
 
 /// Retrieve a key-value pair from the cache.
 pub async fn get(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::Get,
 ) -> std::result::Result<(), ResponseError> {
@@ -18,15 +18,15 @@ pub async fn get(
             GET_KEY_MISS.increment();
             Ok(())
         }
-        Ok(Ok(Some(_))) => {
+        Ok(Ok(Some(value))) => {
             RESPONSE_HIT.increment();
             GET_KEY_HIT.increment();
+            if request.verify {
+                workload::verify_value(&request.key, &value);
+            }
             Ok(())
         }
-        Ok(Err(_)) => {
-            GET_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &GET_EX)),
         Err(_) => {
             GET_TIMEOUT.increment();
             Err(ResponseError::Timeout)
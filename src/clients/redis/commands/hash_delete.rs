@@ -2,7 +2,7 @@ use super::*;
 
 /// Delete a field from a hash stored in the cache.
 pub async fn hash_delete(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashDelete,
 ) -> std::result::Result<(), ResponseError> {
@@ -18,10 +18,7 @@ pub async fn hash_delete(
             HASH_DELETE_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            HASH_DELETE_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &HASH_DELETE_EX)),
         Err(_) => {
             HASH_DELETE_TIMEOUT.increment();
             Err(ResponseError::Timeout)
@@ -2,7 +2,7 @@ use super::*;
 
 /// Checks if a field exists in a hash.
 pub async fn hash_exists(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashExists,
 ) -> std::result::Result<(), ResponseError> {
@@ -25,10 +25,7 @@ pub async fn hash_exists(
             HASH_EXISTS_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            HASH_EXISTS_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &HASH_EXISTS_EX)),
         Err(_) => {
             HASH_EXISTS_TIMEOUT.increment();
             Err(ResponseError::Timeout)
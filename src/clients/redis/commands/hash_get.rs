@@ -2,7 +2,7 @@ use super::*;
 
 /// Retrieves the value for on or more fields in a hash.
 pub async fn hash_get(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashGet,
 ) -> std::result::Result<(), ResponseError> {
@@ -25,7 +25,7 @@ pub async fn hash_get(
                 HASH_GET_FIELD_MISS.increment();
                 Ok(())
             }
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     } else {
@@ -57,7 +57,7 @@ pub async fn hash_get(
                 HASH_GET_FIELD_MISS.add(fields.len() as _);
                 Ok(())
             }
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     };
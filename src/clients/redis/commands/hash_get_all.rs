@@ -2,7 +2,7 @@ use super::*;
 
 /// Retrieve all fields for a hash.
 pub async fn hash_get_all(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashGetAll,
 ) -> std::result::Result<(), ResponseError> {
@@ -25,10 +25,7 @@ pub async fn hash_get_all(
             HASH_GET_ALL_MISS.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            HASH_GET_ALL_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &HASH_GET_ALL_EX)),
         Err(_) => {
             HASH_GET_ALL_TIMEOUT.increment();
             Err(ResponseError::Timeout)
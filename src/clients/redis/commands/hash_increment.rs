@@ -12,7 +12,7 @@ use tokio::time::error::Elapsed;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn hash_increment(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashIncrement,
 ) -> std::result::Result<(), ResponseError> {
@@ -27,10 +27,7 @@ pub async fn hash_increment(
             HASH_INCR_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            HASH_INCR_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &HASH_INCR_EX)),
         Err(_) => {
             HASH_INCR_TIMEOUT.increment();
             Err(ResponseError::Timeout)
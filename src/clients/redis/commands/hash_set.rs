@@ -13,7 +13,7 @@ use std::result::Result;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn hash_set(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::HashSet,
 ) -> Result<(), ResponseError> {
@@ -35,7 +35,7 @@ pub async fn hash_set(
         .await
         {
             Ok(Ok(_)) => Ok(()),
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     } else {
@@ -51,7 +51,7 @@ pub async fn hash_set(
         .await
         {
             Ok(Ok(_)) => Ok(()),
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     };
@@ -2,7 +2,7 @@ use super::*;
 
 /// Retrieve all elements from a list in the cache.
 pub async fn list_fetch(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::ListFetch,
 ) -> std::result::Result<(), ResponseError> {
@@ -17,10 +17,7 @@ pub async fn list_fetch(
             LIST_FETCH_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            LIST_FETCH_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_FETCH_EX)),
         Err(_) => {
             LIST_FETCH_TIMEOUT.increment();
             Err(ResponseError::Timeout)
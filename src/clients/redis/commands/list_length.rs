@@ -2,7 +2,7 @@ use super::*;
 
 /// Retrieve the length of a list in the cache.
 pub async fn list_length(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::ListLength,
 ) -> std::result::Result<(), ResponseError> {
@@ -17,10 +17,7 @@ pub async fn list_length(
             LIST_LENGTH_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            LIST_LENGTH_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_LENGTH_EX)),
         Err(_) => {
             LIST_LENGTH_TIMEOUT.increment();
             Err(ResponseError::Timeout)
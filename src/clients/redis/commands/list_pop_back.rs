@@ -2,7 +2,7 @@ use super::*;
 
 /// Removes and returns the element from the back of a list.
 pub async fn list_pop_back(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::ListPopBack,
 ) -> std::result::Result<(), ResponseError> {
@@ -17,10 +17,7 @@ pub async fn list_pop_back(
             LIST_POP_BACK_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            LIST_POP_BACK_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_POP_BACK_EX)),
         Err(_) => {
             LIST_POP_BACK_TIMEOUT.increment();
             Err(ResponseError::Timeout)
@@ -2,7 +2,7 @@ use super::*;
 
 /// Removes and returns an element from the front of a list.
 pub async fn list_pop_front(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::ListPopFront,
 ) -> std::result::Result<(), ResponseError> {
@@ -17,10 +17,7 @@ pub async fn list_pop_front(
             LIST_POP_FRONT_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            LIST_POP_FRONT_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_POP_FRONT_EX)),
         Err(_) => {
             LIST_POP_FRONT_TIMEOUT.increment();
             Err(ResponseError::Timeout)
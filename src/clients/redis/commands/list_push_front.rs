@@ -12,7 +12,7 @@ use tokio::time::error::Elapsed;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn list_push_front(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::ListPushFront,
 ) -> std::result::Result<(), ResponseError> {
@@ -25,7 +25,7 @@ pub async fn list_push_front(
     .await
     {
         Ok(Ok(_)) => Ok(()),
-        Ok(Err(_)) => Err(ResponseError::Exception),
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_PUSH_FRONT_EX)),
         Err(_) => Err(ResponseError::Timeout),
     };
 
@@ -40,8 +40,8 @@ pub async fn list_push_front(
                 Ok(Ok(_)) => {
                     result = Ok(());
                 }
-                Ok(Err(_)) => {
-                    result = Err(ResponseError::Exception);
+                Ok(Err(e)) => {
+                    result = Err(command_exception(&e, &LIST_PUSH_FRONT_EX));
                 }
                 Err(_) => {
                     result = Err(ResponseError::Timeout);
@@ -57,9 +57,8 @@ pub async fn list_push_front(
         Err(ResponseError::Timeout) => {
             LIST_PUSH_FRONT_TIMEOUT.increment();
         }
-        Err(_) => {
-            LIST_PUSH_FRONT_EX.increment();
-        }
+        // the exception was counted along with its category
+        Err(_) => {}
     }
 
     // If successful, we may need to set an expiration. This is best-effort only
@@ -0,0 +1,27 @@
+use super::*;
+
+/// Remove all instances of an element from a list.
+pub async fn list_remove(
+    connection: &mut (impl ConnectionLike + Send),
+    config: &Config,
+    request: workload::client::ListRemove,
+) -> std::result::Result<(), ResponseError> {
+    LIST_REMOVE.increment();
+    match timeout(
+        config.client().unwrap().request_timeout(),
+        // a count of zero removes all elements which match
+        connection.lrem::<&[u8], &[u8], u64>(request.key.as_ref(), 0, request.element.as_ref()),
+    )
+    .await
+    {
+        Ok(Ok(_)) => {
+            LIST_REMOVE_OK.increment();
+            Ok(())
+        }
+        Ok(Err(e)) => Err(command_exception(&e, &LIST_REMOVE_EX)),
+        Err(_) => {
+            LIST_REMOVE_TIMEOUT.increment();
+            Err(ResponseError::Timeout)
+        }
+    }
+}
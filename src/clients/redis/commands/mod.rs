@@ -15,8 +15,12 @@ mod list_pop_back;
 mod list_pop_front;
 mod list_push_back;
 mod list_push_front;
+mod list_remove;
+mod multi_get;
+mod multi_set;
 mod ping;
 mod replace;
+mod scan;
 mod set;
 mod set_add;
 mod set_members;
@@ -43,8 +47,12 @@ pub use list_pop_back::*;
 pub use list_pop_front::*;
 pub use list_push_back::*;
 pub use list_push_front::*;
+pub use list_remove::*;
+pub use multi_get::*;
+pub use multi_set::*;
 pub use ping::*;
 pub use replace::*;
+pub use scan::*;
 pub use set::*;
 pub use set_add::*;
 pub use set_members::*;
@@ -55,3 +63,92 @@ pub use sorted_set_range::*;
 pub use sorted_set_rank::*;
 pub use sorted_set_remove::*;
 pub use sorted_set_score::*;
+
+/// Adds the expiration for a TTL to a SET command. TTLs with a whole number of
+/// seconds are sent with `EX` and all others with `PX`.
+fn expire(command: &mut ::redis::Cmd, ttl: Option<Duration>) {
+    if let Some(ttl) = ttl {
+        if ttl.subsec_nanos() == 0 {
+            command.arg("EX").arg(ttl.as_secs());
+        } else {
+            command.arg("PX").arg(ttl.as_millis() as u64);
+        }
+    }
+}
+
+/// Records the category of an error returned for a command and converts it
+/// into a response error.
+fn exception(error: &::redis::RedisError) -> ResponseError {
+    if !redirected(error) {
+        record_category(error);
+    }
+
+    ResponseError::Exception
+}
+
+/// Like `exception()`, but also counts the exception for the command. A
+/// redirection is not counted, as the request may still succeed once the
+/// cluster task has followed it.
+fn command_exception(error: &::redis::RedisError, counter: &metriken::Counter) -> ResponseError {
+    if !redirected(error) {
+        counter.increment();
+        record_category(error);
+    }
+
+    ResponseError::Exception
+}
+
+// in cluster mode a redirection is followed by the cluster task, which records
+// the error only if the request is not redirected. Returns true if the error is
+// a redirection which is handed to the cluster task
+fn redirected(error: &::redis::RedisError) -> bool {
+    match super::cluster::Redirect::parse(error.code(), error.detail()) {
+        Some(redirect) => super::cluster::REDIRECT
+            .try_with(|r| r.set(Some(redirect)))
+            .is_ok(),
+        None => false,
+    }
+}
+
+fn record_category(error: &::redis::RedisError) {
+    if error.is_io_error() || error.is_connection_dropped() || error.is_connection_refusal() {
+        record_error(ErrorCategory::Connection);
+    } else if error.kind() == ::redis::ErrorKind::TypeError {
+        // the reply was not of the type expected for the command
+        record_error(ErrorCategory::Protocol);
+    } else {
+        record_error(ErrorCategory::Server);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::redis::{ErrorKind, RedisError};
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn redirect_is_not_an_exception() {
+        let moved = RedisError::from((
+            ErrorKind::Moved,
+            "An error was signalled by the server",
+            "3999 127.0.0.1:6381".to_string(),
+        ));
+        let counter = metriken::Counter::new();
+
+        // the cluster task follows the redirection
+        let redirect = cluster::REDIRECT
+            .scope(Cell::new(None), async {
+                command_exception(&moved, &counter);
+                cluster::REDIRECT.with(|redirect| redirect.take())
+            })
+            .await;
+
+        assert_eq!(redirect.unwrap().slot, 3999);
+        assert_eq!(counter.value(), 0);
+
+        // outside of cluster mode the redirection is an error
+        command_exception(&moved, &counter);
+        assert_eq!(counter.value(), 1);
+    }
+}
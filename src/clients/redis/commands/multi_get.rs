@@ -0,0 +1,145 @@
+use super::*;
+use ::redis::RedisError;
+use tokio::time::error::Elapsed;
+
+use std::result::Result;
+
+/// Retrieve the values for several keys from the cache with a single `MGET`.
+///
+/// The reply must have one element for each requested key, any other reply is
+/// treated as an exception.
+pub async fn multi_get(
+    connection: &mut (impl ConnectionLike + Send),
+    config: &Config,
+    request: workload::client::MultiGet,
+) -> Result<(), ResponseError> {
+    MULTI_GET.increment();
+
+    let result: Result<Result<Vec<Option<Vec<u8>>>, RedisError>, Elapsed> = timeout(
+        config.client().unwrap().request_timeout(),
+        command(&request).query_async(connection),
+    )
+    .await;
+
+    let result = match result {
+        Ok(Ok(values)) if values.len() == request.keys.len() => {
+            let mut hits = 0;
+            let mut misses = 0;
+            for (key, value) in request.keys.iter().zip(values) {
+                if let Some(value) = value {
+                    hits += 1;
+                    if request.verify {
+                        workload::verify_value(key, &value);
+                    }
+                } else {
+                    misses += 1;
+                }
+            }
+            RESPONSE_HIT.add(hits);
+            RESPONSE_MISS.add(misses);
+            MULTI_GET_KEY_HIT.add(hits);
+            MULTI_GET_KEY_MISS.add(misses);
+            Ok(())
+        }
+        Ok(Ok(_)) => {
+            // a value is returned for every key requested
+            record_error(ErrorCategory::Protocol);
+            Err(ResponseError::Exception)
+        }
+        Ok(Err(e)) => Err(exception(&e)),
+        Err(_) => Err(ResponseError::Timeout),
+    };
+
+    match result {
+        Ok(()) => {
+            MULTI_GET_OK.increment();
+        }
+        Err(ResponseError::Exception) => {
+            MULTI_GET_EX.increment();
+        }
+        Err(ResponseError::Timeout) => {
+            MULTI_GET_TIMEOUT.increment();
+        }
+        _ => {}
+    }
+
+    result
+}
+
+fn command(request: &workload::client::MultiGet) -> ::redis::Cmd {
+    let keys: Vec<&[u8]> = request.keys.iter().map(|k| &**k).collect();
+
+    let mut command = ::redis::cmd("MGET");
+    command.arg(&keys);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::redis::{Cmd, Pipeline, RedisFuture, Value};
+
+    // a connection which gives the same reply to every command
+    struct Reply(Value);
+
+    impl ConnectionLike for Reply {
+        fn req_packed_command<'a>(&'a mut self, _: &'a Cmd) -> RedisFuture<'a, Value> {
+            let reply = self.0.clone();
+            Box::pin(async move { Ok(reply) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _: &'a Pipeline,
+            _: usize,
+            _: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            unimplemented!()
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    fn request(keys: &[&str]) -> workload::client::MultiGet {
+        workload::client::MultiGet {
+            keys: keys.iter().map(|key| key.as_bytes().into()).collect(),
+            verify: false,
+        }
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(
+            command(&request(&["a", "bc", "def"])).get_packed_command(),
+            b"*4\r\n$4\r\nMGET\r\n$1\r\na\r\n$2\r\nbc\r\n$3\r\ndef\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_count() {
+        let config = testing::config("resp", "127.0.0.1:12321", "");
+        let hits = MULTI_GET_KEY_HIT.value();
+        let misses = MULTI_GET_KEY_MISS.value();
+
+        // a value or nil for each key
+        let mut connection = Reply(Value::Bulk(vec![
+            Value::Data(b"value".to_vec()),
+            Value::Nil,
+        ]));
+        assert!(multi_get(&mut connection, &config, request(&["a", "b"]))
+            .await
+            .is_ok());
+        assert!(MULTI_GET_KEY_HIT.value() > hits);
+        assert!(MULTI_GET_KEY_MISS.value() > misses);
+
+        // a reply which is missing a value is an exception
+        let exceptions = MULTI_GET_EX.value();
+        assert!(matches!(
+            multi_get(&mut connection, &config, request(&["a", "b", "c"])).await,
+            Err(ResponseError::Exception)
+        ));
+        assert!(MULTI_GET_EX.value() > exceptions);
+    }
+}
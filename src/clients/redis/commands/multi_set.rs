@@ -0,0 +1,68 @@
+use super::*;
+use ::redis::RedisError;
+use tokio::time::error::Elapsed;
+
+use std::result::Result;
+
+/// Set the values for several keys with a single `MSET`.
+///
+/// NOTE: `MSET` has no expiration option, so the keys are stored without a
+/// TTL.
+pub async fn multi_set(
+    connection: &mut (impl ConnectionLike + Send),
+    config: &Config,
+    request: workload::client::MultiSet,
+) -> Result<(), ResponseError> {
+    MULTI_SET.increment();
+
+    let result: Result<Result<(), RedisError>, Elapsed> = timeout(
+        config.client().unwrap().request_timeout(),
+        command(&request).query_async(connection),
+    )
+    .await;
+
+    match result {
+        Ok(Ok(_)) => {
+            MULTI_SET_OK.increment();
+            Ok(())
+        }
+        Ok(Err(e)) => Err(command_exception(&e, &MULTI_SET_EX)),
+        Err(_) => {
+            MULTI_SET_TIMEOUT.increment();
+            Err(ResponseError::Timeout)
+        }
+    }
+}
+
+fn command(request: &workload::client::MultiSet) -> ::redis::Cmd {
+    let pairs: Vec<(&[u8], &[u8])> = request
+        .pairs
+        .iter()
+        .map(|(k, v)| (k.as_ref(), v.as_ref()))
+        .collect();
+
+    let mut command = ::redis::cmd("MSET");
+    command.arg(&pairs);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        let request = workload::client::MultiSet {
+            pairs: vec![
+                (b"a"[..].into(), b"one".to_vec()),
+                (b"b"[..].into(), b"two".to_vec()),
+            ],
+        };
+
+        // the keys and values are interleaved
+        assert_eq!(
+            command(&request).get_packed_command(),
+            b"*5\r\n$4\r\nMSET\r\n$1\r\na\r\n$3\r\none\r\n$1\r\nb\r\n$3\r\ntwo\r\n"
+        );
+    }
+}
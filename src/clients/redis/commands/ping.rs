@@ -2,7 +2,7 @@ use super::*;
 
 /// Sends a `PING` and expects a `PONG` response from the server.
 pub async fn ping(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     _request: workload::client::Ping,
 ) -> std::result::Result<(), ResponseError> {
@@ -17,10 +17,7 @@ pub async fn ping(
             PING_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            PING_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &PING_EX)),
         Err(_) => {
             PING_TIMEOUT.increment();
             Err(ResponseError::Timeout)
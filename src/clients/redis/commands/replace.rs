@@ -2,40 +2,17 @@ use super::*;
 
 /// Sets a key-value pair in the cache if the key already exists.
 pub async fn replace(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::Replace,
 ) -> std::result::Result<(), ResponseError> {
     REPLACE.increment();
 
-    let key = &*request.key;
-    let value = &*request.value;
-
-    let mut base_command = ::redis::cmd("SET");
-
-    let mut command = base_command.arg(key).arg("XX").arg(value);
-
-    if let Some(ttl) = request.ttl {
-        if ttl.subsec_nanos() == 0 {
-            command = base_command
-                .arg(key)
-                .arg("XX")
-                .arg("EX")
-                .arg(ttl.as_secs())
-                .arg(value);
-        } else {
-            command = base_command
-                .arg(key)
-                .arg("XX")
-                .arg("PX")
-                .arg(ttl.as_millis() as u64)
-                .arg(value);
-        }
-    }
+    let command = command(&request);
 
     match timeout(
         config.client().unwrap().request_timeout(),
-        command.query_async::<::redis::aio::Connection<net::Stream>, Option<String>>(connection),
+        command.query_async::<_, Option<String>>(connection),
     )
     .await
     {
@@ -47,13 +24,55 @@ pub async fn replace(
             }
             Ok(())
         }
-        Ok(Err(_)) => {
-            REPLACE_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &REPLACE_EX)),
         Err(_) => {
             REPLACE_TIMEOUT.increment();
             Err(ResponseError::Timeout)
         }
     }
 }
+
+fn command(request: &workload::client::Replace) -> ::redis::Cmd {
+    let mut command = ::redis::cmd("SET");
+    command.arg(&*request.key).arg(&*request.value).arg("XX");
+    expire(&mut command, request.ttl);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(ttl: Option<Duration>) -> Vec<u8> {
+        command(&workload::client::Replace {
+            key: b"key"[..].into(),
+            value: b"value".to_vec(),
+            ttl,
+        })
+        .get_packed_command()
+    }
+
+    #[test]
+    fn without_ttl() {
+        assert_eq!(
+            packed(None),
+            b"*4\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nXX\r\n"
+        );
+    }
+
+    #[test]
+    fn ttl_in_seconds() {
+        assert_eq!(
+            packed(Some(Duration::from_secs(30))),
+            b"*6\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nXX\r\n$2\r\nEX\r\n$2\r\n30\r\n"
+        );
+    }
+
+    #[test]
+    fn ttl_in_milliseconds() {
+        assert_eq!(
+            packed(Some(Duration::from_millis(1500))),
+            b"*6\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nXX\r\n$2\r\nPX\r\n$4\r\n1500\r\n"
+        );
+    }
+}
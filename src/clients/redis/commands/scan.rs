@@ -0,0 +1,212 @@
+use super::*;
+use ::redis::RedisError;
+use tokio::time::error::Elapsed;
+
+use std::result::Result;
+
+/// Iterates over the keys with `SCAN`, following the cursor returned by each
+/// call until the server returns a cursor of `0` or `max_iterations` calls
+/// have been made. The latency of each call is recorded in addition to the
+/// latency of the whole iteration.
+pub async fn scan(
+    connection: &mut (impl ConnectionLike + Send),
+    config: &Config,
+    request: workload::client::Scan,
+) -> Result<(), ResponseError> {
+    SCAN.increment();
+
+    let mut cursor = 0;
+    let mut calls = 0;
+
+    let result = loop {
+        let mut command = ::redis::cmd("SCAN");
+        command.arg(cursor);
+
+        if let Some(count) = request.count {
+            command.arg("COUNT").arg(count);
+        }
+
+        let start = Instant::now();
+
+        // the reply is the next cursor and the keys returned by this call
+        let reply: Result<Result<(u64, Vec<Vec<u8>>), RedisError>, Elapsed> = timeout(
+            config.client().unwrap().request_timeout(),
+            command.query_async(connection),
+        )
+        .await;
+
+        SCAN_CALLS.increment();
+        calls += 1;
+
+        match reply {
+            Ok(Ok((next, keys))) => {
+                let latency = start.elapsed().as_nanos() as u64;
+                let _ = SCAN_CALL_LATENCY.increment(latency);
+
+                SCAN_KEYS.add(keys.len() as u64);
+
+                match next_cursor(next, calls, request.max_iterations) {
+                    Some(next) => cursor = next,
+                    None => break Ok(()),
+                }
+            }
+            Ok(Err(e)) => break Err(exception(&e)),
+            Err(_) => break Err(ResponseError::Timeout),
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            SCAN_OK.increment();
+        }
+        Err(ResponseError::Timeout) => {
+            SCAN_TIMEOUT.increment();
+        }
+        Err(_) => {
+            SCAN_EX.increment();
+        }
+    }
+
+    result
+}
+
+/// Returns the cursor for the next call of an iteration, or `None` once the
+/// iteration is complete. A cursor of `0` ends the iteration, as does reaching
+/// the maximum number of calls.
+fn next_cursor(cursor: u64, calls: usize, max_iterations: Option<usize>) -> Option<u64> {
+    if cursor == 0 || max_iterations.is_some_and(|max| calls >= max) {
+        None
+    } else {
+        Some(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::redis::{Cmd, ErrorKind, Pipeline, RedisFuture, Value};
+    use std::collections::VecDeque;
+
+    // a connection which gives each reply in turn, keeping the commands
+    struct Replies {
+        replies: VecDeque<Value>,
+        commands: Vec<Vec<u8>>,
+    }
+
+    impl Replies {
+        fn new(replies: Vec<Value>) -> Self {
+            Self {
+                replies: replies.into(),
+                commands: Vec::new(),
+            }
+        }
+    }
+
+    impl ConnectionLike for Replies {
+        fn req_packed_command<'a>(&'a mut self, command: &'a Cmd) -> RedisFuture<'a, Value> {
+            self.commands.push(command.get_packed_command());
+            let reply = self
+                .replies
+                .pop_front()
+                .ok_or_else(|| RedisError::from((ErrorKind::IoError, "no more replies")));
+            Box::pin(async move { reply })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _: &'a Pipeline,
+            _: usize,
+            _: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(
+                async move { Err(RedisError::from((ErrorKind::ClientError, "not pipelined"))) },
+            )
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    // the reply to a call, with the next cursor and some keys
+    fn reply(cursor: &str, keys: &[&str]) -> Value {
+        Value::Bulk(vec![
+            Value::Data(cursor.as_bytes().to_vec()),
+            Value::Bulk(
+                keys.iter()
+                    .map(|key| Value::Data(key.as_bytes().to_vec()))
+                    .collect(),
+            ),
+        ])
+    }
+
+    fn command(cursor: u64) -> Vec<u8> {
+        ::redis::cmd("SCAN").arg(cursor).get_packed_command()
+    }
+
+    fn request(max_iterations: Option<usize>) -> workload::client::Scan {
+        workload::client::Scan {
+            count: None,
+            max_iterations,
+        }
+    }
+
+    #[test]
+    fn cursor() {
+        assert_eq!(next_cursor(17, 1, None), Some(17));
+        assert_eq!(next_cursor(0, 1, None), None);
+        assert_eq!(next_cursor(17, 2, Some(3)), Some(17));
+        assert_eq!(next_cursor(17, 3, Some(3)), None);
+    }
+
+    #[tokio::test]
+    async fn follows_cursor() {
+        let config = testing::config("resp", "127.0.0.1:12321", "");
+        let keys = SCAN_KEYS.value();
+
+        // each call continues from the cursor of the previous reply, until the
+        // cursor is zero
+        let mut connection = Replies::new(vec![
+            reply("17", &["a", "b"]),
+            reply("3", &["c"]),
+            reply("0", &["d"]),
+            reply("5", &["e"]),
+        ]);
+        assert!(scan(&mut connection, &config, request(None)).await.is_ok());
+        assert_eq!(
+            connection.commands,
+            vec![command(0), command(17), command(3)]
+        );
+        assert!(SCAN_KEYS.value() >= keys + 4);
+    }
+
+    #[tokio::test]
+    async fn max_iterations() {
+        let config = testing::config("resp", "127.0.0.1:12321", "");
+
+        let mut connection = Replies::new(vec![
+            reply("17", &["a"]),
+            reply("3", &["b"]),
+            reply("5", &["c"]),
+        ]);
+        assert!(scan(&mut connection, &config, request(Some(2)))
+            .await
+            .is_ok());
+        assert_eq!(connection.commands, vec![command(0), command(17)]);
+    }
+
+    #[tokio::test]
+    async fn malformed_reply() {
+        let config = testing::config("resp", "127.0.0.1:12321", "");
+        let exceptions = SCAN_EX.value();
+
+        // the cursor of the second reply is not a number
+        let mut connection = Replies::new(vec![reply("17", &["a"]), reply("next", &["b"])]);
+        assert!(matches!(
+            scan(&mut connection, &config, request(None)).await,
+            Err(ResponseError::Exception)
+        ));
+        assert_eq!(connection.commands.len(), 2);
+        assert!(SCAN_EX.value() > exceptions);
+    }
+}
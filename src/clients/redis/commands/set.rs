@@ -2,34 +2,13 @@ use super::*;
 
 /// Sets a key-value pair in the cache.
 pub async fn set(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::Set,
 ) -> std::result::Result<(), ResponseError> {
     SET.increment();
 
-    let key = &*request.key;
-    let value = &*request.value;
-
-    let mut base_command = ::redis::cmd("SET");
-
-    let mut command = base_command.arg(key).arg(value);
-
-    if let Some(ttl) = request.ttl {
-        if ttl.subsec_nanos() == 0 {
-            command = base_command
-                .arg(key)
-                .arg("EX")
-                .arg(ttl.as_secs())
-                .arg(value);
-        } else {
-            command = base_command
-                .arg(key)
-                .arg("PX")
-                .arg(ttl.as_millis() as u64)
-                .arg(value);
-        }
-    }
+    let command = command(&request);
 
     match timeout(
         config.client().unwrap().request_timeout(),
@@ -41,13 +20,55 @@ pub async fn set(
             SET_STORED.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SET_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SET_EX)),
         Err(_) => {
             SET_TIMEOUT.increment();
             Err(ResponseError::Timeout)
         }
     }
 }
+
+fn command(request: &workload::client::Set) -> ::redis::Cmd {
+    let mut command = ::redis::cmd("SET");
+    command.arg(&*request.key).arg(&*request.value);
+    expire(&mut command, request.ttl);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(ttl: Option<Duration>) -> Vec<u8> {
+        command(&workload::client::Set {
+            key: b"key"[..].into(),
+            value: b"value".to_vec(),
+            ttl,
+        })
+        .get_packed_command()
+    }
+
+    #[test]
+    fn without_ttl() {
+        assert_eq!(
+            packed(None),
+            b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n"
+        );
+    }
+
+    #[test]
+    fn ttl_in_seconds() {
+        assert_eq!(
+            packed(Some(Duration::from_secs(30))),
+            b"*5\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nEX\r\n$2\r\n30\r\n"
+        );
+    }
+
+    #[test]
+    fn ttl_in_milliseconds() {
+        assert_eq!(
+            packed(Some(Duration::from_millis(1500))),
+            b"*5\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nPX\r\n$4\r\n1500\r\n"
+        );
+    }
+}
@@ -12,7 +12,7 @@ use tokio::time::error::Elapsed;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn set_add(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SetAdd,
 ) -> std::result::Result<(), ResponseError> {
@@ -33,10 +33,7 @@ pub async fn set_add(
                 SET_ADD_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SET_ADD_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SET_ADD_EX)),
             Err(_) => {
                 SET_ADD_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
@@ -54,10 +51,7 @@ pub async fn set_add(
                 SET_ADD_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SET_ADD_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SET_ADD_EX)),
             Err(_) => {
                 SET_ADD_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
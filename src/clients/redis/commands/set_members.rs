@@ -2,7 +2,7 @@ use super::*;
 
 /// Return the members of a set.
 pub async fn set_members(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SetMembers,
 ) -> std::result::Result<(), ResponseError> {
@@ -23,10 +23,7 @@ pub async fn set_members(
             SET_MEMBERS_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SET_MEMBERS_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SET_MEMBERS_EX)),
         Err(_) => {
             SET_MEMBERS_TIMEOUT.increment();
             Err(ResponseError::Timeout)
@@ -2,7 +2,7 @@ use super::*;
 
 /// Removes one or more members of a set.
 pub async fn set_remove(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SetRemove,
 ) -> std::result::Result<(), ResponseError> {
@@ -23,10 +23,7 @@ pub async fn set_remove(
                 SET_REMOVE_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SET_REMOVE_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SET_REMOVE_EX)),
             Err(_) => {
                 SET_REMOVE_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
@@ -44,10 +41,7 @@ pub async fn set_remove(
                 SET_REMOVE_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SET_REMOVE_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SET_REMOVE_EX)),
             Err(_) => {
                 SET_REMOVE_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
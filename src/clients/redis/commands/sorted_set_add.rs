@@ -12,7 +12,7 @@ use tokio::time::error::Elapsed;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn sorted_set_add(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetAdd,
 ) -> std::result::Result<(), ResponseError> {
@@ -38,10 +38,7 @@ pub async fn sorted_set_add(
                 SORTED_SET_ADD_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SORTED_SET_ADD_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_ADD_EX)),
             Err(_) => {
                 SORTED_SET_ADD_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
@@ -63,10 +60,7 @@ pub async fn sorted_set_add(
                 SORTED_SET_ADD_OK.increment();
                 Ok(())
             }
-            Ok(Err(_)) => {
-                SORTED_SET_ADD_EX.increment();
-                Err(ResponseError::Exception)
-            }
+            Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_ADD_EX)),
             Err(_) => {
                 SORTED_SET_ADD_TIMEOUT.increment();
                 Err(ResponseError::Timeout)
@@ -12,7 +12,7 @@ use tokio::time::error::Elapsed;
 /// success/failure of the command to set the expiration does not count towards
 /// the request metrics (such as the number of requests, success rate, etc).
 pub async fn sorted_set_increment(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetIncrement,
 ) -> std::result::Result<(), ResponseError> {
@@ -31,10 +31,7 @@ pub async fn sorted_set_increment(
             SORTED_SET_INCR_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SORTED_SET_INCR_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_INCR_EX)),
         Err(_) => {
             SORTED_SET_INCR_TIMEOUT.increment();
             Err(ResponseError::Timeout)
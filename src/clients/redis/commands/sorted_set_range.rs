@@ -3,7 +3,7 @@ use super::*;
 /// Performs a range query on a sorted set, returning the specified range of
 /// elements. Supports selecting a range of keys by index (rank).
 pub async fn sorted_set_range(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetRange,
 ) -> std::result::Result<(), ResponseError> {
@@ -42,10 +42,7 @@ pub async fn sorted_set_range(
             SORTED_SET_RANGE_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SORTED_SET_RANGE_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_RANGE_EX)),
         Err(_) => {
             SORTED_SET_RANGE_TIMEOUT.increment();
             Err(ResponseError::Timeout)
@@ -2,7 +2,7 @@ use super::*;
 
 /// Returns the rank for a member in a sorted set.
 pub async fn sorted_set_rank(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetRank,
 ) -> std::result::Result<(), ResponseError> {
@@ -24,10 +24,7 @@ pub async fn sorted_set_rank(
             SORTED_SET_RANK_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SORTED_SET_RANK_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_RANK_EX)),
         Err(_) => {
             SORTED_SET_RANK_TIMEOUT.increment();
             Err(ResponseError::Timeout)
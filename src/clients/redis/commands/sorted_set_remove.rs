@@ -2,7 +2,7 @@ use super::*;
 
 /// Removes a member from a sorted set.
 pub async fn sorted_set_remove(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetRemove,
 ) -> std::result::Result<(), ResponseError> {
@@ -18,10 +18,7 @@ pub async fn sorted_set_remove(
             SORTED_SET_REMOVE_OK.increment();
             Ok(())
         }
-        Ok(Err(_)) => {
-            SORTED_SET_REMOVE_EX.increment();
-            Err(ResponseError::Exception)
-        }
+        Ok(Err(e)) => Err(command_exception(&e, &SORTED_SET_REMOVE_EX)),
         Err(_) => {
             SORTED_SET_REMOVE_TIMEOUT.increment();
             Err(ResponseError::Timeout)
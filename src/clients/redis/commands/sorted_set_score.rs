@@ -2,7 +2,7 @@ use super::*;
 
 /// Returns the score of one or more members in a sorted set.
 pub async fn sorted_set_score(
-    connection: &mut Connection<net::Stream>,
+    connection: &mut (impl ConnectionLike + Send),
     config: &Config,
     request: workload::client::SortedSetScore,
 ) -> std::result::Result<(), ResponseError> {
@@ -26,7 +26,7 @@ pub async fn sorted_set_score(
                 }
                 Ok(())
             }
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     } else {
@@ -48,7 +48,7 @@ pub async fn sorted_set_score(
                 }
                 Ok(())
             }
-            Ok(Err(_)) => Err(ResponseError::Exception),
+            Ok(Err(e)) => Err(exception(&e)),
             Err(_) => Err(ResponseError::Timeout),
         }
     };
@@ -1,191 +1,801 @@
 use super::*;
 use crate::net::Connector;
-use ::redis::aio::Connection;
+use ::redis::aio::{Connection, ConnectionLike, MultiplexedConnection};
 use ::redis::{AsyncCommands, RedisConnectionInfo};
 use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Arc;
 
+mod cluster;
 mod commands;
 
 use commands::*;
 
+// the number of `MOVED` or `ASK` redirections followed for a request in
+// cluster mode, before it fails
+const MAX_REDIRECTS: usize = 5;
+
 /// Launch tasks with one conncetion per task as RESP protocol is not mux-enabled.
 pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
     debug!("launching resp protocol tasks");
 
+    if config.target().cluster() {
+        // each cluster task manages its own connection to every node in the
+        // cluster, using the endpoints to discover the cluster topology
+        for _ in 0..config.client().unwrap().poolsize() {
+            runtime.spawn(cluster_task(work_receiver.clone(), config.clone()));
+        }
+
+        return;
+    }
+
     // create one task per "connection"
     // note: these may be channels instead of connections for multiplexed protocols
-    for _ in 0..config.client().unwrap().poolsize() {
-        for endpoint in config.target().endpoints() {
-            runtime.spawn(task(
-                work_receiver.clone(),
-                endpoint.clone(),
-                config.clone(),
-            ));
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            if config.client().unwrap().pipeline_depth() > 1 {
+                runtime.spawn(pipeline_task(
+                    work_receiver.clone(),
+                    endpoint.clone(),
+                    config.clone(),
+                ));
+            } else {
+                runtime.spawn(task(
+                    work_receiver.clone(),
+                    endpoint.clone(),
+                    config.clone(),
+                ));
+            }
+        }
+    }
+}
+
+// the connection authenticates and selects the database as part of its setup,
+// failing if either is rejected
+fn connection_info(config: &Config) -> RedisConnectionInfo {
+    match config.redis() {
+        Some(redis) => RedisConnectionInfo {
+            db: redis.db(),
+            username: redis.username().map(|username| username.to_string()),
+            password: redis.password().map(|password| password.to_string()),
+        },
+        None => RedisConnectionInfo {
+            db: 0,
+            username: None,
+            password: None,
+        },
+    }
+}
+
+/// Opens a stream to the endpoint, recording the outcome. The connection is
+/// counted as current until it is dropped by the caller.
+async fn open(connector: &Connector, config: &Config, endpoint: &str) -> Option<net::Stream> {
+    CONNECT.increment();
+    match timeout(
+        config.client().unwrap().connect_timeout(),
+        connector.connect(endpoint),
+    )
+    .await
+    {
+        Ok(Ok(c)) => {
+            CONNECT_OK.increment();
+            CONNECT_CURR.increment();
+            Some(c)
+        }
+        Ok(Err(e)) => {
+            trace!("error connecting: {e}");
+            CONNECT_EX.increment();
+            record_error(ErrorCategory::Connection);
+            None
+        }
+        Err(_) => {
+            trace!("connect timeout");
+            CONNECT_TIMEOUT.increment();
+            record_error(ErrorCategory::Connection);
+            None
         }
     }
 }
 
+// records a connection which was opened but could not be set up
+fn setup_failed(error: ::redis::RedisError) {
+    // the error describes the failed command, never the credentials
+    debug!("error during connection setup: {:?}", error.kind());
+    CONNECT_CURR.decrement();
+    CONNECT_EX.increment();
+    record_error(ErrorCategory::Connection);
+}
+
+/// Attempts to connect to the endpoint, waiting for the backoff on failure.
+/// Returns an error if the backoff gives up on the endpoint.
+async fn connect(
+    connector: &Connector,
+    config: &Config,
+    endpoint: &str,
+    backoff: &mut Backoff,
+) -> Result<Option<Connection<net::Stream>>> {
+    let start = Instant::now();
+    if let Some(stream) = open(connector, config, endpoint).await {
+        match Connection::new(&connection_info(config), stream).await {
+            Ok(c) => {
+                backoff.reset();
+                record_connect(endpoint, start);
+                return Ok(Some(c));
+            }
+            Err(e) => setup_failed(e),
+        }
+    }
+
+    backoff.wait().await?;
+    Ok(None)
+}
+
+/// Attempts to connect to the endpoint with a connection which pipelines the
+/// requests sent on any of its handles, waiting for the backoff on failure.
+/// The connection is closed once every handle has been dropped.
+async fn connect_pipelined(
+    connector: &Connector,
+    config: &Config,
+    endpoint: &str,
+    backoff: &mut Backoff,
+) -> Result<Option<MultiplexedConnection>> {
+    let start = Instant::now();
+    if let Some(stream) = open(connector, config, endpoint).await {
+        match MultiplexedConnection::new(&connection_info(config), stream).await {
+            Ok((c, driver)) => {
+                // the driver writes the requests and reads the responses
+                tokio::spawn(driver);
+                backoff.reset();
+                record_connect(endpoint, start);
+                return Ok(Some(c));
+            }
+            Err(e) => setup_failed(e),
+        }
+    }
+
+    backoff.wait().await?;
+    Ok(None)
+}
+
 #[allow(dead_code)]
 #[allow(clippy::slow_vector_initialization)]
 async fn task(work_receiver: Receiver<WorkItem>, endpoint: String, config: Config) -> Result<()> {
     trace!("launching resp task for endpoint: {endpoint}");
     let connector = Connector::new(&config)?;
-
-    let redis_connection_info = RedisConnectionInfo {
-        db: 0,
-        username: None,
-        password: None,
-    };
+    let mut backoff = Backoff::new(config.client().unwrap());
+    let ratelimit = ConnectionRatelimit::new(config.client().unwrap());
+    let mut lifetime = Lifetime::new(config.client().unwrap());
 
     let mut connection = None;
 
+    wait_for_ramp().await;
+
     while RUNNING.load(Ordering::Relaxed) {
         if connection.is_none() {
-            CONNECT.increment();
-            connection = match timeout(
-                config.client().unwrap().connect_timeout(),
-                connector.connect(&endpoint),
-            )
-            .await
-            {
-                Ok(Ok(c)) => {
-                    CONNECT_OK.increment();
-                    CONNECT_CURR.increment();
-                    if let Ok(c) = ::redis::aio::Connection::new(&redis_connection_info, c).await {
-                        Some(c)
-                    } else {
-                        CONNECT_EX.increment();
-                        sleep(Duration::from_millis(100)).await;
-                        continue;
-                    }
-                }
-                Ok(Err(e)) => {
-                    trace!("error connecting: {e}");
-                    CONNECT_EX.increment();
-                    sleep(Duration::from_millis(100)).await;
-                    continue;
-                }
-                Err(_) => {
-                    trace!("connect timeout");
-                    CONNECT_TIMEOUT.increment();
-                    sleep(Duration::from_millis(100)).await;
-                    continue;
-                }
+            connection = connect(&connector, &config, &endpoint, &mut backoff).await?;
+            if connection.is_none() {
+                continue;
             }
+            lifetime.open();
         }
 
         let mut con = connection.take().unwrap();
+
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
         let work_item = work_receiver
             .recv()
             .await
             .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
 
         REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
+        let request = match work_item {
+            WorkItem::Request { request, .. } => request,
+            WorkItem::Reconnect => {
+                CONNECT_CURR.sub(1);
+                continue;
+            }
+        };
+
+        let attempts = attempts(config.client().unwrap(), &request);
+        let mut request = Some(request);
+        let mut attempt = 1;
+
+        // the latency includes any retries, as it is the time until the
+        // request succeeded
         let start = Instant::now();
-        let result = match work_item {
-            WorkItem::Request { request, .. } => match request {
-                /*
-                 * PING
-                 */
-                ClientRequest::Ping(r) => ping(&mut con, &config, r).await,
-
-                /*
-                 * KEY-VALUE
-                 */
-                ClientRequest::Add(r) => add(&mut con, &config, r).await,
-                ClientRequest::Delete(r) => delete(&mut con, &config, r).await,
-                ClientRequest::Get(r) => get(&mut con, &config, r).await,
-                ClientRequest::Replace(r) => replace(&mut con, &config, r).await,
-                ClientRequest::Set(r) => set(&mut con, &config, r).await,
-
-                /*
-                 * HASHES (DICTIONARIES)
-                 */
-                ClientRequest::HashDelete(r) => hash_delete(&mut con, &config, r).await,
-                ClientRequest::HashExists(r) => hash_exists(&mut con, &config, r).await,
-                ClientRequest::HashIncrement(r) => hash_increment(&mut con, &config, r).await,
-                // transparently issues either a `hget` or `hmget`
-                ClientRequest::HashGet(r) => hash_get(&mut con, &config, r).await,
-                ClientRequest::HashGetAll(r) => hash_get_all(&mut con, &config, r).await,
-                ClientRequest::HashSet(r) => hash_set(&mut con, &config, r).await,
-
-                /*
-                 * LISTS
-                 */
-                // To truncate, we must fuse an LTRIM at the end of the LPUSH
-                ClientRequest::ListPushFront(r) => list_push_front(&mut con, &config, r).await,
-                // To truncate, we must fuse an RTRIM at the end of the RPUSH
-                ClientRequest::ListPushBack(r) => list_push_back(&mut con, &config, r).await,
-                ClientRequest::ListFetch(r) => list_fetch(&mut con, &config, r).await,
-                ClientRequest::ListLength(r) => list_length(&mut con, &config, r).await,
-                ClientRequest::ListPopFront(r) => list_pop_front(&mut con, &config, r).await,
-                ClientRequest::ListPopBack(r) => list_pop_back(&mut con, &config, r).await,
-
-                /*
-                 * SETS
-                 */
-                ClientRequest::SetAdd(r) => set_add(&mut con, &config, r).await,
-                ClientRequest::SetMembers(r) => set_members(&mut con, &config, r).await,
-                ClientRequest::SetRemove(r) => set_remove(&mut con, &config, r).await,
-
-                /*
-                 * SORTED SETS
-                 */
-                ClientRequest::SortedSetAdd(r) => sorted_set_add(&mut con, &config, r).await,
-                ClientRequest::SortedSetRange(r) => sorted_set_range(&mut con, &config, r).await,
-                ClientRequest::SortedSetIncrement(r) => {
-                    sorted_set_increment(&mut con, &config, r).await
-                }
-                ClientRequest::SortedSetRemove(r) => sorted_set_remove(&mut con, &config, r).await,
-                ClientRequest::SortedSetScore(r) => sorted_set_score(&mut con, &config, r).await,
-                ClientRequest::SortedSetRank(r) => sorted_set_rank(&mut con, &config, r).await,
-
-                /*
-                 * UNSUPPORTED
-                 */
-                _ => {
+
+        loop {
+            // a request which may be retried is kept until the last attempt
+            let r = if attempt < attempts {
+                request.clone()
+            } else {
+                request.take()
+            };
+
+            let result = match send(&mut con, &config, r.unwrap()).await {
+                Some(result) => result,
+                None => {
                     REQUEST_UNSUPPORTED.increment();
                     connection = Some(con);
+                    break;
+                }
+            };
+
+            lifetime.request();
+
+            // the connection is closed when a request fails, so the retry is
+            // sent on a new connection. If there is no new connection, the
+            // failure is the result
+            if attempt < attempts
+                && matches!(
+                    result,
+                    Err(ResponseError::Exception | ResponseError::Timeout)
+                )
+            {
+                if let Some(c) = connect(&connector, &config, &endpoint, &mut backoff).await? {
+                    REQUEST_RETRY.increment();
+                    CONNECT_CURR.decrement();
+                    con = c;
+                    lifetime.open();
+                    attempt += 1;
                     continue;
                 }
-            },
-            WorkItem::Reconnect => {
-                CONNECT_CURR.sub(1);
+            }
+
+            if record_result(start, intended, histograms, result).is_ok() {
+                connection = Some(con);
+            } else {
+                CONNECT_CURR.decrement();
+            }
+
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the requests on a connection which pipelines them. Any requests which
+/// are already queued are sent together, up to the pipeline depth, and the
+/// next requests are sent once all of their responses have arrived. The
+/// responses arrive in the same order as the requests were sent, so each
+/// request completes as its response arrives.
+async fn pipeline_task(
+    work_receiver: Receiver<WorkItem>,
+    endpoint: String,
+    config: Config,
+) -> Result<()> {
+    trace!("launching pipelined resp task for endpoint: {endpoint}");
+    let connector = Connector::new(&config)?;
+    let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
+
+    let mut connection = None;
+
+    wait_for_ramp().await;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if connection.is_none() {
+            connection = connect_pipelined(&connector, &config, &endpoint, &mut backoff).await?;
+            if connection.is_none() {
                 continue;
             }
-        };
+            lifetime.open();
+        }
 
-        REQUEST_OK.increment();
+        let con = connection.take().unwrap();
 
-        let stop = Instant::now();
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
 
-        let latency_ns = stop.duration_since(start).as_nanos() as u64;
+        // wait for a work item and then opportunistically fill the pipeline
+        // with any other work items which are already queued
+        let mut work_items = vec![work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?];
 
-        match result {
-            Ok(_) => {
-                connection = Some(con);
-                RESPONSE_OK.increment();
+        while work_items.len() < client_config.pipeline_depth() {
+            match work_receiver.try_recv() {
+                Ok(work_item) => work_items.push(work_item),
+                Err(_) => break,
+            }
+        }
 
-                let _ = RESPONSE_LATENCY.increment(latency_ns);
+        let mut reconnect = false;
+        let mut requests = Vec::with_capacity(work_items.len());
+
+        for work_item in work_items {
+            REQUEST.increment();
+            let histograms = latency_histograms(&work_item);
+            let intended = intended_start(&work_item);
+            match work_item {
+                WorkItem::Request { request, .. } => requests.push((request, histograms, intended)),
+                // the reconnect happens once any requests in the pipeline
+                // have completed
+                WorkItem::Reconnect => reconnect = true,
             }
-            Err(ResponseError::Exception) => {
+        }
+
+        if requests.is_empty() {
+            if reconnect {
                 CONNECT_CURR.decrement();
-                RESPONSE_EX.increment();
+            } else {
+                connection = Some(con);
             }
-            Err(ResponseError::Timeout) => {
-                CONNECT_CURR.decrement();
-                RESPONSE_TIMEOUT.increment();
+            continue;
+        }
+
+        let _ = PIPELINE_DEPTH.increment(requests.len() as _);
+
+        // each request is sent on its own handle to the connection. The
+        // requests are written in the order they are first polled, which is
+        // the order of the work items
+        let start = Instant::now();
+        let results = futures::future::join_all(requests.into_iter().map(
+            |(request, histograms, intended)| {
+                let mut con = con.clone();
+                let config = &config;
+                async move {
+                    send(&mut con, config, request)
+                        .await
+                        .map(|result| record_result(start, intended, histograms, result))
+                }
+            },
+        ))
+        .await;
+
+        let mut failed = false;
+
+        for result in results {
+            match result {
+                Some(result) => {
+                    lifetime.request();
+                    failed |= result.is_err();
+                }
+                None => {
+                    REQUEST_UNSUPPORTED.increment();
+                }
             }
-            Err(ResponseError::Ratelimited) => {
-                RESPONSE_RATELIMITED.increment();
-                connection = Some(con);
+        }
+
+        if failed || reconnect {
+            // any responses for the requests which failed may still arrive,
+            // so the connection is dropped
+            CONNECT_CURR.decrement();
+        } else {
+            // preserve the connection for the next requests
+            connection = Some(con);
+        }
+    }
+
+    Ok(())
+}
+
+async fn cluster_task(work_receiver: Receiver<WorkItem>, config: Config) -> Result<()> {
+    trace!("launching resp cluster task");
+    let connector = Connector::new(&config)?;
+    let mut backoff = Backoff::new(config.client().unwrap());
+
+    let mut slots = None;
+    let mut connections: HashMap<Arc<str>, Connection<net::Stream>> = HashMap::new();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        // discover the slot map using the first endpoint that responds
+        if slots.is_none() {
+            for endpoint in config.target().endpoints() {
+                if let Some(mut con) = connect(&connector, &config, endpoint, &mut backoff).await? {
+                    slots = cluster::SlotMap::discover(&mut con, &config).await;
+                    CONNECT_CURR.decrement();
+                }
+
+                if slots.is_some() {
+                    break;
+                }
             }
-            Err(ResponseError::BackendTimeout) => {
-                RESPONSE_BACKEND_TIMEOUT.increment();
-                connection = Some(con);
+
+            if slots.is_none() {
+                backoff.wait().await?;
+                continue;
+            }
+        }
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+        let histograms = latency_histograms(&work_item);
+        let intended = intended_start(&work_item);
+        let request = match work_item {
+            WorkItem::Request { request, .. } => request,
+            WorkItem::Reconnect => {
+                CONNECT_CURR.sub(connections.len() as _);
+                connections.clear();
+                continue;
+            }
+        };
+
+        let mut node = slots.as_ref().unwrap().route(&request);
+        let mut request = Some(request);
+        let mut asking = false;
+        let mut redirects = 0;
+
+        // the latency includes any redirections, as it is the time until the
+        // request was handled by the node which owns the key
+        let start = Instant::now();
+
+        loop {
+            let mut con = match connections.remove(&node) {
+                Some(con) => con,
+                None => match connect(&connector, &config, &node, &mut backoff).await? {
+                    Some(con) => con,
+                    None => {
+                        // the request fails with the connection error
+                        // recorded by `connect()`, and as the topology may
+                        // have changed the slot map is refreshed
+                        REQUEST_OK.increment();
+                        RESPONSE_EX.increment();
+                        slots = None;
+                        break;
+                    }
+                },
+            };
+
+            // a request which may be redirected is kept until the last attempt
+            let r = if redirects < MAX_REDIRECTS {
+                request.clone()
+            } else {
+                request.take()
+            };
+
+            let (result, redirect) =
+                match cluster::send(&mut con, &config, r.unwrap(), asking).await {
+                    Some(result) => result,
+                    None => {
+                        REQUEST_UNSUPPORTED.increment();
+                        connections.insert(node, con);
+                        break;
+                    }
+                };
+
+            // a redirection is a reply, so the connection is kept
+            if let Some(redirect) = redirect {
+                connections.insert(node, con);
+
+                if redirects < MAX_REDIRECTS {
+                    REQUEST_RETRY.increment();
+
+                    if redirect.moved {
+                        slots
+                            .as_mut()
+                            .unwrap()
+                            .assign(redirect.slot, redirect.node.clone());
+                    }
+
+                    asking = !redirect.moved;
+                    node = redirect.node;
+                    redirects += 1;
+                    continue;
+                }
+
+                record_error(ErrorCategory::Server);
+                let _ = record_result(start, intended, histograms, result);
+                break;
             }
+
+            if record_result(start, intended, histograms, result).is_ok() {
+                connections.insert(node, con);
+            } else {
+                CONNECT_CURR.decrement();
+            }
+
+            break;
         }
     }
 
+    CONNECT_CURR.sub(connections.len() as _);
+
     Ok(())
 }
+
+/// Sends the request using the connection. Returns `None` if the request is
+/// not supported.
+async fn send(
+    con: &mut (impl ConnectionLike + Send),
+    config: &Config,
+    request: ClientRequest,
+) -> Option<std::result::Result<(), ResponseError>> {
+    let result = match request {
+        /*
+         * PING
+         */
+        ClientRequest::Ping(r) => ping(con, config, r).await,
+
+        /*
+         * KEY-VALUE
+         */
+        ClientRequest::Add(r) => add(con, config, r).await,
+        ClientRequest::Delete(r) => delete(con, config, r).await,
+        ClientRequest::Get(r) => get(con, config, r).await,
+        ClientRequest::MultiGet(r) => multi_get(con, config, r).await,
+        ClientRequest::MultiSet(r) => multi_set(con, config, r).await,
+        ClientRequest::Replace(r) => replace(con, config, r).await,
+        ClientRequest::Set(r) => set(con, config, r).await,
+        ClientRequest::Scan(r) => scan(con, config, r).await,
+
+        /*
+         * HASHES (DICTIONARIES)
+         */
+        ClientRequest::HashDelete(r) => hash_delete(con, config, r).await,
+        ClientRequest::HashExists(r) => hash_exists(con, config, r).await,
+        ClientRequest::HashIncrement(r) => hash_increment(con, config, r).await,
+        // transparently issues either a `hget` or `hmget`
+        ClientRequest::HashGet(r) => hash_get(con, config, r).await,
+        ClientRequest::HashGetAll(r) => hash_get_all(con, config, r).await,
+        ClientRequest::HashSet(r) => hash_set(con, config, r).await,
+
+        /*
+         * LISTS
+         */
+        // To truncate, we must fuse an LTRIM at the end of the LPUSH
+        ClientRequest::ListPushFront(r) => list_push_front(con, config, r).await,
+        // To truncate, we must fuse an RTRIM at the end of the RPUSH
+        ClientRequest::ListPushBack(r) => list_push_back(con, config, r).await,
+        ClientRequest::ListFetch(r) => list_fetch(con, config, r).await,
+        ClientRequest::ListLength(r) => list_length(con, config, r).await,
+        ClientRequest::ListPopFront(r) => list_pop_front(con, config, r).await,
+        ClientRequest::ListPopBack(r) => list_pop_back(con, config, r).await,
+        ClientRequest::ListRemove(r) => list_remove(con, config, r).await,
+
+        /*
+         * SETS
+         */
+        ClientRequest::SetAdd(r) => set_add(con, config, r).await,
+        ClientRequest::SetMembers(r) => set_members(con, config, r).await,
+        ClientRequest::SetRemove(r) => set_remove(con, config, r).await,
+
+        /*
+         * SORTED SETS
+         */
+        ClientRequest::SortedSetAdd(r) => sorted_set_add(con, config, r).await,
+        ClientRequest::SortedSetRange(r) => sorted_set_range(con, config, r).await,
+        ClientRequest::SortedSetIncrement(r) => sorted_set_increment(con, config, r).await,
+        ClientRequest::SortedSetRemove(r) => sorted_set_remove(con, config, r).await,
+        ClientRequest::SortedSetScore(r) => sorted_set_score(con, config, r).await,
+        ClientRequest::SortedSetRank(r) => sorted_set_rank(con, config, r).await,
+
+        /*
+         * UNSUPPORTED
+         */
+        _ => {
+            return None;
+        }
+    };
+
+    Some(result)
+}
+
+/// Records the stats for the result of a request. Returns an error if the
+/// connection should be dropped.
+fn record_result(
+    start: Instant,
+    intended: Option<Instant>,
+    histograms: LatencyHistograms,
+    result: std::result::Result<(), ResponseError>,
+) -> std::result::Result<(), ResponseError> {
+    REQUEST_OK.increment();
+
+    let stop = Instant::now();
+
+    let latency_ns = stop.duration_since(start).as_nanos() as u64;
+
+    match result {
+        Ok(_) => {
+            RESPONSE_OK.increment();
+
+            record_latency(histograms, latency_ns);
+            record_corrected_latency(intended, stop);
+
+            Ok(())
+        }
+        Err(ResponseError::Exception) => {
+            RESPONSE_EX.increment();
+
+            Err(ResponseError::Exception)
+        }
+        Err(ResponseError::Timeout) => {
+            RESPONSE_TIMEOUT.increment();
+            record_error(ErrorCategory::Timeout);
+
+            Err(ResponseError::Timeout)
+        }
+        Err(ResponseError::Ratelimited) => {
+            RESPONSE_RATELIMITED.increment();
+
+            Ok(())
+        }
+        Err(ResponseError::BackendTimeout) => {
+            RESPONSE_BACKEND_TIMEOUT.increment();
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+
+    const GET: &[u8] = b"*2\r\n$3\r\nGET\r\n";
+
+    // the replies to four gets, a hit followed by three misses
+    const REPLIES: &[u8] = b"$5\r\nvalue\r\n$-1\r\n$-1\r\n$-1\r\n";
+
+    // reads from the stream until `n` more gets have arrived, returning the
+    // number of gets which arrived without waiting for a reply
+    async fn read_gets(stream: &mut TcpStream, buffer: &mut Vec<u8>, n: usize) -> usize {
+        loop {
+            let gets = buffer.windows(GET.len()).filter(|w| *w == GET).count();
+            if gets >= n {
+                buffer.clear();
+                return gets;
+            }
+
+            let mut chunk = [0; 4096];
+            let len = stream.read(&mut chunk).await.unwrap();
+            assert!(len > 0, "connection closed by the client");
+            buffer.extend_from_slice(&chunk[..len]);
+        }
+    }
+
+    // starts a pipelined client with four gets already queued, so that they
+    // are sent as one pipeline
+    async fn pipeline() -> (
+        TcpListener,
+        async_channel::Sender<WorkItem>,
+        tokio::task::JoinHandle<Result<()>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("resp", &endpoint, "pipeline_depth = 4");
+
+        let (sender, receiver) = async_channel::bounded(16);
+        for key in ["a", "b", "c", "d"] {
+            sender.send(testing::get(key)).await.unwrap();
+        }
+
+        let task = tokio::spawn(pipeline_task(receiver, endpoint, config));
+
+        (listener, sender, task)
+    }
+
+    #[tokio::test]
+    async fn pipeline_coalesced_replies() {
+        let (listener, sender, task) = pipeline().await;
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = Vec::new();
+
+        assert_eq!(read_gets(&mut stream, &mut buffer, 4).await, 4);
+
+        let ok = RESPONSE_OK.value();
+
+        // all of the replies arrive in a single write
+        stream.write_all(REPLIES).await.unwrap();
+
+        // the connection is kept for the next request, as every request in
+        // the pipeline succeeded
+        sender.send(testing::get("e")).await.unwrap();
+        assert_eq!(read_gets(&mut stream, &mut buffer, 1).await, 1);
+        assert!(RESPONSE_OK.value() >= ok + 4);
+
+        drop(sender);
+        stream.write_all(b"$-1\r\n").await.unwrap();
+        let _ = task.await;
+    }
+
+    #[tokio::test]
+    async fn pipeline_partial_replies() {
+        let (listener, sender, task) = pipeline().await;
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = Vec::new();
+
+        assert_eq!(read_gets(&mut stream, &mut buffer, 4).await, 4);
+
+        let ok = RESPONSE_OK.value();
+
+        // the replies are split across writes, including within a reply
+        for chunk in REPLIES.chunks(3) {
+            stream.write_all(chunk).await.unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        sender.send(testing::get("e")).await.unwrap();
+        assert_eq!(read_gets(&mut stream, &mut buffer, 1).await, 1);
+        assert!(RESPONSE_OK.value() >= ok + 4);
+
+        drop(sender);
+        stream.write_all(b"$-1\r\n").await.unwrap();
+        let _ = task.await;
+    }
+
+    #[tokio::test]
+    async fn pipeline_closed_with_requests_outstanding() {
+        let (listener, sender, task) = pipeline().await;
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = Vec::new();
+
+        assert_eq!(read_gets(&mut stream, &mut buffer, 4).await, 4);
+
+        let ok = RESPONSE_OK.value();
+        let ex = RESPONSE_EX.value();
+
+        // only the first two replies are sent before the connection is closed
+        stream.write_all(b"$5\r\nvalue\r\n$-1\r\n").await.unwrap();
+        drop(stream);
+
+        // the requests without a reply fail and the client reconnects
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert!(RESPONSE_OK.value() >= ok + 2);
+        assert!(RESPONSE_EX.value() >= ex + 2);
+
+        sender.send(testing::get("e")).await.unwrap();
+        assert_eq!(read_gets(&mut stream, &mut buffer, 1).await, 1);
+
+        drop(sender);
+        stream.write_all(b"$-1\r\n").await.unwrap();
+        let _ = task.await;
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let config = testing::config("resp", &endpoint, "[client.retry]\nmax_attempts = 2");
+
+        let retries = REQUEST_RETRY.value();
+        let hits = GET_KEY_HIT.value();
+
+        let (sender, receiver) = async_channel::bounded(16);
+        sender.send(testing::get("key")).await.unwrap();
+        let task = tokio::spawn(task(receiver, endpoint, config));
+
+        // the first attempt fails with an error reply
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = Vec::new();
+        read_gets(&mut stream, &mut buffer, 1).await;
+        stream.write_all(b"-ERR transient\r\n").await.unwrap();
+
+        // the retry is sent on a new connection and succeeds
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_gets(&mut stream, &mut buffer, 1).await;
+        stream.write_all(b"$5\r\nvalue\r\n").await.unwrap();
+
+        testing::eventually(|| GET_KEY_HIT.value() > hits).await;
+        assert!(REQUEST_RETRY.value() > retries);
+
+        drop(sender);
+        let _ = task.await;
+    }
+}
@@ -0,0 +1,638 @@
+use super::*;
+use crate::net::Connector;
+use session::{Buf, BufMut, Buffer};
+use std::borrow::{Borrow, BorrowMut};
+use std::sync::Arc;
+
+// the strict binary protocol has the version in the upper half of the first
+// word of each message and the message type in the lowest byte
+const VERSION_1: u32 = 0x8001_0000;
+const VERSION_MASK: u32 = 0xffff_0000;
+
+// message types
+const CALL: u8 = 1;
+const REPLY: u8 = 2;
+const EXCEPTION: u8 = 3;
+
+// field types
+const STOP: u8 = 0;
+const BOOL: u8 = 2;
+const BYTE: u8 = 3;
+const DOUBLE: u8 = 4;
+const I16: u8 = 6;
+const I32: u8 = 8;
+const I64: u8 = 10;
+const STRING: u8 = 11;
+const STRUCT: u8 = 12;
+const MAP: u8 = 13;
+const SET: u8 = 14;
+const LIST: u8 = 15;
+const UUID: u8 = 16;
+
+// replies are not expected to nest deeper than this, and a deeper one is
+// treated as invalid rather than recursing without bound
+const MAX_DEPTH: usize = 64;
+
+/// The outcome of a call, as given by the reply.
+enum Reply {
+    Success,
+    // one of the exceptions declared by the method
+    Exception,
+    // the server failed the call, for example for an unknown method
+    ApplicationException,
+}
+
+/// Launch tasks with one connection per task, as each connection has a single
+/// call outstanding. Each request generated by the workload is sent as a call
+/// to the configured method.
+pub fn launch_tasks(runtime: &mut Runtime, config: Config, work_receiver: Receiver<WorkItem>) {
+    debug!("launching thrift protocol tasks");
+
+    // config validation ensures that the thrift section is present
+    let thrift = config.thrift().unwrap();
+
+    // without a payload the argument struct is empty, which is just the stop
+    // field
+    let payload: Arc<[u8]> = match thrift.payload() {
+        Some(path) => match std::fs::read(path) {
+            Ok(payload) => payload.into(),
+            Err(e) => {
+                eprintln!("error reading thrift payload: {path}\n{e}");
+                std::process::exit(1);
+            }
+        },
+        None => Arc::new([STOP]),
+    };
+
+    for (endpoint, connections) in config
+        .target()
+        .connections(config.client().unwrap().poolsize())
+    {
+        for _ in 0..connections {
+            runtime.spawn(task(
+                work_receiver.clone(),
+                endpoint.clone(),
+                config.clone(),
+                payload.clone(),
+            ));
+        }
+    }
+}
+
+// a task for thrift servers using the binary protocol
+async fn task(
+    work_receiver: Receiver<WorkItem>,
+    endpoint: String,
+    config: Config,
+    payload: Arc<[u8]>,
+) -> Result<()> {
+    let connector = Connector::new(&config)?;
+
+    // this unwrap will succeed because we wouldn't be creating these tasks if
+    // there wasn't a client config.
+    let client_config = config.client().unwrap();
+    let mut backoff = Backoff::new(client_config);
+    let ratelimit = ConnectionRatelimit::new(client_config);
+    let mut lifetime = Lifetime::new(client_config);
+
+    let thrift = config.thrift().unwrap();
+    let method = thrift.method();
+    let framed = thrift.transport() == ThriftTransport::Framed;
+    let mut seqid: i32 = 0;
+
+    let mut stream = None;
+    let mut read_buffer = Buffer::new(client_config.read_buffer_size());
+    let mut write_buffer = Buffer::new(client_config.write_buffer_size());
+
+    wait_for_ramp().await;
+
+    while RUNNING.load(Ordering::Relaxed) {
+        if stream.is_none() {
+            CONNECT.increment();
+            let start = Instant::now();
+            stream = match timeout(
+                client_config.connect_timeout(),
+                connector.connect(&endpoint),
+            )
+            .await
+            {
+                Ok(Ok(s)) => {
+                    CONNECT_OK.increment();
+                    CONNECT_CURR.increment();
+                    backoff.reset();
+                    record_connect(&endpoint, start);
+                    lifetime.open();
+                    read_buffer.clear();
+                    Some(s)
+                }
+                Ok(Err(_)) => {
+                    CONNECT_EX.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+                Err(_) => {
+                    CONNECT_TIMEOUT.increment();
+                    record_error(ErrorCategory::Connection);
+                    backoff.wait().await?;
+                    continue;
+                }
+            }
+        }
+
+        let mut s = stream.take().unwrap();
+
+        // replace the connection once it reaches the end of its lifetime
+        if lifetime.expired() {
+            CONNECT_EXPIRED.increment();
+            CONNECT_CURR.decrement();
+            continue;
+        }
+
+        ratelimit.wait().await;
+
+        let work_item = work_receiver
+            .recv()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Other, "channel closed"))?;
+
+        REQUEST.increment();
+        let intended = intended_start(&work_item);
+
+        // the verb of the request only drives the rate of calls
+        match &work_item {
+            WorkItem::Request { .. } => {
+                seqid = seqid.wrapping_add(1);
+                write_buffer.put_slice(&compose(method, seqid, &payload, framed));
+            }
+            WorkItem::Reconnect => {
+                REQUEST_RECONNECT.increment();
+                CONNECT_CURR.decrement();
+                continue;
+            }
+        }
+
+        REQUEST_OK.increment();
+        lifetime.request();
+
+        // send request
+        let start = Instant::now();
+        s.write_all(write_buffer.borrow()).await?;
+        write_buffer.clear();
+
+        // read until the reply is complete or the request times out
+        let deadline = start + client_config.request_timeout();
+        let response = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                break Err(ResponseError::Timeout);
+            }
+
+            match timeout(remaining, s.read(read_buffer.borrow_mut())).await {
+                Ok(Ok(0)) => {
+                    record_error(ErrorCategory::Connection);
+                    break Err(ResponseError::Exception);
+                }
+                Ok(Ok(n)) => {
+                    unsafe {
+                        read_buffer.advance_mut(n);
+                    }
+                    match parse(read_buffer.borrow(), method, seqid, framed) {
+                        Ok((consumed, reply)) => {
+                            read_buffer.advance(consumed);
+                            record_response_size(consumed);
+                            break Ok(reply);
+                        }
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                        Err(_) => {
+                            record_error(ErrorCategory::Protocol);
+                            break Err(ResponseError::Exception);
+                        }
+                    }
+                }
+                Ok(Err(_)) => {
+                    record_error(ErrorCategory::Connection);
+                    break Err(ResponseError::Exception);
+                }
+                Err(_) => {
+                    break Err(ResponseError::Timeout);
+                }
+            }
+        };
+
+        let stop = Instant::now();
+
+        match response {
+            Ok(reply) => {
+                // the reply is complete, so the connection can be reused
+                // whatever the outcome of the call
+                stream = Some(s);
+
+                match reply {
+                    Reply::Success => {
+                        RESPONSE_OK.increment();
+
+                        let latency = stop.duration_since(start).as_nanos() as u64;
+
+                        record_latency(LatencyHistograms::default(), latency);
+                        record_corrected_latency(intended, stop);
+                    }
+                    Reply::Exception => {
+                        THRIFT_EXCEPTION.increment();
+                        RESPONSE_EX.increment();
+                        record_error(ErrorCategory::Server);
+                    }
+                    Reply::ApplicationException => {
+                        THRIFT_APPLICATION_EXCEPTION.increment();
+                        RESPONSE_EX.increment();
+                        record_error(ErrorCategory::Server);
+                    }
+                }
+            }
+            Err(ResponseError::Timeout) => {
+                RESPONSE_TIMEOUT.increment();
+                record_error(ErrorCategory::Timeout);
+                CONNECT_CURR.decrement();
+            }
+            // the reply was not read, so the connection is closed. Any error
+            // other than a timeout is an exception, as a thrift reply is never
+            // ratelimited or a backend timeout
+            Err(_) => {
+                RESPONSE_EX.increment();
+                CONNECT_CURR.decrement();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// a call message is the header, the method name, the sequence id, and the
+// argument struct. With the framed transport it is prefixed with its length.
+fn compose(method: &str, seqid: i32, payload: &[u8], framed: bool) -> Vec<u8> {
+    let len = 12 + method.len() + payload.len();
+    let mut message = Vec::with_capacity(4 + len);
+
+    if framed {
+        message.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+
+    message.extend_from_slice(&(VERSION_1 | CALL as u32).to_be_bytes());
+    message.extend_from_slice(&(method.len() as u32).to_be_bytes());
+    message.extend_from_slice(method.as_bytes());
+    message.extend_from_slice(&seqid.to_be_bytes());
+    message.extend_from_slice(payload);
+
+    message
+}
+
+/// Parses the reply to a call from the buffer. Returns the number of bytes of
+/// the reply and the outcome of the call, or a `WouldBlock` error if the reply
+/// is incomplete.
+fn parse(buffer: &[u8], method: &str, seqid: i32, framed: bool) -> Result<(usize, Reply)> {
+    if !framed {
+        let mut reader = Reader::new(buffer);
+        let reply = message(&mut reader, method, seqid)?;
+        return Ok((reader.position, reply));
+    }
+
+    let mut reader = Reader::new(buffer);
+    let len = reader.length()?;
+    let frame = reader.take(len)?;
+
+    // the frame is complete, so the message must not extend past it
+    let reply = message(&mut Reader::new(frame), method, seqid).map_err(|e| {
+        if e.kind() == ErrorKind::WouldBlock {
+            invalid("the reply is longer than its frame")
+        } else {
+            e
+        }
+    })?;
+
+    Ok((reader.position, reply))
+}
+
+// parses the reply message, which must match the method and sequence id of
+// the call
+fn message(reader: &mut Reader, method: &str, seqid: i32) -> Result<Reply> {
+    let header = reader.i32()? as u32;
+
+    if header & VERSION_MASK != VERSION_1 {
+        return Err(invalid("the reply is not in the strict binary protocol"));
+    }
+
+    let len = reader.length()?;
+
+    if reader.take(len)? != method.as_bytes() {
+        return Err(invalid("the reply is for a different method"));
+    }
+
+    if reader.i32()? != seqid {
+        return Err(invalid("the reply has an unexpected sequence id"));
+    }
+
+    match (header & 0xff) as u8 {
+        // the result struct has the return value as field 0 and each declared
+        // exception as another field, with no field set for a void method
+        REPLY => {
+            let kind = reader.u8()?;
+
+            if kind == STOP {
+                return Ok(Reply::Success);
+            }
+
+            let reply = if reader.i16()? == 0 {
+                Reply::Success
+            } else {
+                Reply::Exception
+            };
+
+            skip(reader, kind, 0)?;
+            skip_fields(reader, 0)?;
+
+            Ok(reply)
+        }
+        EXCEPTION => {
+            skip(reader, STRUCT, 0)?;
+
+            Ok(Reply::ApplicationException)
+        }
+        _ => Err(invalid("the reply has an unexpected message type")),
+    }
+}
+
+// skips over a value of the given type
+fn skip(reader: &mut Reader, kind: u8, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("the reply is nested too deeply"));
+    }
+
+    match kind {
+        BOOL | BYTE => {
+            reader.take(1)?;
+        }
+        I16 => {
+            reader.take(2)?;
+        }
+        I32 => {
+            reader.take(4)?;
+        }
+        DOUBLE | I64 => {
+            reader.take(8)?;
+        }
+        UUID => {
+            reader.take(16)?;
+        }
+        STRING => {
+            let len = reader.length()?;
+            reader.take(len)?;
+        }
+        STRUCT => {
+            skip_fields(reader, depth + 1)?;
+        }
+        MAP => {
+            let key = reader.u8()?;
+            let value = reader.u8()?;
+            for _ in 0..reader.length()? {
+                skip(reader, key, depth + 1)?;
+                skip(reader, value, depth + 1)?;
+            }
+        }
+        SET | LIST => {
+            let element = reader.u8()?;
+            for _ in 0..reader.length()? {
+                skip(reader, element, depth + 1)?;
+            }
+        }
+        _ => return Err(invalid("the reply has an unknown field type")),
+    }
+
+    Ok(())
+}
+
+// skips over the fields of a struct up to and including its stop field
+fn skip_fields(reader: &mut Reader, depth: usize) -> Result<()> {
+    loop {
+        let kind = reader.u8()?;
+
+        if kind == STOP {
+            return Ok(());
+        }
+
+        reader.i16()?;
+        skip(reader, kind, depth)?;
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// reads big-endian values from the buffer, with a `WouldBlock` error if the
+// buffer ends first
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.buffer.len())
+            .ok_or_else(|| Error::from(ErrorKind::WouldBlock))?;
+
+        let bytes = &self.buffer[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        let bytes = self.take(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // the length of a string, frame, or collection, which must not be negative
+    fn length(&mut self) -> Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid("the reply has a negative length"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a reply message with the body following the header, method, and
+    // sequence id
+    fn reply(kind: u8, seqid: i32, body: &[u8], framed: bool) -> Vec<u8> {
+        let mut message = (VERSION_1 | kind as u32).to_be_bytes().to_vec();
+        message.extend_from_slice(&3_u32.to_be_bytes());
+        message.extend_from_slice(b"get");
+        message.extend_from_slice(&seqid.to_be_bytes());
+        message.extend_from_slice(body);
+
+        if framed {
+            let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(&message);
+            frame
+        } else {
+            message
+        }
+    }
+
+    // a string field with the id
+    fn string(id: i16, value: &[u8]) -> Vec<u8> {
+        let mut field = vec![STRING];
+        field.extend_from_slice(&id.to_be_bytes());
+        field.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        field.extend_from_slice(value);
+        field
+    }
+
+    // a struct field with the id, holding the fields
+    fn structure(id: i16, fields: &[u8]) -> Vec<u8> {
+        let mut field = vec![STRUCT];
+        field.extend_from_slice(&id.to_be_bytes());
+        field.extend_from_slice(fields);
+        field.push(STOP);
+        field
+    }
+
+    #[test]
+    fn call() {
+        let payload = [STOP];
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&[0x80, 0x01, 0x00, CALL]);
+        expected.extend_from_slice(&[0, 0, 0, 3]);
+        expected.extend_from_slice(b"get");
+        expected.extend_from_slice(&7_i32.to_be_bytes());
+        expected.push(STOP);
+        assert_eq!(compose("get", 7, &payload, false), expected);
+
+        // the frame is prefixed with the length of the message
+        let framed = compose("get", 7, &payload, true);
+        assert_eq!(framed[..4], (expected.len() as u32).to_be_bytes());
+        assert_eq!(framed[4..], expected);
+    }
+
+    #[test]
+    fn replies() {
+        for framed in [false, true] {
+            // a void method sets no field in the result struct
+            let bytes = reply(REPLY, 7, &[STOP], framed);
+            let (len, outcome) = parse(&bytes, "get", 7, framed).unwrap();
+            assert_eq!(len, bytes.len());
+            assert!(matches!(outcome, Reply::Success));
+
+            // the return value is field 0
+            let mut body = string(0, b"value");
+            body.push(STOP);
+            let bytes = reply(REPLY, 7, &body, framed);
+            assert!(matches!(
+                parse(&bytes, "get", 7, framed).unwrap(),
+                (len, Reply::Success) if len == bytes.len()
+            ));
+
+            // a declared exception is another field, which may be a struct
+            let mut body = structure(1, &string(1, b"not found"));
+            body.push(STOP);
+            let bytes = reply(REPLY, 7, &body, framed);
+            assert!(matches!(
+                parse(&bytes, "get", 7, framed).unwrap(),
+                (len, Reply::Exception) if len == bytes.len()
+            ));
+
+            // an application exception is the exception struct on its own
+            let mut body = string(1, b"unknown method");
+            body.extend_from_slice(&[I32, 0, 2, 0, 0, 0, 1, STOP]);
+            let bytes = reply(EXCEPTION, 7, &body, framed);
+            assert!(matches!(
+                parse(&bytes, "get", 7, framed).unwrap(),
+                (len, Reply::ApplicationException) if len == bytes.len()
+            ));
+
+            // only the first reply is consumed
+            let mut bytes = reply(REPLY, 7, &[STOP], framed);
+            let len = bytes.len();
+            bytes.extend_from_slice(&reply(REPLY, 8, &[STOP], framed));
+            assert_eq!(parse(&bytes, "get", 7, framed).unwrap().0, len);
+        }
+    }
+
+    #[test]
+    fn incomplete() {
+        for framed in [false, true] {
+            let mut body = string(0, b"value");
+            body.push(STOP);
+            let bytes = reply(REPLY, 7, &body, framed);
+
+            for len in 0..bytes.len() {
+                let error = parse(&bytes[..len], "get", 7, framed).err().unwrap();
+                assert_eq!(error.kind(), ErrorKind::WouldBlock, "{len}");
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_replies() {
+        let invalid = |bytes: &[u8], framed| {
+            let error = parse(bytes, "get", 7, framed).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{error}");
+        };
+
+        // the seqid or method of another call
+        invalid(&reply(REPLY, 8, &[STOP], false), false);
+        invalid(&parse_as("put", reply(REPLY, 7, &[STOP], false)), false);
+
+        // not the strict binary protocol
+        let mut bytes = reply(REPLY, 7, &[STOP], false);
+        bytes[0] = 0;
+        invalid(&bytes, false);
+
+        // a message type which is not a reply
+        invalid(&reply(CALL, 7, &[STOP], false), false);
+
+        // an unknown field type
+        invalid(&reply(REPLY, 7, &[99, 0, 0], false), false);
+
+        // a message which is longer than its frame
+        let mut bytes = reply(REPLY, 7, &[STOP], true);
+        bytes[3] -= 1;
+        invalid(&bytes[..bytes.len() - 1], true);
+
+        // structs nested beyond the limit
+        let mut body = Vec::new();
+        for _ in 0..=MAX_DEPTH + 1 {
+            body.extend_from_slice(&[STRUCT, 0, 1]);
+        }
+        body.extend(std::iter::repeat(STOP).take(MAX_DEPTH + 3));
+        invalid(&reply(REPLY, 7, &body, false), false);
+    }
+
+    // replaces the method name of a reply to `get` with another of the same
+    // length
+    fn parse_as(method: &str, mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes[8..11].copy_from_slice(method.as_bytes());
+        bytes
+    }
+}
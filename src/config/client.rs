@@ -17,6 +17,65 @@ pub struct Client {
     /// is useful to model steady-state connect pressure on a backend.
     #[serde(default)]
     reconnect_rate: u64,
+    /// Limits the rate of requests on each connection, in requests per second.
+    /// This is applied in addition to the workload ratelimit, so a request is
+    /// only sent when both limits permit it.
+    #[serde(default)]
+    per_connection_rate: u64,
+    /// The delay before reconnecting after a failed connection attempt, in
+    /// milliseconds. The delay doubles with each consecutive failure.
+    #[serde(default = "default_reconnect_backoff_initial")]
+    reconnect_backoff_initial: u64,
+    /// The maximum delay between connection attempts, in milliseconds.
+    #[serde(default = "default_reconnect_backoff_max")]
+    reconnect_backoff_max: u64,
+    /// Randomizes each delay by up to this fraction of the delay, in the
+    /// range 0.0 - 1.0, to avoid reconnecting in lock-step.
+    #[serde(default)]
+    reconnect_backoff_jitter: f64,
+    /// The number of consecutive failed attempts after which a connection is
+    /// abandoned. By default, connections are retried indefinitely.
+    #[serde(default)]
+    reconnect_max_retries: Option<usize>,
+    /// The maximum number of requests which may be written to a connection
+    /// before awaiting their responses. Responses are matched to requests in
+    /// order. Only supported for the memcache and resp protocols, and not in
+    /// cluster mode.
+    #[serde(default)]
+    pipeline_depth: usize,
+    /// Reuse HTTP/1.1 connections for multiple requests. When disabled each
+    /// connection is closed after a single request. Defaults to enabled.
+    #[serde(default = "default_http_keepalive")]
+    http_keepalive: bool,
+    /// Opens the connections gradually instead of all at once, to observe how
+    /// a backend behaves as the number of clients grows.
+    #[serde(default)]
+    connection_ramp: Option<ConnectionRamp>,
+    /// Closes each connection once it has served a number of requests or has
+    /// been open for a duration, and then opens a new one, to model clients
+    /// with short-lived connections.
+    #[serde(default)]
+    connection_lifetime: Option<ConnectionLifetime>,
+    /// Sends a request again when it fails with an error or a timeout, for
+    /// the commands which are safe to repeat. Only supported for the resp
+    /// protocol.
+    #[serde(default)]
+    retry: Option<Retry>,
+    /// Sets `TCP_NODELAY` on each connection, so that requests are sent
+    /// without waiting to be coalesced. Defaults to enabled.
+    #[serde(default = "default_tcp_nodelay")]
+    tcp_nodelay: bool,
+    /// Enables `SO_KEEPALIVE` on each connection. The value is the time in
+    /// seconds that a connection is idle before the first probe is sent, and
+    /// the interval between the probes which follow.
+    #[serde(default)]
+    tcp_keepalive: Option<u64>,
+    /// The kernel send and receive buffer sizes for each connection, in
+    /// bytes. By default the system defaults are used.
+    #[serde(default)]
+    socket_send_buffer: Option<u32>,
+    #[serde(default)]
+    socket_recv_buffer: Option<u32>,
 
     /// Specify the default sizes for the read and write buffers (in bytes).
     /// It is useful to increase the sizes if you expect to send and/or receive
@@ -32,7 +91,209 @@ pub struct Client {
     write_buffer_size: usize,
 }
 
+/// Opens `step` more connections every `interval` seconds until all of the
+/// connections in the pool are open.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ConnectionRamp {
+    step: usize,
+    interval: u64,
+}
+
+impl ConnectionRamp {
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+/// The end of the lifetime of a connection. When both are given, the
+/// connection is closed at whichever comes first. It is checked before each
+/// request is sent, so a connection is replaced when it is next used.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ConnectionLifetime {
+    /// The number of requests each connection sends.
+    #[serde(default)]
+    requests: Option<usize>,
+    /// How long each connection is used for, in milliseconds.
+    #[serde(default)]
+    duration: Option<u64>,
+}
+
+impl ConnectionLifetime {
+    pub fn requests(&self) -> Option<usize> {
+        self.requests
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration.map(Duration::from_millis)
+    }
+}
+
+/// Retries the requests which fail. A retry is sent on a new connection, as
+/// the connection is closed when a request fails. Only the final attempt is
+/// counted as the response, so a request which succeeds on a retry is a
+/// success.
+#[derive(Clone, Deserialize)]
+pub struct Retry {
+    /// The most times a request is sent, including the first attempt.
+    max_attempts: usize,
+    /// The commands which are retried. Defaults to all of the commands which
+    /// only read.
+    #[serde(default)]
+    commands: Option<Vec<Verb>>,
+    /// Allows commands which write to be retried, which may apply them more
+    /// than once.
+    #[serde(default)]
+    allow_non_idempotent: bool,
+}
+
+impl Retry {
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Returns true if requests for the command are retried.
+    pub fn retries(&self, verb: Verb) -> bool {
+        match &self.commands {
+            Some(commands) => commands.contains(&verb),
+            None => verb.is_idempotent(),
+        }
+    }
+
+    pub fn validate(&self) {
+        if self.max_attempts < 2 {
+            eprintln!("retry max_attempts must be at least 2, which includes the first attempt");
+            std::process::exit(2);
+        }
+
+        for verb in self.commands.iter().flatten() {
+            if !verb.is_idempotent() && !self.allow_non_idempotent {
+                eprintln!(
+                    "retrying {verb:?} may apply it more than once, this requires allow_non_idempotent"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
 impl Client {
+    pub fn validate(&self, protocol: Protocol, target: &Target, memcache: Option<&Memcache>) {
+        // the jitter is clamped to the range 0.0 - 1.0, which keeps a NaN
+        if !self.reconnect_backoff_jitter.is_finite() {
+            eprintln!("reconnect_backoff_jitter must be a finite number");
+            std::process::exit(2);
+        }
+
+        // in cluster mode the requests of every task are routed over shared
+        // connections to the nodes
+        let connection_per_task = protocol.has_connection_per_task() && !target.cluster();
+
+        if self.pipeline_depth() > 1 {
+            // in cluster mode the requests are routed to the nodes one at
+            // a time, so that redirections can be followed
+            if !matches!(protocol, Protocol::Memcache | Protocol::Resp) || target.cluster() {
+                eprintln!("pipelining is only supported for the memcache and resp protocols outside of cluster mode");
+                std::process::exit(2);
+            }
+
+            // a retry is sent on a new connection, which would break up the
+            // pipeline
+            if self.retry.is_some() {
+                eprintln!("retry is not supported with pipelining");
+                std::process::exit(2);
+            }
+        }
+
+        if self.per_connection_rate().is_some() {
+            // the multiplexed protocols share a connection between
+            // sessions and a pipeline sends several requests at once
+            if !connection_per_task {
+                eprintln!(
+                    "per_connection_rate is not supported for the selected protocol or cluster mode"
+                );
+                std::process::exit(2);
+            }
+
+            if self.pipeline_depth() > 1 {
+                eprintln!("per_connection_rate is not supported with pipelining");
+                std::process::exit(2);
+            }
+        }
+
+        if self.tcp_keepalive().map(|k| k.is_zero()).unwrap_or(false)
+            || self.socket_send_buffer() == Some(0)
+            || self.socket_recv_buffer() == Some(0)
+        {
+            eprintln!("the tcp keepalive and socket buffer sizes must be non-zero");
+            std::process::exit(2);
+        }
+
+        if let Some(ramp) = self.connection_ramp() {
+            if ramp.step() == 0 || ramp.interval().is_zero() {
+                eprintln!("a connection ramp needs a non-zero step and interval");
+                std::process::exit(2);
+            }
+
+            // the ramp holds back client tasks, which only maps to the
+            // connections for protocols with one connection per task
+            if !connection_per_task {
+                eprintln!(
+                    "a connection ramp is not supported for the selected protocol or cluster mode"
+                );
+                std::process::exit(2);
+            }
+        }
+
+        if let Some(retry) = self.retry() {
+            retry.validate();
+
+            // the requests are only retried by the resp client, which has one
+            // connection per task
+            if !matches!(protocol, Protocol::Resp) || target.cluster() {
+                eprintln!("retry is only supported for the resp protocol outside of cluster mode");
+                std::process::exit(2);
+            }
+        }
+
+        if let Some(lifetime) = self.connection_lifetime() {
+            if lifetime.requests().is_none() && lifetime.duration().is_none() {
+                eprintln!("a connection lifetime needs a number of requests or a duration");
+                std::process::exit(2);
+            }
+
+            if lifetime.requests() == Some(0)
+                || lifetime.duration().map(|d| d.is_zero()).unwrap_or(false)
+            {
+                eprintln!("the connection lifetime requests and duration must be non-zero");
+                std::process::exit(2);
+            }
+
+            // connections are only replaced by the protocols which have one
+            // connection per task, and a udp socket has no connection to set up
+            if !connection_per_task
+                || memcache.map(|m| m.transport()) == Some(MemcacheTransport::Udp)
+            {
+                eprintln!(
+                    "a connection lifetime is not supported for the selected protocol or cluster mode"
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// A copy of the client config with a single connection to each endpoint,
+    /// which are all opened immediately.
+    pub fn calibration(&self) -> Self {
+        let mut client = self.clone();
+        client.poolsize = 1;
+        client.connection_ramp = None;
+        client
+    }
+
     pub fn connect_timeout(&self) -> Duration {
         Duration::from_millis(self.connect_timeout)
     }
@@ -49,14 +310,77 @@ impl Client {
         std::cmp::max(1, self.poolsize)
     }
 
+    pub fn set_poolsize(&mut self, poolsize: usize) {
+        self.poolsize = poolsize;
+    }
+
     pub fn concurrency(&self) -> usize {
         std::cmp::max(1, self.concurrency)
     }
 
+    pub fn pipeline_depth(&self) -> usize {
+        std::cmp::max(1, self.pipeline_depth)
+    }
+
+    pub fn reconnect_backoff_initial(&self) -> Duration {
+        Duration::from_millis(self.reconnect_backoff_initial)
+    }
+
+    pub fn reconnect_backoff_max(&self) -> Duration {
+        Duration::from_millis(std::cmp::max(
+            self.reconnect_backoff_initial,
+            self.reconnect_backoff_max,
+        ))
+    }
+
+    pub fn reconnect_backoff_jitter(&self) -> f64 {
+        self.reconnect_backoff_jitter.clamp(0.0, 1.0)
+    }
+
+    pub fn reconnect_max_retries(&self) -> Option<usize> {
+        self.reconnect_max_retries
+    }
+
+    pub fn http_keepalive(&self) -> bool {
+        self.http_keepalive
+    }
+
     pub fn reconnect_rate(&self) -> Option<NonZeroU64> {
         NonZeroU64::new(self.reconnect_rate)
     }
 
+    pub fn connection_ramp(&self) -> Option<ConnectionRamp> {
+        self.connection_ramp
+    }
+
+    pub fn connection_lifetime(&self) -> Option<ConnectionLifetime> {
+        self.connection_lifetime
+    }
+
+    pub fn retry(&self) -> Option<&Retry> {
+        self.retry.as_ref()
+    }
+
+    pub fn tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay
+    }
+
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        self.tcp_keepalive.map(Duration::from_secs)
+    }
+
+    pub fn socket_send_buffer(&self) -> Option<u32> {
+        self.socket_send_buffer
+    }
+
+    pub fn socket_recv_buffer(&self) -> Option<u32> {
+        self.socket_recv_buffer
+    }
+
+    pub fn per_connection_rate(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.per_connection_rate)
+    }
+
     pub fn read_buffer_size(&self) -> usize {
         // rounds the read buffer size up to the next nearest multiple of the
         // pagesize
@@ -69,3 +393,19 @@ impl Client {
         ((std::cmp::max(1, self.write_buffer_size) + PAGESIZE - 1) / PAGESIZE) * PAGESIZE
     }
 }
+
+fn default_reconnect_backoff_initial() -> u64 {
+    100
+}
+
+fn default_reconnect_backoff_max() -> u64 {
+    5000
+}
+
+fn default_http_keepalive() -> bool {
+    true
+}
+
+fn default_tcp_nodelay() -> bool {
+    true
+}
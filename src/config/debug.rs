@@ -5,6 +5,7 @@ pub const GB: usize = 1024 * MB;
 
 use ringlog::Level;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 // constants to define default values
 const LOG_LEVEL: Level = Level::Info;
@@ -55,8 +56,27 @@ pub struct Debug {
     log_queue_depth: usize,
     #[serde(default = "log_single_message_size")]
     log_single_message_size: usize,
+    #[serde(default)]
+    log_format: LogFormat,
+    // the level for each module, such as `"rpc_perf::clients" = "debug"`
+    #[serde(default)]
+    log_modules: BTreeMap<String, ModuleLevel>,
 }
 
+/// The format of the debug log.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, with the structured fields of an event.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct ModuleLevel(#[serde(with = "LevelDef")] Level);
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 #[serde(remote = "Level")]
@@ -97,6 +117,23 @@ impl Debug {
     pub fn log_single_message_size(&self) -> usize {
         self.log_single_message_size
     }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.log_format = format;
+    }
+
+    /// The level for each module which overrides the log level. A module
+    /// includes its submodules.
+    pub fn log_modules(&self) -> Vec<(String, Level)> {
+        self.log_modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.0))
+            .collect()
+    }
 }
 
 // trait implementations
@@ -109,6 +146,8 @@ impl Default for Debug {
             log_max_size: log_max_size(),
             log_queue_depth: log_queue_depth(),
             log_single_message_size: log_single_message_size(),
+            log_format: LogFormat::default(),
+            log_modules: BTreeMap::new(),
         }
     }
 }
@@ -12,10 +12,35 @@ pub fn metrics_interval() -> String {
 pub struct General {
     /// The protocol to be used for the test.
     protocol: Protocol,
-    /// The reporting interval in seconds.
+    /// The reporting interval in seconds. Rates are computed over each
+    /// interval, and any remainder at the end of the test is reported as a
+    /// final, shorter, window.
     interval: u64,
     /// The test duration in seconds.
     duration: u64,
+    /// The length of the warmup at the start of the test, in seconds. Traffic
+    /// is sent during the warmup, but the results are not reported. The
+    /// warmup is part of the test duration.
+    #[serde(default)]
+    warmup: u64,
+    /// An optional limit on the total number of requests. The test ends once
+    /// this many requests have been sent or the duration has elapsed,
+    /// whichever happens first.
+    #[serde(default)]
+    max_requests: Option<u64>,
+    /// Optional path to write a JSON summary of the test to once it ends. With
+    /// the `remote` feature this may be an `http(s)://` or `s3://` url, which
+    /// the summary is uploaded to.
+    #[serde(default)]
+    summary_output: Option<String>,
+    /// Optional path to write a CSV file with one row of client stats for each
+    /// reporting interval.
+    #[serde(default)]
+    csv_output: Option<String>,
+    /// Optional path to write a PNG heatmap of the response latency over time
+    /// to once the test ends. Requires the `heatmap` feature.
+    #[serde(default)]
+    heatmap_output: Option<String>,
     /// Optional path to output metrics. The extension, if specified, is
     /// ignored and the metrics stored in the format specified below.
     #[serde(default)]
@@ -29,8 +54,16 @@ pub struct General {
     metrics_interval: String,
     /// The admin listen address
     admin: String,
+    /// An optional listen address for a line based admin console, which
+    /// serves the current stats over plain TCP, eg: with telnet or netcat.
+    #[serde(default)]
+    admin_console: Option<String>,
     /// The initial seed for initializing the PRNGs. This can be any string and
-    /// we will hash it to determine a corresponding seed.
+    /// we will hash it to determine a corresponding seed. If not specified, a
+    /// random seed is chosen when the config is loaded. Runs with the same
+    /// seed and config generate the same sequence of requests for each
+    /// workload thread.
+    #[serde(alias = "seed")]
     initial_seed: Option<String>,
 }
 
@@ -47,6 +80,67 @@ impl General {
         Duration::from_secs(self.duration)
     }
 
+    pub fn warmup(&self) -> Duration {
+        Duration::from_secs(self.warmup)
+    }
+
+    pub fn max_requests(&self) -> Option<u64> {
+        self.max_requests
+    }
+
+    /// A copy of this section for a calibration run, which has no request
+    /// budget.
+    pub fn calibration(&self) -> Self {
+        Self {
+            max_requests: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) {
+        if self.heatmap_output.is_some() && !cfg!(feature = "heatmap") {
+            eprintln!("heatmap output requires rpc-perf to be built with the `heatmap` feature");
+            std::process::exit(2);
+        }
+
+        crate::remote::validate("summary_output", self.summary_output.as_deref());
+
+        // these outputs are written as the test runs or by other libraries, so
+        // only the summary may be uploaded
+        if [&self.csv_output, &self.heatmap_output, &self.metrics_output]
+            .into_iter()
+            .flatten()
+            .any(|output| crate::remote::is_remote(output))
+        {
+            eprintln!(
+                "only the summary output may be a url, the other outputs must be local paths"
+            );
+            std::process::exit(2);
+        }
+
+        if self.max_requests == Some(0) {
+            eprintln!("max_requests must be greater than zero");
+            std::process::exit(2);
+        }
+
+        if self.warmup > 0 && self.warmup >= self.duration {
+            eprintln!("warmup must be shorter than the test duration");
+            std::process::exit(2);
+        }
+    }
+
+    pub fn summary_output(&self) -> Option<String> {
+        self.summary_output.clone()
+    }
+
+    pub fn csv_output(&self) -> Option<String> {
+        self.csv_output.clone()
+    }
+
+    pub fn heatmap_output(&self) -> Option<String> {
+        self.heatmap_output.clone()
+    }
+
     pub fn metrics_output(&self) -> Option<String> {
         self.metrics_output.clone()
     }
@@ -63,16 +157,28 @@ impl General {
         self.admin.clone()
     }
 
-    pub fn initial_seed(&self) -> Seed512 {
-        if let Some(initial_seed) = &self.initial_seed {
-            let mut hasher = Sha512::new();
-            hasher.update(initial_seed.as_bytes());
-            Seed512(hasher.finalize().into())
-        } else {
-            let mut rng = rand::thread_rng();
-            let mut seed = [0_u8; 64];
-            rng.fill(&mut seed);
-            Seed512(seed)
+    pub fn admin_console(&self) -> Option<&str> {
+        self.admin_console.as_deref()
+    }
+
+    /// Picks a random seed if one was not specified, so that every PRNG is
+    /// initialized from the same seed and the seed can be reported.
+    pub fn resolve_seed(&mut self) {
+        if self.initial_seed.is_none() {
+            let seed: u64 = rand::thread_rng().gen();
+            self.initial_seed = Some(format!("{seed:016x}"));
         }
     }
+
+    /// The seed as it was given in the config, or as chosen by
+    /// `resolve_seed()`.
+    pub fn seed(&self) -> &str {
+        self.initial_seed.as_deref().unwrap_or_default()
+    }
+
+    pub fn initial_seed(&self) -> Seed512 {
+        let mut hasher = Sha512::new();
+        hasher.update(self.seed().as_bytes());
+        Seed512(hasher.finalize().into())
+    }
 }
@@ -0,0 +1,22 @@
+use super::*;
+
+#[derive(Clone, Deserialize)]
+pub struct Grpc {
+    /// The full path of the method to call, for example:
+    /// `/helloworld.Greeter/SayHello`
+    method: String,
+    /// Path to a file containing the serialized protobuf request message. If
+    /// not specified, an empty message is sent.
+    #[serde(default)]
+    payload: Option<String>,
+}
+
+impl Grpc {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn payload(&self) -> Option<&str> {
+        self.payload.as_deref()
+    }
+}
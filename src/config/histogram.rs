@@ -0,0 +1,134 @@
+use super::*;
+
+fn default_grouping_power() -> u8 {
+    7
+}
+
+fn default_max_value_power() -> u8 {
+    64
+}
+
+/// The parameters of the histograms which the latencies are recorded in. The
+/// buckets have a relative error of at most `2^-grouping_power` and the
+/// largest value which is tracked is `2^max_value_power - 1` nanoseconds.
+/// Larger values are not recorded.
+#[derive(Clone, Deserialize)]
+pub struct Histogram {
+    #[serde(default = "default_grouping_power")]
+    grouping_power: u8,
+    #[serde(default = "default_max_value_power")]
+    max_value_power: u8,
+    /// The percentiles which are reported for each window, in ascending
+    /// order, eg: `[50, 90, 99, 99.9]`. Defaults to the percentiles which are
+    /// exposed on the admin endpoints.
+    #[serde(default)]
+    percentiles: Option<Vec<f64>>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            grouping_power: default_grouping_power(),
+            max_value_power: default_max_value_power(),
+            percentiles: None,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn grouping_power(&self) -> u8 {
+        self.grouping_power
+    }
+
+    pub fn max_value_power(&self) -> u8 {
+        self.max_value_power
+    }
+
+    /// The percentiles which are reported for each window, with their labels.
+    pub fn percentiles(&self) -> Vec<(String, f64)> {
+        match &self.percentiles {
+            Some(percentiles) => percentiles.iter().map(|p| (label(*p), *p)).collect(),
+            None => crate::metrics::PERCENTILES
+                .iter()
+                .map(|(label, percentile)| (label.to_string(), *percentile))
+                .collect(),
+        }
+    }
+
+    pub fn validate(&self) {
+        if let Err(e) = ::histogram::Config::new(self.grouping_power, self.max_value_power) {
+            eprintln!("histogram parameters are not valid: {e}");
+            std::process::exit(2);
+        }
+
+        if let Some(percentiles) = &self.percentiles {
+            if percentiles.is_empty() {
+                eprintln!("histogram percentiles must not be empty");
+                std::process::exit(2);
+            }
+
+            if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+                eprintln!("histogram percentile {p} is not in the range [0.0, 100.0]");
+                std::process::exit(2);
+            }
+
+            // each percentile is a column of the output, so they are kept in
+            // the order they are given
+            if percentiles.windows(2).any(|w| w[0] >= w[1]) {
+                eprintln!("histogram percentiles must be in ascending order without duplicates");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+// the label of a percentile, such as `p999` for 99.9 and `max` for 100
+fn label(percentile: f64) -> String {
+    if percentile == 100.0 {
+        "max".to_string()
+    } else {
+        format!("p{percentile}").replace('.', "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        assert_eq!(label(50.0), "p50");
+        assert_eq!(label(99.0), "p99");
+        assert_eq!(label(99.9), "p999");
+        assert_eq!(label(99.99), "p9999");
+        assert_eq!(label(100.0), "max");
+    }
+
+    #[test]
+    fn default_percentiles() {
+        let percentiles = Histogram::default().percentiles();
+
+        assert_eq!(percentiles.len(), crate::metrics::PERCENTILES.len());
+
+        for ((label, percentile), (default_label, default_percentile)) in
+            percentiles.iter().zip(crate::metrics::PERCENTILES)
+        {
+            assert_eq!(label, default_label);
+            assert_eq!(percentile, default_percentile);
+        }
+    }
+
+    #[test]
+    fn configured_percentiles() {
+        let histogram: Histogram = toml::from_str("percentiles = [50, 99.9, 100]").unwrap();
+
+        assert_eq!(
+            histogram.percentiles(),
+            vec![
+                ("p50".to_string(), 50.0),
+                ("p999".to_string(), 99.9),
+                ("max".to_string(), 100.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use super::*;
+
+/// Selects how requests are sent with the memcache protocol.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemcacheTransport {
+    #[default]
+    Tcp,
+    /// Each request is sent in a single datagram with the memcache UDP frame
+    /// header, and responses which span several datagrams are reassembled.
+    /// A lost datagram results in a timeout and a datagram which arrives out
+    /// of order is an error.
+    Udp,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Memcache {
+    #[serde(default)]
+    transport: MemcacheTransport,
+}
+
+impl Memcache {
+    pub fn transport(&self) -> MemcacheTransport {
+        self.transport
+    }
+}
@@ -6,24 +6,42 @@ use std::time::Duration;
 mod client;
 mod debug;
 mod general;
+mod grpc;
+mod histogram;
+mod memcache;
 mod metrics;
+mod overrides;
+mod ping;
 mod protocol;
 mod pubsub;
+mod raw;
+mod redis;
+mod slo;
+mod statsd;
 mod target;
+mod thrift;
 mod tls;
 mod workload;
 
-pub use client::Client;
-pub use debug::Debug;
+pub use client::{Client, ConnectionRamp};
+pub use debug::{Debug, LogFormat};
 pub use general::General;
+pub use grpc::Grpc;
+pub use memcache::{Memcache, MemcacheTransport};
 pub use metrics::{Format as MetricsFormat, Metrics};
+pub use ping::{Ping, PingMode};
 pub use protocol::Protocol;
 pub use pubsub::Pubsub;
+pub use raw::{Raw, TemplatePart};
+pub use redis::Redis;
+pub use slo::{Slo, SloAssertion, SloMetric, SloOperator};
+pub use statsd::Statsd;
 pub use target::Target;
-pub use tls::Tls;
+pub use thrift::{Thrift, ThriftTransport};
+pub use tls::{Tls, TlsVersion};
 pub use workload::{
-    Command, Distribution, Keyspace, RampCompletionAction, RampType, Topics, ValueKind, Verb,
-    Workload,
+    Command, Distribution, HotSet, KeyLength, Keyspace, Mode, Overflow, RampCompletionAction,
+    RampType, Shape, Topics, ValueKind, ValueLength, ValueMode, Verb, Workload,
 };
 
 pub const PAGESIZE: usize = 4096;
@@ -38,15 +56,27 @@ pub struct Config {
     general: General,
     client: Option<Client>,
     debug: Debug,
+    grpc: Option<Grpc>,
+    #[serde(default)]
+    histogram: histogram::Histogram,
+    memcache: Option<Memcache>,
+    ping: Option<Ping>,
     pubsub: Option<Pubsub>,
+    raw: Option<Raw>,
+    redis: Option<Redis>,
+    slo: Option<Slo>,
+    statsd: Option<Statsd>,
     target: Target,
+    thrift: Option<Thrift>,
     tls: Option<Tls>,
     workload: Workload,
     metrics: Option<Metrics>,
 }
 
 impl Config {
-    pub fn new(filename: &str) -> Self {
+    /// Loads the config file, with any `key.path=value` overrides applied
+    /// before the config is validated.
+    pub fn new(filename: &str, overrides: &[String]) -> Self {
         let mut file = match std::fs::File::open(filename) {
             Ok(c) => c,
             Err(error) => {
@@ -62,14 +92,304 @@ impl Config {
                 std::process::exit(1);
             }
         }
-        let mut config: Config = toml::from_str(&content)
-            .map_err(|e| {
-                eprintln!("Failed to parse TOML config: {filename}\n{e}");
-                std::process::exit(1);
-            })
-            .unwrap();
+        let mut config: Config = if overrides.is_empty() {
+            toml::from_str(&content)
+                .map_err(|e| {
+                    eprintln!("Failed to parse TOML config: {filename}\n{e}");
+                    std::process::exit(1);
+                })
+                .unwrap()
+        } else {
+            let mut table: toml::Table = content
+                .parse()
+                .map_err(|e| {
+                    eprintln!("Failed to parse TOML config: {filename}\n{e}");
+                    std::process::exit(1);
+                })
+                .unwrap();
+
+            let added = overrides::apply(&mut table, overrides);
+
+            let config = Config::deserialize(toml::Value::Table(table.clone()))
+                .map_err(|e| {
+                    eprintln!("Failed to parse TOML config with the overrides: {filename}\n{e}");
+                    std::process::exit(1);
+                })
+                .unwrap();
 
+            overrides::verify(&table, &added);
+
+            config
+        };
+
+        config.general.validate();
+        config.histogram.validate();
+        config.general.resolve_seed();
+        config.target.validate();
+        if let Some(tls) = config.tls.as_ref() {
+            tls.validate();
+        }
+        config.workload.validate();
+        config.workload.resolve_key_prefixes();
         config.workload.ratelimit().validate();
+        if config.workload.mode() == Mode::ClosedLoop
+            && config.workload.ratelimit().start().is_some()
+        {
+            eprintln!("the ratelimit must not be set for a closed loop workload");
+            std::process::exit(2);
+        }
+        if let Some(trace) = config.workload.trace() {
+            trace.validate();
+
+            if config.workload.ratelimit().start().is_some() {
+                eprintln!("the ratelimit must not be set when replaying a trace");
+                std::process::exit(2);
+            }
+
+            if config.workload.mode() == Mode::ClosedLoop {
+                eprintln!("a trace is replayed with its own timing and cannot be a closed loop");
+                std::process::exit(2);
+            }
+
+            if config.workload.keyspaces().is_empty() {
+                eprintln!("replaying a trace requires a keyspace to generate values with");
+                std::process::exit(2);
+            }
+        }
+        if config.workload.record().is_some() {
+            if config.workload.trace().is_some() {
+                eprintln!("the requests of a trace replay cannot be recorded");
+                std::process::exit(2);
+            }
+
+            // a trace is replayed with the prefix and values of one keyspace
+            if config.workload.keyspaces().len() != 1 {
+                eprintln!("recording the requests requires a single keyspace");
+                std::process::exit(2);
+            }
+
+            if crate::remote::is_remote(config.workload.record().unwrap()) {
+                eprintln!("the requests can only be recorded to a local file");
+                std::process::exit(2);
+            }
+        }
+        let mut names = std::collections::HashSet::new();
+        for (id, keyspace) in config.workload.keyspaces().iter().enumerate() {
+            keyspace.validate();
+
+            if !names.insert(config.workload.keyspace_name(id)) {
+                eprintln!(
+                    "keyspace name `{}` is used more than once",
+                    config.workload.keyspace_name(id)
+                );
+                std::process::exit(2);
+            }
+
+            if keyspace.verify()
+                && !matches!(
+                    config.general.protocol(),
+                    Protocol::Memcache | Protocol::Resp
+                )
+            {
+                eprintln!("verify is only supported for the memcache and resp protocols");
+                std::process::exit(2);
+            }
+
+            if keyspace.preload().is_some()
+                && !matches!(
+                    config.general.protocol(),
+                    Protocol::Memcache
+                        | Protocol::MemcacheBinary
+                        | Protocol::Momento
+                        | Protocol::Resp
+                )
+            {
+                eprintln!("preload is only supported for protocols which support set");
+                std::process::exit(2);
+            }
+
+            if keyspace
+                .commands()
+                .iter()
+                .any(|command| command.verb() == Verb::Scan)
+                && (!matches!(config.general.protocol(), Protocol::Resp) || config.target.cluster())
+            {
+                // a scan covers the keys of a single node, not the whole cluster
+                eprintln!("scan is only supported for the resp protocol outside of cluster mode");
+                std::process::exit(2);
+            }
+
+            // RESP rejects an expiration time of zero, so we cannot express an
+            // immediate expiration for that protocol
+            if matches!(config.general.protocol(), Protocol::Resp)
+                && keyspace.ttl().map(|ttl| ttl.is_zero()).unwrap_or(false)
+            {
+                eprintln!("a ttl of zero is not supported for the resp protocol");
+                std::process::exit(2);
+            }
+        }
+        if let Some(ping) = config.ping.as_ref() {
+            if !matches!(config.general.protocol(), Protocol::Ping) {
+                eprintln!("the `ping` section is only valid for the ping protocol");
+                std::process::exit(2);
+            }
+
+            ping.validate();
+        }
+        if let Some(memcache) = config.memcache.as_ref() {
+            if !matches!(config.general.protocol(), Protocol::Memcache) {
+                eprintln!("the `memcache` section is only valid for the memcache protocol");
+                std::process::exit(2);
+            }
+
+            if memcache.transport() == MemcacheTransport::Udp {
+                if config.tls.is_some() {
+                    eprintln!("tls is not supported for the memcache udp transport");
+                    std::process::exit(2);
+                }
+
+                if config
+                    .target
+                    .endpoints()
+                    .iter()
+                    .any(|endpoint| endpoint.starts_with("unix://"))
+                {
+                    eprintln!("unix domain socket endpoints are not supported for the memcache udp transport");
+                    std::process::exit(2);
+                }
+
+                // each datagram carries a single request
+                if config
+                    .client
+                    .as_ref()
+                    .map(|client| client.pipeline_depth() > 1)
+                    .unwrap_or(false)
+                {
+                    eprintln!("pipelining is not supported for the memcache udp transport");
+                    std::process::exit(2);
+                }
+            }
+        }
+        if let Some(redis) = config.redis.as_ref() {
+            if !matches!(config.general.protocol(), Protocol::Resp) {
+                eprintln!("the `redis` section is only valid for the resp protocol");
+                std::process::exit(2);
+            }
+
+            redis.validate();
+
+            // a cluster only has the default database
+            if config.target.cluster() && redis.db() != 0 {
+                eprintln!("the redis db must not be set in cluster mode");
+                std::process::exit(2);
+            }
+        }
+        if matches!(config.general.protocol(), Protocol::Grpc) {
+            match config.grpc.as_ref() {
+                Some(grpc) if grpc.method().starts_with('/') => {}
+                Some(_) => {
+                    eprintln!("grpc method must be a full path, eg: `/package.Service/Method`");
+                    std::process::exit(2);
+                }
+                None => {
+                    eprintln!("the grpc protocol requires a `grpc` section in the config");
+                    std::process::exit(2);
+                }
+            }
+        }
+        if let Some(raw) = config.raw.as_ref() {
+            if !matches!(config.general.protocol(), Protocol::Raw) {
+                eprintln!("the `raw` section is only valid for the raw protocol");
+                std::process::exit(2);
+            }
+
+            raw.validate();
+
+            // only the requests which write a value have one to send
+            if raw.has_value()
+                && config.workload.keyspaces().iter().any(|keyspace| {
+                    keyspace.commands().iter().any(|command| {
+                        command.weight() > 0
+                            && !matches!(command.verb(), Verb::Add | Verb::Replace | Verb::Set)
+                    })
+                })
+            {
+                eprintln!("a raw template with `{{value}}` or `{{vlen}}` requires that every command is add, replace, or set");
+                std::process::exit(2);
+            }
+        } else if matches!(config.general.protocol(), Protocol::Raw) {
+            eprintln!("the raw protocol requires a `raw` section in the config");
+            std::process::exit(2);
+        }
+        if let Some(thrift) = config.thrift.as_ref() {
+            if !matches!(config.general.protocol(), Protocol::Thrift) {
+                eprintln!("the `thrift` section is only valid for the thrift protocol");
+                std::process::exit(2);
+            }
+
+            thrift.validate();
+        } else if matches!(config.general.protocol(), Protocol::Thrift) {
+            eprintln!("the thrift protocol requires a `thrift` section in the config");
+            std::process::exit(2);
+        }
+        if config
+            .target
+            .endpoints()
+            .iter()
+            .any(|endpoint| endpoint.starts_with("unix://"))
+        {
+            if config.tls.is_some() {
+                eprintln!("tls is not supported for unix domain socket endpoints");
+                std::process::exit(2);
+            }
+
+            if !matches!(
+                config.general.protocol(),
+                Protocol::Memcache
+                    | Protocol::MemcacheBinary
+                    | Protocol::Ping
+                    | Protocol::Raw
+                    | Protocol::Resp
+                    | Protocol::Thrift
+            ) {
+                eprintln!(
+                    "unix domain socket endpoints are not supported for the selected protocol"
+                );
+                std::process::exit(2);
+            }
+        }
+        if config.target.cluster() && !matches!(config.general.protocol(), Protocol::Resp) {
+            eprintln!("cluster mode is only supported for the resp protocol");
+            std::process::exit(2);
+        }
+        if config.target.cluster()
+            && config.workload.keyspaces().iter().any(|keyspace| {
+                keyspace.commands().iter().any(|command| {
+                    matches!(command.verb(), Verb::Get | Verb::Set) && command.cardinality() > 1
+                })
+            })
+        {
+            // the keys of a multi-key command may hash to different slots
+            eprintln!("multi-key get and set are not supported in cluster mode");
+            std::process::exit(2);
+        }
+        if let Some(client) = config.client.as_ref() {
+            client.validate(
+                config.general.protocol(),
+                &config.target,
+                config.memcache.as_ref(),
+            );
+        }
+        if let Some(slo) = config.slo.as_ref() {
+            slo.validate();
+
+            if config.workload.keyspaces().is_empty() {
+                eprintln!(
+                    "slo assertions require a keyspace, as they are checked against the responses"
+                );
+                std::process::exit(2);
+            }
+        }
         if config.metrics().is_none() {
             config.metrics = Metrics::from_general(&config.general);
         }
@@ -79,6 +399,29 @@ impl Config {
         config
     }
 
+    /// A copy of the config for measuring the throughput of a single
+    /// connection to each endpoint. The workload is sent in a closed loop
+    /// without a ratelimit or request budget.
+    pub fn calibration(&self) -> Self {
+        let mut config = self.clone();
+        config.general = self.general.calibration();
+        config.workload = self.workload.calibration();
+        config.client = self.client.as_ref().map(|client| client.calibration());
+        config
+    }
+
+    /// Overrides the format of the debug log.
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.debug.set_log_format(format);
+    }
+
+    /// Overrides the number of connections to each endpoint.
+    pub fn set_poolsize(&mut self, poolsize: usize) {
+        if let Some(client) = self.client.as_mut() {
+            client.set_poolsize(poolsize);
+        }
+    }
+
     pub fn general(&self) -> &General {
         &self.general
     }
@@ -87,14 +430,50 @@ impl Config {
         self.client.as_ref()
     }
 
+    pub fn grpc(&self) -> Option<&Grpc> {
+        self.grpc.as_ref()
+    }
+
+    pub fn histogram(&self) -> &histogram::Histogram {
+        &self.histogram
+    }
+
+    pub fn memcache(&self) -> Option<&Memcache> {
+        self.memcache.as_ref()
+    }
+
+    pub fn ping(&self) -> Option<&Ping> {
+        self.ping.as_ref()
+    }
+
     pub fn pubsub(&self) -> Option<&Pubsub> {
         self.pubsub.as_ref()
     }
 
+    pub fn raw(&self) -> Option<&Raw> {
+        self.raw.as_ref()
+    }
+
+    pub fn redis(&self) -> Option<&Redis> {
+        self.redis.as_ref()
+    }
+
+    pub fn slo(&self) -> Option<&Slo> {
+        self.slo.as_ref()
+    }
+
+    pub fn statsd(&self) -> Option<&Statsd> {
+        self.statsd.as_ref()
+    }
+
     pub fn target(&self) -> &Target {
         &self.target
     }
 
+    pub fn thrift(&self) -> Option<&Thrift> {
+        self.thrift.as_ref()
+    }
+
     pub fn tls(&self) -> Option<&Tls> {
         self.tls.as_ref()
     }
@@ -111,3 +490,170 @@ impl Config {
         self.metrics.as_ref()
     }
 }
+
+/// Helpers for the tests of config validation.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use std::io::Write;
+
+    const CONFIG: &str = "RPC_PERF_TEST_CONFIG";
+
+    /// A minimal valid config for the protocol and endpoint, followed by the
+    /// extra sections.
+    pub fn config(protocol: &str, endpoint: &str, extra: &str) -> String {
+        format!(
+            r#"
+            [general]
+            protocol = "{protocol}"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            initial_seed = "0"
+
+            [debug]
+
+            [target]
+            endpoints = ["{endpoint}"]
+
+            [client]
+            threads = 1
+            poolsize = 1
+            connect_timeout = 1000
+            request_timeout = 1000
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+            start = 1000
+
+            {extra}
+            "#
+        )
+    }
+
+    /// Loads and validates the config. As an invalid config exits the process,
+    /// the config is validated in a child process which runs only the named
+    /// test, for example `config::tests::unix_tls`. Returns the error printed
+    /// for an invalid config.
+    pub fn validate(test: &str, config: &str) -> std::result::Result<(), String> {
+        if let Ok(path) = std::env::var(CONFIG) {
+            Config::new(&path, &[]);
+            std::process::exit(0);
+        }
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([test, "--exact", "--nocapture"])
+            .env(CONFIG, file.path())
+            .output()
+            .unwrap();
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_tls() {
+        let plaintext = testing::config("ping", "unix:///tmp/rpc-perf.sock", "");
+        assert!(testing::validate("config::tests::unix_tls", &plaintext).is_ok());
+
+        let tls = testing::config(
+            "ping",
+            "unix:///tmp/rpc-perf.sock",
+            "[tls]\nverify_hostname = false",
+        );
+        let error = testing::validate("config::tests::unix_tls", &tls).unwrap_err();
+        assert!(error.contains("tls is not supported for unix domain socket endpoints"));
+    }
+
+    #[test]
+    fn multi_key_cardinality() {
+        let keyspace = |cardinality| {
+            testing::config(
+                "resp",
+                "127.0.0.1:6379",
+                &format!(
+                    r#"
+                    [[workload.keyspace]]
+                    nkeys = 100
+                    klen = 8
+                    vlen = 16
+                    commands = [
+                        {{ verb = "mget", cardinality = {cardinality} }},
+                        {{ verb = "mset", cardinality = {cardinality} }},
+                    ]
+                    "#
+                ),
+            )
+        };
+        let test = "config::tests::multi_key_cardinality";
+
+        assert!(testing::validate(test, &keyspace(4)).is_ok());
+
+        let error = testing::validate(test, &keyspace(0)).unwrap_err();
+        assert!(error.contains("cardinality must not be zero"), "{error}");
+    }
+
+    #[test]
+    fn connection_ramp_protocols() {
+        let ramp = "[client.connection_ramp]\nstep = 1\ninterval = 1";
+        let test = "config::tests::connection_ramp_protocols";
+
+        let ping = testing::config("ping", "127.0.0.1:12321", ramp);
+        assert!(testing::validate(test, &ping).is_ok());
+
+        // the tasks of a multiplexed protocol share their connections
+        let http2 = testing::config("http2", "127.0.0.1:12321", ramp);
+        let error = testing::validate(test, &http2).unwrap_err();
+        assert!(
+            error.contains("a connection ramp is not supported"),
+            "{error}"
+        );
+    }
+
+    #[cfg(not(feature = "remote"))]
+    #[test]
+    fn remote_urls() {
+        let test = "config::tests::remote_urls";
+
+        let summary = testing::config("ping", "127.0.0.1:12321", "").replace(
+            "interval = 60",
+            "interval = 60\nsummary_output = \"s3://bucket/summary.json\"",
+        );
+        let error = testing::validate(test, &summary).unwrap_err();
+        assert!(error.contains("a summary_output url requires"), "{error}");
+        assert!(error.contains("the `remote` feature"), "{error}");
+
+        let trace = testing::config(
+            "memcache",
+            "127.0.0.1:11211",
+            "[workload.trace]\nfile = \"https://example.com/trace.csv\"",
+        );
+        let error = testing::validate(test, &trace).unwrap_err();
+        assert!(error.contains("a trace file url requires"), "{error}");
+
+        let keys = testing::config(
+            "memcache",
+            "127.0.0.1:11211",
+            r#"
+            [[workload.keyspace]]
+            key_file = "http://example.com/keys.txt"
+            commands = [{ verb = "get" }]
+            "#,
+        );
+        let error = testing::validate(test, &keys).unwrap_err();
+        assert!(error.contains("a key_file url requires"), "{error}");
+    }
+}
@@ -0,0 +1,251 @@
+use super::*;
+use toml::{Table, Value};
+
+/// Applies the `--set key.path=value` overrides to the parsed config file.
+/// Each segment of the path is a table key, or the index of an array element
+/// such as `workload.keyspace.0.vlen`. The value is parsed as a TOML value and
+/// is otherwise taken as a string. An override of a value in the config file
+/// is coerced to the type of that value.
+///
+/// Returns the paths which were not in the config file, so that they can be
+/// checked against the known fields with `verify()`.
+pub fn apply(table: &mut Table, overrides: &[String]) -> Vec<Vec<String>> {
+    let mut added = Vec::new();
+
+    for o in overrides {
+        let (path, value) = match o.split_once('=') {
+            Some((path, value)) => (path.trim(), value.trim()),
+            None => {
+                eprintln!("config override must be of the form `key.path=value`: {o}");
+                std::process::exit(1);
+            }
+        };
+
+        let path: Vec<String> = path.split('.').map(|s| s.trim().to_string()).collect();
+
+        if path.iter().any(|s| s.is_empty()) {
+            eprintln!("config override has an empty key in the path: {o}");
+            std::process::exit(1);
+        }
+
+        if set(table, &path, 0, parse(value)) {
+            added.push(path);
+        }
+    }
+
+    added
+}
+
+/// Exits with an error for any added path which is not a field of the config.
+/// Fields which are not known are ignored when the config is deserialized, so
+/// each path is probed by setting it to a value which no field accepts. The
+/// path is known if the config is then rejected.
+pub fn verify(table: &Table, added: &[Vec<String>]) {
+    for path in added {
+        let mut probe = table.clone();
+        let datetime = "1970-01-01T00:00:00Z".parse().unwrap();
+
+        set(&mut probe, path, 0, Value::Datetime(datetime));
+
+        if Config::deserialize(Value::Table(probe)).is_ok() {
+            eprintln!("config override for an unknown path: {}", path.join("."));
+            std::process::exit(1);
+        }
+    }
+}
+
+// a value which is not valid TOML, such as a bare word, is a string
+fn parse(value: &str) -> Value {
+    match format!("value = {value}").parse::<Table>() {
+        Ok(mut t) => t.remove("value").unwrap(),
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+// sets the value at the path, creating any tables which are missing, and
+// returns whether the value was added rather than replaced
+fn set(table: &mut Table, path: &[String], depth: usize, value: Value) -> bool {
+    let key = &path[depth];
+
+    if depth + 1 == path.len() {
+        return match table.get_mut(key) {
+            Some(existing) => {
+                *existing = coerce(existing, value);
+                false
+            }
+            None => {
+                table.insert(key.clone(), value);
+                true
+            }
+        };
+    }
+
+    let mut added = false;
+    let mut next = table.entry(key.clone()).or_insert_with(|| {
+        added = true;
+        Value::Table(Table::new())
+    });
+
+    // array elements are indexed by the next key in the path, and must
+    // already be in the config file
+    let mut depth = depth + 1;
+
+    while let Value::Array(array) = next {
+        let element = path[depth]
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| array.get_mut(index));
+
+        match element {
+            Some(element) if depth + 1 < path.len() => {
+                next = element;
+                depth += 1;
+            }
+            Some(element) => {
+                *element = coerce(element, value);
+                return added;
+            }
+            None => {
+                eprintln!(
+                    "config override for an unknown path: {} is not an element of {}",
+                    path[..=depth].join("."),
+                    path[..depth].join(".")
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match next {
+        Value::Table(t) => set(t, path, depth, value) || added,
+        _ => {
+            eprintln!(
+                "config override for an unknown path: {} is not a table",
+                path[..depth].join(".")
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// matches the type of the value in the config file, so that an override such
+// as `ratelimit=1000` is not rejected for a float field, or `seed=42` for a
+// string field
+fn coerce(existing: &Value, value: Value) -> Value {
+    match (existing, value) {
+        (Value::String(_), Value::String(s)) => Value::String(s),
+        (Value::String(_), v @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_))) => {
+            Value::String(v.to_string())
+        }
+        (Value::Float(_), Value::Integer(i)) => Value::Float(i as f64),
+        (_, v) => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(config: &str, overrides: &[&str]) -> (Table, Vec<Vec<String>>) {
+        let mut table: Table = config.parse().unwrap();
+        let overrides: Vec<String> = overrides.iter().map(|o| o.to_string()).collect();
+        let added = apply(&mut table, &overrides);
+        (table, added)
+    }
+
+    #[test]
+    fn apply_replace() {
+        let (table, added) = overrides(
+            "[general]\nduration = 60\nprotocol = \"ping\"",
+            &["general.duration=300", " general.protocol = resp "],
+        );
+
+        assert!(added.is_empty());
+        assert_eq!(table["general"]["duration"], Value::Integer(300));
+        assert_eq!(
+            table["general"]["protocol"],
+            Value::String("resp".to_string())
+        );
+    }
+
+    #[test]
+    fn apply_add() {
+        let (table, added) = overrides(
+            "[general]\nduration = 60",
+            &["general.warmup=10", "client.poolsize=4"],
+        );
+
+        assert_eq!(
+            added,
+            vec![
+                vec!["general".to_string(), "warmup".to_string()],
+                vec!["client".to_string(), "poolsize".to_string()],
+            ]
+        );
+        assert_eq!(table["general"]["warmup"], Value::Integer(10));
+        assert_eq!(table["client"]["poolsize"], Value::Integer(4));
+    }
+
+    #[test]
+    fn apply_array_element() {
+        let (table, added) = overrides(
+            "[[workload.keyspace]]\nvlen = 32\n[[workload.keyspace]]\nvlen = 64",
+            &["workload.keyspace.1.vlen=128"],
+        );
+
+        assert!(added.is_empty());
+        assert_eq!(table["workload"]["keyspace"][0]["vlen"], Value::Integer(32));
+        assert_eq!(
+            table["workload"]["keyspace"][1]["vlen"],
+            Value::Integer(128)
+        );
+    }
+
+    #[test]
+    fn apply_array_value() {
+        let (table, _) = overrides(
+            "[target]\nendpoints = [\"127.0.0.1:6379\"]",
+            &["target.endpoints.0=10.0.0.1:6379"],
+        );
+
+        assert_eq!(
+            table["target"]["endpoints"][0],
+            Value::String("10.0.0.1:6379".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse("42"), Value::Integer(42));
+        assert_eq!(parse("0.5"), Value::Float(0.5));
+        assert_eq!(parse("true"), Value::Boolean(true));
+        assert_eq!(parse("\"quoted\""), Value::String("quoted".to_string()));
+        assert_eq!(parse("bare"), Value::String("bare".to_string()));
+        assert_eq!(
+            parse("[1, 2]"),
+            Value::Array(vec![Value::Integer(1), Value::Integer(2)])
+        );
+    }
+
+    #[test]
+    fn coerce_to_existing() {
+        let string = Value::String("seed".to_string());
+        let float = Value::Float(1.0);
+
+        assert_eq!(
+            coerce(&string, Value::Integer(42)),
+            Value::String("42".to_string())
+        );
+        assert_eq!(
+            coerce(&string, Value::Boolean(false)),
+            Value::String("false".to_string())
+        );
+        assert_eq!(coerce(&float, Value::Integer(1000)), Value::Float(1000.0));
+        // other combinations are left to be rejected when deserialized
+        assert_eq!(coerce(&float, Value::Boolean(true)), Value::Boolean(true));
+        assert_eq!(
+            coerce(&Value::Integer(1), Value::Float(0.5)),
+            Value::Float(0.5)
+        );
+    }
+}
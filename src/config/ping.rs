@@ -0,0 +1,73 @@
+use super::*;
+
+/// Selects what is sent for each request with the ping protocol.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PingMode {
+    /// Sends `PING` and expects `PONG`.
+    #[default]
+    Ping,
+    /// Sends the `payload` line and expects the `response` line.
+    Custom,
+    /// Sends a unique token, prefixed with the `payload` if one is given, and
+    /// expects the same token to be echoed back.
+    Pingpong,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Ping {
+    #[serde(default)]
+    mode: PingMode,
+    /// The request line, without the trailing `\r\n`.
+    #[serde(default)]
+    payload: Option<String>,
+    /// The expected response line, without the trailing `\r\n`. Any other
+    /// response is counted as an error.
+    #[serde(default)]
+    response: Option<String>,
+}
+
+impl Ping {
+    pub fn mode(&self) -> PingMode {
+        self.mode
+    }
+
+    pub fn payload(&self) -> Option<&str> {
+        self.payload.as_deref()
+    }
+
+    pub fn response(&self) -> Option<&str> {
+        self.response.as_deref()
+    }
+
+    pub fn validate(&self) {
+        match self.mode {
+            PingMode::Ping => {
+                if self.payload.is_some() || self.response.is_some() {
+                    eprintln!("ping `payload` and `response` require the custom or pingpong mode");
+                    std::process::exit(2);
+                }
+            }
+            PingMode::Custom => {
+                if self.payload.is_none() || self.response.is_none() {
+                    eprintln!("the custom ping mode requires both a `payload` and `response`");
+                    std::process::exit(2);
+                }
+            }
+            PingMode::Pingpong => {
+                if self.response.is_some() {
+                    eprintln!("the pingpong mode expects the token echoed and takes no `response`");
+                    std::process::exit(2);
+                }
+            }
+        }
+
+        // the request and response are delimited by line endings
+        for line in [&self.payload, &self.response].into_iter().flatten() {
+            if line.contains(['\r', '\n']) {
+                eprintln!("ping `payload` and `response` must be a single line");
+                std::process::exit(2);
+            }
+        }
+    }
+}
@@ -4,11 +4,33 @@ use super::*;
 #[serde(rename_all = "snake_case")]
 pub enum Protocol {
     Blabber,
+    Grpc,
     Http1,
     Http2,
     Memcache,
+    MemcacheBinary,
     Momento,
     Ping,
+    Raw,
     Resp,
+    Thrift,
     Kafka,
 }
+
+impl Protocol {
+    /// Returns `true` if the client has one connection for each of its tasks,
+    /// which each send one request at a time. The other protocols multiplex
+    /// the requests of several tasks onto shared connections.
+    pub fn has_connection_per_task(&self) -> bool {
+        matches!(
+            self,
+            Self::Http1
+                | Self::Memcache
+                | Self::MemcacheBinary
+                | Self::Ping
+                | Self::Raw
+                | Self::Resp
+                | Self::Thrift
+        )
+    }
+}
@@ -0,0 +1,177 @@
+use super::*;
+
+/// A part of a raw command template, see `Raw`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplatePart {
+    Literal(Vec<u8>),
+    Key,
+    Value,
+    /// The length of the value in bytes, for protocols which frame the value
+    /// with its length.
+    Vlen,
+    /// A number of random alphanumeric characters.
+    Rand(usize),
+}
+
+fn default_terminator() -> String {
+    "\r\n".to_string()
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Raw {
+    /// The command which is sent for each request. The placeholders `{key}`,
+    /// `{value}`, and `{vlen}` are replaced by the key, the value, and the
+    /// length of the value of the request, and `{rand:N}` by N random
+    /// alphanumeric characters. A literal brace is written as `{{` or `}}`.
+    template: String,
+    /// The bytes which end each response. Defaults to `\r\n`.
+    #[serde(default = "default_terminator")]
+    terminator: String,
+}
+
+impl Raw {
+    /// The parts of the template. The template must be valid, which is
+    /// checked by `validate()`.
+    pub fn template(&self) -> Vec<TemplatePart> {
+        parse(&self.template).unwrap()
+    }
+
+    pub fn terminator(&self) -> &[u8] {
+        self.terminator.as_bytes()
+    }
+
+    /// Returns true if the template sends the value of the request, which
+    /// only the requests which write a value have.
+    pub fn has_value(&self) -> bool {
+        self.template()
+            .iter()
+            .any(|part| matches!(part, TemplatePart::Value | TemplatePart::Vlen))
+    }
+
+    pub fn validate(&self) {
+        match parse(&self.template) {
+            Ok(parts) if parts.is_empty() => {
+                eprintln!("raw template must not be empty");
+                std::process::exit(2);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("raw template is not valid: {e}");
+                std::process::exit(2);
+            }
+        }
+
+        if self.terminator.is_empty() {
+            eprintln!("raw terminator must not be empty");
+            std::process::exit(2);
+        }
+    }
+}
+
+// splits the template into the literal bytes and the placeholders
+fn parse(template: &str) -> std::result::Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut literal = Vec::new();
+    let mut rest = template;
+
+    while let Some(position) = rest.find(['{', '}']) {
+        literal.extend_from_slice(rest[..position].as_bytes());
+        rest = &rest[position..];
+
+        if let Some(r) = rest.strip_prefix("{{").or_else(|| rest.strip_prefix("}}")) {
+            literal.push(rest.as_bytes()[0]);
+            rest = r;
+            continue;
+        }
+
+        if rest.starts_with('}') {
+            return Err("unmatched `}`, a literal brace is written as `}}`".to_string());
+        }
+
+        let end = rest
+            .find('}')
+            .ok_or_else(|| "unmatched `{`, a literal brace is written as `{{`".to_string())?;
+
+        let part = match &rest[1..end] {
+            "key" => TemplatePart::Key,
+            "value" => TemplatePart::Value,
+            "vlen" => TemplatePart::Vlen,
+            name => match name.strip_prefix("rand:").map(|n| n.parse::<usize>()) {
+                Some(Ok(len)) if len > 0 => TemplatePart::Rand(len),
+                _ => return Err(format!("unknown placeholder `{{{name}}}`")),
+            },
+        };
+
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+        }
+
+        parts.push(part);
+        rest = &rest[end + 1..];
+    }
+
+    literal.extend_from_slice(rest.as_bytes());
+
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(bytes: &str) -> TemplatePart {
+        TemplatePart::Literal(bytes.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            parse("set {key} 0 0 {vlen}\r\n{value}\r\n"),
+            Ok(vec![
+                literal("set "),
+                TemplatePart::Key,
+                literal(" 0 0 "),
+                TemplatePart::Vlen,
+                literal("\r\n"),
+                TemplatePart::Value,
+                literal("\r\n"),
+            ])
+        );
+
+        assert_eq!(
+            parse("{key}{rand:8}"),
+            Ok(vec![TemplatePart::Key, TemplatePart::Rand(8)])
+        );
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(
+            parse("{{\"key\": \"{key}\"}}"),
+            Ok(vec![
+                literal("{\"key\": \""),
+                TemplatePart::Key,
+                literal("\"}"),
+            ])
+        );
+        assert_eq!(parse("{{}}"), Ok(vec![literal("{}")]));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("get {key").is_err());
+        assert!(parse("get key}").is_err());
+        assert!(parse("get {name}").is_err());
+        assert!(parse("{rand:0}").is_err());
+        assert!(parse("{rand:n}").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+/// Connection setup for the resp protocol. When a password is given, each
+/// connection authenticates with `AUTH` before any requests are sent, and
+/// selects the database with `SELECT` when it is not the default. A
+/// connection which fails either step is closed and retried.
+#[derive(Clone, Deserialize)]
+pub struct Redis {
+    /// The username for servers which use ACLs. Requires a password.
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    /// The database index to select.
+    #[serde(default)]
+    db: i64,
+}
+
+impl Redis {
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    pub fn db(&self) -> i64 {
+        self.db
+    }
+
+    pub fn validate(&self) {
+        if self.username.is_some() && self.password.is_none() {
+            eprintln!("a redis username requires a password");
+            std::process::exit(2);
+        }
+
+        if self.db < 0 {
+            eprintln!("the redis db must not be negative");
+            std::process::exit(2);
+        }
+    }
+}
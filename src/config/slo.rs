@@ -0,0 +1,145 @@
+use super::*;
+
+/// Assertions which are checked against the whole measurement period once the
+/// test ends. If any of them fail, rpc-perf exits with a nonzero code, so that
+/// a test can gate a CI pipeline.
+#[derive(Clone, Deserialize)]
+pub struct Slo {
+    /// Each assertion is given as `METRIC OP THRESHOLD`, for example
+    /// `p99 < 5ms` or `error_rate < 0.1%`. The metric is a latency percentile
+    /// (`p50`, `p99`, `p999`, ..., or `max`) with a duration threshold, or the
+    /// `error_rate` with a percentage threshold. The operator is one of `<`,
+    /// `<=`, `>`, or `>=`.
+    assertions: Vec<String>,
+}
+
+impl Slo {
+    pub fn assertions(&self) -> Vec<SloAssertion> {
+        self.assertions
+            .iter()
+            .map(|assertion| SloAssertion::parse(assertion).unwrap())
+            .collect()
+    }
+
+    pub fn validate(&self) {
+        if self.assertions.is_empty() {
+            eprintln!("the slo section must have at least one assertion");
+            std::process::exit(2);
+        }
+
+        for assertion in &self.assertions {
+            if let Err(e) = SloAssertion::parse(assertion) {
+                eprintln!("slo assertion `{assertion}` is not valid: {e}");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SloMetric {
+    /// The response latency at the percentile, in the range `0.0..=100.0`.
+    Percentile(f64),
+    /// The share of the responses which were errors or timeouts, as a
+    /// fraction.
+    ErrorRate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SloOperator {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl SloOperator {
+    pub fn check(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Less => value < threshold,
+            Self::LessEqual => value <= threshold,
+            Self::Greater => value > threshold,
+            Self::GreaterEqual => value >= threshold,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SloAssertion {
+    /// The assertion as it was written in the config.
+    pub text: String,
+    pub metric: SloMetric,
+    pub operator: SloOperator,
+    /// The threshold in nanoseconds for a percentile, or as a fraction for
+    /// the error rate.
+    pub threshold: f64,
+}
+
+impl SloAssertion {
+    fn parse(assertion: &str) -> Result<Self, String> {
+        let mut parts = assertion.split_whitespace();
+
+        let (metric, operator, threshold) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(metric), Some(operator), Some(threshold)) if parts.next().is_none() => {
+                (metric, operator, threshold)
+            }
+            _ => return Err("expected `METRIC OP THRESHOLD`".to_owned()),
+        };
+
+        let operator = match operator {
+            "<" => SloOperator::Less,
+            "<=" => SloOperator::LessEqual,
+            ">" => SloOperator::Greater,
+            ">=" => SloOperator::GreaterEqual,
+            _ => return Err(format!("unknown operator `{operator}`")),
+        };
+
+        let (metric, threshold) = if metric == "error_rate" {
+            let threshold = threshold
+                .strip_suffix('%')
+                .ok_or("the error rate threshold must be a percentage")?
+                .parse::<f64>()
+                .map_err(|e| format!("the error rate threshold is not a number: {e}"))?;
+
+            if !(0.0..=100.0).contains(&threshold) {
+                return Err("the error rate threshold must be between 0% and 100%".to_owned());
+            }
+
+            (SloMetric::ErrorRate, threshold / 100.0)
+        } else {
+            let threshold = threshold
+                .parse::<humantime::Duration>()
+                .map_err(|e| format!("the latency threshold is not a duration: {e}"))?;
+
+            let threshold = Duration::from(threshold).as_nanos() as f64;
+
+            (SloMetric::Percentile(percentile(metric)?), threshold)
+        };
+
+        Ok(Self {
+            text: assertion.to_owned(),
+            metric,
+            operator,
+            threshold,
+        })
+    }
+}
+
+// a percentile is named as in the summary, where the digits after the first
+// two are the fraction, so `p999` is the 99.9th percentile
+fn percentile(metric: &str) -> Result<f64, String> {
+    if metric == "max" || metric == "p100" {
+        return Ok(100.0);
+    }
+
+    let digits = metric
+        .strip_prefix('p')
+        .filter(|digits| digits.len() >= 2 && digits.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| format!("unknown metric `{metric}`"))?;
+
+    let (integer, fraction) = digits.split_at(2);
+
+    format!("{integer}.{fraction}0")
+        .parse::<f64>()
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+fn max_packet_size() -> usize {
+    // fits within a standard ethernet MTU after the IP and UDP headers
+    1432
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Statsd {
+    /// The address (`HOST:PORT`) of the StatsD collector.
+    endpoint: String,
+    /// An optional prefix which is prepended to each metric name.
+    #[serde(default)]
+    prefix: Option<String>,
+    /// Optional tags which are added to each metric, using the DogStatsD
+    /// extension. Each tag is given as `key:value`.
+    #[serde(default)]
+    tags: Vec<String>,
+    /// The maximum size of each datagram in bytes. Metrics are batched into as
+    /// few datagrams as possible without exceeding this size.
+    #[serde(default = "max_packet_size")]
+    max_packet_size: usize,
+}
+
+impl Statsd {
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+}
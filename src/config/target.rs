@@ -2,10 +2,30 @@ use super::*;
 
 #[derive(Clone, Deserialize)]
 pub struct Target {
-    /// A list of target endpoints (`IP:PORT`).
+    /// A list of target endpoints (`HOST:PORT`, with IPv6 addresses in
+    /// brackets, eg: `[::1]:6379`) or unix domain sockets
+    /// (`unix:///path/to.sock`).
     endpoints: Vec<String>,
+    /// Optional relative weights for the endpoints, in the same order. The
+    /// connection pool is split across the endpoints proportionally. By default
+    /// each endpoint receives an equal share.
+    #[serde(default)]
+    weights: Vec<usize>,
     /// A cache name
     cache_name: Option<String>,
+    /// Treat the endpoints as seed nodes for a Redis Cluster and route each
+    /// request to the node which owns the hash slot for its key.
+    #[serde(default)]
+    cluster: bool,
+    /// Optional interval, in seconds, to resolve the hostnames of the endpoints
+    /// again. Changes in the addresses are logged.
+    #[serde(default)]
+    resolve_interval: Option<u64>,
+    /// When the addresses for an endpoint change, reconnect the existing
+    /// connections gradually so that they move to the new addresses. Requires
+    /// a `resolve_interval`.
+    #[serde(default)]
+    resolve_rebalance: bool,
 }
 
 impl Target {
@@ -13,7 +33,172 @@ impl Target {
         &self.endpoints
     }
 
+    /// Splits a pool of `poolsize` connections per endpoint across the
+    /// endpoints according to their weights, returning the number of
+    /// connections for each endpoint. The total number of connections is
+    /// always `poolsize` times the number of endpoints.
+    pub fn connections(&self, poolsize: usize) -> Vec<(String, usize)> {
+        if self.weights.is_empty() {
+            return self
+                .endpoints
+                .iter()
+                .map(|endpoint| (endpoint.clone(), poolsize))
+                .collect();
+        }
+
+        let total = poolsize * self.endpoints.len();
+        let weight_sum: usize = self.weights.iter().sum();
+
+        // each endpoint gets the integer part of its quota, and the remaining
+        // connections go to the endpoints with the largest remainders
+        let mut connections: Vec<usize> = self
+            .weights
+            .iter()
+            .map(|weight| total * weight / weight_sum)
+            .collect();
+
+        let mut remainders: Vec<(usize, usize)> = self
+            .weights
+            .iter()
+            .enumerate()
+            .map(|(idx, weight)| (total * weight % weight_sum, idx))
+            .collect();
+
+        // sort by descending remainder, ties go to the earlier endpoint
+        remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let assigned: usize = connections.iter().sum();
+
+        for (_, idx) in remainders.iter().take(total - assigned) {
+            connections[*idx] += 1;
+        }
+
+        self.endpoints.iter().cloned().zip(connections).collect()
+    }
+
+    pub fn validate(&self) {
+        if self.resolve_interval == Some(0) {
+            eprintln!("resolve_interval must be greater than zero");
+            std::process::exit(2);
+        }
+
+        if self.resolve_rebalance && self.resolve_interval.is_none() {
+            eprintln!("resolve_rebalance requires that resolve_interval is set");
+            std::process::exit(2);
+        }
+
+        for endpoint in &self.endpoints {
+            if endpoint.starts_with("unix://") {
+                continue;
+            }
+
+            // the port follows the last colon. An IPv6 address must be
+            // enclosed in brackets, eg: `[::1]:6379`, so that the port can be
+            // told apart from the address
+            let valid = match endpoint.rsplit_once(':') {
+                Some((host, port)) if port.parse::<u16>().is_ok() => {
+                    match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+                        Some(ip) => ip.parse::<std::net::Ipv6Addr>().is_ok(),
+                        None => !host.is_empty() && !host.contains(':'),
+                    }
+                }
+                _ => false,
+            };
+
+            if !valid {
+                eprintln!(
+                    "endpoint is not valid: {endpoint}, expected `HOST:PORT`, `[IPV6]:PORT`, or `unix:///path/to.sock`"
+                );
+                std::process::exit(2);
+            }
+        }
+
+        if self.weights.is_empty() {
+            return;
+        }
+
+        if self.weights.len() != self.endpoints.len() {
+            eprintln!("the number of endpoint weights must match the number of endpoints");
+            std::process::exit(2);
+        }
+
+        if self.weights.iter().sum::<usize>() == 0 {
+            eprintln!("at least one endpoint must have a non-zero weight");
+            std::process::exit(2);
+        }
+    }
+
     pub fn cache_name(&self) -> Option<&str> {
         self.cache_name.as_deref()
     }
+
+    pub fn cluster(&self) -> bool {
+        self.cluster
+    }
+
+    pub fn resolve_interval(&self) -> Option<Duration> {
+        self.resolve_interval.map(Duration::from_secs)
+    }
+
+    pub fn resolve_rebalance(&self) -> bool {
+        self.resolve_rebalance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(endpoints: usize, weights: &[usize]) -> Target {
+        let endpoints: Vec<String> = (0..endpoints)
+            .map(|idx| format!("\"127.0.0.1:{}\"", 12321 + idx))
+            .collect();
+
+        toml::from_str(&format!(
+            "endpoints = [{}]\nweights = {weights:?}",
+            endpoints.join(", ")
+        ))
+        .unwrap()
+    }
+
+    fn connections(target: &Target, poolsize: usize) -> Vec<usize> {
+        target
+            .connections(poolsize)
+            .into_iter()
+            .map(|(_, connections)| connections)
+            .collect()
+    }
+
+    #[test]
+    fn unweighted() {
+        assert_eq!(connections(&target(3, &[]), 4), vec![4, 4, 4]);
+    }
+
+    #[test]
+    fn weighted() {
+        assert_eq!(connections(&target(3, &[1, 1, 2]), 4), vec![3, 3, 6]);
+        assert_eq!(connections(&target(2, &[0, 1]), 2), vec![0, 4]);
+    }
+
+    #[test]
+    fn largest_remainder() {
+        // 8/3 and 4/3, the remaining connection goes to the larger remainder
+        assert_eq!(connections(&target(2, &[2, 1]), 2), vec![3, 1]);
+        // 3/5, 6/5, and 6/5
+        assert_eq!(connections(&target(3, &[1, 2, 2]), 1), vec![1, 1, 1]);
+        // 9/5, 3/5, and 3/5, the tied remainders go to the earlier endpoint
+        assert_eq!(connections(&target(3, &[3, 1, 1]), 1), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn total() {
+        let target = target(4, &[5, 3, 7, 1]);
+
+        for poolsize in 1..64 {
+            assert_eq!(
+                connections(&target, poolsize).iter().sum::<usize>(),
+                4 * poolsize
+            );
+        }
+    }
 }
@@ -0,0 +1,48 @@
+use super::*;
+
+/// Selects how the messages of the thrift protocol are delimited.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThriftTransport {
+    /// Each message is prefixed with its length, as for servers which use the
+    /// framed transport or a non-blocking server.
+    #[default]
+    Framed,
+    /// The messages are sent back to back, as for servers which use the
+    /// buffered transport.
+    Unframed,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Thrift {
+    /// The name of the method to call, for example: `getValue`
+    method: String,
+    /// Path to a file containing the argument struct of the call, serialized
+    /// with the binary protocol and including its stop field. If not specified,
+    /// a call without arguments is sent.
+    #[serde(default)]
+    payload: Option<String>,
+    #[serde(default)]
+    transport: ThriftTransport,
+}
+
+impl Thrift {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn payload(&self) -> Option<&str> {
+        self.payload.as_deref()
+    }
+
+    pub fn transport(&self) -> ThriftTransport {
+        self.transport
+    }
+
+    pub fn validate(&self) {
+        if self.method.is_empty() {
+            eprintln!("the thrift `method` must not be empty");
+            std::process::exit(2);
+        }
+    }
+}
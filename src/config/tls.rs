@@ -4,6 +4,19 @@ fn default_true() -> bool {
     true
 }
 
+/// A TLS protocol version.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[serde(rename = "1.2")]
+    Tls1_2,
+    #[serde(rename = "1.3")]
+    Tls1_3,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Tls {
     private_key: Option<String>,
@@ -15,6 +28,34 @@ pub struct Tls {
     verify_hostname: bool,
     #[serde(default = "default_true")]
     use_sni: bool,
+    /// Disables verification of the server certificate. This is insecure and
+    /// is only intended for testing against servers with self-signed
+    /// certificates.
+    #[serde(default, alias = "tls_insecure_skip_verify")]
+    insecure_skip_verify: bool,
+    /// Overrides the server name sent with SNI and used to verify the server
+    /// certificate. By default, the host of the endpoint is used. This allows
+    /// connecting to an IP address with a certificate issued for a hostname.
+    #[serde(default, alias = "tls_server_name")]
+    server_name: Option<String>,
+    /// The lowest TLS version which may be negotiated. Defaults to the lowest
+    /// version supported by the TLS library.
+    #[serde(default, alias = "tls_min_version")]
+    min_version: Option<TlsVersion>,
+    /// The highest TLS version which may be negotiated. Defaults to the
+    /// highest version supported by the TLS library.
+    #[serde(default, alias = "tls_max_version")]
+    max_version: Option<TlsVersion>,
+    /// Restricts the cipher suites which may be negotiated. The names use the
+    /// OpenSSL format, eg: `ECDHE-RSA-AES128-GCM-SHA256`. TLS 1.3 suites, such
+    /// as `TLS_AES_128_GCM_SHA256`, are only configurable with OpenSSL.
+    #[serde(default)]
+    ciphers: Vec<String>,
+    /// Resumes the TLS session from an earlier connection when reconnecting
+    /// to the same endpoint, instead of performing a full handshake. This
+    /// reduces the handshake overhead when connections are short-lived.
+    #[serde(default)]
+    session_resumption: bool,
 }
 
 impl Tls {
@@ -39,10 +80,228 @@ impl Tls {
     }
 
     pub fn verify_hostname(&self) -> bool {
-        self.verify_hostname
+        // there is nothing to match the hostname against without verifying the
+        // certificate
+        self.verify_hostname && !self.insecure_skip_verify
+    }
+
+    pub fn insecure_skip_verify(&self) -> bool {
+        self.insecure_skip_verify
     }
 
     pub fn use_sni(&self) -> bool {
         self.use_sni
     }
+
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    pub fn session_resumption(&self) -> bool {
+        self.session_resumption
+    }
+
+    pub fn min_version(&self) -> Option<TlsVersion> {
+        self.min_version
+    }
+
+    pub fn max_version(&self) -> Option<TlsVersion> {
+        self.max_version
+    }
+
+    /// The TLS 1.2 and earlier cipher suites as an OpenSSL cipher list.
+    pub fn cipher_list(&self) -> Option<String> {
+        let ciphers: Vec<&str> = self
+            .ciphers
+            .iter()
+            .filter(|c| !c.starts_with("TLS_"))
+            .map(|c| c.as_str())
+            .collect();
+
+        if ciphers.is_empty() {
+            None
+        } else {
+            Some(ciphers.join(":"))
+        }
+    }
+
+    /// The TLS 1.3 cipher suites as an OpenSSL ciphersuites string.
+    pub fn ciphersuites(&self) -> Option<String> {
+        let ciphers: Vec<&str> = self
+            .ciphers
+            .iter()
+            .filter(|c| c.starts_with("TLS_"))
+            .map(|c| c.as_str())
+            .collect();
+
+        if ciphers.is_empty() {
+            None
+        } else {
+            Some(ciphers.join(":"))
+        }
+    }
+
+    pub fn validate(&self) {
+        if self.server_name.as_deref() == Some("") {
+            eprintln!("tls `server_name` must not be empty");
+            std::process::exit(2);
+        }
+
+        if let (Some(min), Some(max)) = (self.min_version, self.max_version) {
+            if min > max {
+                eprintln!("tls `min_version` must not be greater than `max_version`");
+                std::process::exit(2);
+            }
+        }
+
+        // a client certificate is only presented when both the key and a
+        // certificate are provided
+        if self.private_key.is_some()
+            != (self.certificate.is_some() || self.certificate_chain.is_some())
+        {
+            eprintln!(
+                "tls client authentication requires both a `private_key` and a `certificate` or `certificate_chain`"
+            );
+            std::process::exit(2);
+        }
+
+        if self.private_key_password.is_some() && self.private_key.is_none() {
+            eprintln!("tls `private_key_password` requires that `private_key` is set");
+            std::process::exit(2);
+        }
+
+        for file in [
+            &self.private_key,
+            &self.certificate,
+            &self.certificate_chain,
+            &self.ca_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Err(e) = std::fs::metadata(file) {
+                eprintln!("tls file could not be read: {file}\n{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_certificate() {
+        let test = "config::tls::tests::client_certificate";
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("client.key");
+        let certificate = dir.path().join("client.crt");
+        std::fs::write(&key, "key").unwrap();
+        std::fs::write(&certificate, "certificate").unwrap();
+
+        let tls = |settings: &str| {
+            testing::config("ping", "127.0.0.1:12321", &format!("[tls]\n{settings}"))
+        };
+
+        let both = format!("private_key = {key:?}\ncertificate = {certificate:?}");
+        assert!(testing::validate(test, &tls(&both)).is_ok());
+
+        // the key is only presented along with a certificate
+        let error = testing::validate(test, &tls(&format!("private_key = {key:?}"))).unwrap_err();
+        assert!(
+            error.contains("requires both a `private_key` and a `certificate`"),
+            "{error}"
+        );
+
+        let missing = format!(
+            "private_key = {key:?}\ncertificate = {:?}",
+            dir.path().join("missing.crt")
+        );
+        let error = testing::validate(test, &tls(&missing)).unwrap_err();
+        assert!(error.contains("tls file could not be read"), "{error}");
+    }
+
+    #[test]
+    fn version_range() {
+        let test = "config::tls::tests::version_range";
+        let tls = |min: &str, max: &str| {
+            testing::config(
+                "ping",
+                "127.0.0.1:12321",
+                &format!("[tls]\ntls_min_version = {min:?}\ntls_max_version = {max:?}"),
+            )
+        };
+
+        assert!(testing::validate(test, &tls("1.2", "1.3")).is_ok());
+        assert!(testing::validate(test, &tls("1.3", "1.3")).is_ok());
+
+        let error = testing::validate(test, &tls("1.3", "1.2")).unwrap_err();
+        assert!(
+            error.contains("`min_version` must not be greater than `max_version`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn cipher_lists() {
+        let tls: Tls = toml::from_str(
+            r#"ciphers = ["ECDHE-RSA-AES128-GCM-SHA256", "TLS_AES_128_GCM_SHA256", "AES256-SHA"]"#,
+        )
+        .unwrap();
+
+        // the tls 1.3 suites are configured separately with openssl
+        assert_eq!(
+            tls.cipher_list().as_deref(),
+            Some("ECDHE-RSA-AES128-GCM-SHA256:AES256-SHA")
+        );
+        assert_eq!(
+            tls.ciphersuites().as_deref(),
+            Some("TLS_AES_128_GCM_SHA256")
+        );
+
+        let tls: Tls = toml::from_str("").unwrap();
+        assert!(tls.cipher_list().is_none());
+        assert!(tls.ciphersuites().is_none());
+    }
+
+    #[test]
+    fn server_name() {
+        let test = "config::tls::tests::server_name";
+        let tls = |name: &str| {
+            testing::config(
+                "ping",
+                "127.0.0.1:12321",
+                &format!("[tls]\ntls_server_name = {name:?}"),
+            )
+        };
+
+        // an ip endpoint may be verified against a certificate for a hostname
+        assert!(testing::validate(test, &tls("cache.example.com")).is_ok());
+
+        let error = testing::validate(test, &tls("")).unwrap_err();
+        assert!(error.contains("`server_name` must not be empty"), "{error}");
+
+        let tls: Tls = toml::from_str(r#"server_name = "cache.example.com""#).unwrap();
+        assert_eq!(tls.server_name(), Some("cache.example.com"));
+    }
+
+    #[test]
+    fn insecure_skip_verify() {
+        let tls: Tls = toml::from_str("").unwrap();
+        assert!(!tls.insecure_skip_verify());
+        assert!(tls.verify_hostname());
+
+        // the hostname is not checked without verifying the certificate
+        let tls: Tls = toml::from_str("tls_insecure_skip_verify = true").unwrap();
+        assert!(tls.insecure_skip_verify());
+        assert!(!tls.verify_hostname());
+
+        let config = testing::config(
+            "ping",
+            "127.0.0.1:12321",
+            "[tls]\ninsecure_skip_verify = true",
+        );
+        assert!(testing::validate("config::tls::tests::insecure_skip_verify", &config).is_ok());
+    }
 }
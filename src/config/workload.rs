@@ -1,4 +1,5 @@
 use super::*;
+use std::collections::BTreeSet;
 
 fn one() -> usize {
     1
@@ -12,6 +13,94 @@ pub struct Workload {
     topics: Vec<Topics>,
     threads: usize,
     ratelimit: Ratelimit,
+    #[serde(default)]
+    mode: Mode,
+    #[serde(default)]
+    trace: Option<Trace>,
+    /// Writes each generated request which was queued to this file, in the
+    /// trace format, so that the run can be replayed later with `trace`.
+    /// Values and ttls are generated again on replay.
+    #[serde(default)]
+    record: Option<String>,
+    /// The number of generated requests which may wait for a client. Defaults
+    /// to 128, or to 1 for a closed loop workload.
+    #[serde(default)]
+    queue_depth: Option<usize>,
+    /// What happens to a request when the queue is full. Defaults to `drop`.
+    #[serde(default)]
+    overflow: Option<Overflow>,
+    /// Additionally records the response latency from the time each request
+    /// was intended to be sent, which is when the ratelimit released it or
+    /// when it was due in a trace. Time spent waiting to be sent, such as when
+    /// the clients stall, is then included in the latency instead of being
+    /// omitted.
+    #[serde(default)]
+    corrected_latency: bool,
+}
+
+/// Controls what happens to a generated request when the queue is full.
+#[derive(Clone, Deserialize, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// The request is dropped and counted as an overload. The generator keeps
+    /// to the ratelimit regardless of how quickly the clients keep up.
+    Drop,
+    /// The generator waits until there is room in the queue, so the rate of
+    /// requests falls to what the clients sustain.
+    Block,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// Replays the requests from a captured trace instead of generating them.
+/// Each line of the trace file is `timestamp,verb,key` where the timestamp is
+/// in seconds and is relative to the first line. The supported verbs are
+/// `get`, `set`, `add`, `replace`, and `delete`. Values and ttls are generated
+/// using the first keyspace. Lines which cannot be parsed are skipped. With the
+/// `remote` feature the file may be an `http(s)://` or `s3://` url.
+#[derive(Clone, Deserialize)]
+pub struct Trace {
+    file: String,
+    /// Scales the timing of the trace. A speed of `2.0` replays the trace in
+    /// half the time it took to capture.
+    #[serde(default = "default_speed")]
+    speed: f64,
+}
+
+impl Trace {
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn validate(&self) {
+        if !self.speed.is_finite() || self.speed <= 0.0 {
+            eprintln!("trace speed must be a positive number");
+            std::process::exit(2);
+        }
+
+        crate::remote::validate("trace file", Some(&self.file));
+    }
+}
+
+/// Controls how the rate of requests is determined.
+#[derive(Clone, Deserialize, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Requests are generated at the rate set by the ratelimit, independent of
+    /// how quickly responses are received.
+    #[default]
+    OpenLoop,
+    /// Each client session sends its next request as soon as it receives the
+    /// previous response. The number of outstanding requests is bounded by the
+    /// number of client sessions and the throughput is whatever the backend
+    /// sustains. Requires that the ratelimit is not set.
+    ClosedLoop,
 }
 
 #[derive(Clone, Deserialize, Copy, Debug, Ord, Eq, PartialOrd, PartialEq, Hash)]
@@ -21,11 +110,48 @@ pub enum ValueKind {
     Bytes,
 }
 
+/// Controls the entropy of generated `bytes` values.
+#[derive(Clone, Deserialize, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueMode {
+    /// Values are entirely random bytes and are incompressible.
+    Random,
+    /// Values are entirely zero bytes.
+    Zeros,
+    /// Values are a mix of random bytes and zero bytes such that they compress
+    /// to approximately the given fraction of their original size.
+    Compressible(f64),
+}
+
 impl Workload {
     pub fn keyspaces(&self) -> &[Keyspace] {
         &self.keyspace
     }
 
+    /// The name of the keyspace at the index, which is the index itself if the
+    /// keyspace is unnamed.
+    pub fn keyspace_name(&self, id: usize) -> String {
+        self.keyspace[id]
+            .name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Chooses the salt for the key prefixes which are salted. One salt is
+    /// used for every keyspace so that it can be reported.
+    pub fn resolve_key_prefixes(&mut self) {
+        use rand::Rng;
+
+        let salt: u32 = rand::thread_rng().gen();
+        let salt = format!("{salt:08x}");
+
+        for keyspace in self.keyspace.iter_mut() {
+            if keyspace.key_prefix_salt {
+                keyspace.salt = Some(salt.clone());
+            }
+        }
+    }
+
     pub fn topics(&self) -> &[Topics] {
         &self.topics
     }
@@ -34,9 +160,76 @@ impl Workload {
         self.threads
     }
 
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
     pub fn ratelimit(&self) -> &Ratelimit {
         &self.ratelimit
     }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn record(&self) -> Option<&str> {
+        self.record.as_deref()
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        // in a closed loop, a minimal queue keeps the number of outstanding
+        // requests bounded by the number of client sessions
+        match self.mode {
+            Mode::OpenLoop => self.queue_depth.unwrap_or(128),
+            Mode::ClosedLoop => 1,
+        }
+    }
+
+    pub fn corrected_latency(&self) -> bool {
+        self.corrected_latency
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        match self.mode {
+            Mode::OpenLoop => self.overflow.unwrap_or(Overflow::Drop),
+            Mode::ClosedLoop => Overflow::Block,
+        }
+    }
+
+    /// A copy of this section for a calibration run, which sends requests in
+    /// a closed loop without a ratelimit.
+    pub fn calibration(&self) -> Self {
+        Self {
+            ratelimit: Ratelimit::default(),
+            mode: Mode::ClosedLoop,
+            trace: None,
+            record: None,
+            queue_depth: None,
+            overflow: None,
+            corrected_latency: false,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) {
+        if self.queue_depth == Some(0) {
+            eprintln!("queue_depth must be greater than zero");
+            std::process::exit(2);
+        }
+
+        if self.mode == Mode::ClosedLoop && (self.queue_depth.is_some() || self.overflow.is_some())
+        {
+            eprintln!("queue_depth and overflow must not be set for a closed loop workload");
+            std::process::exit(2);
+        }
+
+        // a closed loop only sends a request once a client is ready for it, so
+        // there is no intended send time to correct for
+        if self.mode == Mode::ClosedLoop && self.corrected_latency {
+            eprintln!("corrected_latency is not supported for a closed loop workload");
+            std::process::exit(2);
+        }
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -118,11 +311,13 @@ impl Topics {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum Distribution {
     Uniform,
     Zipf,
+    /// Keys are used in order, wrapping around after the last key.
+    Sequential,
 }
 
 impl Default for Distribution {
@@ -133,12 +328,51 @@ impl Default for Distribution {
 
 #[derive(Clone, Deserialize)]
 pub struct Keyspace {
+    /// An optional name for the keyspace, used to report its stats
+    /// separately. Unnamed keyspaces are named by their index.
+    #[serde(default)]
+    name: Option<String>,
     #[serde(default)]
     nkeys: usize,
     #[serde(default)]
     klen: usize,
+    /// Optional distribution of the key lengths, used instead of a fixed
+    /// `klen` so that the keys vary in length. The lengths do not include the
+    /// key prefix.
+    #[serde(default)]
+    klen_distribution: Option<KeyLength>,
+    /// Optional path to a file with the keys to use instead of generating
+    /// them. Each line holds one key. For a `.csv` file, the key is the first
+    /// field of each line. Blank lines are skipped. The keys keep the order of
+    /// the file, so the first keys are the hottest for the zipf distribution.
+    /// With the `remote` feature this may be an `http(s)://` or `s3://` url.
+    #[serde(default)]
+    key_file: Option<String>,
+    /// An optional prefix which is prepended to every key, including the keys
+    /// from a key file or a trace, so that runs sharing a backend do not
+    /// collide. The `klen` is the length of the key without the prefix.
+    #[serde(default)]
+    key_prefix: Option<String>,
+    /// When set, a random salt is appended to the key prefix. The salt is
+    /// chosen once for each instance of rpc-perf, so concurrent runs with the
+    /// same config and seed still use distinct keys.
+    #[serde(default)]
+    key_prefix_salt: bool,
+    // the salt chosen for this instance, see `Workload::resolve_key_prefixes()`
+    #[serde(skip)]
+    salt: Option<String>,
     #[serde(default)]
     key_distribution: Distribution,
+    /// When set, most of the requests are sent to a hot subset of the keys
+    /// which moves through the keyspace over time, see `HotSet`.
+    #[serde(default)]
+    hot_set: Option<HotSet>,
+    /// The exponent (skew) for the zipf key distribution. Larger values
+    /// concentrate more of the traffic on the hottest keys. Defaults to 1.0.
+    #[serde(default)]
+    zipf_exponent: Option<f64>,
+    /// The share of the requests which are sent to this keyspace, relative to
+    /// the weights of the other keyspaces and topics.
     #[serde(default = "one")]
     weight: usize,
     #[serde(default)]
@@ -149,15 +383,117 @@ pub struct Keyspace {
     #[serde(default)]
     vlen: Option<usize>,
     #[serde(default)]
+    vlen_distribution: Option<ValueLength>,
+    #[serde(default)]
     vkind: Option<ValueKind>,
     #[serde(default)]
+    vmode: Option<ValueMode>,
+    #[serde(default)]
     compression_ratio: Option<f64>,
     #[serde(default)]
     // no ttl is treated as no-expires or max ttl for the protocol
     ttl: Option<String>,
+    #[serde(default)]
+    // when set, ttls are drawn uniformly between `ttl` and `ttl_max`
+    ttl_max: Option<String>,
+    #[serde(default)]
+    // when set, written values are derived from the key and any values which
+    // are read back are checked against the expected value
+    verify: bool,
+    /// When set, the keys are written before the test begins, see `Preload`.
+    #[serde(default)]
+    preload: Option<Preload>,
+}
+
+/// Writes the keys of a keyspace with generated values before the test
+/// begins, so that reads find data. Each key is set once, in order, as fast as
+/// the clients complete the requests. The latencies are not recorded. A
+/// preload of `{}` writes every key.
+#[derive(Clone, Copy, Default, Deserialize)]
+pub struct Preload {
+    /// The index of the first key to write. An interrupted preload reports
+    /// where to resume from.
+    #[serde(default)]
+    start: usize,
+    /// The number of keys to write. Defaults to the remainder of the keys.
+    #[serde(default)]
+    count: Option<usize>,
+}
+
+impl Preload {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+}
+
+/// A window of consecutive keys which receives most of the requests, with the
+/// rest spread over the other keys. The window moves forward by its own size
+/// at each interval, wrapping around after the last key, so that the working
+/// set shifts to keys which have gone cold. Within the hot and cold sets the
+/// keys are picked uniformly.
+#[derive(Clone, Deserialize)]
+pub struct HotSet {
+    /// The fraction of the keys which are in the hot set.
+    fraction: f64,
+    /// The fraction of the requests which are sent to the hot set. Defaults to
+    /// 0.9.
+    #[serde(default = "default_hot_traffic")]
+    traffic: f64,
+    /// How long the hot set stays on the same keys before it moves, eg: `60s`
+    interval: String,
+}
+
+fn default_hot_traffic() -> f64 {
+    0.9
+}
+
+impl HotSet {
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    pub fn traffic(&self) -> f64 {
+        self.traffic
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval.parse::<humantime::Duration>().unwrap().into()
+    }
+
+    pub fn validate(&self) {
+        if !(self.fraction > 0.0 && self.fraction < 1.0) {
+            eprintln!("hot_set fraction must be in the range (0.0, 1.0)");
+            std::process::exit(2);
+        }
+
+        if !(0.0..=1.0).contains(&self.traffic) {
+            eprintln!("hot_set traffic must be in the range [0.0, 1.0]");
+            std::process::exit(2);
+        }
+
+        match self.interval.parse::<humantime::Duration>() {
+            Ok(interval) if !Duration::from(interval).is_zero() => {}
+            Ok(_) => {
+                eprintln!("hot_set interval must be non-zero");
+                std::process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("hot_set interval is not valid: {e}");
+                std::process::exit(2);
+            }
+        }
+    }
 }
 
 impl Keyspace {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn nkeys(&self) -> usize {
         self.nkeys
     }
@@ -166,10 +502,35 @@ impl Keyspace {
         self.klen
     }
 
+    pub fn klen_distribution(&self) -> Option<&KeyLength> {
+        self.klen_distribution.as_ref()
+    }
+
+    pub fn key_file(&self) -> Option<&str> {
+        self.key_file.as_deref()
+    }
+
+    /// The prefix for every key, including the salt if there is one.
+    pub fn key_prefix(&self) -> String {
+        format!(
+            "{}{}",
+            self.key_prefix.as_deref().unwrap_or_default(),
+            self.salt.as_deref().unwrap_or_default()
+        )
+    }
+
     pub fn key_distribution(&self) -> Distribution {
         self.key_distribution
     }
 
+    pub fn hot_set(&self) -> Option<&HotSet> {
+        self.hot_set.as_ref()
+    }
+
+    pub fn zipf_exponent(&self) -> f64 {
+        self.zipf_exponent.unwrap_or(1.0)
+    }
+
     pub fn weight(&self) -> usize {
         self.weight
     }
@@ -190,10 +551,22 @@ impl Keyspace {
         self.vlen
     }
 
+    pub fn vlen_distribution(&self) -> Option<&ValueLength> {
+        self.vlen_distribution.as_ref()
+    }
+
     pub fn vkind(&self) -> ValueKind {
         self.vkind.unwrap_or(ValueKind::Bytes)
     }
 
+    pub fn vmode(&self) -> ValueMode {
+        match (self.vmode, self.compression_ratio) {
+            (Some(vmode), _) => vmode,
+            (None, Some(ratio)) if ratio > 1.0 => ValueMode::Compressible(1.0 / ratio),
+            (None, _) => ValueMode::Random,
+        }
+    }
+
     pub fn compression_ratio(&self) -> f64 {
         self.compression_ratio.unwrap_or(1.0)
     }
@@ -203,6 +576,385 @@ impl Keyspace {
             .as_ref()
             .map(|ttl| ttl.parse::<humantime::Duration>().unwrap().into())
     }
+
+    pub fn ttl_max(&self) -> Option<Duration> {
+        self.ttl_max
+            .as_ref()
+            .map(|ttl| ttl.parse::<humantime::Duration>().unwrap().into())
+    }
+
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    pub fn preload(&self) -> Option<Preload> {
+        self.preload
+    }
+
+    pub fn validate(&self) {
+        if let Some(name) = &self.name {
+            // the name is used in the metric names
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                eprintln!("keyspace name must only contain letters, digits, `_`, and `-`");
+                std::process::exit(2);
+            }
+        }
+
+        if self.verify && self.vkind() != ValueKind::Bytes {
+            eprintln!("verify is only supported for keyspaces with bytes values");
+            std::process::exit(2);
+        }
+
+        // commands with zero weight are never selected, so at least one command
+        // must have a non-zero weight for the keyspace to produce any requests
+        if self.commands.iter().all(|command| command.weight() == 0) {
+            eprintln!("keyspace requires at least one command with a non-zero weight");
+            std::process::exit(2);
+        }
+
+        // each request is for at least one key, commands which support a
+        // cardinality above one send several keys in a single request
+        if self
+            .commands
+            .iter()
+            .any(|command| command.cardinality() == 0)
+        {
+            eprintln!("cardinality must not be zero");
+            std::process::exit(2);
+        }
+
+        if self.key_file.is_some() && (self.nkeys > 0 || self.klen > 0) {
+            eprintln!("key_file is mutually exclusive with nkeys and klen");
+            std::process::exit(2);
+        }
+
+        crate::remote::validate("key_file", self.key_file.as_deref());
+
+        if let Some(klen_distribution) = &self.klen_distribution {
+            if self.key_file.is_some() {
+                eprintln!("key_file is mutually exclusive with klen_distribution");
+                std::process::exit(2);
+            }
+
+            if self.klen > 0 {
+                eprintln!("klen and klen_distribution are mutually exclusive");
+                std::process::exit(2);
+            }
+
+            klen_distribution.validate(self.nkeys);
+        }
+
+        // the keys are unique, so there must be enough distinct alphanumeric
+        // keys of the length. The prefix is the same for every key and does
+        // not add to the number of distinct keys
+        if self.key_file.is_none()
+            && self.klen_distribution.is_none()
+            && 62_f64.powi(self.klen.min(16) as i32) < self.nkeys as f64
+        {
+            eprintln!(
+                "klen of {} is too short for {} unique keys, the key prefix is not included in klen",
+                self.klen, self.nkeys
+            );
+            std::process::exit(2);
+        }
+
+        if self.key_prefix_salt && self.key_prefix.is_none() {
+            eprintln!("key_prefix_salt requires a key_prefix");
+            std::process::exit(2);
+        }
+
+        if let Some(exponent) = self.zipf_exponent {
+            if self.key_distribution != Distribution::Zipf {
+                eprintln!("zipf_exponent is only valid with the zipf key distribution");
+                std::process::exit(2);
+            }
+
+            if !exponent.is_finite() || exponent <= 0.0 {
+                eprintln!("zipf_exponent must be a positive number");
+                std::process::exit(2);
+            }
+        }
+
+        if let Some(hot_set) = &self.hot_set {
+            // the hot set picks the keys itself
+            if self.key_distribution != Distribution::Uniform {
+                eprintln!("hot_set is only valid with the uniform key distribution");
+                std::process::exit(2);
+            }
+
+            hot_set.validate();
+        }
+
+        if let Some(vlen_distribution) = &self.vlen_distribution {
+            if self.vlen.is_some() {
+                eprintln!("vlen and vlen_distribution are mutually exclusive");
+                std::process::exit(2);
+            }
+
+            vlen_distribution.validate();
+        }
+
+        if let Some(vmode) = self.vmode {
+            if self.compression_ratio.is_some() {
+                eprintln!("vmode and compression_ratio are mutually exclusive");
+                std::process::exit(2);
+            }
+
+            if let ValueMode::Compressible(fraction) = vmode {
+                if !(fraction > 0.0 && fraction <= 1.0) {
+                    eprintln!("compressible vmode requires a fraction in the range (0.0, 1.0]");
+                    std::process::exit(2);
+                }
+            }
+        }
+
+        for ttl in [&self.ttl, &self.ttl_max].into_iter().flatten() {
+            if let Err(e) = ttl.parse::<humantime::Duration>() {
+                eprintln!("ttl is not valid: {e}");
+                std::process::exit(2);
+            }
+        }
+
+        if let Some(ttl_max) = self.ttl_max() {
+            match self.ttl() {
+                None => {
+                    eprintln!("ttl_max requires that ttl is also set");
+                    std::process::exit(2);
+                }
+                Some(ttl) if ttl > ttl_max => {
+                    eprintln!("ttl_max must not be less than ttl");
+                    std::process::exit(2);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Describes how the lengths of generated keys are distributed. Used instead of
+/// a fixed `klen` so that the keys vary in length like real keys do.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyLength {
+    /// Key lengths are uniformly distributed in the inclusive range.
+    Uniform { min: usize, max: usize },
+    /// Key lengths are picked from the list of sizes with the corresponding
+    /// weights. If no weights are provided, each size is equally likely.
+    Weighted {
+        sizes: Vec<usize>,
+        #[serde(default)]
+        weights: Vec<usize>,
+    },
+}
+
+impl KeyLength {
+    // the distinct key lengths which can be produced, in ascending order
+    fn lengths(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self {
+            Self::Uniform { min, max } => Box::new(*min..=*max),
+            Self::Weighted { sizes, weights } => {
+                let lengths: BTreeSet<usize> = if weights.is_empty() {
+                    sizes.iter().copied().collect()
+                } else {
+                    sizes
+                        .iter()
+                        .zip(weights.iter())
+                        .filter(|(_, weight)| **weight > 0)
+                        .map(|(size, _)| *size)
+                        .collect()
+                };
+
+                Box::new(lengths.into_iter())
+            }
+        }
+    }
+
+    // the number of distinct alphanumeric keys with the lengths, which stops
+    // counting once there are enough for the number of keys
+    fn capacity(&self, nkeys: usize) -> usize {
+        let mut capacity: usize = 0;
+
+        for length in self.lengths() {
+            let keys = 62_usize.checked_pow(length as u32).unwrap_or(usize::MAX);
+            capacity = capacity.saturating_add(keys);
+
+            if capacity >= nkeys {
+                break;
+            }
+        }
+
+        capacity
+    }
+
+    pub fn validate(&self, nkeys: usize) {
+        match self {
+            Self::Uniform { min, max } => {
+                if min > max {
+                    eprintln!("klen_distribution min must not be greater than max");
+                    std::process::exit(2);
+                }
+            }
+            Self::Weighted { sizes, weights } => {
+                if sizes.is_empty() {
+                    eprintln!("klen_distribution requires at least one size");
+                    std::process::exit(2);
+                }
+
+                if !weights.is_empty() && weights.len() != sizes.len() {
+                    eprintln!("klen_distribution must have one weight for each size");
+                    std::process::exit(2);
+                }
+
+                if !weights.is_empty() && weights.iter().all(|w| *w == 0) {
+                    eprintln!("klen_distribution requires at least one non-zero weight");
+                    std::process::exit(2);
+                }
+            }
+        }
+
+        if self.lengths().next() == Some(0) {
+            eprintln!("klen_distribution must not produce empty keys");
+            std::process::exit(2);
+        }
+
+        // the keys are unique, so together the lengths must have enough
+        // distinct alphanumeric keys
+        if self.capacity(nkeys) < nkeys {
+            eprintln!(
+                "klen_distribution is too short for {nkeys} unique keys, the key prefix is not included in the lengths"
+            );
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Describes how the lengths of generated values are distributed. Used instead
+/// of a fixed `vlen` to exercise a mix of value sizes.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ValueLength {
+    /// Value lengths are uniformly distributed in the inclusive range.
+    Uniform { min: usize, max: usize },
+    /// Value lengths are normally distributed. Samples are clamped to the range
+    /// of zero to four standard deviations above the mean.
+    Normal { mean: f64, stddev: f64 },
+    /// Value lengths are picked from the list of sizes with the corresponding
+    /// weights. If no weights are provided, each size is equally likely.
+    Weighted {
+        sizes: Vec<usize>,
+        #[serde(default)]
+        weights: Vec<usize>,
+    },
+    /// Each key is assigned to one of the size classes by the class weights,
+    /// and its values have the size of its class. The latencies of the
+    /// requests for the keys of each class are recorded separately.
+    Classes { classes: Vec<ValueClass> },
+}
+
+/// A named value size class, which is picked with the given weight. The name
+/// defaults to the size in bytes, eg: `100B`.
+#[derive(Clone, Deserialize)]
+pub struct ValueClass {
+    #[serde(default)]
+    name: Option<String>,
+    size: usize,
+    #[serde(default = "one")]
+    weight: usize,
+}
+
+impl ValueClass {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}B", self.size))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+}
+
+impl ValueLength {
+    /// The largest value length that can be produced.
+    pub fn max(&self) -> usize {
+        match self {
+            Self::Uniform { max, .. } => *max,
+            Self::Normal { mean, stddev } => (mean + 4.0 * stddev).ceil() as usize,
+            Self::Weighted { sizes, .. } => sizes.iter().copied().max().unwrap_or(0),
+            Self::Classes { classes } => classes.iter().map(|c| c.size).max().unwrap_or(0),
+        }
+    }
+
+    pub fn validate(&self) {
+        match self {
+            Self::Uniform { min, max } => {
+                if min > max {
+                    eprintln!("vlen_distribution min must not be greater than max");
+                    std::process::exit(2);
+                }
+            }
+            Self::Normal { mean, stddev } => {
+                if !mean.is_finite() || *mean < 0.0 || !stddev.is_finite() || *stddev < 0.0 {
+                    eprintln!("vlen_distribution mean and stddev must be non-negative");
+                    std::process::exit(2);
+                }
+            }
+            Self::Weighted { sizes, weights } => {
+                if sizes.is_empty() {
+                    eprintln!("vlen_distribution requires at least one size");
+                    std::process::exit(2);
+                }
+
+                if !weights.is_empty() && weights.len() != sizes.len() {
+                    eprintln!("vlen_distribution must have one weight for each size");
+                    std::process::exit(2);
+                }
+
+                if !weights.is_empty() && weights.iter().all(|w| *w == 0) {
+                    eprintln!("vlen_distribution requires at least one non-zero weight");
+                    std::process::exit(2);
+                }
+            }
+            Self::Classes { classes } => {
+                if classes.is_empty() {
+                    eprintln!("vlen_distribution requires at least one class");
+                    std::process::exit(2);
+                }
+
+                if classes.iter().all(|class| class.weight == 0) {
+                    eprintln!("vlen_distribution requires at least one non-zero weight");
+                    std::process::exit(2);
+                }
+
+                let mut names = std::collections::HashSet::new();
+
+                for class in classes {
+                    let name = class.name();
+
+                    // the name is part of the metric name
+                    if name.is_empty() || name.contains('/') {
+                        eprintln!(
+                            "value class name `{name}` must be non-empty and not contain `/`"
+                        );
+                        std::process::exit(2);
+                    }
+
+                    if !names.insert(name.clone()) {
+                        eprintln!("value class name `{name}` is used more than once");
+                        std::process::exit(2);
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Deserialize)]
@@ -220,6 +972,10 @@ pub struct Command {
     end: Option<i32>,
     #[serde(default)]
     by_score: bool,
+    #[serde(default)]
+    count: Option<usize>,
+    #[serde(default)]
+    max_iterations: Option<usize>,
 }
 
 impl Command {
@@ -250,6 +1006,14 @@ impl Command {
     pub fn by_score(&self) -> bool {
         self.by_score
     }
+
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    pub fn max_iterations(&self) -> Option<usize> {
+        self.max_iterations
+    }
 }
 
 // #[derive(Deserialize, Clone, Copy, Eq, PartialEq)]
@@ -271,15 +1035,19 @@ pub enum Verb {
     /// * Momento: unsupported
     /// * RESP: `SET` with `NX` option
     Add,
-    /// Read the value for one or more keys.
-    /// * Memcache: `get`
+    /// Read the value for one or more keys. The cardinality is the number of
+    /// keys read by each request.
+    /// * Memcache: `get` (NOTE: cardinality > 1 is not supported)
     /// * Momento: `get` (NOTE: cardinality > 1 is not supported)
     /// * RESP: `GET` or `MGET`
+    #[serde(alias = "mget")]
     Get,
-    /// Set the value for a key.
-    /// * Memcache: `set`
-    /// * Momento: `set`
-    /// * RESP: `SET`
+    /// Set the value for one or more keys. The cardinality is the number of
+    /// keys written by each request.
+    /// * Memcache: `set` (NOTE: cardinality > 1 is not supported)
+    /// * Momento: `set` (NOTE: cardinality > 1 is not supported)
+    /// * RESP: `SET` or `MSET` (NOTE: `MSET` does not support a ttl)
+    #[serde(alias = "mset")]
     Set,
     /// Remove a key.
     /// * Memcache: `delete`
@@ -292,6 +1060,15 @@ pub enum Verb {
     /// * Momento: unsupported
     /// * RESP: `SET` with `XX` option
     Replace,
+    /// Set the value for a key only if it has not been modified since it was
+    /// last read. The CAS value is read with a `get` if not already known.
+    /// * Memcache (binary): `get` followed by `set` with a CAS value
+    Cas,
+    /// Iterate over the keys, following the cursor until the iteration is
+    /// complete or `max_iterations` calls have been made. The `count` is
+    /// passed as a hint for the number of keys returned by each call.
+    /// * RESP: `SCAN` with an optional `COUNT`
+    Scan,
 
     /*
      * HASHES (DICTIONARIES)
@@ -367,7 +1144,7 @@ pub enum Verb {
     ListPopBack,
     /// Removes all elements with given value from the list
     /// * Momento: `list_remove`
-    /// * RESP: `LREM` (Not implemented currently)
+    /// * RESP: `LREM`
     #[serde(alias = "lrem")]
     ListRemove,
 
@@ -441,7 +1218,9 @@ impl Verb {
     pub fn supports_cardinality(&self) -> bool {
         matches!(
             self,
-            Self::HashDelete
+            Self::Get
+                | Self::Set
+                | Self::HashDelete
                 | Self::HashGet
                 | Self::HashSet
                 | Self::ListPushBack
@@ -466,6 +1245,14 @@ impl Verb {
         matches!(self, Self::SortedSetRange)
     }
 
+    pub fn supports_count(&self) -> bool {
+        matches!(self, Self::Scan)
+    }
+
+    pub fn supports_max_iterations(&self) -> bool {
+        matches!(self, Self::Scan)
+    }
+
     pub fn needs_inner_key(&self) -> bool {
         matches!(
             self,
@@ -489,6 +1276,26 @@ impl Verb {
     pub fn supports_truncate(&self) -> bool {
         matches!(self, Self::ListPushBack | Self::ListPushFront)
     }
+
+    /// Returns `true` if the command only reads, so that sending it more than
+    /// once has the same effect as sending it once.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Self::Ping
+                | Self::Get
+                | Self::Scan
+                | Self::HashExists
+                | Self::HashGet
+                | Self::HashGetAll
+                | Self::ListFetch
+                | Self::ListLength
+                | Self::SetMembers
+                | Self::SortedSetRange
+                | Self::SortedSetRank
+                | Self::SortedSetScore
+        )
+    }
 }
 
 // A linear ramp means that the ratelimit is increased between the start
@@ -516,7 +1323,7 @@ pub enum RampCompletionAction {
     Mirror,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub struct Ratelimit {
     #[serde(default)]
     start: u64,
@@ -535,10 +1342,219 @@ pub struct Ratelimit {
 
     #[serde(default)]
     on_ramp_completion: RampCompletionAction,
+
+    /// An explicit schedule of ratelimits, each held for its duration.
+    #[serde(default)]
+    schedule: Vec<RatelimitStep>,
+
+    /// A linear ramp between two ratelimits.
+    #[serde(default)]
+    linear: Option<LinearRamp>,
+
+    /// A traffic shape which modulates the ratelimit around the start rate.
+    #[serde(default)]
+    shape: Option<Shape>,
+
+    /// Adjusts the ratelimit from the start rate to find the highest rate
+    /// which meets a latency target.
+    #[serde(default)]
+    adaptive: Option<Adaptive>,
+}
+
+fn default_adaptive_percentile() -> f64 {
+    99.0
+}
+
+fn default_adaptive_decrease() -> f64 {
+    0.5
+}
+
+fn default_adaptive_interval() -> u64 {
+    5
+}
+
+/// An additive increase, multiplicative decrease controller for the
+/// ratelimit. After each interval the latency percentile over that interval
+/// is compared with the target. The rate is raised by the increase while the
+/// target is met and multiplied by the decrease when it is not, so the rate
+/// settles around the saturation point for the target.
+#[derive(Clone, Deserialize)]
+pub struct Adaptive {
+    /// The latency target, as a duration such as `5ms`.
+    target: String,
+    /// The percentile which must stay under the target.
+    #[serde(default = "default_adaptive_percentile")]
+    percentile: f64,
+    /// The amount the rate is raised by after an interval which met the
+    /// target.
+    increase: u64,
+    /// The factor the rate is multiplied by after an interval which missed
+    /// the target.
+    #[serde(default = "default_adaptive_decrease")]
+    decrease: f64,
+    /// The length of each interval, in seconds.
+    #[serde(default = "default_adaptive_interval")]
+    interval: u64,
+    /// The lowest rate the controller backs off to.
+    #[serde(default)]
+    min: Option<u64>,
+    /// The highest rate the controller raises the rate to.
+    #[serde(default)]
+    max: Option<u64>,
+}
+
+impl Adaptive {
+    /// The latency target in nanoseconds.
+    pub fn target(&self) -> u64 {
+        let target: humantime::Duration = self.target.parse().unwrap();
+        Duration::from(target).as_nanos() as u64
+    }
+
+    pub fn percentile(&self) -> f64 {
+        self.percentile
+    }
+
+    pub fn increase(&self) -> u64 {
+        self.increase
+    }
+
+    pub fn decrease(&self) -> f64 {
+        self.decrease
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    pub fn min(&self) -> u64 {
+        self.min.unwrap_or(1)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    /// The target as it is reported, for example `p99 < 5ms`.
+    pub fn describe(&self) -> String {
+        format!("p{} < {}", self.percentile, self.target)
+    }
+
+    fn validate(&self, start: u64) {
+        if self.target.parse::<humantime::Duration>().is_err() {
+            eprintln!(
+                "the adaptive ratelimit target `{}` is not a duration",
+                self.target
+            );
+            std::process::exit(2);
+        }
+
+        if !(self.percentile > 0.0 && self.percentile <= 100.0) {
+            eprintln!("the adaptive ratelimit percentile must be in the range (0, 100]");
+            std::process::exit(2);
+        }
+
+        if self.increase == 0 || self.interval == 0 {
+            eprintln!("the adaptive ratelimit needs a non-zero increase and interval");
+            std::process::exit(2);
+        }
+
+        if !(self.decrease > 0.0 && self.decrease < 1.0) {
+            eprintln!("the adaptive ratelimit decrease must be in the range (0, 1)");
+            std::process::exit(2);
+        }
+
+        if start == 0 {
+            eprintln!("the adaptive ratelimit starts from the ratelimit start, which must be set");
+            std::process::exit(2);
+        }
+
+        if self.min() == 0
+            || self.min() > start
+            || self.max().map(|max| max < start).unwrap_or(false)
+        {
+            eprintln!("the adaptive ratelimit bounds must be non-zero and include the start rate");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// A periodic traffic shape applied on top of a base ratelimit.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shape {
+    /// The ratelimit follows a sine wave around the base rate.
+    Sine {
+        /// The peak deviation from the base rate.
+        amplitude: u64,
+        /// The length of one cycle, in seconds.
+        period: u64,
+    },
+    /// The ratelimit is raised to the burst rate at the start of each period
+    /// and returns to the base rate after the burst duration.
+    Burst {
+        /// The ratelimit during a burst.
+        rate: u64,
+        /// The length of a burst, in seconds.
+        duration: u64,
+        /// The time between the start of each burst, in seconds.
+        period: u64,
+    },
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct RatelimitStep {
+    /// The length of this step, in seconds.
+    duration: u64,
+    /// The ratelimit for this step.
+    rate: u64,
+}
+
+impl RatelimitStep {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration)
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct LinearRamp {
+    /// The initial ratelimit.
+    from: u64,
+    /// The final ratelimit.
+    to: u64,
+    /// The length of the ramp, in seconds.
+    over: u64,
+}
+
+impl LinearRamp {
+    pub fn from(&self) -> u64 {
+        self.from
+    }
+
+    pub fn to(&self) -> u64 {
+        self.to
+    }
+
+    pub fn over(&self) -> u64 {
+        self.over
+    }
 }
 
 impl Ratelimit {
+    /// The initial ratelimit. For a schedule or linear ramp this is the first
+    /// rate of the schedule.
     pub fn start(&self) -> Option<NonZeroU64> {
+        if let Some(step) = self.schedule.first() {
+            return NonZeroU64::new(step.rate);
+        }
+
+        if let Some(linear) = self.linear {
+            return NonZeroU64::new(linear.from);
+        }
+
         NonZeroU64::new(self.start)
     }
 
@@ -562,15 +1578,104 @@ impl Ratelimit {
         self.on_ramp_completion
     }
 
-    pub fn is_dynamic(&self) -> bool {
+    pub fn schedule(&self) -> &[RatelimitStep] {
+        &self.schedule
+    }
+
+    pub fn linear(&self) -> Option<LinearRamp> {
+        self.linear
+    }
+
+    pub fn shape(&self) -> Option<Shape> {
+        self.shape
+    }
+
+    pub fn adaptive(&self) -> Option<&Adaptive> {
+        self.adaptive.as_ref()
+    }
+
+    fn is_stepped(&self) -> bool {
         self.end.is_some() || self.step.is_some() || self.interval.is_some()
     }
 
+    pub fn is_dynamic(&self) -> bool {
+        self.is_stepped()
+            || !self.schedule.is_empty()
+            || self.linear.is_some()
+            || self.shape.is_some()
+            || self.adaptive.is_some()
+    }
+
     pub fn validate(&self) {
         if !self.is_dynamic() {
             return;
         }
 
+        let kinds = [
+            self.is_stepped(),
+            !self.schedule.is_empty(),
+            self.linear.is_some(),
+            self.shape.is_some(),
+            self.adaptive.is_some(),
+        ];
+
+        if kinds.iter().filter(|kind| **kind).count() > 1 {
+            eprintln!("only one of a stepped ramp, a schedule, a linear ramp, a shape, or an adaptive ratelimit may be used for the ratelimit");
+            std::process::exit(2);
+        }
+
+        if self.start > 0 && (!self.schedule.is_empty() || self.linear.is_some()) {
+            eprintln!(
+                "the ratelimit start is taken from the schedule or linear ramp and must not be set"
+            );
+            std::process::exit(2);
+        }
+
+        if !self.schedule.is_empty() {
+            if self
+                .schedule
+                .iter()
+                .any(|step| step.rate == 0 || step.duration == 0)
+            {
+                eprintln!("each step of the ratelimit schedule needs a non-zero rate and duration");
+                std::process::exit(2);
+            }
+
+            return;
+        }
+
+        if let Some(linear) = self.linear {
+            if linear.from == 0 || linear.to == 0 || linear.over == 0 {
+                eprintln!("a linear ratelimit ramp needs non-zero rates and duration");
+                std::process::exit(2);
+            }
+
+            return;
+        }
+
+        if let Some(adaptive) = &self.adaptive {
+            adaptive.validate(self.start);
+            return;
+        }
+
+        if let Some(shape) = self.shape {
+            let valid = match shape {
+                Shape::Sine { amplitude, period } => self.start > amplitude && period > 0,
+                Shape::Burst {
+                    rate,
+                    duration,
+                    period,
+                } => self.start > 0 && rate > 0 && duration > 0 && duration < period,
+            };
+
+            if !valid {
+                eprintln!("invalid traffic shape for the ratelimit, the start rate must be set and the shape must not reduce the rate to zero");
+                std::process::exit(2);
+            }
+
+            return;
+        }
+
         if !(self.end.is_some() && self.step.is_some() && self.interval.is_some()) {
             eprintln!("end, step, and interval need to be specified for dynamic ratelimit");
             std::process::exit(2);
@@ -582,3 +1687,61 @@ impl Ratelimit {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn klen_capacity() {
+        let uniform = |min, max| KeyLength::Uniform { min, max };
+
+        assert_eq!(uniform(1, 1).capacity(100), 62);
+        assert_eq!(uniform(1, 2).capacity(100), 62 + 62 * 62);
+
+        // the counting stops as soon as there are enough keys, so a huge range
+        // is cheap to check
+        assert_eq!(uniform(1, usize::MAX).capacity(1_000), 62 + 62 * 62);
+        assert_eq!(uniform(20, 30).capacity(usize::MAX), usize::MAX);
+
+        // each length is only counted once, and only if it can be picked
+        let weighted = |sizes: &[usize], weights: &[usize]| KeyLength::Weighted {
+            sizes: sizes.to_vec(),
+            weights: weights.to_vec(),
+        };
+        assert_eq!(weighted(&[2, 2], &[]).capacity(usize::MAX), 62 * 62);
+        assert_eq!(weighted(&[1, 2], &[1, 0]).capacity(usize::MAX), 62);
+    }
+
+    #[test]
+    fn klen_cardinality() {
+        let keyspace = |nkeys, min, max| {
+            testing::config(
+                "memcache",
+                "127.0.0.1:11211",
+                &format!(
+                    r#"
+                    [[workload.keyspace]]
+                    nkeys = {nkeys}
+                    klen_distribution = {{ type = "uniform", min = {min}, max = {max} }}
+                    commands = [{{ verb = "get" }}]
+                    "#
+                ),
+            )
+        };
+        let test = "config::workload::tests::klen_cardinality";
+
+        assert!(testing::validate(test, &keyspace(62, 1, 1)).is_ok());
+        assert!(testing::validate(test, &keyspace(1_000_000, 1, 1_000_000_000)).is_ok());
+
+        // there are only 62 distinct keys of a single character
+        let error = testing::validate(test, &keyspace(63, 1, 1)).unwrap_err();
+        assert!(
+            error.contains("klen_distribution is too short for 63 unique keys"),
+            "{error}"
+        );
+
+        let error = testing::validate(test, &keyspace(10, 0, 4)).unwrap_err();
+        assert!(error.contains("must not produce empty keys"), "{error}");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::{Debug, LogFormat};
+use log::kv::{self, Key, VisitSource};
+use log::Record;
+use ringlog::Level;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::OnceLock;
+
+// the log formats are plain functions, so the module levels are shared with
+// them once the log is configured
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+struct Filter {
+    format: LogFormat,
+    level: Level,
+    // sorted with the longest module first, so the most specific one matches
+    modules: Vec<(String, Level)>,
+}
+
+/// Configures the module levels and the format of the debug log. Returns the
+/// most verbose level which any module is logged at, which is the level the
+/// log must be filtered at.
+pub fn init(debug: &Debug) -> Level {
+    let mut modules = debug.log_modules();
+    modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+    let level = modules
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(debug.log_level(), std::cmp::max);
+
+    let _ = FILTER.set(Filter {
+        format: debug.log_format(),
+        level: debug.log_level(),
+        modules,
+    });
+
+    level
+}
+
+/// Returns true if the debug log is JSON. Events which are already reported
+/// in the human-readable output, such as each window, are only logged as
+/// JSON for log pipelines.
+pub fn json() -> bool {
+    FILTER
+        .get()
+        .map(|filter| filter.format == LogFormat::Json)
+        .unwrap_or(false)
+}
+
+/// Formats a record as a human-readable line. This is only used when there are
+/// module levels, as the default format does not filter by module.
+pub fn text_format<T: Display>(
+    w: &mut dyn Write,
+    now: T,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    if !enabled(record) {
+        return Ok(());
+    }
+
+    writeln!(
+        w,
+        "{} {} [{}] {}",
+        now,
+        record.level(),
+        record.module_path().unwrap_or("<unnamed>"),
+        record.args()
+    )
+}
+
+/// Formats a record as a single line JSON object, with the structured fields
+/// of the record alongside the timestamp, level, module, and message.
+pub fn json_format<T: Display>(
+    w: &mut dyn Write,
+    now: T,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    if !enabled(record) {
+        return Ok(());
+    }
+
+    let mut line = Map::new();
+
+    let _ = record.key_values().visit(&mut Fields(&mut line));
+
+    line.insert("timestamp".to_string(), now.to_string().into());
+    line.insert("level".to_string(), record.level().as_str().into());
+    line.insert(
+        "module".to_string(),
+        record.module_path().unwrap_or("<unnamed>").into(),
+    );
+    line.insert("message".to_string(), record.args().to_string().into());
+
+    serde_json::to_writer(&mut *w, &line)?;
+    writeln!(w)
+}
+
+// a record is logged at the level of the most specific module which contains
+// it, or at the log level
+fn enabled(record: &Record) -> bool {
+    let filter = match FILTER.get() {
+        Some(filter) => filter,
+        None => return true,
+    };
+
+    let path = record.module_path().unwrap_or(record.target());
+
+    let level = filter
+        .modules
+        .iter()
+        .find(|(module, _)| {
+            path.strip_prefix(module.as_str())
+                .map(|rest| rest.is_empty() || rest.starts_with("::"))
+                .unwrap_or(false)
+        })
+        .map(|(_, level)| *level)
+        .unwrap_or(filter.level);
+
+    record.level() <= level
+}
+
+// numbers and booleans are kept as such, so that the fields can be queried
+// without parsing them
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            v.into()
+        } else if let Some(v) = value.to_i64() {
+            v.into()
+        } else if let Some(v) = value.to_f64() {
+            v.into()
+        } else if let Some(v) = value.to_bool() {
+            v.into()
+        } else {
+            value.to_string().into()
+        };
+
+        self.0.insert(key.as_str().to_string(), value);
+
+        Ok(())
+    }
+}
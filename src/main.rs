@@ -11,22 +11,29 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Builder;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 
 mod admin;
+mod calibrate;
 mod clients;
 mod config;
+mod logging;
 mod metrics;
 mod net;
 mod output;
 mod pubsub;
+mod remote;
 mod workload;
 
 use config::*;
 use metrics::*;
 
 static RUNNING: AtomicBool = AtomicBool::new(true);
+// set once a signal asks for the test to end early. The generators stop and the
+// test ends after the outstanding requests have completed
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 static WAIT: AtomicUsize = AtomicUsize::new(0);
 
 static METRICS_SNAPSHOT: Lazy<Arc<RwLock<MetricsSnapshot>>> =
@@ -53,16 +60,120 @@ fn main() {
                 .action(clap::ArgAction::Set)
                 .index(1),
         )
+        .arg(
+            Arg::new("DRY_RUN")
+                .long("dry-run")
+                .help("Validate the configuration and print the test plan without sending any traffic")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("CALIBRATE")
+                .long("calibrate")
+                .help("Measure the throughput of a connection and recommend the number of connections for the ratelimit, then exit")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("DRY_RUN"),
+        )
+        .arg(
+            Arg::new("CALIBRATE_APPLY")
+                .long("calibrate-apply")
+                .help("Measure the throughput of a connection and run the test with the recommended number of connections")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["DRY_RUN", "CALIBRATE"]),
+        )
+        .arg(
+            Arg::new("LOG_FORMAT")
+                .long("log-format")
+                .value_name("FORMAT")
+                .help("The format of the debug log, overriding the configuration")
+                .value_parser(["text", "json"])
+                .action(clap::ArgAction::Set),
+        )
+        .arg(
+            Arg::new("SET")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .help("Override a value in the configuration file by its dotted path, such as `workload.ratelimit.start=1000`. May be repeated")
+                .action(clap::ArgAction::Append),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compare the latency of a candidate run against a baseline run, using the histograms in their summaries or snapshots")
+                .arg(
+                    Arg::new("BASELINE")
+                        .help("The summary or snapshot of the baseline run")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("CANDIDATE")
+                        .help("The summary or snapshot of the candidate run")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("THRESHOLD")
+                        .long("threshold")
+                        .value_name("PERCENT")
+                        .help("The largest increase in latency at a percentile which is not a regression")
+                        .default_value("5%"),
+                ),
+        )
         .get_matches();
 
+    // the comparison of two runs exits with code 3 if there is a regression
+    if let Some(("compare", compare)) = matches.subcommand() {
+        let threshold = compare.get_one::<String>("THRESHOLD").unwrap();
+        let threshold = match threshold.trim_end_matches('%').parse::<f64>() {
+            Ok(t) if t >= 0.0 => t / 100.0,
+            _ => {
+                eprintln!("the threshold must be a non-negative percentage: {threshold}");
+                std::process::exit(2);
+            }
+        };
+
+        match output::compare(
+            compare.get_one::<String>("BASELINE").unwrap(),
+            compare.get_one::<String>("CANDIDATE").unwrap(),
+            threshold,
+        ) {
+            Ok(false) => std::process::exit(0),
+            Ok(true) => std::process::exit(3),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let dry_run = matches.get_flag("DRY_RUN");
+    let calibrate = matches.get_flag("CALIBRATE");
+    let calibrate_apply = matches.get_flag("CALIBRATE_APPLY");
+    let overrides: Vec<String> = matches
+        .get_many::<String>("SET")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+
     // load config from file
-    let config = if let Some(file) = matches.get_one::<String>("CONFIG") {
-        Config::new(file)
+    let mut config = if let Some(file) = matches.get_one::<String>("CONFIG") {
+        Config::new(file, &overrides)
     } else {
         eprintln!("configuration file not provided");
         std::process::exit(1);
     };
 
+    match matches.get_one::<String>("LOG_FORMAT").map(|f| f.as_str()) {
+        Some("json") => config.set_log_format(LogFormat::Json),
+        Some(_) => config.set_log_format(LogFormat::Text),
+        None => {}
+    }
+
+    // the histograms are created when first used, with these parameters
+    set_histogram_parameters(
+        config.histogram().grouping_power(),
+        config.histogram().max_value_power(),
+    );
+
     // configure debug log
     let debug_output: Box<dyn Output> = if let Some(file) = config.debug().log_file() {
         let backup = config
@@ -79,11 +190,17 @@ fn main() {
     };
 
     let level = config.debug().log_level();
+    let level_filter = logging::init(config.debug());
 
-    let debug_log = if level <= Level::Info {
-        LogBuilder::new().format(ringlog::default_format)
-    } else {
-        LogBuilder::new()
+    let debug_log = match config.debug().log_format() {
+        LogFormat::Json => LogBuilder::new().format(logging::json_format),
+        LogFormat::Text if !config.debug().log_modules().is_empty() => {
+            LogBuilder::new().format(logging::text_format)
+        }
+        LogFormat::Text if level <= Level::Info => {
+            LogBuilder::new().format(ringlog::default_format)
+        }
+        LogFormat::Text => LogBuilder::new(),
     }
     .output(debug_output)
     .log_queue_depth(config.debug().log_queue_depth())
@@ -92,7 +209,7 @@ fn main() {
     .expect("failed to initialize debug log");
 
     let mut log = MultiLogBuilder::new()
-        .level_filter(config.debug().log_level().to_level_filter())
+        .level_filter(level_filter.to_level_filter())
         .default(debug_log)
         .build()
         .start();
@@ -115,6 +232,36 @@ fn main() {
         let _ = log.flush();
     });
 
+    // stop the test gracefully on SIGINT or SIGTERM, a second signal exits
+    // immediately
+    control_runtime.spawn(async move {
+        let mut sigterm = match signal(SignalKind::terminate()) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("failed to install the SIGTERM handler: {e}");
+                return;
+            }
+        };
+
+        loop {
+            let name = tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = sigterm.recv() => "SIGTERM",
+            };
+
+            if SHUTDOWN.swap(true, Ordering::Relaxed) {
+                eprintln!("received {name} again, exiting immediately");
+                std::process::exit(130);
+            }
+
+            output!("Received {name}, stopping the test");
+
+            if logging::json() {
+                info!(event = "signal", signal = name; "received {name}, stopping the test");
+            }
+        }
+    });
+
     // spawn thread to maintain histogram snapshots
     {
         let interval = config.general().interval();
@@ -132,15 +279,188 @@ fn main() {
         });
     }
 
-    // TODO: figure out what a reasonable size is here
-    let (client_sender, client_receiver) = bounded(128);
-    let (pubsub_sender, pubsub_receiver) = bounded(128);
+    if calibrate || calibrate_apply {
+        // the connections are counted per endpoint, which does not apply to
+        // the nodes of a cluster
+        if config.client().is_none() || config.target().cluster() {
+            eprintln!("calibration requires a client config and is not supported in cluster mode");
+            std::process::exit(2);
+        }
+
+        let poolsize = calibrate::calibrate(&config);
+
+        if calibrate || SHUTDOWN.load(Ordering::Relaxed) {
+            // let the logging thread flush the output before exiting
+            RUNNING.store(false, Ordering::Relaxed);
+            std::thread::sleep(Duration::from_millis(100));
+            return;
+        }
+
+        if let Some(poolsize) = poolsize {
+            config.set_poolsize(poolsize);
+        }
+    }
+
+    let queue_depth = config.workload().queue_depth();
+    let (client_sender, client_receiver) = bounded(queue_depth);
+    let (pubsub_sender, pubsub_receiver) = bounded(queue_depth);
 
     output!("Protocol: {:?}", config.general().protocol());
+    output!("Seed: {}", config.general().seed());
+
+    if let Some(client) = config.client() {
+        if !config.target().cluster() {
+            for (endpoint, connections) in config.target().connections(client.poolsize()) {
+                output!("Endpoint: {endpoint} Connections: {connections}");
+            }
+        }
+    }
+
+    if config.workload().mode() == Mode::ClosedLoop {
+        if let Some(client) = config.client() {
+            // only the multiplexed protocols have concurrent sessions on each
+            // connection
+            let sessions = match config.general().protocol() {
+                Protocol::Grpc | Protocol::Http2 => client.concurrency(),
+                _ => 1,
+            };
+            let outstanding = client.poolsize() * config.target().endpoints().len() * sessions;
+
+            output!("Mode: closed loop with at most {outstanding} outstanding requests");
+        }
+    } else {
+        output!(
+            "Queue: Depth: {} Overflow: {:?}",
+            config.workload().queue_depth(),
+            config.workload().overflow()
+        );
+    }
+
+    if let Some(client) = config.client() {
+        if let Some(rate) = client.per_connection_rate() {
+            let connections = client.poolsize() * config.target().endpoints().len();
+            let aggregate = rate.get() * connections as u64;
+
+            output!("Per-Connection Ratelimit: {rate}/s ({aggregate}/s across all connections)");
+
+            // whichever limit is lower determines the achievable rate
+            match config.workload().ratelimit().start() {
+                Some(start) if start.get() <= aggregate => {
+                    output!("Binding Ratelimit: global")
+                }
+                _ => output!("Binding Ratelimit: per-connection"),
+            }
+        }
+
+        print_connection_ramp(&config);
+    }
+
+    if let Some(redis) = config.redis() {
+        // the password is never output
+        output!(
+            "Redis: Username: {} Password: {} Database: {}",
+            redis.username().unwrap_or("default"),
+            if redis.password().is_some() {
+                "<redacted>"
+            } else {
+                "none"
+            },
+            redis.db()
+        );
+    }
+
+    if let Some(trace) = config.workload().trace() {
+        output!("Trace: {} Speed: {}x", trace.file(), trace.speed());
+    }
+
+    if let Some(max_requests) = config.general().max_requests() {
+        output!("Max Requests: {max_requests}");
+    }
+
+    for (id, keyspace) in config.workload().keyspaces().iter().enumerate() {
+        let id = config.workload().keyspace_name(id);
+
+        let prefix = keyspace.key_prefix();
+        if !prefix.is_empty() {
+            output!("Keyspace {id}: Key Prefix: {prefix}");
+        }
+
+        if let Some(hot_set) = keyspace.hot_set() {
+            output!(
+                "Keyspace {id}: Hot Set: {:.1} % of the keys with {:.1} % of the requests, moving every {:?}",
+                100.0 * hot_set.fraction(),
+                100.0 * hot_set.traffic(),
+                hot_set.interval()
+            );
+        }
+
+        if let Some(file) = keyspace.key_file() {
+            output!(
+                "Keyspace {id}: Keys: {file} Distribution: {:?}",
+                keyspace.key_distribution()
+            );
+            continue;
+        }
+
+        match keyspace.key_distribution() {
+            Distribution::Zipf => output!(
+                "Keyspace {id}: Keys: {} Distribution: {:?} (exponent: {})",
+                keyspace.nkeys(),
+                keyspace.key_distribution(),
+                keyspace.zipf_exponent()
+            ),
+            Distribution::Uniform | Distribution::Sequential => output!(
+                "Keyspace {id}: Keys: {} Distribution: {:?}",
+                keyspace.nkeys(),
+                keyspace.key_distribution()
+            ),
+        }
+    }
+
+    if config
+        .tls()
+        .map(|tls| tls.insecure_skip_verify())
+        .unwrap_or(false)
+    {
+        output!("*****");
+        output!("WARNING: TLS certificate verification is disabled by `insecure_skip_verify`");
+        output!("WARNING: the identity of the server is not checked, do not use in production");
+        output!("*****");
+    }
+
+    // the clients each build their own connector, we build one up front so
+    // that a bad tls configuration is reported once instead of by every task
+    if let Err(e) = net::Connector::new(&config) {
+        eprintln!("failed to initialize the tls configuration: {e}");
+        std::process::exit(2);
+    }
 
     debug!("Initializing workload generator");
     let workload_generator = Generator::new(&config);
 
+    if dry_run {
+        print_plan(&config);
+
+        // let the logging thread flush the output before exiting
+        RUNNING.store(false, Ordering::Relaxed);
+        std::thread::sleep(Duration::from_millis(100));
+        return;
+    }
+
+    // the keyspaces are preloaded before any of the test is measured
+    if config
+        .workload()
+        .keyspaces()
+        .iter()
+        .any(|keyspace| keyspace.preload().is_some())
+        && !workload::preload(&config, &workload_generator)
+    {
+        // let the logging thread flush the output before exiting
+        RUNNING.store(false, Ordering::Relaxed);
+        std::thread::sleep(Duration::from_millis(100));
+        return;
+    }
+
     let workload_ratelimit = workload_generator.ratelimiter();
 
     let workload_components = workload_generator.components().to_owned();
@@ -148,12 +468,39 @@ fn main() {
     // spawn the admin thread
     control_runtime.spawn(admin::http(config.clone(), workload_ratelimit.clone()));
 
+    if let Some(addr) = config.general().admin_console() {
+        control_runtime.spawn(admin::console(addr.to_string()));
+    }
+
     // launch metrics file output
     control_runtime.spawn(output::metrics(config.clone()));
 
+    // launch csv output
+    control_runtime.spawn(output::csv(config.clone()));
+
+    // launch statsd metrics push
+    control_runtime.spawn(output::statsd(config.clone()));
+
+    // launch summary file output
+    control_runtime.spawn(output::summary(config.clone()));
+
+    // write a snapshot of the results so far on each SIGUSR1
+    control_runtime.spawn(output::snapshots(config.clone()));
+
+    // check the slo assertions once the test ends
+    control_runtime.spawn(output::slo(config.clone()));
+
+    // launch latency heatmap output
+    #[cfg(feature = "heatmap")]
+    control_runtime.spawn(output::heatmap(config.clone()));
+
     // begin cli output
     control_runtime.spawn(output::log(config.clone()));
 
+    // keep handles to the queues so we can tell when they have been drained
+    let client_queue = client_sender.clone();
+    let pubsub_queue = pubsub_sender.clone();
+
     // start the workload generator(s)
     let workload_runtime =
         launch_workload(workload_generator, &config, client_sender, pubsub_sender);
@@ -179,10 +526,53 @@ fn main() {
                 }
             });
         }
+
+        control_runtime.spawn(workload::adaptive(
+            config.clone(),
+            workload_ratelimit.clone(),
+        ));
     }
 
     while RUNNING.load(Ordering::Relaxed) {
         std::thread::sleep(Duration::from_secs(1));
+
+        // once the request budget is used up, the trace has been replayed, or
+        // a signal was received, the generators stop. We wait for the queued
+        // requests to be sent and give the outstanding requests time to
+        // complete before ending the test.
+        if (config.general().max_requests().is_some()
+            || config.workload().trace().is_some()
+            || SHUTDOWN.load(Ordering::Relaxed))
+            && workload::GENERATORS.load(Ordering::Relaxed) == 0
+        {
+            while RUNNING.load(Ordering::Relaxed)
+                && (!client_queue.is_empty() || !pubsub_queue.is_empty())
+            {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            if let Some(client) = config.client() {
+                std::thread::sleep(client.request_timeout());
+            }
+
+            let end = if SHUTDOWN.load(Ordering::Relaxed) {
+                output!("Outstanding requests completed, ending test");
+                "signal"
+            } else if config.workload().trace().is_some() {
+                output!("Trace replay complete, ending test");
+                "trace"
+            } else {
+                output!("Request budget exhausted, ending test");
+                "budget"
+            };
+
+            // the log is no longer flushed once the test has stopped
+            if logging::json() {
+                info!(event = "shutdown", reason = end; "ending the test");
+            }
+
+            RUNNING.store(false, Ordering::Relaxed);
+        }
     }
 
     // shutdown thread pools
@@ -194,6 +584,7 @@ fn main() {
     pubsub_runtimes.shutdown_timeout(std::time::Duration::from_millis(100));
 
     workload_runtime.shutdown_timeout(std::time::Duration::from_millis(100));
+    workload::finish_recording();
 
     // delay before exiting
 
@@ -202,4 +593,125 @@ fn main() {
     }
 
     std::thread::sleep(std::time::Duration::from_millis(100));
+
+    // a failed slo assertion fails the test, so that it can gate a pipeline
+    if output::slo_violated() {
+        std::process::exit(3);
+    }
+}
+
+/// Outputs the parts of the test plan which are otherwise only visible while
+/// the test is running. Exits with an error if an endpoint cannot be resolved.
+fn print_plan(config: &Config) {
+    use std::net::ToSocketAddrs;
+
+    output!("-----");
+    output!("Dry Run: the configuration is valid, no traffic will be sent");
+
+    for endpoint in config.target().endpoints() {
+        if endpoint.starts_with("unix://") {
+            continue;
+        }
+
+        match endpoint.to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs: Vec<String> = addrs.map(|addr| addr.to_string()).collect();
+                output!("Endpoint: {endpoint} Resolves To: {}", addrs.join(", "));
+            }
+            Err(e) => {
+                eprintln!("failed to resolve endpoint: {endpoint}\n{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    output!(
+        "Histogram: Grouping Power: {} Max Value Power: {}",
+        config.histogram().grouping_power(),
+        config.histogram().max_value_power()
+    );
+
+    if let Some(client) = config.client() {
+        output!(
+            "Connections: {} ({} per endpoint) Client Threads: {}",
+            client.poolsize() * config.target().endpoints().len(),
+            client.poolsize(),
+            client.threads()
+        );
+
+        print_connection_ramp(config);
+    }
+
+    let ratelimit = config.workload().ratelimit();
+    match ratelimit.start() {
+        Some(start) if ratelimit.is_dynamic() => {
+            output!("Ratelimit: {start}/s initially, adjusted during the test")
+        }
+        Some(start) => output!("Ratelimit: {start}/s"),
+        None => output!("Ratelimit: unlimited"),
+    }
+
+    output!(
+        "Duration: {}s Warmup: {}s Interval: {}s",
+        config.general().duration().as_secs(),
+        config.general().warmup().as_secs(),
+        config.general().interval().as_secs()
+    );
+
+    let keyspace_weights: usize = config
+        .workload()
+        .keyspaces()
+        .iter()
+        .map(|k| k.weight())
+        .sum();
+    let topics_weights: usize = config.workload().topics().iter().map(|t| t.weight()).sum();
+    let total_weight = (keyspace_weights + topics_weights).max(1) as f64;
+
+    for (id, keyspace) in config.workload().keyspaces().iter().enumerate() {
+        let id = config.workload().keyspace_name(id);
+
+        output!(
+            "Keyspace {id}: Share: {:.1} %",
+            100.0 * keyspace.weight() as f64 / total_weight
+        );
+
+        let command_weights: usize = keyspace.commands().iter().map(|c| c.weight()).sum();
+
+        for command in keyspace.commands() {
+            output!(
+                "Keyspace {id}: Command: {:?} Share: {:.1} % Cardinality: {}",
+                command.verb(),
+                100.0 * command.weight() as f64 / command_weights.max(1) as f64,
+                command.cardinality()
+            );
+        }
+    }
+
+    for (id, topics) in config.workload().topics().iter().enumerate() {
+        output!(
+            "Topics {id}: Share: {:.1} %",
+            100.0 * topics.weight() as f64 / total_weight
+        );
+    }
+}
+
+/// Outputs the schedule which the connections are opened with, if they are
+/// opened gradually.
+fn print_connection_ramp(config: &Config) {
+    let client = match config.client() {
+        Some(client) => client,
+        None => return,
+    };
+
+    if let Some(ramp) = client.connection_ramp() {
+        let total = client.poolsize() * config.target().endpoints().len();
+        let steps = (total + ramp.step() - 1) / ramp.step();
+
+        output!(
+            "Connection Ramp: {} more every {}s, all {total} open after {}s",
+            ramp.step(),
+            ramp.interval().as_secs(),
+            ramp.interval().as_secs() * (steps as u64 - 1)
+        );
+    }
 }
@@ -8,8 +8,23 @@ use ahash::HashMapExt;
 use metriken::Lazy;
 use paste::paste;
 use std::concat;
+use std::sync::OnceLock;
 use std::time::SystemTime;
 
+// the grouping power and max value power of the histograms, which are set from
+// the config before any histogram is used
+static HISTOGRAM_PARAMETERS: OnceLock<(u8, u8)> = OnceLock::new();
+
+/// Sets the parameters which every histogram is created with. Histograms are
+/// created when they are first used, so this must be called before then.
+pub fn set_histogram_parameters(grouping_power: u8, max_value_power: u8) {
+    let _ = HISTOGRAM_PARAMETERS.set((grouping_power, max_value_power));
+}
+
+pub fn histogram_parameters() -> (u8, u8) {
+    HISTOGRAM_PARAMETERS.get().copied().unwrap_or((7, 64))
+}
+
 pub static PERCENTILES: &[(&str, f64)] = &[
     ("p25", 25.0),
     ("p50", 50.0),
@@ -58,6 +73,14 @@ impl MetricsSnapshot {
         self.histograms.percentiles(name)
     }
 
+    pub fn percentiles_at(
+        &self,
+        name: &str,
+        percentiles: &[(String, f64)],
+    ) -> Vec<(String, f64, u64)> {
+        self.histograms.percentiles_at(name, percentiles)
+    }
+
     pub fn counter_rate(&self, name: &str) -> f64 {
         self.counter_delta(name) as f64
             / (self.current.duration_since(self.previous).unwrap()).as_secs_f64()
@@ -149,18 +172,32 @@ impl HistogramsSnapshot {
     }
 
     pub fn percentiles(&self, metric: &str) -> Vec<(String, f64, u64)> {
+        let percentiles: Vec<(String, f64)> = PERCENTILES
+            .iter()
+            .map(|(label, percentile)| (label.to_string(), *percentile))
+            .collect();
+
+        self.percentiles_at(metric, &percentiles)
+    }
+
+    /// The given percentiles of the histogram for the latest window, with
+    /// their labels.
+    pub fn percentiles_at(
+        &self,
+        metric: &str,
+        percentiles: &[(String, f64)],
+    ) -> Vec<(String, f64, u64)> {
         let mut result = Vec::new();
 
-        let percentiles: Vec<f64> = PERCENTILES
+        let values: Vec<f64> = percentiles
             .iter()
             .map(|(_, percentile)| *percentile)
             .collect();
 
         if let Some(snapshot) = self.deltas.get(metric) {
-            if let Ok(Some(percentiles)) = snapshot.percentiles(&percentiles) {
-                for ((label, _), (percentile, bucket)) in PERCENTILES.iter().zip(percentiles.iter())
-                {
-                    result.push((label.to_string(), *percentile, bucket.end()));
+            if let Ok(Some(values)) = snapshot.percentiles(&values) {
+                for ((label, _), (percentile, bucket)) in percentiles.iter().zip(values.iter()) {
+                    result.push((label.clone(), *percentile, bucket.end()));
                 }
             }
         }
@@ -222,6 +259,59 @@ impl CountersSnapshot {
     }
 }
 
+/// The values of every counter and gauge, so that they can be restored after
+/// the workload is sent outside of the test, such as for calibration.
+pub struct ValuesSnapshot {
+    counters: HashMap<String, u64>,
+    gauges: HashMap<String, i64>,
+}
+
+impl Default for ValuesSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ValuesSnapshot {
+    pub fn new() -> Self {
+        let mut counters = HashMap::new();
+        let mut gauges = HashMap::new();
+
+        for metric in metriken::metrics().iter() {
+            let any = if let Some(any) = metric.as_any() {
+                any
+            } else {
+                continue;
+            };
+
+            if let Some(counter) = any.downcast_ref::<metriken::Counter>() {
+                counters.insert(metric.name().to_string(), counter.value());
+            } else if let Some(gauge) = any.downcast_ref::<metriken::Gauge>() {
+                gauges.insert(metric.name().to_string(), gauge.value());
+            }
+        }
+
+        Self { counters, gauges }
+    }
+
+    /// Sets each counter and gauge back to its value in the snapshot.
+    pub fn restore(&self) {
+        for metric in metriken::metrics().iter() {
+            let any = if let Some(any) = metric.as_any() {
+                any
+            } else {
+                continue;
+            };
+
+            if let Some(counter) = any.downcast_ref::<metriken::Counter>() {
+                counter.set(self.counters.get(metric.name()).copied().unwrap_or(0));
+            } else if let Some(gauge) = any.downcast_ref::<metriken::Gauge>() {
+                gauge.set(self.gauges.get(metric.name()).copied().unwrap_or(0));
+            }
+        }
+    }
+}
+
 #[macro_export]
 #[rustfmt::skip]
 macro_rules! counter {
@@ -287,11 +377,12 @@ macro_rules! histogram {
             name = $name,
             crate = metriken
         )]
-        pub static $ident: metriken::AtomicHistogram = metriken::AtomicHistogram::new(
-            7,
-            64,
-        );
+        pub static $ident: Lazy<metriken::AtomicHistogram> = metriken::Lazy::new(|| {
+            let (grouping_power, max_value_power) = $crate::metrics::histogram_parameters();
+            metriken::AtomicHistogram::new(grouping_power, max_value_power)
+        });
         paste! {
+            #[allow(dead_code)]
             pub static [<$ident _HISTOGRAM>]: &'static str = $name;
         }
     };
@@ -301,10 +392,10 @@ macro_rules! histogram {
             description = $description,
             crate = metriken
         )]
-        pub static $ident: metriken::AtomicHistogram = metriken::AtomicHistogram::new(
-            7,
-            64,
-        );
+        pub static $ident: Lazy<metriken::AtomicHistogram> = metriken::Lazy::new(|| {
+            let (grouping_power, max_value_power) = $crate::metrics::histogram_parameters();
+            metriken::AtomicHistogram::new(grouping_power, max_value_power)
+        });
         paste! {
             #[allow(dead_code)]
             pub static [<$ident _HISTOGRAM>]: &'static str = $name;
@@ -382,6 +473,77 @@ histogram!(
     "distribution of response latencies in nanoseconds."
 );
 
+histogram!(
+    RESPONSE_LATENCY_CORRECTED,
+    "response_latency_corrected",
+    "distribution of response latencies from the intended send time in nanoseconds."
+);
+
+// Per-command response latencies. These share a common prefix so that they can
+// be exposed as a single metric with a command label.
+pub static COMMAND_LATENCY_PREFIX: &str = "command_latency/";
+
+histogram!(PING_LATENCY, "command_latency/ping");
+histogram!(ADD_LATENCY, "command_latency/add");
+histogram!(GET_LATENCY, "command_latency/get");
+histogram!(DELETE_LATENCY, "command_latency/delete");
+histogram!(MULTI_GET_LATENCY, "command_latency/multi_get");
+histogram!(MULTI_SET_LATENCY, "command_latency/multi_set");
+histogram!(REPLACE_LATENCY, "command_latency/replace");
+histogram!(SET_LATENCY, "command_latency/set");
+histogram!(CAS_LATENCY, "command_latency/cas");
+histogram!(SCAN_LATENCY, "command_latency/scan");
+histogram!(SCAN_CALL_LATENCY, "scan/call_latency");
+histogram!(HASH_EXISTS_LATENCY, "command_latency/hash_exists");
+histogram!(HASH_DELETE_LATENCY, "command_latency/hash_delete");
+histogram!(HASH_GET_LATENCY, "command_latency/hash_get");
+histogram!(HASH_GET_ALL_LATENCY, "command_latency/hash_get_all");
+histogram!(HASH_INCR_LATENCY, "command_latency/hash_incr");
+histogram!(HASH_SET_LATENCY, "command_latency/hash_set");
+histogram!(LIST_FETCH_LATENCY, "command_latency/list_fetch");
+histogram!(LIST_LENGTH_LATENCY, "command_latency/list_length");
+histogram!(LIST_POP_BACK_LATENCY, "command_latency/list_pop_back");
+histogram!(LIST_POP_FRONT_LATENCY, "command_latency/list_pop_front");
+histogram!(LIST_PUSH_BACK_LATENCY, "command_latency/list_push_back");
+histogram!(LIST_PUSH_FRONT_LATENCY, "command_latency/list_push_front");
+histogram!(LIST_RANGE_LATENCY, "command_latency/list_range");
+histogram!(LIST_REMOVE_LATENCY, "command_latency/list_remove");
+histogram!(LIST_STORE_LATENCY, "command_latency/list_store");
+histogram!(SET_ADD_LATENCY, "command_latency/set_add");
+histogram!(SET_MEMBERS_LATENCY, "command_latency/set_members");
+histogram!(SET_REMOVE_LATENCY, "command_latency/set_remove");
+histogram!(SORTED_SET_ADD_LATENCY, "command_latency/sorted_set_add");
+histogram!(SORTED_SET_INCR_LATENCY, "command_latency/sorted_set_incr");
+histogram!(SORTED_SET_RANGE_LATENCY, "command_latency/sorted_set_range");
+histogram!(SORTED_SET_RANK_LATENCY, "command_latency/sorted_set_rank");
+histogram!(
+    SORTED_SET_REMOVE_LATENCY,
+    "command_latency/sorted_set_remove"
+);
+histogram!(SORTED_SET_SCORE_LATENCY, "command_latency/sorted_set_score");
+
+// The response latencies of each value size class, which are registered once
+// the keyspaces are created.
+pub static VALUE_CLASS_LATENCY_PREFIX: &str = "value_class_latency/";
+
+histogram!(
+    REQUEST_VALUE_SIZE,
+    "request_value_size",
+    "distribution of generated value sizes in bytes."
+);
+
+histogram!(
+    RESPONSE_SIZE,
+    "response_size",
+    "distribution of received response sizes in bytes."
+);
+
+histogram!(
+    PIPELINE_DEPTH,
+    "pipeline_depth",
+    "distribution of the number of requests sent together in a pipeline."
+);
+
 histogram!(
     SESSION_LIFECYCLE_REQUESTS,
     "session_lifecycle_requests",
@@ -393,11 +555,48 @@ histogram!(PUBSUB_LATENCY, "pubsub_latency");
 histogram!(PUBSUB_PUBLISH_LATENCY, "pubsub_publish_latency");
 
 gauge!(RATELIMIT_CURR, "ratelimit/current");
+gauge!(RATELIMIT_DISCOVERED, "ratelimit/discovered");
 counter!(RATELIMIT_DROPPED, "ratelimit/dropped");
+counter!(
+    RATELIMIT_CONNECTION_DELAYED,
+    "ratelimit/connection/delayed",
+    "requests which were delayed by the per-connection ratelimit"
+);
 
 gauge!(CONNECT_CURR, "client/connections/current");
+gauge!(CONNECT_TARGET, "client/connections/target");
 counter!(CONNECT_OK, "client/connect/ok");
+counter!(
+    RESOLVE_CHANGED,
+    "client/resolve/changed",
+    "the number of times the addresses for an endpoint changed"
+);
 counter!(CONNECT_TIMEOUT, "client/connect/timeout");
+counter!(
+    TLS_HANDSHAKE_FULL,
+    "client/connect/tls/full",
+    "tls connections which performed a full handshake"
+);
+counter!(
+    TLS_HANDSHAKE_RESUMED,
+    "client/connect/tls/resumed",
+    "tls connections which resumed an earlier session"
+);
+counter!(
+    CONNECT_EXPIRED,
+    "client/connect/expired",
+    "connections which were closed at the end of their lifetime"
+);
+histogram!(
+    CONNECT_LATENCY,
+    "client/connect/latency",
+    "distribution of the time to open a connection in nanoseconds."
+);
+counter!(
+    CONNECT_BACKOFF,
+    "client/connect/backoff",
+    "connection attempts which were delayed by the reconnect backoff"
+);
 
 counter!(REQUEST, "client/request/total", "total requests dequeued");
 counter!(
@@ -405,6 +604,12 @@ counter!(
     "client/request/dropped",
     "number of requests dropped due to a full work queue"
 );
+counter!(
+    REQUEST_BLOCKED,
+    "client/request/blocked",
+    "number of requests which waited for room in a full work queue"
+);
+gauge!(CLIENT_QUEUE_DEPTH, "client/queue/depth");
 counter!(
     REQUEST_OK,
     "client/request/ok",
@@ -415,6 +620,11 @@ counter!(
     "client/connect/reconnect",
     "requests to reconnect"
 );
+counter!(
+    REQUEST_RETRY,
+    "client/request/retry",
+    "requests which were sent again after they failed"
+);
 counter!(
     REQUEST_UNSUPPORTED,
     "client/request/unsupported",
@@ -475,14 +685,47 @@ counter!(
     "client/response/timeout",
     "responses not received due to timeout"
 );
+// Failed requests are also counted by the category of the error
+counter!(
+    ERROR_CONNECTION,
+    "client/error/connection",
+    "connection attempts which failed and requests which failed due to the connection"
+);
+counter!(
+    ERROR_PROTOCOL,
+    "client/error/protocol",
+    "responses which could not be parsed or were unexpected for the request"
+);
+counter!(
+    ERROR_SERVER,
+    "client/error/server",
+    "error replies from the server"
+);
+counter!(
+    ERROR_TIMEOUT,
+    "client/error/timeout",
+    "requests which did not receive a response in time"
+);
+
 counter!(
     RESPONSE_INVALID,
     "client/response/invalid",
     "responses that were invalid for the protocol"
 );
 
+counter!(HTTP_RESPONSE_1XX, "client/response/http/1xx");
+counter!(HTTP_RESPONSE_2XX, "client/response/http/2xx");
+counter!(HTTP_RESPONSE_3XX, "client/response/http/3xx");
+counter!(HTTP_RESPONSE_4XX, "client/response/http/4xx");
+counter!(HTTP_RESPONSE_5XX, "client/response/http/5xx");
+
 counter!(RESPONSE_HIT, "client/response/hit");
 counter!(RESPONSE_MISS, "client/response/miss");
+counter!(
+    RESPONSE_BYTES,
+    "client/response/bytes",
+    "the total size of the responses which were received, in bytes"
+);
 
 // augment the add stats
 counter!(
@@ -493,12 +736,29 @@ counter!(
 
 // augment the get stats
 counter!(GET_OK, "get/ok", "get requests that were successful");
+counter!(
+    GET_VERIFIED,
+    "get/verified",
+    "get responses with a value which matched the written value"
+);
+counter!(
+    GET_MISMATCH,
+    "get/mismatch",
+    "get responses with a value which did not match the written value"
+);
 counter!(
     GET_TIMEOUT,
     "get/timeout",
     "get requests that resulted in timeout"
 );
 
+// multi-key get and set
+request!(MULTI_GET, "multi_get");
+counter!(MULTI_GET_KEY_HIT, "multi_get/key_hit");
+counter!(MULTI_GET_KEY_MISS, "multi_get/key_miss");
+
+request!(MULTI_SET, "multi_set");
+
 // augment the replace stats
 counter!(
     REPLACE_TIMEOUT,
@@ -525,6 +785,36 @@ counter!(
     "delete requests that resulted in timeout"
 );
 
+// compare-and-swap
+request!(CAS, "cas");
+counter!(
+    CAS_STORED,
+    "cas/stored",
+    "cas requests which stored the value"
+);
+counter!(
+    CAS_EXISTS,
+    "cas/exists",
+    "cas requests which failed due to a cas value mismatch"
+);
+counter!(
+    CAS_NOT_FOUND,
+    "cas/not_found",
+    "cas requests which failed as the key was not found"
+);
+
+request!(SCAN, "scan");
+counter!(
+    SCAN_CALLS,
+    "scan/calls",
+    "the number of SCAN calls made by the scan requests"
+);
+counter!(
+    SCAN_KEYS,
+    "scan/keys",
+    "the number of keys returned by scans"
+);
+
 request!(HASH_GET, "hash_get");
 counter!(HASH_GET_FIELD_HIT, "hash_get/field_hit");
 counter!(HASH_GET_FIELD_MISS, "hash_get/field_miss");
@@ -544,6 +834,25 @@ counter!(SESSION_CLOSED_SERVER, "client/session/server_closed");
  * PING
  */
 request!(PING, "ping");
+counter!(
+    PING_MISMATCH,
+    "ping/mismatch",
+    "ping responses which did not match the expected response"
+);
+
+/*
+ * THRIFT
+ */
+counter!(
+    THRIFT_EXCEPTION,
+    "thrift/exception",
+    "thrift replies with an exception declared by the method"
+);
+counter!(
+    THRIFT_APPLICATION_EXCEPTION,
+    "thrift/application_exception",
+    "thrift replies with an application exception, such as for an unknown method"
+);
 
 /*
  * HASHES (DICTIONARIES)
@@ -625,3 +934,73 @@ counter!(PUBSUB_RECEIVE_CLOSED, "subscriber/receive/closed");
 counter!(PUBSUB_RECEIVE_CORRUPT, "subscriber/receive/corrupt");
 counter!(PUBSUB_RECEIVE_INVALID, "subscriber/receive/invalid");
 counter!(PUBSUB_RECEIVE_OK, "subscriber/receive/ok");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(values: impl Iterator<Item = u64>) -> HistogramsSnapshot {
+        let mut histogram = histogram::Histogram::new(7, 64).unwrap();
+
+        for value in values {
+            histogram.increment(value).unwrap();
+        }
+
+        let mut deltas = HashMap::new();
+        deltas.insert("latency".to_string(), histogram);
+
+        HistogramsSnapshot {
+            previous: HashMap::new(),
+            deltas,
+        }
+    }
+
+    #[test]
+    fn percentiles_at() {
+        let snapshot = snapshot((1..=1000).map(|value| value * 1_000));
+
+        let percentiles = vec![("p50".to_string(), 50.0), ("max".to_string(), 100.0)];
+        let result = snapshot.percentiles_at("latency", &percentiles);
+
+        assert_eq!(result.len(), 2);
+
+        // the values are the upper edge of the buckets, which are within 1%
+        // with a grouping power of 7
+        assert_eq!((result[0].0.as_str(), result[0].1), ("p50", 50.0));
+        assert!((500_000..=505_000).contains(&result[0].2));
+        assert_eq!((result[1].0.as_str(), result[1].1), ("max", 100.0));
+        assert!((1_000_000..=1_010_000).contains(&result[1].2));
+    }
+
+    #[test]
+    fn percentiles_default() {
+        let snapshot = snapshot(1..=100);
+
+        let labels: Vec<String> = snapshot
+            .percentiles("latency")
+            .into_iter()
+            .map(|(label, _, _)| label)
+            .collect();
+
+        assert_eq!(
+            labels,
+            PERCENTILES
+                .iter()
+                .map(|(label, _)| label.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn percentiles_missing() {
+        let snapshot = snapshot(std::iter::empty());
+
+        assert!(snapshot
+            .percentiles_at("other", &[("p50".to_string(), 50.0)])
+            .is_empty());
+        // an empty histogram has no percentiles
+        assert!(snapshot
+            .percentiles_at("latency", &[("p50".to_string(), 50.0)])
+            .is_empty());
+    }
+}
@@ -1,10 +1,26 @@
-use crate::error;
+use crate::config::TlsVersion;
 use crate::Config;
+use crate::{debug, error};
 
 use std::io::Result;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+use std::collections::{HashMap, VecDeque};
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+use std::sync::{Arc, Mutex, OnceLock};
 
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
+// the delay before starting the next connection attempt when racing the
+// addresses for a host, as recommended by RFC 8305
+const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+// the number of tls sessions which are kept for resumption with each endpoint
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+const SESSIONS_PER_ENDPOINT: usize = 4;
+
 pub enum SslProvider {
     #[cfg(feature = "boringssl")]
     Boringssl,
@@ -35,37 +51,91 @@ impl Default for SslProvider {
 
 pub struct Connector {
     inner: ConnectorImpl,
+    socket: SocketOptions,
 }
 
 impl Connector {
     pub fn new(config: &Config) -> Result<Self> {
-        if config.tls().is_none() {
-            Self::plaintext()
+        let mut connector = if config.tls().is_none() {
+            Self::plaintext()?
         } else {
             match SslProvider::default() {
                 #[cfg(feature = "boringssl")]
-                SslProvider::Boringssl => Self::boringssl(config),
+                SslProvider::Boringssl => Self::boringssl(config)?,
                 #[cfg(feature = "openssl")]
-                SslProvider::Openssl => Self::openssl(config),
+                SslProvider::Openssl => Self::openssl(config)?,
                 SslProvider::Unknown => {
                     error!("no TLS/SSL provider could be found. Check that rpc-perf was built with either boringssl or openssl support");
                     std::process::exit(1);
                 }
             }
-        }
+        };
+
+        connector.socket = SocketOptions::new(config);
+
+        Ok(connector)
     }
 
-    fn plaintext() -> Result<Self> {
+    pub fn plaintext() -> Result<Self> {
         Ok(Connector {
             inner: ConnectorImpl::Tcp,
+            socket: SocketOptions::default(),
         })
     }
 
+    /// A connector which verifies servers against the default trust store,
+    /// for transfers which are not configured by the `tls` section.
+    #[cfg(feature = "remote")]
+    #[allow(unreachable_code)]
+    pub fn https() -> Result<Self> {
+        #[cfg(feature = "boringssl")]
+        {
+            let mut ssl_connector =
+                boring::ssl::SslConnector::builder(boring::ssl::SslMethod::tls_client())?;
+            ssl_connector.set_default_verify_paths()?;
+
+            return Ok(Connector {
+                inner: ConnectorImpl::BoringsslTlsTcp(BoringsslTlsTcp {
+                    inner: ssl_connector.build(),
+                    verify_hostname: true,
+                    use_sni: true,
+                    server_name: None,
+                    sessions: None,
+                }),
+                socket: SocketOptions::default(),
+            });
+        }
+
+        #[cfg(feature = "openssl")]
+        {
+            let mut ssl_connector =
+                openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls_client())?;
+            ssl_connector.set_default_verify_paths()?;
+
+            return Ok(Connector {
+                inner: ConnectorImpl::OpensslTlsTcp(OpensslTlsTcp {
+                    inner: ssl_connector.build(),
+                    verify_hostname: true,
+                    use_sni: true,
+                    server_name: None,
+                    sessions: None,
+                }),
+                socket: SocketOptions::default(),
+            });
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "https requires rpc-perf to be built with either boringssl or openssl support",
+        ))
+    }
+
     #[cfg(feature = "boringssl")]
     fn boringssl(config: &Config) -> Result<Self> {
         if config.tls().is_none() {
             return Ok(Connector {
                 inner: ConnectorImpl::Tcp,
+                socket: SocketOptions::default(),
             });
         }
 
@@ -82,10 +152,42 @@ impl Connector {
             ssl_connector.set_ca_file(ca_file)?;
         }
 
+        if tls_config.insecure_skip_verify() {
+            ssl_connector.set_verify(boring::ssl::SslVerifyMode::NONE);
+        }
+
+        let version = |version| match version {
+            TlsVersion::Tls1_0 => boring::ssl::SslVersion::TLS1,
+            TlsVersion::Tls1_1 => boring::ssl::SslVersion::TLS1_1,
+            TlsVersion::Tls1_2 => boring::ssl::SslVersion::TLS1_2,
+            TlsVersion::Tls1_3 => boring::ssl::SslVersion::TLS1_3,
+        };
+
+        ssl_connector.set_min_proto_version(tls_config.min_version().map(version))?;
+        ssl_connector.set_max_proto_version(tls_config.max_version().map(version))?;
+
+        if let Some(cipher_list) = tls_config.cipher_list() {
+            ssl_connector.set_cipher_list(&cipher_list)?;
+        }
+
+        if tls_config.ciphersuites().is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "tls 1.3 cipher suites are not configurable with boringssl",
+            ));
+        }
+
         // mTLS configuration
         if private_key.is_some() && (certificate.is_some() || certificate_chain.is_some()) {
-            ssl_connector
-                .set_private_key_file(private_key.unwrap(), boring::ssl::SslFiletype::PEM)?;
+            if let Some(password) = tls_config.private_key_password() {
+                let pem = std::fs::read(private_key.unwrap())?;
+                let key =
+                    boring::pkey::PKey::private_key_from_pem_passphrase(&pem, password.as_bytes())?;
+                ssl_connector.set_private_key(&key)?;
+            } else {
+                ssl_connector
+                    .set_private_key_file(private_key.unwrap(), boring::ssl::SslFiletype::PEM)?;
+            }
 
             match (certificate, certificate_chain) {
                 (Some(cert), Some(chain)) => {
@@ -109,8 +211,36 @@ impl Connector {
                 }
                 (None, None) => unreachable!(),
             }
+
+            // catch a key which does not belong to the certificate now, rather
+            // than as a failed handshake with every connection
+            if ssl_connector.check_private_key().is_err() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "the tls private key does not match the certificate",
+                ));
+            }
         }
 
+        // the sessions are captured as the server issues them, which is after
+        // the handshake with tls 1.3
+        let sessions = if tls_config.session_resumption() {
+            let sessions = Arc::new(SessionCache::new());
+            let cache = sessions.clone();
+            let index = boringssl_endpoint_index()?;
+
+            ssl_connector.set_session_cache_mode(boring::ssl::SslSessionCacheMode::CLIENT);
+            ssl_connector.set_new_session_callback(move |ssl, session| {
+                if let Some(endpoint) = ssl.ex_data(index) {
+                    cache.insert(endpoint, session);
+                }
+            });
+
+            Some(sessions)
+        } else {
+            None
+        };
+
         let ssl_connector = ssl_connector.build();
 
         Ok(Connector {
@@ -118,7 +248,10 @@ impl Connector {
                 inner: ssl_connector,
                 verify_hostname: tls_config.verify_hostname(),
                 use_sni: tls_config.use_sni(),
+                server_name: tls_config.server_name().map(|name| name.to_owned()),
+                sessions,
             }),
+            socket: SocketOptions::default(),
         })
     }
 
@@ -127,6 +260,7 @@ impl Connector {
         if config.tls().is_none() {
             return Ok(Connector {
                 inner: ConnectorImpl::Tcp,
+                socket: SocketOptions::default(),
             });
         }
 
@@ -143,10 +277,41 @@ impl Connector {
             ssl_connector.set_ca_file(ca_file)?;
         }
 
+        if tls_config.insecure_skip_verify() {
+            ssl_connector.set_verify(openssl::ssl::SslVerifyMode::NONE);
+        }
+
+        let version = |version| match version {
+            TlsVersion::Tls1_0 => openssl::ssl::SslVersion::TLS1,
+            TlsVersion::Tls1_1 => openssl::ssl::SslVersion::TLS1_1,
+            TlsVersion::Tls1_2 => openssl::ssl::SslVersion::TLS1_2,
+            TlsVersion::Tls1_3 => openssl::ssl::SslVersion::TLS1_3,
+        };
+
+        ssl_connector.set_min_proto_version(tls_config.min_version().map(version))?;
+        ssl_connector.set_max_proto_version(tls_config.max_version().map(version))?;
+
+        if let Some(cipher_list) = tls_config.cipher_list() {
+            ssl_connector.set_cipher_list(&cipher_list)?;
+        }
+
+        if let Some(ciphersuites) = tls_config.ciphersuites() {
+            ssl_connector.set_ciphersuites(&ciphersuites)?;
+        }
+
         // mTLS configuration
         if private_key.is_some() && (certificate.is_some() || certificate_chain.is_some()) {
-            ssl_connector
-                .set_private_key_file(private_key.unwrap(), openssl::ssl::SslFiletype::PEM)?;
+            if let Some(password) = tls_config.private_key_password() {
+                let pem = std::fs::read(private_key.unwrap())?;
+                let key = openssl::pkey::PKey::private_key_from_pem_passphrase(
+                    &pem,
+                    password.as_bytes(),
+                )?;
+                ssl_connector.set_private_key(&key)?;
+            } else {
+                ssl_connector
+                    .set_private_key_file(private_key.unwrap(), openssl::ssl::SslFiletype::PEM)?;
+            }
 
             match (certificate, certificate_chain) {
                 (Some(cert), Some(chain)) => {
@@ -170,8 +335,36 @@ impl Connector {
                 }
                 (None, None) => unreachable!(),
             }
+
+            // catch a key which does not belong to the certificate now, rather
+            // than as a failed handshake with every connection
+            if ssl_connector.check_private_key().is_err() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "the tls private key does not match the certificate",
+                ));
+            }
         }
 
+        // the sessions are captured as the server issues them, which is after
+        // the handshake with tls 1.3
+        let sessions = if tls_config.session_resumption() {
+            let sessions = Arc::new(SessionCache::new());
+            let cache = sessions.clone();
+            let index = openssl_endpoint_index()?;
+
+            ssl_connector.set_session_cache_mode(openssl::ssl::SslSessionCacheMode::CLIENT);
+            ssl_connector.set_new_session_callback(move |ssl, session| {
+                if let Some(endpoint) = ssl.ex_data(index) {
+                    cache.insert(endpoint, session);
+                }
+            });
+
+            Some(sessions)
+        } else {
+            None
+        };
+
         let ssl_connector = ssl_connector.build();
 
         Ok(Connector {
@@ -179,37 +372,70 @@ impl Connector {
                 inner: ssl_connector,
                 verify_hostname: tls_config.verify_hostname(),
                 use_sni: tls_config.use_sni(),
+                server_name: tls_config.server_name().map(|name| name.to_owned()),
+                sessions,
             }),
+            socket: SocketOptions::default(),
         })
     }
 
     pub async fn connect(&self, addr: &str) -> Result<Stream> {
+        // unix domain sockets are always plaintext, config validation ensures
+        // that they are not used with TLS
+        if let Some(path) = addr.strip_prefix("unix://") {
+            let s = tokio::net::UnixStream::connect(path).await?;
+            return Ok(Stream {
+                inner: StreamImpl::Unix(s),
+            });
+        }
+
         match &self.inner {
             ConnectorImpl::Tcp => {
-                let s = tokio::net::TcpStream::connect(addr).await?;
-                let res = s.set_nodelay(true);
-                if res.is_err() {
-                    eprintln!("Error setting TCP_NODELAY: {:?}", res.err());
-                }
+                let s = tcp_connect(addr, self.socket).await?;
                 Ok(Stream {
                     inner: StreamImpl::Tcp(s),
                 })
             }
             #[cfg(feature = "boringssl")]
             ConnectorImpl::BoringsslTlsTcp(connector) => {
-                let stream = tokio::net::TcpStream::connect(addr).await?;
-                let domain = addr.split(':').next().unwrap().to_owned();
+                let stream = tcp_connect(addr, self.socket).await?;
+                let domain = connector
+                    .server_name
+                    .clone()
+                    .unwrap_or_else(|| host(addr).to_owned());
 
-                let config = connector
+                let mut config = connector
                     .inner
                     .configure()?
                     .verify_hostname(connector.verify_hostname)
                     .use_server_name_indication(connector.use_sni);
 
+                if let Some(sessions) = &connector.sessions {
+                    config.set_ex_data(boringssl_endpoint_index()?, addr.to_owned());
+
+                    if let Some(session) = sessions.take(addr) {
+                        // safety: the session was issued to a connection
+                        // which was made with the same context
+                        unsafe { config.set_session(&session)? };
+                    }
+                }
+
                 match tokio_boring::connect(config, &domain, stream).await {
-                    Ok(stream) => Ok(Stream {
-                        inner: StreamImpl::BoringsslTlsTcp(stream),
-                    }),
+                    Ok(stream) => {
+                        record_handshake(stream.ssl().session_reused());
+                        debug!(
+                            "tls connection to {addr}: protocol: {} cipher: {}",
+                            stream.ssl().version_str(),
+                            stream
+                                .ssl()
+                                .current_cipher()
+                                .map(|c| c.name())
+                                .unwrap_or("unknown")
+                        );
+                        Ok(Stream {
+                            inner: StreamImpl::BoringsslTlsTcp(stream),
+                        })
+                    }
                     Err(e) => match e.as_io_error() {
                         Some(e) => Err(std::io::Error::new(e.kind(), e.to_string())),
                         None => Err(std::io::Error::new(
@@ -221,8 +447,11 @@ impl Connector {
             }
             #[cfg(feature = "openssl")]
             ConnectorImpl::OpensslTlsTcp(connector) => {
-                let stream = tokio::net::TcpStream::connect(addr).await?;
-                let domain = addr.split(':').next().unwrap().to_owned();
+                let stream = tcp_connect(addr, self.socket).await?;
+                let domain = connector
+                    .server_name
+                    .clone()
+                    .unwrap_or_else(|| host(addr).to_owned());
 
                 let config = connector
                     .inner
@@ -230,12 +459,35 @@ impl Connector {
                     .verify_hostname(connector.verify_hostname)
                     .use_server_name_indication(connector.use_sni);
 
-                let mut ssl = tokio_openssl::SslStream::new(config.into_ssl(&domain)?, stream)?;
+                let mut ssl = config.into_ssl(&domain)?;
+
+                if let Some(sessions) = &connector.sessions {
+                    ssl.set_ex_data(openssl_endpoint_index()?, addr.to_owned());
+
+                    if let Some(session) = sessions.take(addr) {
+                        // safety: the session was issued to a connection
+                        // which was made with the same context
+                        unsafe { ssl.set_session(&session)? };
+                    }
+                }
+
+                let mut ssl = tokio_openssl::SslStream::new(ssl, stream)?;
 
                 match tokio_openssl::SslStream::connect(std::pin::Pin::new(&mut ssl)).await {
-                    Ok(_) => Ok(Stream {
-                        inner: StreamImpl::OpensslTlsTcp(ssl),
-                    }),
+                    Ok(_) => {
+                        record_handshake(ssl.ssl().session_reused());
+                        debug!(
+                            "tls connection to {addr}: protocol: {} cipher: {}",
+                            ssl.ssl().version_str(),
+                            ssl.ssl()
+                                .current_cipher()
+                                .map(|c| c.name())
+                                .unwrap_or("unknown")
+                        );
+                        Ok(Stream {
+                            inner: StreamImpl::OpensslTlsTcp(ssl),
+                        })
+                    }
                     Err(e) => match e.io_error() {
                         Some(e) => Err(std::io::Error::new(e.kind(), e.to_string())),
                         None => Err(std::io::Error::new(
@@ -249,6 +501,171 @@ impl Connector {
     }
 }
 
+/// The options which are set on each TCP socket before it connects.
+#[derive(Clone, Copy)]
+struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    send_buffer: Option<u32>,
+    recv_buffer: Option<u32>,
+}
+
+// without a client config, such as for the remote transfers, only nodelay is
+// set as it always has been
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            send_buffer: None,
+            recv_buffer: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    fn new(config: &Config) -> Self {
+        match config.client() {
+            Some(client) => Self {
+                nodelay: client.tcp_nodelay(),
+                keepalive: client.tcp_keepalive(),
+                send_buffer: client.socket_send_buffer(),
+                recv_buffer: client.socket_recv_buffer(),
+            },
+            None => Self::default(),
+        }
+    }
+
+    fn apply(&self, socket: &tokio::net::TcpSocket) -> Result<()> {
+        socket.set_nodelay(self.nodelay)?;
+
+        if let Some(keepalive) = self.keepalive {
+            // the keepalive time and the interval between probes are not
+            // exposed by tokio
+            let params = socket2::TcpKeepalive::new()
+                .with_time(keepalive)
+                .with_interval(keepalive);
+
+            socket2::SockRef::from(socket).set_tcp_keepalive(&params)?;
+        }
+
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        // the kernel may adjust the buffer sizes, so we log the values which
+        // took effect
+        debug!(
+            "socket options: nodelay: {} keepalive: {} send buffer: {} recv buffer: {}",
+            socket.nodelay()?,
+            socket.keepalive()?,
+            socket.send_buffer_size()?,
+            socket.recv_buffer_size()?
+        );
+
+        Ok(())
+    }
+}
+
+// connects a new socket to the address with the options applied
+async fn tcp_socket_connect(
+    addr: SocketAddr,
+    options: SocketOptions,
+) -> Result<tokio::net::TcpStream> {
+    let socket = if addr.is_ipv6() {
+        tokio::net::TcpSocket::new_v6()?
+    } else {
+        tokio::net::TcpSocket::new_v4()?
+    };
+
+    options.apply(&socket)?;
+
+    socket.connect(addr).await
+}
+
+/// Connects to a `host:port` address. When the host resolves to several
+/// addresses, the connection attempts are raced using happy eyeballs
+/// (RFC 8305): the addresses alternate between IPv6 and IPv4 and each attempt
+/// starts after a short delay, or as soon as the previous attempt fails. The
+/// first successful connection is used and any others are closed.
+async fn tcp_connect(addr: &str, options: SocketOptions) -> Result<tokio::net::TcpStream> {
+    let addrs = interleave(tokio::net::lookup_host(addr).await?);
+
+    if addrs.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no addresses found for: {addr}"),
+        ));
+    }
+
+    race(addrs, options).await
+}
+
+/// Orders the addresses so that the address families alternate, starting
+/// with IPv6. Within each family the resolved order is kept.
+fn interleave(addrs: impl IntoIterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        addrs.into_iter().partition(|addr| addr.is_ipv6());
+
+    let mut addrs = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => break,
+            (a, b) => addrs.extend(a.into_iter().chain(b)),
+        }
+    }
+
+    addrs
+}
+
+/// Races the connection attempts to the addresses in order, returning the
+/// first connection which is established, or the last error if every attempt
+/// fails. There must be at least one address.
+async fn race(addrs: Vec<SocketAddr>, options: SocketOptions) -> Result<tokio::net::TcpStream> {
+    let mut addrs = addrs.into_iter();
+    let mut attempts = tokio::task::JoinSet::new();
+    let mut error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match addrs.next() {
+                Some(addr) => {
+                    attempts.spawn(tcp_socket_connect(addr, options));
+                }
+                None => {
+                    return Err(error.unwrap());
+                }
+            }
+        }
+
+        tokio::select! {
+            Some(result) = attempts.join_next() => match result {
+                // dropping the remaining attempts aborts them and closes any
+                // connections which they have established
+                Ok(Ok(stream)) => return Ok(stream),
+                Ok(Err(e)) => error = Some(e),
+                Err(e) => error = Some(std::io::Error::new(std::io::ErrorKind::Other, e)),
+            },
+            _ = tokio::time::sleep(HAPPY_EYEBALLS_DELAY), if !addrs.as_slice().is_empty() => {
+                attempts.spawn(tcp_socket_connect(addrs.next().unwrap(), options));
+            }
+        }
+    }
+}
+
+/// Returns the host of a `host:port` address. IPv6 addresses may be enclosed
+/// in brackets, eg: `[::1]:6379`.
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+fn host(addr: &str) -> &str {
+    let host = addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(addr);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
 enum ConnectorImpl {
     Tcp,
     #[cfg(feature = "boringssl")]
@@ -262,6 +679,8 @@ pub struct BoringsslTlsTcp {
     inner: boring::ssl::SslConnector,
     verify_hostname: bool,
     use_sni: bool,
+    server_name: Option<String>,
+    sessions: Option<Arc<SessionCache<boring::ssl::SslSession>>>,
 }
 
 #[cfg(feature = "openssl")]
@@ -269,6 +688,80 @@ pub struct OpensslTlsTcp {
     inner: openssl::ssl::SslConnector,
     verify_hostname: bool,
     use_sni: bool,
+    server_name: Option<String>,
+    sessions: Option<Arc<SessionCache<openssl::ssl::SslSession>>>,
+}
+
+/// The TLS sessions which may be resumed, by endpoint. Only the most recent
+/// sessions are kept for each endpoint, and each session is only offered
+/// once, as TLS 1.3 tickets are intended to be used a single time.
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+struct SessionCache<T> {
+    sessions: Mutex<HashMap<String, VecDeque<T>>>,
+}
+
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+impl<T> SessionCache<T> {
+    fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, endpoint: &str, session: T) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let sessions = sessions.entry(endpoint.to_owned()).or_default();
+
+        if sessions.len() == SESSIONS_PER_ENDPOINT {
+            sessions.pop_front();
+        }
+
+        sessions.push_back(session);
+    }
+
+    // the newest session is the most likely to still be accepted
+    fn take(&self, endpoint: &str) -> Option<T> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get_mut(endpoint)
+            .and_then(|sessions| sessions.pop_back())
+    }
+}
+
+// the endpoint of each connection is attached to it, so that the sessions
+// which the server issues are cached for that endpoint
+#[cfg(feature = "boringssl")]
+fn boringssl_endpoint_index() -> Result<boring::ex_data::Index<boring::ssl::Ssl, String>> {
+    static INDEX: OnceLock<boring::ex_data::Index<boring::ssl::Ssl, String>> = OnceLock::new();
+
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+
+    let index = boring::ssl::Ssl::new_ex_index()?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+#[cfg(feature = "openssl")]
+fn openssl_endpoint_index() -> Result<openssl::ex_data::Index<openssl::ssl::Ssl, String>> {
+    static INDEX: OnceLock<openssl::ex_data::Index<openssl::ssl::Ssl, String>> = OnceLock::new();
+
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+
+    let index = openssl::ssl::Ssl::new_ex_index()?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+#[cfg(any(feature = "boringssl", feature = "openssl"))]
+fn record_handshake(resumed: bool) {
+    if resumed {
+        crate::metrics::TLS_HANDSHAKE_RESUMED.increment();
+    } else {
+        crate::metrics::TLS_HANDSHAKE_FULL.increment();
+    }
 }
 
 pub struct Stream {
@@ -277,6 +770,7 @@ pub struct Stream {
 
 enum StreamImpl {
     Tcp(tokio::net::TcpStream),
+    Unix(tokio::net::UnixStream),
     #[cfg(feature = "boringssl")]
     BoringsslTlsTcp(tokio_boring::SslStream<tokio::net::TcpStream>),
     #[cfg(feature = "openssl")]
@@ -291,6 +785,7 @@ impl AsyncRead for Stream {
     ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_read(cx, buf),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_read(cx, buf),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_read(cx, buf),
             #[cfg(feature = "openssl")]
@@ -307,6 +802,7 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<std::result::Result<usize, std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_write(cx, buf),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_write(cx, buf),
             #[cfg(feature = "openssl")]
@@ -319,6 +815,7 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_flush(cx),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_flush(cx),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_flush(cx),
             #[cfg(feature = "openssl")]
@@ -331,6 +828,7 @@ impl AsyncWrite for Stream {
     ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_shutdown(cx),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_shutdown(cx),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_shutdown(cx),
             #[cfg(feature = "openssl")]
@@ -350,6 +848,10 @@ impl hyper::rt::Read for Stream {
                 let mut buf = ReadBuf::uninit(unsafe { rbc.as_mut() });
                 std::pin::Pin::new(s).poll_read(cx, &mut buf)
             }
+            StreamImpl::Unix(s) => {
+                let mut buf = ReadBuf::uninit(unsafe { rbc.as_mut() });
+                std::pin::Pin::new(s).poll_read(cx, &mut buf)
+            }
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => {
                 let mut buf = ReadBuf::uninit(unsafe { rbc.as_mut() });
@@ -372,6 +874,7 @@ impl hyper::rt::Write for Stream {
     ) -> std::task::Poll<std::result::Result<usize, std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_write(cx, buf),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_write(cx, buf),
             #[cfg(feature = "openssl")]
@@ -385,6 +888,7 @@ impl hyper::rt::Write for Stream {
     ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_flush(cx),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_flush(cx),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_flush(cx),
             #[cfg(feature = "openssl")]
@@ -398,6 +902,7 @@ impl hyper::rt::Write for Stream {
     ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
         match &mut self.inner {
             StreamImpl::Tcp(s) => std::pin::Pin::new(s).poll_shutdown(cx),
+            StreamImpl::Unix(s) => std::pin::Pin::new(s).poll_shutdown(cx),
             #[cfg(feature = "boringssl")]
             StreamImpl::BoringsslTlsTcp(s) => std::pin::Pin::new(s).poll_shutdown(cx),
             #[cfg(feature = "openssl")]
@@ -405,3 +910,144 @@ impl hyper::rt::Write for Stream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn connect_unix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ping.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+        // a ping server which replies to a single ping
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "PING\r\n");
+
+            stream.get_mut().write_all(b"PONG\r\n").await.unwrap();
+        });
+
+        let connector = Connector::plaintext().unwrap();
+        let mut stream = connector
+            .connect(&format!("unix://{}", path.display()))
+            .await
+            .unwrap();
+
+        stream.write_all(b"PING\r\n").await.unwrap();
+
+        let mut response = [0; 6];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"PONG\r\n");
+
+        server.await.unwrap();
+    }
+
+    #[cfg(any(feature = "boringssl", feature = "openssl"))]
+    #[test]
+    fn session_cache() {
+        let cache = SessionCache::new();
+
+        cache.insert("a:443", 1);
+        cache.insert("a:443", 2);
+        cache.insert("b:443", 3);
+
+        // the newest session for the endpoint is taken, and only once
+        assert_eq!(cache.take("a:443"), Some(2));
+        assert_eq!(cache.take("a:443"), Some(1));
+        assert_eq!(cache.take("a:443"), None);
+        assert_eq!(cache.take("b:443"), Some(3));
+        assert_eq!(cache.take("c:443"), None);
+    }
+
+    #[cfg(any(feature = "boringssl", feature = "openssl"))]
+    #[test]
+    fn session_cache_limit() {
+        let cache = SessionCache::new();
+
+        for session in 0..SESSIONS_PER_ENDPOINT + 2 {
+            cache.insert("a:443", session);
+        }
+
+        // the oldest sessions are dropped once the limit is reached
+        let sessions: Vec<usize> = std::iter::from_fn(|| cache.take("a:443")).collect();
+        assert_eq!(sessions.len(), SESSIONS_PER_ENDPOINT);
+        assert_eq!(sessions.first(), Some(&(SESSIONS_PER_ENDPOINT + 1)));
+        assert_eq!(sessions.last(), Some(&2));
+    }
+
+    fn addrs(addrs: &[&str]) -> Vec<SocketAddr> {
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn interleave_families() {
+        // the families alternate starting with IPv6, and the remainder of the
+        // larger family is kept in order at the end
+        assert_eq!(
+            interleave(addrs(&[
+                "10.0.0.1:80",
+                "10.0.0.2:80",
+                "10.0.0.3:80",
+                "[::1]:80",
+                "[::2]:80",
+            ])),
+            addrs(&[
+                "[::1]:80",
+                "10.0.0.1:80",
+                "[::2]:80",
+                "10.0.0.2:80",
+                "10.0.0.3:80",
+            ])
+        );
+
+        assert_eq!(
+            interleave(addrs(&["10.0.0.1:80", "10.0.0.2:80"])),
+            addrs(&["10.0.0.1:80", "10.0.0.2:80"])
+        );
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    // connects to the port of the listener on both loopback addresses, where
+    // only the family of the listener accepts the connection
+    async fn connect_mixed(listener: tokio::net::TcpListener) {
+        let port = listener.local_addr().unwrap().port();
+        let addrs = interleave(addrs(&[
+            &format!("127.0.0.1:{port}"),
+            &format!("[::1]:{port}"),
+        ]));
+
+        let stream = race(addrs, SocketOptions::default()).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        assert_eq!(stream.local_addr().unwrap(), accepted.peer_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn connect_ipv6_refused() {
+        connect_mixed(tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn connect_ipv4_refused() {
+        // not every host has an IPv6 loopback address
+        if let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await {
+            connect_mixed(listener).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn connect_all_refused() {
+        // the port of a closed listener refuses the connection
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert!(race(vec![addr], SocketOptions::default()).await.is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use super::summary::Histogram;
+use histogram::SparseHistogram;
+use serde::Deserialize;
+
+// the percentiles which are compared between the runs
+static COMPARE_PERCENTILES: &[(&str, f64)] =
+    &[("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("p999", 99.9)];
+
+// the summary or snapshot of a run, of which only the histogram is compared
+#[derive(Deserialize)]
+struct Run {
+    histogram: Option<Histogram>,
+}
+
+/// Compares the latency histograms in the summaries or snapshots of two runs
+/// and prints the change at each percentile. Returns true if the candidate is
+/// slower than the baseline at any percentile by more than the threshold,
+/// which is a fraction of the baseline latency.
+///
+/// The runs are only comparable when their histograms have the same
+/// parameters, as the buckets would otherwise differ.
+pub fn compare(baseline: &str, candidate: &str, threshold: f64) -> Result<bool, String> {
+    let before = read(baseline)?;
+    let after = read(candidate)?;
+
+    if (before.grouping_power, before.max_value_power)
+        != (after.grouping_power, after.max_value_power)
+    {
+        return Err(format!(
+            "the histograms have different parameters and cannot be compared: \
+            {baseline} has grouping_power = {} and max_value_power = {}, \
+            {candidate} has grouping_power = {} and max_value_power = {}",
+            before.grouping_power,
+            before.max_value_power,
+            after.grouping_power,
+            after.max_value_power
+        ));
+    }
+
+    let before = latencies(baseline, before)?;
+    let after = latencies(candidate, after)?;
+
+    println!("Baseline: {baseline}");
+    println!("Candidate: {candidate}");
+    println!("Latency (us):");
+
+    let mut regressions = Vec::new();
+
+    for ((label, before), (_, after)) in before.iter().zip(after.iter()) {
+        let change = relative_change(*before, *after);
+
+        if change > threshold {
+            regressions.push(*label);
+        }
+
+        println!(
+            "  {label}: {:.1} -> {:.1} ({:+.1} %)",
+            *before as f64 / 1000.0,
+            *after as f64 / 1000.0,
+            100.0 * change
+        );
+    }
+
+    if regressions.is_empty() {
+        println!(
+            "Verdict: no regression, within {:.1} % at each percentile",
+            100.0 * threshold
+        );
+    } else {
+        println!(
+            "Verdict: regression of more than {:.1} % at: {}",
+            100.0 * threshold,
+            regressions.join(", ")
+        );
+    }
+
+    Ok(!regressions.is_empty())
+}
+
+fn read(path: &str) -> Result<Histogram, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("error reading run: {path}\n{e}"))?;
+
+    let run: Run =
+        serde_json::from_str(&content).map_err(|e| format!("error parsing run: {path}\n{e}"))?;
+
+    run.histogram
+        .ok_or_else(|| format!("the run has no latency histogram: {path}"))
+}
+
+// the latencies in nanoseconds at each of the compared percentiles
+fn latencies(path: &str, histogram: Histogram) -> Result<Vec<(&'static str, u64)>, String> {
+    let config = histogram::Config::new(histogram.grouping_power, histogram.max_value_power)
+        .map_err(|e| format!("invalid histogram parameters: {path}\n{e}"))?;
+
+    let histogram = SparseHistogram::from_parts(config, histogram.index, histogram.count)
+        .map_err(|e| format!("invalid histogram: {path}\n{e}"))?;
+
+    let percentiles: Vec<f64> = COMPARE_PERCENTILES.iter().map(|(_, p)| *p).collect();
+
+    let values = histogram
+        .percentiles(&percentiles)
+        .map_err(|e| format!("invalid histogram: {path}\n{e}"))?
+        .ok_or_else(|| format!("the run has no latencies: {path}"))?;
+
+    Ok(COMPARE_PERCENTILES
+        .iter()
+        .zip(values.iter())
+        .map(|((label, _), (_, bucket))| (*label, bucket.end()))
+        .collect())
+}
+
+// a baseline without any latency can not regress
+fn relative_change(before: u64, after: u64) -> f64 {
+    if before == 0 {
+        return 0.0;
+    }
+
+    (after as f64 - before as f64) / before as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    // writes a run with a histogram of the latencies to a temporary file
+    fn run(grouping_power: u8, latencies: &[u64]) -> NamedTempFile {
+        let mut histogram = histogram::Histogram::new(grouping_power, 64).unwrap();
+
+        for latency in latencies {
+            histogram.increment(*latency).unwrap();
+        }
+
+        let histogram = SparseHistogram::from(&histogram);
+        let histogram = Histogram {
+            grouping_power,
+            max_value_power: 64,
+            index: histogram.index,
+            count: histogram.count,
+        };
+
+        let mut file = NamedTempFile::new().unwrap();
+        serde_json::to_writer(&mut file, &serde_json::json!({ "histogram": histogram })).unwrap();
+        file
+    }
+
+    fn compare_runs(baseline: &NamedTempFile, candidate: &NamedTempFile) -> Result<bool, String> {
+        compare(
+            baseline.path().to_str().unwrap(),
+            candidate.path().to_str().unwrap(),
+            0.1,
+        )
+    }
+
+    #[test]
+    fn relative_changes() {
+        assert_eq!(relative_change(1000, 1500), 0.5);
+        assert_eq!(relative_change(1000, 500), -0.5);
+        assert_eq!(relative_change(1000, 1000), 0.0);
+        assert_eq!(relative_change(0, 1000), 0.0);
+    }
+
+    #[test]
+    fn regression() {
+        let latencies: Vec<u64> = (1..=1000).map(|i| i * 1_000).collect();
+        let slower: Vec<u64> = latencies.iter().map(|l| l * 2).collect();
+
+        let baseline = run(7, &latencies);
+        let candidate = run(7, &slower);
+
+        assert_eq!(compare_runs(&baseline, &candidate), Ok(true));
+        // the candidate being faster is not a regression
+        assert_eq!(compare_runs(&candidate, &baseline), Ok(false));
+        assert_eq!(compare_runs(&baseline, &baseline), Ok(false));
+    }
+
+    #[test]
+    fn different_parameters() {
+        let baseline = run(7, &[1_000]);
+        let candidate = run(4, &[1_000]);
+
+        assert!(compare_runs(&baseline, &candidate).is_err());
+    }
+
+    #[test]
+    fn missing_histogram() {
+        let baseline = run(7, &[1_000]);
+
+        let mut candidate = NamedTempFile::new().unwrap();
+        candidate.write_all(b"{}").unwrap();
+
+        assert!(compare_runs(&baseline, &candidate).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use super::*;
+
+static HEADER: &str = "timestamp,target_rate,achieved_qps,success,error,timeout,p50,p99,p999,error_connection,error_protocol,error_server\n";
+
+/// Appends a row of client stats to the configured CSV file for each reporting
+/// interval. Rates are per second and latencies are in microseconds. Each row
+/// is flushed as it is written, so a partial run still leaves usable data.
+pub async fn csv(config: Config) {
+    let output = match config.general().csv_output() {
+        Some(output) => output,
+        None => return,
+    };
+
+    let mut file = match File::create(&output).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("error creating csv file: {output}\n{e}");
+            return;
+        }
+    };
+
+    if file.write_all(HEADER.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let mut snapshot = MetricsSnapshot::default();
+    let mut interval = tokio::time::interval(config.general().interval());
+
+    // the first tick completes immediately
+    interval.tick().await;
+    snapshot.update();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        // use a timeout here so we always check RUNNING at least once a second
+        if timeout(Duration::from_secs(1), interval.tick())
+            .await
+            .is_err()
+        {
+            continue;
+        }
+
+        snapshot.update();
+
+        let success = snapshot.counter_rate(RESPONSE_OK_COUNTER);
+        let error = snapshot.counter_rate(RESPONSE_EX_COUNTER);
+        let timeouts = snapshot.counter_rate(RESPONSE_TIMEOUT_COUNTER);
+
+        let error_connection = snapshot.counter_rate(ERROR_CONNECTION_COUNTER);
+        let error_protocol = snapshot.counter_rate(ERROR_PROTOCOL_COUNTER);
+        let error_server = snapshot.counter_rate(ERROR_SERVER_COUNTER);
+
+        let mut latencies = [0; 3];
+
+        for (label, _percentile, nanoseconds) in snapshot.percentiles(RESPONSE_LATENCY_HISTOGRAM) {
+            let idx = match label.as_str() {
+                "p50" => 0,
+                "p99" => 1,
+                "p999" => 2,
+                _ => continue,
+            };
+
+            latencies[idx] = nanoseconds / 1000;
+        }
+
+        let row = format!(
+            "{},{},{:.2},{:.2},{:.2},{:.2},{},{},{},{:.2},{:.2},{:.2}\n",
+            Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            RATELIMIT_CURR.value(),
+            success + error + timeouts,
+            success,
+            error,
+            timeouts,
+            latencies[0],
+            latencies[1],
+            latencies[2],
+            error_connection,
+            error_protocol,
+            error_server,
+        );
+
+        if file.write_all(row.as_bytes()).await.is_err() || file.flush().await.is_err() {
+            eprintln!("error writing csv file: {output}");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("stats.csv");
+        let config = config::testing::config("ping", "127.0.0.1:12321", "").replace(
+            "interval = 60",
+            &format!("interval = 1\ncsv_output = {:?}", output.to_str().unwrap()),
+        );
+        tokio::spawn(csv(toml::from_str(&config).unwrap()));
+
+        // the rows are flushed as each interval ends
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let content = loop {
+            let content = std::fs::read_to_string(&output).unwrap_or_default();
+            if content.lines().count() >= 3 {
+                break content;
+            }
+            assert!(std::time::Instant::now() < deadline, "{content}");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        let mut lines = content.lines();
+        let columns = HEADER.trim_end().split(',').count();
+        assert_eq!(columns, 12);
+        assert_eq!(lines.next().unwrap(), HEADER.trim_end());
+
+        for row in lines {
+            let fields: Vec<&str> = row.split(',').collect();
+            assert_eq!(fields.len(), columns, "{row}");
+
+            assert!(chrono::DateTime::parse_from_rfc3339(fields[0]).is_ok());
+            for field in &fields[1..] {
+                assert!(field.parse::<f64>().is_ok(), "{row}");
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use super::*;
+use histogram::SparseHistogram;
+use std::io::Write;
+use std::path::Path;
+
+// the width of the column for each second of the test, in pixels
+const COLUMN_WIDTH: usize = 4;
+
+// the maximum height of the image, adjacent buckets are merged into a single
+// row if they would not fit
+const MAX_HEIGHT: usize = 512;
+
+/// Records the response latency histogram for each second of the test and
+/// renders the histograms as a heatmap PNG once the test ends.
+pub async fn heatmap(config: Config) {
+    let output = match config.general().heatmap_output() {
+        Some(output) => output,
+        None => return,
+    };
+
+    WAIT.fetch_add(1, Ordering::Relaxed);
+
+    let stop = Instant::now() + config.general().duration();
+
+    // like the summary, the heatmap only covers the measurement period
+    tokio::time::sleep(config.general().warmup()).await;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut previous = RESPONSE_LATENCY.load();
+    let mut snapshots = Vec::new();
+
+    while RUNNING.load(Ordering::Relaxed) && Instant::now() < stop {
+        // use a timeout here so we always check RUNNING at least once a second
+        if timeout(Duration::from_secs(1), interval.tick())
+            .await
+            .is_err()
+        {
+            continue;
+        }
+
+        let current = RESPONSE_LATENCY.load();
+
+        // a second without a histogram is an empty column, so that each
+        // column stays aligned with the time it covers
+        let delta = match (&previous, &current) {
+            (Some(p), Some(c)) => c.wrapping_sub(p).ok(),
+            _ => None,
+        };
+
+        snapshots.push(delta.as_ref().map(SparseHistogram::from));
+
+        previous = current;
+    }
+
+    let result = render(&snapshots)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|png| write(&png, &output));
+
+    if let Err(e) = result {
+        eprintln!("error writing heatmap file: {output}\n{e}");
+    }
+
+    WAIT.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Renders one column for each histogram, with latency buckets increasing
+/// from the bottom of the image and the color of each pixel showing the
+/// count for that bucket on a log scale. Missing histograms are rendered as
+/// empty columns.
+fn render(snapshots: &[Option<SparseHistogram>]) -> Result<Vec<u8>, png::EncodingError> {
+    let min = snapshots
+        .iter()
+        .flatten()
+        .filter_map(|h| h.index.first())
+        .min()
+        .copied()
+        .unwrap_or(0);
+    let max = snapshots
+        .iter()
+        .flatten()
+        .filter_map(|h| h.index.last())
+        .max()
+        .copied()
+        .unwrap_or(0);
+
+    let span = max - min + 1;
+    let buckets_per_row = (span + MAX_HEIGHT - 1) / MAX_HEIGHT;
+    let height = (span + buckets_per_row - 1) / buckets_per_row;
+    let width = std::cmp::max(1, snapshots.len()) * COLUMN_WIDTH;
+
+    // sum the counts for each cell, indexed by column and then row
+    let mut cells = vec![0_u64; snapshots.len() * height];
+
+    for (column, histogram) in snapshots.iter().enumerate() {
+        let Some(histogram) = histogram else {
+            continue;
+        };
+
+        for (index, count) in histogram.index.iter().zip(histogram.count.iter()) {
+            let row = (index - min) / buckets_per_row;
+            cells[column * height + row] += count;
+        }
+    }
+
+    let peak = (cells.iter().max().copied().unwrap_or(0) as f64).ln_1p();
+
+    let mut pixels = Vec::with_capacity(height * width * 3);
+
+    for y in 0..height {
+        let row = height - 1 - y;
+
+        for x in 0..width {
+            let count = cells
+                .get((x / COLUMN_WIDTH) * height + row)
+                .copied()
+                .unwrap_or(0);
+
+            let intensity = if peak > 0.0 {
+                (count as f64).ln_1p() / peak
+            } else {
+                0.0
+            };
+
+            pixels.extend_from_slice(&color(intensity));
+        }
+    }
+
+    let mut image = Vec::new();
+
+    let mut encoder = png::Encoder::new(&mut image, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(image)
+}
+
+// maps an intensity in the range 0.0 - 1.0 onto a black-red-yellow-white scale
+fn color(intensity: f64) -> [u8; 3] {
+    let channel = |offset: f64| ((3.0 * intensity - offset).clamp(0.0, 1.0) * 255.0) as u8;
+
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+// the image is written to a temporary file in the same directory and then
+// renamed, so a partial image is never observed
+fn write(png: &[u8], output: &str) -> std::io::Result<()> {
+    let dir = Path::new(output)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("./"));
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+
+    file.write_all(png)?;
+
+    file.persist(output).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use histogram::Histogram;
+
+    fn histogram(values: &[u64]) -> SparseHistogram {
+        let mut histogram = Histogram::new(7, 64).unwrap();
+
+        for value in values {
+            histogram.increment(*value).unwrap();
+        }
+
+        SparseHistogram::from(&histogram)
+    }
+
+    // the width and height from the image header
+    fn dimensions(png: &[u8]) -> (u32, u32) {
+        (
+            u32::from_be_bytes(png[16..20].try_into().unwrap()),
+            u32::from_be_bytes(png[20..24].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn render_png() {
+        let png = render(&[
+            Some(histogram(&[1_000, 2_000, 2_000])),
+            None,
+            Some(histogram(&[1_000_000])),
+        ])
+        .unwrap();
+
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]));
+
+        // the missing second is kept as an empty column
+        let (width, height) = dimensions(&png);
+        assert_eq!(width, 3 * COLUMN_WIDTH as u32);
+        assert!(height > 0 && height <= MAX_HEIGHT as u32);
+    }
+
+    #[test]
+    fn render_empty() {
+        let png = render(&[]).unwrap();
+
+        assert!(!png.is_empty());
+        assert_eq!(dimensions(&png), (COLUMN_WIDTH as u32, 1));
+    }
+}
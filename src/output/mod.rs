@@ -7,6 +7,24 @@ use tokio::fs::File;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::time::{timeout, Instant};
 
+mod compare;
+mod csv;
+#[cfg(feature = "heatmap")]
+mod heatmap;
+mod slo;
+mod snapshot;
+mod statsd;
+mod summary;
+
+pub use compare::compare;
+pub use csv::csv;
+#[cfg(feature = "heatmap")]
+pub use heatmap::heatmap;
+pub use slo::{slo, slo_violated};
+pub use snapshot::snapshots;
+pub use statsd::statsd;
+pub use summary::summary;
+
 #[macro_export]
 macro_rules! output {
     () => {
@@ -28,9 +46,6 @@ pub async fn log(config: Config) {
     tokio::time::sleep(Duration::from_secs(1)).await;
     snapshot.update();
 
-    let client = !config.workload().keyspaces().is_empty();
-    let pubsub = !config.workload().topics().is_empty();
-
     // get an aligned start time
     let start = tokio::time::Instant::now() - Duration::from_nanos(Utc::now().nanosecond() as u64)
         + config.general().interval();
@@ -40,39 +55,224 @@ pub async fn log(config: Config) {
 
     let mut interval = tokio::time::interval_at(start, config.general().interval());
 
+    let warmup = config.general().warmup();
+    let mut measuring = warmup.is_zero();
+
+    if !measuring {
+        output!("Warmup: {}s", warmup.as_secs());
+    }
+
     while RUNNING.load(Ordering::Relaxed) && Instant::now() + config.general().interval() <= stop {
         // use a timeout here so we always check RUNNING at least once a second
-        if timeout(Duration::from_secs(1), interval.tick())
-            .await
-            .is_err()
-        {
-            continue;
-        }
+        let tick = match timeout(Duration::from_secs(1), interval.tick()).await {
+            Ok(tick) => tick,
+            Err(_) => {
+                continue;
+            }
+        };
 
         snapshot.update();
 
+        // windows which begin before the end of the warmup are not reported
+        if !measuring {
+            if tick < start + warmup + config.general().interval() {
+                continue;
+            }
+
+            measuring = true;
+            output!("===== Warmup complete, measurement started =====");
+        }
+
         output!("-----");
         output!("Window: {}", window_id);
 
-        // output the client stats
-        if client {
-            client_stats(&mut snapshot);
-        }
+        window_stats(&config, &mut snapshot);
+        window_event(&config, window_id, &snapshot);
+
+        window_id += 1;
+    }
+
+    // unless the test was ended early, it runs until the stop time and the
+    // remainder is reported as a final window which may be shorter than the
+    // reporting interval
+    while RUNNING.load(Ordering::Relaxed) && Instant::now() < stop {
+        tokio::time::sleep(std::cmp::min(
+            Duration::from_secs(1),
+            stop.saturating_duration_since(Instant::now()),
+        ))
+        .await;
+    }
+
+    snapshot.update();
+
+    let elapsed = snapshot
+        .current
+        .duration_since(snapshot.previous)
+        .unwrap_or_default();
 
-        // output the pubsub stats
-        if pubsub {
-            pubsub_stats(&mut snapshot);
+    // a very short final window would not have meaningful rates
+    if measuring && elapsed >= Duration::from_millis(100) {
+        output!("-----");
+        if elapsed < config.general().interval() {
+            output!(
+                "Window: {} (partial: {:.2}s)",
+                window_id,
+                elapsed.as_secs_f64()
+            );
+        } else {
+            output!("Window: {}", window_id);
         }
 
-        window_id += 1;
+        window_stats(&config, &mut snapshot);
+        window_event(&config, window_id, &snapshot);
+    }
+
+    if config
+        .workload()
+        .keyspaces()
+        .iter()
+        .any(|keyspace| keyspace.verify())
+    {
+        output!("-----");
+        output!(
+            "Verification: Verified: {} Mismatched: {}",
+            GET_VERIFIED.value(),
+            GET_MISMATCH.value()
+        );
+    }
+
+    // the log is no longer flushed once the test has stopped
+    if RUNNING.load(Ordering::Relaxed) && logging::json() {
+        info!(event = "shutdown", reason = "duration"; "ending the test");
     }
 
     RUNNING.store(false, Ordering::Relaxed);
     WAIT.fetch_sub(1, Ordering::Relaxed);
 }
 
+/// Outputs the stats for a window
+fn window_stats(config: &Config, snapshot: &mut MetricsSnapshot) {
+    if config.workload().ratelimit().start().is_some() {
+        output!("Ratelimit: Current: {}", RATELIMIT_CURR.value());
+    }
+
+    // the rate is only discovered once the adaptive ratelimit first backs off
+    if config.workload().ratelimit().adaptive().is_some() && RATELIMIT_DISCOVERED.value() > 0 {
+        output!("Ratelimit: Discovered: {}", RATELIMIT_DISCOVERED.value());
+    }
+
+    if config
+        .client()
+        .and_then(|client| client.per_connection_rate())
+        .is_some()
+    {
+        // requests are only delayed on a connection once it reaches its limit
+        let delayed = snapshot.counter_rate(RATELIMIT_CONNECTION_DELAYED_COUNTER);
+        output!(
+            "Ratelimit: Per-Connection Delayed (/s): {:.2} Binding: {}",
+            delayed,
+            if delayed > 0.0 {
+                "per-connection"
+            } else {
+                "global"
+            }
+        );
+    }
+
+    // requests are dropped or block the generator once the queue is full
+    if config.workload().mode() == Mode::OpenLoop {
+        output!(
+            "Queue: Depth: {} Dropped (/s): {:.2} Blocked (/s): {:.2}",
+            CLIENT_QUEUE_DEPTH.value(),
+            snapshot.counter_rate(REQUEST_DROPPED_COUNTER),
+            snapshot.counter_rate(REQUEST_BLOCKED_COUNTER)
+        );
+    }
+
+    // with several keyspaces, report how the requests were split between them
+    let keyspaces = config.workload().keyspaces();
+    if keyspaces.len() > 1 {
+        let total_weight: usize = keyspaces.iter().map(|keyspace| keyspace.weight()).sum();
+        let names: Vec<String> = (0..keyspaces.len())
+            .map(|id| config.workload().keyspace_name(id))
+            .collect();
+        let total_sent: u64 = names
+            .iter()
+            .map(|name| snapshot.counter_delta(&workload::keyspace_sent_counter(name)))
+            .sum();
+
+        for (keyspace, name) in keyspaces.iter().zip(names.iter()) {
+            let sent = snapshot.counter_delta(&workload::keyspace_sent_counter(name));
+
+            output!(
+                "Keyspace {name}: Sent (/s): {:.2} Dropped (/s): {:.2} Share: {:.1} % (expected: {:.1} %)",
+                snapshot.counter_rate(&workload::keyspace_sent_counter(name)),
+                snapshot.counter_rate(&workload::keyspace_dropped_counter(name)),
+                if total_sent == 0 {
+                    0.0
+                } else {
+                    100.0 * sent as f64 / total_sent as f64
+                },
+                100.0 * keyspace.weight() as f64 / total_weight as f64
+            );
+        }
+    }
+
+    // output the client stats
+    if !config.workload().keyspaces().is_empty() {
+        client_stats(config, snapshot);
+
+        // in a closed loop the throughput is the result of the test, so we
+        // report it directly
+        if config.workload().mode() == config::Mode::ClosedLoop {
+            output!(
+                "Client Throughput (/s): {:.2}",
+                snapshot.counter_rate(RESPONSE_OK_COUNTER)
+            );
+        }
+    }
+
+    // output the pubsub stats
+    if !config.workload().topics().is_empty() {
+        pubsub_stats(config, snapshot);
+    }
+}
+
+/// Logs the client stats for a window as an event, for log pipelines which
+/// ingest the JSON debug log
+fn window_event(config: &Config, window: usize, snapshot: &MetricsSnapshot) {
+    if !logging::json() || config.workload().keyspaces().is_empty() {
+        return;
+    }
+
+    let latency = snapshot.percentiles(RESPONSE_LATENCY_HISTOGRAM);
+    let latency_us = |label: &str| {
+        latency
+            .iter()
+            .find(|(l, _, _)| l == label)
+            .map(|(_, _, value)| *value / 1000)
+            .unwrap_or(0)
+    };
+
+    info!(
+        event = "interval",
+        window = window,
+        request_rate = snapshot.counter_rate(REQUEST_OK_COUNTER),
+        response_rate = snapshot.counter_rate(RESPONSE_OK_COUNTER),
+        response_ex_rate = snapshot.counter_rate(RESPONSE_EX_COUNTER),
+        response_timeout_rate = snapshot.counter_rate(RESPONSE_TIMEOUT_COUNTER),
+        latency_p50_us = latency_us("p50"),
+        latency_p99_us = latency_us("p99"),
+        latency_p999_us = latency_us("p999");
+        "window {window}"
+    );
+}
+
 /// Outputs client stats
-fn client_stats(snapshot: &mut MetricsSnapshot) {
+fn client_stats(config: &Config, snapshot: &mut MetricsSnapshot) {
+    // the percentiles which are reported for each window
+    let percentiles = config.histogram().percentiles();
+
     let connect_ok = snapshot.counter_rate(CONNECT_OK_COUNTER);
     let connect_ex = snapshot.counter_rate(CONNECT_EX_COUNTER);
     let connect_timeout = snapshot.counter_rate(CONNECT_TIMEOUT_COUNTER);
@@ -89,15 +289,27 @@ fn client_stats(snapshot: &mut MetricsSnapshot) {
     let response_hit = snapshot.counter_rate(RESPONSE_HIT_COUNTER);
     let response_miss = snapshot.counter_rate(RESPONSE_MISS_COUNTER);
 
+    let error_connection = snapshot.counter_rate(ERROR_CONNECTION_COUNTER);
+    let error_protocol = snapshot.counter_rate(ERROR_PROTOCOL_COUNTER);
+    let error_server = snapshot.counter_rate(ERROR_SERVER_COUNTER);
+    let error_timeout = snapshot.counter_rate(ERROR_TIMEOUT_COUNTER);
+
     let connect_sr = 100.0 * connect_ok / connect_total;
 
-    let response_latency = snapshot.percentiles(RESPONSE_LATENCY_HISTOGRAM);
+    let response_latency = snapshot.percentiles_at(RESPONSE_LATENCY_HISTOGRAM, &percentiles);
+    let value_size = snapshot.percentiles_at(REQUEST_VALUE_SIZE_HISTOGRAM, &percentiles);
+    let pipeline_depth = snapshot.percentiles_at(PIPELINE_DEPTH_HISTOGRAM, &percentiles);
 
     output!(
         "Client Connection: Open: {} Success Rate: {:.2} %",
         CONNECT_CURR.value(),
         connect_sr
     );
+
+    // the target is only set while the connections are ramped
+    if CONNECT_TARGET.value() > 0 {
+        output!("Client Connection Ramp: Target: {}", CONNECT_TARGET.value());
+    }
     output!(
         "Client Connection Rates (/s): Attempt: {:.2} Opened: {:.2} Errors: {:.2} Timeout: {:.2} Closed: {:.2}",
         connect_total,
@@ -107,6 +319,27 @@ fn client_stats(snapshot: &mut MetricsSnapshot) {
         request_reconnect,
     );
 
+    // the handshakes are only counted for tls connections
+    if config.tls().is_some() {
+        output!(
+            "Client TLS Handshakes (/s): Full: {:.2} Resumed: {:.2}",
+            snapshot.counter_rate(TLS_HANDSHAKE_FULL_COUNTER),
+            snapshot.counter_rate(TLS_HANDSHAKE_RESUMED_COUNTER)
+        );
+    }
+
+    // connections are only closed at the end of a lifetime if one is set
+    if config
+        .client()
+        .and_then(|client| client.connection_lifetime())
+        .is_some()
+    {
+        output!(
+            "Client Connection Lifetime: Expired (/s): {:.2}",
+            snapshot.counter_rate(CONNECT_EXPIRED_COUNTER)
+        );
+    }
+
     let request_sr = 100.0 * request_ok / request_total;
     let request_ur = 100.0 * request_unsupported / request_total;
 
@@ -121,6 +354,13 @@ fn client_stats(snapshot: &mut MetricsSnapshot) {
         request_unsupported,
     );
 
+    if config.client().and_then(|client| client.retry()).is_some() {
+        output!(
+            "Client Request Retry (/s): {:.2}",
+            snapshot.counter_rate(REQUEST_RETRY_COUNTER)
+        );
+    }
+
     let response_total = response_ok + response_ex + response_timeout;
 
     let response_sr = 100.0 * response_ok / response_total;
@@ -139,6 +379,13 @@ fn client_stats(snapshot: &mut MetricsSnapshot) {
         response_ex,
         response_timeout,
     );
+    output!(
+        "Client Errors (/s): Connection: {:.2} Protocol: {:.2} Server: {:.2} Timeout: {:.2}",
+        error_connection,
+        error_protocol,
+        error_server,
+        error_timeout,
+    );
 
     let mut latencies = "Client Response Latency (us):".to_owned();
 
@@ -148,17 +395,87 @@ fn client_stats(snapshot: &mut MetricsSnapshot) {
     }
 
     output!("{latencies}");
+
+    // the connect latency is only recorded by the clients which open their
+    // own connections
+    let connect_latency = snapshot.percentiles_at(CONNECT_LATENCY_HISTOGRAM, &percentiles);
+
+    if !connect_latency.is_empty() {
+        let mut latencies = "Client Connect Latency (us):".to_owned();
+
+        for (label, _percentile, nanoseconds) in connect_latency {
+            let microseconds = nanoseconds / 1000;
+            latencies.push_str(&format!(" {label}: {microseconds}"))
+        }
+
+        output!("{latencies}");
+    }
+
+    if config.workload().corrected_latency() {
+        let mut latencies = "Client Response Latency Corrected (us):".to_owned();
+
+        for (label, _percentile, nanoseconds) in
+            snapshot.percentiles_at(RESPONSE_LATENCY_CORRECTED_HISTOGRAM, &percentiles)
+        {
+            let microseconds = nanoseconds / 1000;
+            latencies.push_str(&format!(" {label}: {microseconds}"))
+        }
+
+        output!("{latencies}");
+    }
+
+    // only workloads which generate values will have value sizes
+    if !value_size.is_empty() {
+        let mut sizes = "Client Request Value Size (B):".to_owned();
+
+        for (label, _percentile, bytes) in value_size {
+            sizes.push_str(&format!(" {label}: {bytes}"))
+        }
+
+        output!("{sizes}");
+    }
+
+    // only the clients which parse the responses themselves record their
+    // sizes
+    let response_size = snapshot.percentiles_at(RESPONSE_SIZE_HISTOGRAM, &percentiles);
+
+    if !response_size.is_empty() {
+        let mut sizes = format!(
+            "Client Response Size (B): Received (B/s): {:.2}",
+            snapshot.counter_rate(RESPONSE_BYTES_COUNTER)
+        );
+
+        for (label, _percentile, bytes) in response_size {
+            sizes.push_str(&format!(" {label}: {bytes}"))
+        }
+
+        output!("{sizes}");
+    }
+
+    // only clients which support pipelining will record the pipeline depth
+    if !pipeline_depth.is_empty() {
+        let mut depths = "Client Pipeline Depth:".to_owned();
+
+        for (label, _percentile, depth) in pipeline_depth {
+            depths.push_str(&format!(" {label}: {depth}"))
+        }
+
+        output!("{depths}");
+    }
 }
 
 /// Output pubsub metrics and return the number of successful publish operations
-fn pubsub_stats(snapshot: &mut MetricsSnapshot) {
+fn pubsub_stats(config: &Config, snapshot: &mut MetricsSnapshot) {
+    let percentiles = config.histogram().percentiles();
+
     // publisher stats
     let pubsub_tx_ex = snapshot.counter_rate(PUBSUB_PUBLISH_EX_COUNTER);
     let pubsub_tx_ok = snapshot.counter_rate(PUBSUB_PUBLISH_OK_COUNTER);
     let pubsub_tx_timeout = snapshot.counter_rate(PUBSUB_PUBLISH_TIMEOUT_COUNTER);
     let pubsub_tx_total = snapshot.counter_rate(PUBSUB_PUBLISH_COUNTER);
 
-    let pubsub_publish_latency = snapshot.percentiles(PUBSUB_PUBLISH_LATENCY_HISTOGRAM);
+    let pubsub_publish_latency =
+        snapshot.percentiles_at(PUBSUB_PUBLISH_LATENCY_HISTOGRAM, &percentiles);
 
     // subscriber stats
     let pubsub_rx_ok = snapshot.counter_rate(PUBSUB_RECEIVE_OK_COUNTER);
@@ -168,8 +485,7 @@ fn pubsub_stats(snapshot: &mut MetricsSnapshot) {
     let pubsub_rx_total = snapshot.counter_rate(PUBSUB_RECEIVE_COUNTER);
 
     // end-to-end stats
-    let pubsub_latency = snapshot.percentiles(PUBSUB_LATENCY_HISTOGRAM);
-    output!("Ratelimit: Current: {}", RATELIMIT_CURR.value());
+    let pubsub_latency = snapshot.percentiles_at(PUBSUB_LATENCY_HISTOGRAM, &percentiles);
     output!("Publishers: Current: {}", PUBSUB_PUBLISHER_CURR.value());
 
     let pubsub_tx_sr = 100.0 * pubsub_tx_ok / pubsub_tx_total;
@@ -254,6 +570,10 @@ pub async fn metrics(config: Config) {
         .metadata("version".to_string(), env!("CARGO_PKG_VERSION").to_string())
         .build();
 
+    // snapshots are not written until the warmup completes, so the first
+    // snapshot in the file is the baseline for the measurement
+    let warmup_end = start + config.general().warmup();
+
     while RUNNING.load(Ordering::Relaxed) && Instant::now() + config.general().interval() <= stop {
         // use a timeout here so we always check RUNNING at least once a second
         if timeout(Duration::from_secs(1), interval.tick())
@@ -263,6 +583,10 @@ pub async fn metrics(config: Config) {
             continue;
         }
 
+        if Instant::now() < warmup_end {
+            continue;
+        }
+
         let snapshot = snapshotter.snapshot();
 
         let buf = match metrics_config.format() {
@@ -306,3 +630,53 @@ pub async fn metrics(config: Config) {
 
     WAIT.fetch_sub(1, Ordering::Relaxed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG_TEST: &str = "RPC_PERF_TEST_LOG";
+
+    #[test]
+    fn windows() {
+        // the log ends the test once it is done, so it is run in a child
+        // process which runs only this test
+        if std::env::var(LOG_TEST).is_ok() {
+            let config = config::testing::config("ping", "127.0.0.1:12321", "")
+                .replace("interval = 60", "interval = 1")
+                .replace("duration = 300", "duration = 2");
+
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(log(toml::from_str(&config).unwrap()));
+            return;
+        }
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["output::tests::windows", "--exact", "--nocapture"])
+            .env(LOG_TEST, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let windows: Vec<(chrono::DateTime<chrono::FixedOffset>, &str)> = stdout
+            .lines()
+            .filter_map(|line| line.split_once(" Window: "))
+            .map(|(time, window)| (chrono::DateTime::parse_from_rfc3339(time).unwrap(), window))
+            .collect();
+
+        // a window is reported at each of the intervals in the duration, and
+        // the remainder is a final window
+        let ids: Vec<&str> = windows
+            .iter()
+            .map(|(_, window)| window.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(ids, ["0", "1", "2"], "{stdout}");
+
+        for pair in windows.windows(2) {
+            let spacing = (pair[1].0 - pair[0].0).num_milliseconds();
+            assert!((800..=1200).contains(&spacing), "{stdout}");
+        }
+    }
+}
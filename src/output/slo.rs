@@ -0,0 +1,188 @@
+use super::summary::Totals;
+use super::*;
+use config::{SloAssertion, SloMetric};
+use std::sync::atomic::AtomicBool;
+
+// set once an assertion fails, so that rpc-perf exits with an error
+static SLO_VIOLATED: AtomicBool = AtomicBool::new(false);
+
+/// Returns true if any of the slo assertions failed.
+pub fn slo_violated() -> bool {
+    SLO_VIOLATED.load(Ordering::Relaxed)
+}
+
+/// Checks the slo assertions against the measurement period once the test
+/// ends, reporting the result of each one.
+pub async fn slo(config: Config) {
+    let assertions = match config.slo() {
+        Some(slo) => slo.assertions(),
+        None => return,
+    };
+
+    WAIT.fetch_add(1, Ordering::Relaxed);
+
+    let stop = Instant::now() + config.general().duration();
+
+    // the assertions only cover the measurement period, so we take a baseline
+    // once the warmup completes. A test which ends during the warmup has no
+    // responses to check
+    let measured = tokio::select! {
+        _ = tokio::time::sleep(config.general().warmup()) => Some(measurement(stop).await),
+        _ = stopped() => None,
+    };
+
+    output!("-----");
+
+    for assertion in assertions {
+        let value = measured.as_ref().and_then(|(latency, errors, responses)| {
+            measure(assertion.metric, latency.as_ref(), *errors, *responses)
+        });
+
+        if !report(&assertion, value) {
+            SLO_VIOLATED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    WAIT.fetch_sub(1, Ordering::Relaxed);
+}
+
+// waits for the test to end
+async fn stopped() {
+    while RUNNING.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+// waits for the measurement period to end, returning the latencies, errors,
+// and responses recorded during it
+async fn measurement(stop: Instant) -> (Option<histogram::Histogram>, u64, u64) {
+    let baseline = Totals::load();
+
+    while RUNNING.load(Ordering::Relaxed) && Instant::now() < stop {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let totals = Totals::load();
+
+    let latency = match (&baseline.latency, &totals.latency) {
+        (Some(previous), Some(current)) => current.wrapping_sub(previous).ok(),
+        _ => None,
+    };
+
+    let errors = (totals.errors - baseline.errors) + (totals.timeouts - baseline.timeouts);
+    let responses = (totals.responses - baseline.responses) + errors;
+
+    (latency, errors, responses)
+}
+
+// the value of the metric over the measurement period, which is `None` if
+// there were no responses
+fn measure(
+    metric: SloMetric,
+    latency: Option<&histogram::Histogram>,
+    errors: u64,
+    responses: u64,
+) -> Option<f64> {
+    match metric {
+        SloMetric::Percentile(percentile) => latency
+            .and_then(|latency| latency.percentiles(&[percentile]).ok().flatten())
+            .and_then(|values| values.first().map(|(_, bucket)| bucket.end() as f64)),
+        SloMetric::ErrorRate if responses > 0 => Some(errors as f64 / responses as f64),
+        SloMetric::ErrorRate => None,
+    }
+}
+
+// reports the result of an assertion, returning true if it held. An assertion
+// without a value, because there were no responses, fails as it cannot be
+// shown to hold
+fn report(assertion: &SloAssertion, value: Option<f64>) -> bool {
+    let value = match value {
+        Some(value) => value,
+        None => {
+            output!("SLO: {} violated: no responses", assertion.text);
+            return false;
+        }
+    };
+
+    let measured = match assertion.metric {
+        SloMetric::Percentile(_) => {
+            humantime::format_duration(Duration::from_nanos(value as u64)).to_string()
+        }
+        SloMetric::ErrorRate => format!("{:.3}%", 100.0 * value),
+    };
+
+    if assertion.operator.check(value, assertion.threshold) {
+        output!("SLO: {} passed: {measured}", assertion.text);
+        true
+    } else {
+        output!("SLO: {} violated: {measured}", assertion.text);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SloOperator;
+
+    fn assertion(metric: SloMetric, operator: SloOperator, threshold: f64) -> SloAssertion {
+        SloAssertion {
+            text: "test".to_owned(),
+            metric,
+            operator,
+            threshold,
+        }
+    }
+
+    #[test]
+    fn report_latency() {
+        // p99 < 5ms
+        let p99 = assertion(SloMetric::Percentile(99.0), SloOperator::Less, 5_000_000.0);
+
+        assert!(report(&p99, Some(4_000_000.0)));
+        assert!(!report(&p99, Some(5_000_000.0)));
+        assert!(!report(&p99, Some(6_000_000.0)));
+    }
+
+    #[test]
+    fn report_error_rate() {
+        // error_rate <= 1%
+        let error_rate = assertion(SloMetric::ErrorRate, SloOperator::LessEqual, 0.01);
+
+        assert!(report(&error_rate, Some(0.0)));
+        assert!(report(&error_rate, Some(0.01)));
+        assert!(!report(&error_rate, Some(0.02)));
+    }
+
+    #[test]
+    fn report_no_responses() {
+        let error_rate = assertion(SloMetric::ErrorRate, SloOperator::GreaterEqual, 0.0);
+
+        assert!(!report(&error_rate, None));
+    }
+
+    #[test]
+    fn measure_error_rate() {
+        assert_eq!(measure(SloMetric::ErrorRate, None, 5, 100), Some(0.05));
+        assert_eq!(measure(SloMetric::ErrorRate, None, 0, 0), None);
+    }
+
+    #[test]
+    fn measure_latency() {
+        let mut latency = histogram::Histogram::new(7, 64).unwrap();
+
+        for value in 1..=100 {
+            latency.increment(value * 1_000).unwrap();
+        }
+
+        let max = measure(SloMetric::Percentile(100.0), Some(&latency), 0, 100).unwrap();
+        let p50 = measure(SloMetric::Percentile(50.0), Some(&latency), 0, 100).unwrap();
+
+        // the value is the upper edge of the bucket, which is within 1% with
+        // a grouping power of 7
+        assert!((100_000.0..=101_000.0).contains(&max));
+        assert!((50_000.0..=50_500.0).contains(&p50));
+
+        assert_eq!(measure(SloMetric::Percentile(99.0), None, 0, 0), None);
+    }
+}
@@ -0,0 +1,138 @@
+use super::summary::{Summary, Totals};
+use super::*;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Writes a snapshot of the test so far each time a `SIGUSR1` is received,
+/// without affecting the results of the test. Each snapshot is written to its
+/// own timestamped file next to the summary output, or in the working
+/// directory if there is no summary output. A snapshot covers the whole test
+/// so far, including the warmup.
+pub async fn snapshots(config: Config) {
+    let mut sigusr1 = match signal(SignalKind::user_defined1()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("failed to install the SIGUSR1 handler: {e}");
+            return;
+        }
+    };
+
+    let baseline = Totals::load();
+    let start = Instant::now();
+
+    while sigusr1.recv().await.is_some() {
+        let totals = Totals::load();
+
+        let snapshot = Summary::new(&config, &baseline, &totals, start.elapsed().as_secs_f64());
+
+        let output = path(config.general().summary_output().as_deref());
+
+        #[cfg(feature = "remote")]
+        let result = if crate::remote::is_remote(&output) {
+            super::summary::upload(&snapshot, &output).await
+        } else {
+            super::summary::write(&snapshot, &output)
+        };
+
+        #[cfg(not(feature = "remote"))]
+        let result = super::summary::write(&snapshot, &output);
+
+        match result {
+            Ok(()) => output!("Snapshot: written to {output}"),
+            Err(e) => eprintln!("error writing snapshot file: {output}\n{e}"),
+        }
+    }
+}
+
+// the timestamp is inserted before the extension of the summary output, so
+// `summary.json` has snapshots such as `summary.20240101T000000000Z.json`
+fn path(summary_output: Option<&str>) -> String {
+    let timestamp = Utc::now().format("%Y%m%dT%H%M%S%3fZ");
+
+    match summary_output {
+        Some(output) => match output.strip_suffix(".json") {
+            Some(stem) => format!("{stem}.{timestamp}.json"),
+            None => format!("{output}.{timestamp}"),
+        },
+        None => format!("rpc-perf-snapshot.{timestamp}.json"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // checks that the path is the prefix and suffix around a timestamp such as
+    // `20240101T000000000Z`
+    fn assert_timestamped(path: &str, prefix: &str, suffix: &str) {
+        let timestamp = path
+            .strip_prefix(prefix)
+            .and_then(|path| path.strip_suffix(suffix))
+            .unwrap_or_else(|| panic!("unexpected snapshot path: {path}"));
+
+        assert_eq!(timestamp.len(), 19, "{timestamp}");
+        assert!(chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%dT%H%M%S%3fZ").is_ok());
+    }
+
+    #[test]
+    fn paths() {
+        assert_timestamped(&path(Some("out/summary.json")), "out/summary.", ".json");
+        assert_timestamped(&path(Some("out/summary")), "out/summary.", "");
+        assert_timestamped(&path(None), "rpc-perf-snapshot.", ".json");
+    }
+
+    // waits for the number of snapshots in the directory, ignoring the
+    // temporary files which they are written to
+    async fn snapshots_in(dir: &std::path::Path, count: usize) -> Vec<std::path::PathBuf> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        loop {
+            let paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect();
+
+            if paths.len() == count {
+                return paths;
+            }
+
+            assert!(Instant::now() < deadline, "expected {count} snapshots");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn sigusr1() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("summary.json");
+        let config: Config = toml::from_str(
+            &config::testing::config("ping", "127.0.0.1:12321", "").replace(
+                "interval = 60",
+                &format!(
+                    "interval = 60\nsummary_output = {:?}",
+                    output.display().to_string()
+                ),
+            ),
+        )
+        .unwrap();
+
+        tokio::spawn(snapshots(config));
+
+        // wait for the handler to be installed, as the default action for the
+        // signal ends the process
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // each signal writes its own snapshot, which is named by the time in
+        // milliseconds
+        assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+        snapshots_in(dir.path(), 1).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+
+        for path in snapshots_in(dir.path(), 2).await {
+            let snapshot: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(snapshot["protocol"], "Ping", "{}", path.display());
+        }
+    }
+}
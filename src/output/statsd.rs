@@ -0,0 +1,183 @@
+use super::*;
+use tokio::net::UdpSocket;
+
+/// Pushes the client metrics to a StatsD collector at each reporting interval.
+/// Counters are sent as the change over the interval and the response latency
+/// percentiles are sent as gauges.
+pub async fn statsd(config: Config) {
+    let statsd = match config.statsd() {
+        Some(statsd) => statsd.clone(),
+        None => return,
+    };
+
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("error binding statsd socket: {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = socket.connect(statsd.endpoint()).await {
+        eprintln!(
+            "error connecting to statsd endpoint: {}\n{e}",
+            statsd.endpoint()
+        );
+        return;
+    }
+
+    let prefix = statsd
+        .prefix()
+        .map(|prefix| format!("{prefix}."))
+        .unwrap_or_default();
+
+    let tags = if statsd.tags().is_empty() {
+        String::new()
+    } else {
+        format!("|#{}", statsd.tags().join(","))
+    };
+
+    let mut snapshot = MetricsSnapshot::default();
+    let mut interval = tokio::time::interval(config.general().interval());
+
+    // the first tick completes immediately
+    interval.tick().await;
+    snapshot.update();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        // use a timeout here so we always check RUNNING at least once a second
+        if timeout(Duration::from_secs(1), interval.tick())
+            .await
+            .is_err()
+        {
+            continue;
+        }
+
+        snapshot.update();
+
+        let mut lines = Vec::new();
+
+        for name in [
+            REQUEST_COUNTER,
+            REQUEST_OK_COUNTER,
+            RESPONSE_OK_COUNTER,
+            RESPONSE_EX_COUNTER,
+            RESPONSE_TIMEOUT_COUNTER,
+            ERROR_CONNECTION_COUNTER,
+            ERROR_PROTOCOL_COUNTER,
+            ERROR_SERVER_COUNTER,
+            ERROR_TIMEOUT_COUNTER,
+        ] {
+            lines.push(format!(
+                "{prefix}{}:{}|c{tags}",
+                name.replace('/', "."),
+                snapshot.counter_delta(name)
+            ));
+        }
+
+        for (label, _percentile, value) in snapshot.percentiles(RESPONSE_LATENCY_HISTOGRAM) {
+            lines.push(format!(
+                "{prefix}{}.{label}:{value}|g{tags}",
+                RESPONSE_LATENCY_HISTOGRAM.replace('/', ".")
+            ));
+        }
+
+        for datagram in batch(&lines, statsd.max_packet_size()) {
+            let _ = socket.send(datagram.as_bytes()).await;
+        }
+    }
+}
+
+// joins the lines into newline separated datagrams which do not exceed the
+// maximum size, unless a single line is larger than the maximum
+fn batch(lines: &[String], max_size: usize) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut current = String::new();
+
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > max_size {
+            datagrams.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn batch_size() {
+        let lines: Vec<String> = (0..10).map(|i| format!("metric.{i}:1|c")).collect();
+
+        // each line is 12 bytes, so 3 lines and their separators fit in 40
+        let datagrams = batch(&lines, 40);
+        assert_eq!(datagrams.len(), 4);
+        assert!(datagrams.iter().all(|datagram| datagram.len() <= 40));
+        assert_eq!(datagrams.join("\n"), lines.join("\n"));
+
+        // a line larger than the maximum is still sent on its own
+        assert_eq!(batch(&lines[..2], 4), lines[..2]);
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let extra = format!(
+            r#"
+            [statsd]
+            endpoint = "{}"
+            prefix = "rpc-perf"
+            tags = ["env:test", "target:ping"]
+            "#,
+            socket.local_addr().unwrap()
+        );
+        let config = config::testing::config("ping", "127.0.0.1:12321", &extra)
+            .replace("interval = 60", "interval = 1");
+        tokio::spawn(statsd(toml::from_str(&config).unwrap()));
+
+        // the metrics are pushed once the first interval ends
+        let mut buffer = vec![0; 65536];
+        let len = timeout(Duration::from_secs(5), socket.recv(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        let datagram = std::str::from_utf8(&buffer[..len]).unwrap();
+
+        let mut metrics = HashMap::new();
+        for line in datagram.lines() {
+            // name:value|type|#tags
+            let (name, rest) = line.split_once(':').unwrap();
+            let fields: Vec<&str> = rest.split('|').collect();
+            assert_eq!(fields.len(), 3, "{line}");
+            assert_eq!(fields[2], "#env:test,target:ping");
+
+            let value: u64 = fields[0].parse().unwrap();
+            metrics.insert(name.to_string(), (value, fields[1].to_string()));
+        }
+
+        for name in [
+            "rpc-perf.client.request.ok",
+            "rpc-perf.client.response.ok",
+            "rpc-perf.client.response.timeout",
+        ] {
+            assert_eq!(metrics[name].1, "c", "{name}");
+        }
+        assert!(metrics
+            .iter()
+            .filter(|(name, _)| name.starts_with("rpc-perf.response_latency."))
+            .all(|(_, (_, kind))| kind == "g"));
+    }
+}
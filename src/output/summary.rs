@@ -0,0 +1,423 @@
+use super::*;
+use histogram::SparseHistogram;
+use metriken::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+// the percentiles which are reported in the summary
+static SUMMARY_PERCENTILES: &[(&str, f64)] = &[
+    ("p50", 50.0),
+    ("p90", 90.0),
+    ("p99", 99.0),
+    ("p999", 99.9),
+    ("max", 100.0),
+];
+
+#[derive(Serialize)]
+pub(super) struct Summary {
+    protocol: String,
+    endpoints: Vec<String>,
+    /// the length of the measurement, excluding the warmup, in seconds
+    duration: f64,
+    warmup: u64,
+    requests: u64,
+    responses: u64,
+    errors: u64,
+    timeouts: u64,
+    /// the number of errors of each category, which includes the errors
+    /// while connecting
+    error_categories: BTreeMap<String, u64>,
+    /// achieved rate of successful responses per second
+    qps: f64,
+    /// response latency percentiles in nanoseconds
+    latency: BTreeMap<String, u64>,
+    /// response latency percentiles in nanoseconds from the intended send
+    /// time, when the corrected latency is recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_corrected: Option<BTreeMap<String, u64>>,
+    /// response latency percentiles in nanoseconds for each command which
+    /// received responses
+    commands: BTreeMap<String, BTreeMap<String, u64>>,
+    /// response latency percentiles in nanoseconds for each value size class
+    /// which received responses
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    value_classes: BTreeMap<String, BTreeMap<String, u64>>,
+    /// the total size of the received responses in bytes, for the protocols
+    /// which record it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_bytes: Option<u64>,
+    /// received response size percentiles in bytes
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    response_size: BTreeMap<String, u64>,
+    /// the highest rate found by the adaptive ratelimit which meets the
+    /// latency target, once it has backed off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discovered_rate: Option<u64>,
+    /// the response latency histogram, which two runs can be compared with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histogram: Option<Histogram>,
+}
+
+/// A sparse histogram, with the index and count of each non-empty bucket.
+/// The buckets are determined by the histogram parameters.
+#[derive(Deserialize, Serialize)]
+pub(super) struct Histogram {
+    pub(super) grouping_power: u8,
+    pub(super) max_value_power: u8,
+    pub(super) index: Vec<usize>,
+    pub(super) count: Vec<u64>,
+}
+
+impl From<SparseHistogram> for Histogram {
+    fn from(other: SparseHistogram) -> Self {
+        let (grouping_power, max_value_power) = histogram_parameters();
+
+        Self {
+            grouping_power,
+            max_value_power,
+            index: other.index,
+            count: other.count,
+        }
+    }
+}
+
+impl Summary {
+    /// Summarizes the period between the two loads of the totals, which is
+    /// `duration` seconds long.
+    pub(super) fn new(config: &Config, baseline: &Totals, totals: &Totals, duration: f64) -> Self {
+        let latency = percentiles(&baseline.latency, &totals.latency);
+        let latency_corrected = config
+            .workload()
+            .corrected_latency()
+            .then(|| percentiles(&baseline.latency_corrected, &totals.latency_corrected));
+
+        let commands = labeled_percentiles(&baseline.commands, &totals.commands);
+        let value_classes = labeled_percentiles(&baseline.value_classes, &totals.value_classes);
+
+        let responses = totals.responses - baseline.responses;
+
+        let response_size = percentiles(&baseline.response_size, &totals.response_size);
+        let response_bytes =
+            (!response_size.is_empty()).then(|| totals.response_bytes - baseline.response_bytes);
+
+        let histogram = match (&baseline.latency, &totals.latency) {
+            (Some(previous), Some(current)) => current
+                .wrapping_sub(previous)
+                .ok()
+                .map(|delta| SparseHistogram::from(&delta).into()),
+            (None, Some(current)) => Some(SparseHistogram::from(current).into()),
+            _ => None,
+        };
+
+        let error_categories = totals
+            .error_categories
+            .iter()
+            .map(|(category, count)| {
+                let previous = baseline.error_categories.get(category).unwrap_or(&0);
+                (category.to_string(), count - previous)
+            })
+            .collect();
+
+        Self {
+            protocol: format!("{:?}", config.general().protocol()),
+            endpoints: config.target().endpoints().to_vec(),
+            duration,
+            warmup: config.general().warmup().as_secs(),
+            requests: totals.requests - baseline.requests,
+            responses,
+            errors: totals.errors - baseline.errors,
+            timeouts: totals.timeouts - baseline.timeouts,
+            error_categories,
+            qps: responses as f64 / duration,
+            latency,
+            latency_corrected,
+            commands,
+            value_classes,
+            response_bytes,
+            response_size,
+            discovered_rate: config
+                .workload()
+                .ratelimit()
+                .adaptive()
+                .and_then(|_| u64::try_from(RATELIMIT_DISCOVERED.value()).ok())
+                .filter(|rate| *rate > 0),
+            histogram,
+        }
+    }
+}
+
+/// Writes a JSON summary of the measurement period to the configured file
+/// once the test ends.
+pub async fn summary(config: Config) {
+    let output = match config.general().summary_output() {
+        Some(output) => output,
+        None => return,
+    };
+
+    WAIT.fetch_add(1, Ordering::Relaxed);
+
+    let stop = Instant::now() + config.general().duration();
+
+    // the summary only covers the measurement period, so we take a baseline
+    // once the warmup completes
+    tokio::time::sleep(config.general().warmup()).await;
+
+    let baseline = Totals::load();
+    let start = Instant::now();
+
+    while RUNNING.load(Ordering::Relaxed) && Instant::now() < stop {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let duration = start.elapsed().as_secs_f64();
+    let totals = Totals::load();
+
+    let summary = Summary::new(&config, &baseline, &totals, duration);
+
+    #[cfg(feature = "remote")]
+    let result = if crate::remote::is_remote(&output) {
+        upload(&summary, &output).await
+    } else {
+        write(&summary, &output)
+    };
+
+    #[cfg(not(feature = "remote"))]
+    let result = write(&summary, &output);
+
+    if let Err(e) = result {
+        eprintln!("error writing summary file: {output}\n{e}");
+    }
+
+    WAIT.fetch_sub(1, Ordering::Relaxed);
+}
+
+// the percentiles of the latencies recorded between two loads of a histogram
+fn percentiles(
+    previous: &Option<histogram::Histogram>,
+    current: &Option<histogram::Histogram>,
+) -> BTreeMap<String, u64> {
+    let mut result = BTreeMap::new();
+
+    if let (Some(previous), Some(current)) = (previous, current) {
+        if let Ok(delta) = current.wrapping_sub(previous) {
+            let percentiles: Vec<f64> = SUMMARY_PERCENTILES.iter().map(|(_, p)| *p).collect();
+
+            if let Ok(Some(values)) = delta.percentiles(&percentiles) {
+                for ((label, _), (_, bucket)) in SUMMARY_PERCENTILES.iter().zip(values.iter()) {
+                    result.insert(label.to_string(), bucket.end());
+                }
+            }
+        }
+    }
+
+    result
+}
+
+// the percentiles for each of the labeled histograms which recorded latencies
+fn labeled_percentiles(
+    previous: &BTreeMap<String, histogram::Histogram>,
+    current: &BTreeMap<String, histogram::Histogram>,
+) -> BTreeMap<String, BTreeMap<String, u64>> {
+    let mut result = BTreeMap::new();
+
+    for (label, histogram) in current {
+        let percentiles = percentiles(&previous.get(label).cloned(), &Some(histogram.clone()));
+
+        if !percentiles.is_empty() {
+            result.insert(label.clone(), percentiles);
+        }
+    }
+
+    result
+}
+
+// the summary is written to a temporary file in the same directory and then
+// renamed, so a partial summary is never observed
+pub(super) fn write<T: Serialize>(summary: &T, output: &str) -> std::io::Result<()> {
+    let dir = Path::new(output)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("./"));
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+
+    serde_json::to_writer_pretty(&mut file, summary)?;
+    file.write_all(b"\n")?;
+
+    file.persist(output).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+// a summary url is uploaded to in a single request once the summary is complete
+#[cfg(feature = "remote")]
+pub(super) async fn upload<T: Serialize>(summary: &T, output: &str) -> std::io::Result<()> {
+    let mut content = serde_json::to_vec_pretty(summary)?;
+    content.push(b'\n');
+
+    crate::remote::upload(output, content).await
+}
+
+/// The totals of the client metrics, which are loaded at the start and end of
+/// the measurement period.
+pub(super) struct Totals {
+    pub(super) requests: u64,
+    pub(super) responses: u64,
+    pub(super) errors: u64,
+    pub(super) timeouts: u64,
+    pub(super) error_categories: BTreeMap<&'static str, u64>,
+    pub(super) latency: Option<histogram::Histogram>,
+    pub(super) latency_corrected: Option<histogram::Histogram>,
+    pub(super) commands: BTreeMap<String, histogram::Histogram>,
+    pub(super) value_classes: BTreeMap<String, histogram::Histogram>,
+    pub(super) response_bytes: u64,
+    pub(super) response_size: Option<histogram::Histogram>,
+}
+
+impl Totals {
+    pub(super) fn load() -> Self {
+        Self {
+            requests: REQUEST_OK.value(),
+            responses: RESPONSE_OK.value(),
+            errors: RESPONSE_EX.value(),
+            timeouts: RESPONSE_TIMEOUT.value(),
+            error_categories: BTreeMap::from([
+                ("connection", ERROR_CONNECTION.value()),
+                ("protocol", ERROR_PROTOCOL.value()),
+                ("server", ERROR_SERVER.value()),
+                ("timeout", ERROR_TIMEOUT.value()),
+            ]),
+            latency: RESPONSE_LATENCY.load(),
+            latency_corrected: RESPONSE_LATENCY_CORRECTED.load(),
+            commands: labeled_latencies(COMMAND_LATENCY_PREFIX),
+            value_classes: labeled_latencies(VALUE_CLASS_LATENCY_PREFIX),
+            response_bytes: RESPONSE_BYTES.value(),
+            response_size: RESPONSE_SIZE.load(),
+        }
+    }
+}
+
+// loads the latency histograms which share the prefix, by the remainder of
+// their names
+fn labeled_latencies(prefix: &str) -> BTreeMap<String, histogram::Histogram> {
+    let mut result = BTreeMap::new();
+
+    for metric in &metriken::metrics() {
+        let label = match metric.name().strip_prefix(prefix) {
+            Some(label) => label,
+            None => continue,
+        };
+
+        if let Some(Value::Other(other)) = metric.value() {
+            if let Some(histogram) = other
+                .downcast_ref::<metriken::AtomicHistogram>()
+                .and_then(|histogram| histogram.load())
+            {
+                result.insert(label.to_string(), histogram);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(latencies: &[u64]) -> Option<histogram::Histogram> {
+        let mut histogram = histogram::Histogram::new(7, 64).unwrap();
+
+        for latency in latencies {
+            histogram.increment(*latency).unwrap();
+        }
+
+        Some(histogram)
+    }
+
+    // the totals after the responses with the latencies
+    fn totals(latencies: &[u64]) -> Totals {
+        let count = latencies.len() as u64;
+
+        Totals {
+            requests: count,
+            responses: count,
+            errors: 0,
+            timeouts: 0,
+            error_categories: BTreeMap::new(),
+            latency: histogram(latencies),
+            latency_corrected: None,
+            commands: BTreeMap::from([("get".to_string(), histogram(latencies).unwrap())]),
+            value_classes: BTreeMap::new(),
+            response_bytes: 0,
+            response_size: None,
+        }
+    }
+
+    fn config(extra: &str) -> Config {
+        toml::from_str(&config::testing::config("ping", "127.0.0.1:12321", extra)).unwrap()
+    }
+
+    #[test]
+    fn excludes_warmup() {
+        let config = config("");
+
+        // the slow responses during the warmup are in the baseline
+        let warmup = vec![1_000_000_000; 100];
+        let measured = vec![1_000_000; 50];
+
+        let baseline = totals(&warmup);
+        let totals = totals(&[warmup, measured].concat());
+
+        let summary = Summary::new(&config, &baseline, &totals, 10.0);
+
+        assert_eq!(summary.requests, 50);
+        assert_eq!(summary.responses, 50);
+        assert_eq!(summary.qps, 5.0);
+
+        // only the measured latencies are in the percentiles and histogram
+        assert!(summary.latency["max"] < 1_100_000, "{:?}", summary.latency);
+        assert!(summary.commands["get"]["max"] < 1_100_000);
+
+        let histogram = summary.histogram.unwrap();
+        assert_eq!(histogram.count.iter().sum::<u64>(), 50);
+        assert_eq!(histogram.count.len(), 1);
+    }
+
+    #[test]
+    fn json_file() {
+        let config = config("");
+        let latencies: Vec<u64> = (1..=1000).map(|i| i * 1_000).collect();
+
+        let summary = Summary::new(&config, &totals(&[]), &totals(&latencies), 10.0);
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("summary.json");
+        write(&summary, output.to_str().unwrap()).unwrap();
+
+        // only the summary itself remains once the temporary file is renamed
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(json["protocol"], "Ping");
+        assert_eq!(json["requests"], 1000);
+        assert_eq!(json["qps"], 100.0);
+
+        // each percentile is the upper end of the bucket holding it, which is
+        // within the relative error of the grouping power
+        for (label, expected) in [
+            ("p50", 500_000),
+            ("p90", 900_000),
+            ("p99", 990_000),
+            ("p999", 999_000),
+            ("max", 1_000_000),
+        ] {
+            let value = json["latency"][label].as_u64().unwrap();
+            assert!(value >= expected, "{label}: {value}");
+            assert!(value <= expected + expected / 128, "{label}: {value}");
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use super::*;
+use crate::admin::handlers::update_ratelimit;
+use std::time::Instant;
+
+/// Adjusts the ratelimit with the adaptive controller, if one is configured,
+/// and reports the rate it discovered once the test ends. The latency
+/// percentile is taken over each interval, so that each adjustment reflects
+/// the rate which was held during that interval.
+pub async fn adaptive(config: Config, ratelimiter: Option<Arc<Ratelimiter>>) {
+    let adaptive = match config.workload().ratelimit().adaptive() {
+        Some(adaptive) => adaptive.clone(),
+        None => return,
+    };
+
+    WAIT.fetch_add(1, Ordering::Relaxed);
+
+    // the start rate is required to be set for an adaptive ratelimit
+    let start: u64 = config.workload().ratelimit().start().unwrap().into();
+
+    let mut controller = Controller {
+        rate: start,
+        increase: adaptive.increase(),
+        decrease: adaptive.decrease(),
+        min: adaptive.min(),
+        max: adaptive.max(),
+        sustained: None,
+        peaks: Vec::new(),
+    };
+
+    let mut previous = RESPONSE_LATENCY.load();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        // wait out the interval in short sleeps, so the test is not held up
+        // once it ends
+        let end = Instant::now() + adaptive.interval();
+        while RUNNING.load(Ordering::Relaxed) && Instant::now() < end {
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        if !RUNNING.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let current = RESPONSE_LATENCY.load();
+        let latency = percentile(&previous, &current, adaptive.percentile());
+        previous = current;
+
+        let rate = controller.next_ratelimit(latency.map(|latency| latency <= adaptive.target()));
+
+        if let Some(discovered) = controller.discovered() {
+            RATELIMIT_DISCOVERED.set(discovered as i64);
+        }
+
+        let _ = update_ratelimit(rate, ratelimiter.clone()).await;
+    }
+
+    output!("-----");
+
+    match controller.discovered() {
+        Some(rate) => output!(
+            "Adaptive Ratelimit: Discovered Rate (/s): {rate} Target: {}",
+            adaptive.describe()
+        ),
+        None => output!(
+            "Adaptive Ratelimit: Target: {} was never exceeded, the final rate was {}/s",
+            adaptive.describe(),
+            controller.rate
+        ),
+    }
+
+    WAIT.fetch_sub(1, Ordering::Relaxed);
+}
+
+// additive increase, multiplicative decrease of the rate
+struct Controller {
+    rate: u64,
+    increase: u64,
+    decrease: f64,
+    min: u64,
+    max: Option<u64>,
+    // the latest rate which met the target since the last back off
+    sustained: Option<u64>,
+    // the highest rate which met the target before each back off
+    peaks: Vec<u64>,
+}
+
+impl Controller {
+    // an interval without any responses did not meet the target, as the
+    // backend is not keeping up at all
+    fn next_ratelimit(&mut self, met: Option<bool>) -> u64 {
+        if met == Some(true) {
+            self.sustained = Some(self.rate);
+            self.rate = self.rate.saturating_add(self.increase);
+
+            if let Some(max) = self.max {
+                self.rate = self.rate.min(max);
+            }
+        } else {
+            if let Some(sustained) = self.sustained.take() {
+                self.peaks.push(sustained);
+            }
+
+            self.rate = ((self.rate as f64 * self.decrease) as u64).max(self.min);
+        }
+
+        self.rate
+    }
+
+    // each peak is one approach to the saturation point, so the mean of them
+    // is taken as the discovered rate
+    fn discovered(&self) -> Option<u64> {
+        if self.peaks.is_empty() {
+            return None;
+        }
+
+        Some(self.peaks.iter().sum::<u64>() / self.peaks.len() as u64)
+    }
+}
+
+// the latency at the percentile for the responses recorded between two loads
+// of the histogram
+fn percentile(
+    previous: &Option<histogram::Histogram>,
+    current: &Option<histogram::Histogram>,
+    percentile: f64,
+) -> Option<u64> {
+    let delta = match (previous, current) {
+        (Some(previous), Some(current)) => current.wrapping_sub(previous).ok()?,
+        (None, Some(current)) => current.clone(),
+        _ => return None,
+    };
+
+    delta
+        .percentiles(&[percentile])
+        .ok()
+        .flatten()
+        .and_then(|values| values.first().map(|(_, bucket)| bucket.end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the rate beyond which the synthetic backend is saturated
+    const SATURATION: u64 = 5_000;
+
+    // the latency of the synthetic backend, which is 1ms until it is
+    // saturated, then grows quickly with the rate
+    fn latency(rate: u64) -> u64 {
+        1_000_000 + rate.saturating_sub(SATURATION) * 100_000
+    }
+
+    fn controller(rate: u64, max: Option<u64>) -> Controller {
+        Controller {
+            rate,
+            increase: 100,
+            decrease: 0.5,
+            min: 100,
+            max,
+            sustained: None,
+            peaks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn converges() {
+        // a p99 target of 2ms is met up to 10/s beyond the saturation point
+        let target = 2_000_000;
+        let mut controller = controller(1_000, None);
+        let mut histogram = histogram::Histogram::new(7, 64).unwrap();
+        let mut previous = Some(histogram.clone());
+
+        for _ in 0..500 {
+            // each interval records a sample of the responses at the rate
+            for _ in 0..100 {
+                histogram.increment(latency(controller.rate)).unwrap();
+            }
+
+            let current = Some(histogram.clone());
+            let p99 = percentile(&previous, &current, 99.0);
+            previous = current;
+
+            controller.next_ratelimit(p99.map(|p99| p99 <= target));
+        }
+
+        let discovered = controller.discovered().unwrap();
+        assert!(
+            (SATURATION - 500..=SATURATION + 10).contains(&discovered),
+            "{discovered}"
+        );
+
+        // the rate keeps probing around the saturation point
+        assert!(controller.rate <= SATURATION + 110);
+        assert!(controller.rate >= (SATURATION - 500) / 2);
+    }
+
+    #[test]
+    fn no_responses() {
+        // an interval without responses backs off, down to the minimum
+        let mut controller = controller(1_000, None);
+
+        assert_eq!(controller.next_ratelimit(None), 500);
+        assert_eq!(controller.next_ratelimit(None), 250);
+        assert_eq!(controller.next_ratelimit(None), 125);
+        assert_eq!(controller.next_ratelimit(None), 100);
+        assert_eq!(controller.discovered(), None);
+    }
+
+    #[test]
+    fn max() {
+        let mut controller = controller(1_000, Some(1_150));
+
+        assert_eq!(controller.next_ratelimit(Some(true)), 1_100);
+        assert_eq!(controller.next_ratelimit(Some(true)), 1_150);
+        assert_eq!(controller.next_ratelimit(Some(true)), 1_150);
+
+        // the peak is the rate which was held when the target was last met
+        assert_eq!(controller.next_ratelimit(Some(false)), 575);
+        assert_eq!(controller.discovered(), Some(1_150));
+    }
+}
@@ -1,6 +1,7 @@
 use core::time::Duration;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 #[derive(Debug, PartialEq)]
 pub enum ClientWorkItem {
@@ -8,74 +9,102 @@ pub enum ClientWorkItem {
     Request {
         request: ClientRequest,
         sequence: u64,
+        /// When the request was intended to be sent. Only set when the
+        /// corrected latency is recorded.
+        intended: Option<Instant>,
+        /// The value size class of the request, when the keyspace splits the
+        /// value lengths into classes.
+        class: Option<usize>,
     },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Ping {}
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Add {
     pub key: Arc<[u8]>,
     pub value: Vec<u8>,
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Get {
     pub key: Arc<[u8]>,
+    /// Check that the value matches the one written by the workload.
+    pub verify: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct MultiGet {
     pub keys: Vec<Arc<[u8]>>,
+    /// Check that the values match the ones written by the workload.
+    pub verify: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiSet {
+    pub pairs: Vec<(Arc<[u8]>, Vec<u8>)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Delete {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Replace {
     pub key: Arc<[u8]>,
     pub value: Vec<u8>,
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Set {
     pub key: Arc<[u8]>,
     pub value: Vec<u8>,
     pub ttl: Option<Duration>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cas {
+    pub key: Arc<[u8]>,
+    pub value: Vec<u8>,
+    pub ttl: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scan {
+    pub count: Option<usize>,
+    pub max_iterations: Option<usize>,
+}
+
 // Hash
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashExists {
     pub key: Arc<[u8]>,
     pub field: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashDelete {
     pub key: Arc<[u8]>,
     pub fields: Vec<Arc<[u8]>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashGet {
     pub key: Arc<[u8]>,
     pub fields: Vec<Arc<[u8]>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashGetAll {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashIncrement {
     pub key: Arc<[u8]>,
     pub field: Arc<[u8]>,
@@ -83,7 +112,7 @@ pub struct HashIncrement {
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashSet {
     pub key: Arc<[u8]>,
     pub data: HashMap<Arc<[u8]>, Vec<u8>>,
@@ -92,27 +121,27 @@ pub struct HashSet {
 
 // List
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListFetch {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListLength {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListPopBack {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListPopFront {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListPushFront {
     pub key: Arc<[u8]>,
     pub elements: Vec<Arc<[u8]>>,
@@ -120,7 +149,7 @@ pub struct ListPushFront {
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListPushBack {
     pub key: Arc<[u8]>,
     pub elements: Vec<Arc<[u8]>>,
@@ -128,52 +157,52 @@ pub struct ListPushBack {
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListRange {
     pub key: Arc<[u8]>,
     pub start: i64,
     pub stop: i64,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListRemove {
     pub key: Arc<[u8]>,
     pub element: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListStore {
     pub key: Arc<[u8]>,
     pub elements: Vec<Arc<[u8]>>,
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SetAdd {
     pub key: Arc<[u8]>,
     pub members: Vec<Arc<[u8]>>,
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SetMembers {
     pub key: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SetRemove {
     pub key: Arc<[u8]>,
     pub members: Vec<Arc<[u8]>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetAdd {
     pub key: Arc<[u8]>,
     pub members: Vec<(Arc<[u8]>, f64)>,
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetRange {
     pub key: Arc<[u8]>,
     pub start: Option<i32>,
@@ -181,7 +210,7 @@ pub struct SortedSetRange {
     pub by_score: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetIncrement {
     pub key: Arc<[u8]>,
     pub member: Arc<[u8]>,
@@ -189,26 +218,26 @@ pub struct SortedSetIncrement {
     pub ttl: Option<Duration>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetRank {
     pub key: Arc<[u8]>,
     pub member: Arc<[u8]>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetRemove {
     pub key: Arc<[u8]>,
     pub members: Vec<Arc<[u8]>>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SortedSetScore {
     pub key: Arc<[u8]>,
     pub members: Vec<Arc<[u8]>>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ClientRequest {
     // Ping
     Ping(Ping),
@@ -218,8 +247,12 @@ pub enum ClientRequest {
     Get(Get),
     Delete(Delete),
     MultiGet(MultiGet),
+    MultiSet(MultiSet),
     Replace(Replace),
     Set(Set),
+    Cas(Cas),
+    /// Iterate over the keys with a cursor.
+    Scan(Scan),
 
     // Hash Commands
     HashExists(HashExists),
@@ -1,29 +1,63 @@
 use super::*;
-use config::{Command, RampCompletionAction, RampType, ValueKind, Verb};
+use async_channel::TrySendError;
+use config::{Command, RampCompletionAction, RampType, Shape, ValueKind, ValueMode, Verb};
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use metriken::{AtomicHistogram, Counter, DynBoxedMetric, MetricBuilder};
 use rand::distributions::{Alphanumeric, Uniform};
 use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use rand::{Rng, RngCore, SeedableRng};
 use rand_distr::Distribution as RandomDistribution;
 use rand_distr::WeightedAliasIndex;
 use rand_xoshiro::{Seed512, Xoshiro512PlusPlus};
 use ratelimit::Ratelimiter;
 use std::collections::{HashMap, HashSet};
-use std::io::{Result, Write};
-use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, Result, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::{Arc, OnceLock};
 use tokio::runtime::Runtime;
 use zipf::ZipfDistribution;
 
+mod adaptive;
 pub mod client;
+mod preload;
 mod publisher;
+mod record;
+mod resolve;
+mod trace;
 
+pub use adaptive::adaptive;
 pub use client::{ClientRequest, ClientWorkItem};
+pub use preload::{preload, PRELOADING};
 pub use publisher::PublisherWorkItem;
+pub use record::finish as finish_recording;
 
 static SEQUENCE_NUMBER: AtomicU64 = AtomicU64::new(0);
 
+// the number of requests sent, used to enforce the request budget
+static ISSUED: AtomicU64 = AtomicU64::new(0);
+
+// the number of request generator threads which are still running. They only
+// stop early once the request budget has been used up
+pub static GENERATORS: AtomicUsize = AtomicUsize::new(0);
+
+/// The name of the counter for the requests sent for a keyspace.
+pub fn keyspace_sent_counter(name: &str) -> String {
+    format!("keyspace/{name}/request/sent")
+}
+
+/// The name of the counter for the requests dropped for a keyspace.
+pub fn keyspace_dropped_counter(name: &str) -> String {
+    format!("keyspace/{name}/request/dropped")
+}
+
+// additional bytes in the value pool beyond the largest value length
+const VALUE_POOL_PADDING: usize = 1024 * 1024;
+
+// number of times per second the ratelimit is updated for a sine shape
+const SHAPE_TICKS_PER_SECOND: u64 = 10;
+
 pub fn launch_workload(
     generator: Generator,
     config: &Config,
@@ -43,8 +77,37 @@ pub fn launch_workload(
     // generate unique seeds for each workload thread.
     let mut rng = Xoshiro512PlusPlus::from_seed(config.general().initial_seed());
 
+    // a trace is replayed by a single thread, in place of the generators, so
+    // that the requests keep their order
+    if config.workload().trace().is_some() {
+        let client_sender = client_sender.clone();
+        let generator = generator.clone();
+        let config = config.clone();
+
+        let mut seed = [0; 64];
+        rng.fill_bytes(&mut seed);
+
+        GENERATORS.fetch_add(1, Ordering::Relaxed);
+
+        workload_rt.spawn_blocking(move || {
+            let mut rng = Xoshiro512PlusPlus::from_seed(Seed512(seed));
+
+            trace::replay(&config, &generator, &client_sender, &mut rng);
+
+            GENERATORS.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    let threads = if config.workload().trace().is_some() {
+        0
+    } else {
+        config.workload().threads()
+    };
+
+    record::start(config);
+
     // spawn the request generators on a blocking threads
-    for _ in 0..config.workload().threads() {
+    for _ in 0..threads {
         let client_sender = client_sender.clone();
         let pubsub_sender = pubsub_sender.clone();
         let generator = generator.clone();
@@ -53,17 +116,24 @@ pub fn launch_workload(
         let mut seed = [0; 64];
         rng.fill_bytes(&mut seed);
 
+        GENERATORS.fetch_add(1, Ordering::Relaxed);
+
         workload_rt.spawn_blocking(move || {
             // since this seed is unique, each workload thread should produce
             // requests in a different sequence
             let mut rng = Xoshiro512PlusPlus::from_seed(Seed512(seed));
 
-            while RUNNING.load(Ordering::Relaxed) {
-                generator.generate(&client_sender, &pubsub_sender, &mut rng);
-            }
+            while RUNNING.load(Ordering::Relaxed)
+                && !SHUTDOWN.load(Ordering::Relaxed)
+                && generator.generate(&client_sender, &pubsub_sender, &mut rng)
+            {}
+
+            GENERATORS.fetch_sub(1, Ordering::Relaxed);
         });
     }
 
+    workload_rt.spawn(resolve::resolve(client_sender.clone(), config.clone()));
+
     let c = config.clone();
     workload_rt.spawn_blocking(move || reconnect(client_sender, c));
 
@@ -73,6 +143,10 @@ pub fn launch_workload(
 #[derive(Clone)]
 pub struct Generator {
     ratelimiter: Option<Arc<Ratelimiter>>,
+    closed_loop: bool,
+    block: bool,
+    corrected_latency: bool,
+    max_requests: Option<u64>,
     components: Vec<Component>,
     component_dist: WeightedAliasIndex<usize>,
 }
@@ -102,8 +176,9 @@ impl Generator {
         let mut components = Vec::new();
         let mut component_weights = Vec::new();
 
-        for keyspace in config.workload().keyspaces() {
-            components.push(Component::Keyspace(Keyspace::new(config, keyspace)));
+        for (id, keyspace) in config.workload().keyspaces().iter().enumerate() {
+            let name = config.workload().keyspace_name(id);
+            components.push(Component::Keyspace(Keyspace::new(config, keyspace, &name)));
             component_weights.push(keyspace.weight());
         }
 
@@ -119,6 +194,10 @@ impl Generator {
 
         Self {
             ratelimiter,
+            closed_loop: config.workload().mode() == config::Mode::ClosedLoop,
+            block: config.workload().overflow() == config::Overflow::Block,
+            corrected_latency: config.workload().corrected_latency(),
+            max_requests: config.general().max_requests(),
             components,
             component_dist: WeightedAliasIndex::new(component_weights).unwrap(),
         }
@@ -133,7 +212,16 @@ impl Generator {
         client_sender: &Sender<ClientWorkItem>,
         pubsub_sender: &Sender<PublisherWorkItem>,
         rng: &mut dyn RngCore,
-    ) {
+    ) -> bool {
+        // reserve a request from the budget, if the budget is used up, the
+        // generator is done
+        if let Some(max) = self.max_requests {
+            if ISSUED.fetch_add(1, Ordering::Relaxed) >= max {
+                ISSUED.fetch_sub(1, Ordering::Relaxed);
+                return false;
+            }
+        }
+
         if let Some(ref ratelimiter) = self.ratelimiter {
             loop {
                 RATELIMIT_DROPPED.set(ratelimiter.dropped());
@@ -146,13 +234,37 @@ impl Generator {
             }
         }
 
-        match &self.components[self.component_dist.sample(rng)] {
+        let sent = match &self.components[self.component_dist.sample(rng)] {
+            // in a closed loop, or when the overflow policy is to block, we
+            // wait for a client to be ready for the next request instead of
+            // dropping it, the send only fails once the clients have shutdown
+            Component::Keyspace(keyspace) if self.block => {
+                let work_item = self.generate_request(keyspace, rng);
+                let line = record::line(keyspace, &work_item);
+
+                if self.send_blocking(client_sender, work_item) {
+                    keyspace.sent.increment();
+                    record::write(line);
+                    true
+                } else {
+                    false
+                }
+            }
+            Component::Topics(topics) if self.block => {
+                self.send_blocking(pubsub_sender, self.generate_pubsub(topics, rng))
+            }
             Component::Keyspace(keyspace) => {
-                if client_sender
-                    .try_send(self.generate_request(keyspace, rng))
-                    .is_err()
-                {
+                let work_item = self.generate_request(keyspace, rng);
+                let line = record::line(keyspace, &work_item);
+
+                if client_sender.try_send(work_item).is_err() {
                     REQUEST_DROPPED.increment();
+                    keyspace.dropped.increment();
+                    false
+                } else {
+                    keyspace.sent.increment();
+                    record::write(line);
+                    true
                 }
             }
             Component::Topics(topics) => {
@@ -161,8 +273,35 @@ impl Generator {
                     .is_err()
                 {
                     REQUEST_DROPPED.increment();
+                    false
+                } else {
+                    true
+                }
+            }
+        };
+
+        CLIENT_QUEUE_DEPTH.set(client_sender.len() as i64);
+
+        // requests which were not sent are returned to the budget
+        if !sent && self.max_requests.is_some() {
+            ISSUED.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        true
+    }
+
+    // waits for room in the queue. In an open loop, the requests which had to
+    // wait are counted as they were sent later than the ratelimit intended
+    fn send_blocking<T>(&self, sender: &Sender<T>, item: T) -> bool {
+        match sender.try_send(item) {
+            Ok(()) => true,
+            Err(TrySendError::Full(item)) => {
+                if !self.closed_loop {
+                    REQUEST_BLOCKED.increment();
                 }
+                sender.send_blocking(item).is_ok()
             }
+            Err(TrySendError::Closed(_)) => false,
         }
     }
 
@@ -202,27 +341,68 @@ impl Generator {
         let command = &keyspace.commands[keyspace.command_dist.sample(rng)];
 
         let request = match command.verb() {
-            Verb::Add => ClientRequest::Add(client::Add {
-                key: keyspace.sample(rng),
-                value: keyspace.gen_value(rng),
-                ttl: keyspace.ttl(),
-            }),
-            Verb::Get => ClientRequest::Get(client::Get {
-                key: keyspace.sample(rng),
-            }),
-            Verb::Set => ClientRequest::Set(client::Set {
-                key: keyspace.sample(rng),
-                value: keyspace.gen_value(rng),
-                ttl: keyspace.ttl(),
-            }),
+            Verb::Add => {
+                let key = keyspace.sample(rng);
+                ClientRequest::Add(client::Add {
+                    value: keyspace.gen_value_for(&key, rng),
+                    key,
+                    ttl: keyspace.gen_ttl(rng),
+                })
+            }
+            Verb::Get => {
+                if command.cardinality() == 1 {
+                    ClientRequest::Get(client::Get {
+                        key: keyspace.sample(rng),
+                        verify: keyspace.verify,
+                    })
+                } else {
+                    let mut keys = Vec::with_capacity(command.cardinality());
+                    for _ in 0..command.cardinality() {
+                        keys.push(keyspace.sample(rng));
+                    }
+                    ClientRequest::MultiGet(client::MultiGet {
+                        keys,
+                        verify: keyspace.verify,
+                    })
+                }
+            }
+            Verb::Set => {
+                if command.cardinality() == 1 {
+                    let key = keyspace.sample(rng);
+                    ClientRequest::Set(client::Set {
+                        value: keyspace.gen_value_for(&key, rng),
+                        key,
+                        ttl: keyspace.gen_ttl(rng),
+                    })
+                } else {
+                    let mut pairs = Vec::with_capacity(command.cardinality());
+                    for _ in 0..command.cardinality() {
+                        let key = keyspace.sample(rng);
+                        let value = keyspace.gen_value_for(&key, rng);
+                        pairs.push((key, value));
+                    }
+                    ClientRequest::MultiSet(client::MultiSet { pairs })
+                }
+            }
             Verb::Delete => ClientRequest::Delete(client::Delete {
                 key: keyspace.sample(rng),
             }),
-            Verb::Replace => ClientRequest::Replace(client::Replace {
-                key: keyspace.sample(rng),
-                value: keyspace.gen_value(rng),
-                ttl: keyspace.ttl(),
-            }),
+            Verb::Replace => {
+                let key = keyspace.sample(rng);
+                ClientRequest::Replace(client::Replace {
+                    value: keyspace.gen_value_for(&key, rng),
+                    key,
+                    ttl: keyspace.gen_ttl(rng),
+                })
+            }
+            Verb::Cas => {
+                let key = keyspace.sample(rng);
+                ClientRequest::Cas(client::Cas {
+                    value: keyspace.gen_value_for(&key, rng),
+                    key,
+                    ttl: keyspace.gen_ttl(rng),
+                })
+            }
             Verb::HashGet => {
                 let cardinality = command.cardinality();
                 let mut fields = Vec::with_capacity(cardinality);
@@ -258,7 +438,7 @@ impl Generator {
                 key: keyspace.sample(rng),
                 field: keyspace.sample_inner(rng),
                 amount: rng.gen(),
-                ttl: keyspace.ttl(),
+                ttl: keyspace.gen_ttl(rng),
             }),
             Verb::HashSet => {
                 let mut data = HashMap::new();
@@ -268,7 +448,7 @@ impl Generator {
                 ClientRequest::HashSet(client::HashSet {
                     key: keyspace.sample(rng),
                     data,
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::ListPushFront => {
@@ -281,7 +461,7 @@ impl Generator {
                     key: keyspace.sample(rng),
                     elements,
                     truncate: command.truncate(),
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::ListPushBack => {
@@ -294,7 +474,7 @@ impl Generator {
                     key: keyspace.sample(rng),
                     elements,
                     truncate: command.truncate(),
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::ListFetch => ClientRequest::ListFetch(client::ListFetch {
@@ -314,6 +494,10 @@ impl Generator {
                 element: keyspace.sample_inner(rng),
             }),
             Verb::Ping => ClientRequest::Ping(client::Ping {}),
+            Verb::Scan => ClientRequest::Scan(client::Scan {
+                count: command.count(),
+                max_iterations: command.max_iterations(),
+            }),
             Verb::SetAdd => {
                 let mut members = HashSet::new();
                 while members.len() < command.cardinality() {
@@ -323,7 +507,7 @@ impl Generator {
                 ClientRequest::SetAdd(client::SetAdd {
                     key: keyspace.sample(rng),
                     members,
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::SetMembers => ClientRequest::SetMembers(client::SetMembers {
@@ -349,7 +533,7 @@ impl Generator {
                 ClientRequest::SortedSetAdd(client::SortedSetAdd {
                     key: keyspace.sample(rng),
                     members,
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::SortedSetRange => ClientRequest::SortedSetRange(client::SortedSetRange {
@@ -374,7 +558,7 @@ impl Generator {
                     key: keyspace.sample(rng),
                     member: keyspace.sample_inner(rng),
                     amount: rng.gen(),
-                    ttl: keyspace.ttl(),
+                    ttl: keyspace.gen_ttl(rng),
                 })
             }
             Verb::SortedSetScore => {
@@ -395,8 +579,11 @@ impl Generator {
         };
 
         ClientWorkItem::Request {
+            class: keyspace.value_class(&request),
             request,
             sequence: SEQUENCE_NUMBER.fetch_add(1, Ordering::Relaxed),
+            // the request is generated as soon as the ratelimit releases it
+            intended: self.corrected_latency.then(std::time::Instant::now),
         }
     }
 
@@ -443,6 +630,10 @@ impl Topics {
             config::Distribution::Zipf => {
                 Distribution::Zipf(ZipfDistribution::new(ntopics, 1.0).unwrap())
             }
+            config::Distribution::Sequential => Distribution::Sequential {
+                next: Arc::new(AtomicUsize::new(0)),
+                len: ntopics,
+            },
         };
         let topic_names: Vec<Arc<String>>;
         // if the given topic_names has the matched format, we use topic names there
@@ -523,37 +714,352 @@ pub struct Keyspace {
     command_dist: WeightedAliasIndex<usize>,
     inner_keys: Vec<Arc<[u8]>>,
     inner_key_dist: Distribution,
-    vlen: usize,
+    vlen: ValueLength,
     vkind: ValueKind,
-    value_random_bytes: usize,
+    value_random_fraction: f64,
+    value_pool: Arc<[u8]>,
     ttl: Option<Duration>,
+    ttl_dist: Option<Uniform<u64>>,
+    verify: bool,
+    prefix: Vec<u8>,
+    // per-keyspace counts of the requests which were sent to the clients and
+    // those which were dropped because the queue was full
+    sent: Arc<DynBoxedMetric<Counter>>,
+    dropped: Arc<DynBoxedMetric<Counter>>,
 }
 
 #[derive(Clone)]
 pub enum Distribution {
     Uniform(rand::distributions::Uniform<usize>),
     Zipf(zipf::ZipfDistribution),
+    /// The position is shared by all the clones of the keyspace, so the
+    /// workload threads step through the keys together.
+    Sequential {
+        next: Arc<AtomicUsize>,
+        len: usize,
+    },
+    /// Most samples are from a window of `hot` keys which moves forward by its
+    /// size at each interval. The start is shared by all the clones of the
+    /// keyspace, so the workload threads move the window together.
+    HotSet {
+        len: usize,
+        hot: usize,
+        traffic: f64,
+        interval: Duration,
+        start: std::time::Instant,
+    },
 }
 
 impl Distribution {
     pub fn sample(&self, rng: &mut dyn RngCore) -> usize {
         match self {
             Self::Uniform(dist) => dist.sample(rng),
-            Self::Zipf(dist) => dist.sample(rng),
+            // the zipf distribution samples ranks starting from one
+            Self::Zipf(dist) => dist.sample(rng) - 1,
+            Self::Sequential { next, len } => next.fetch_add(1, Ordering::Relaxed) % len,
+            Self::HotSet {
+                len,
+                hot,
+                traffic,
+                interval,
+                start,
+            } => {
+                let rotations = start.elapsed().as_nanos() / interval.as_nanos();
+                let offset = (rotations * *hot as u128 % *len as u128) as usize;
+
+                // the cold set is the rest of the keys after the window
+                let index = if rng.gen_bool(*traffic) {
+                    rng.gen_range(0..*hot)
+                } else {
+                    rng.gen_range(*hot..*len)
+                };
+
+                (offset + index) % len
+            }
         }
     }
 }
 
-impl Keyspace {
+/// Reads the keys for a keyspace from a file, which may be a remote url. The
+/// file is streamed so that only the keys themselves are held in memory.
+fn load_keys(path: &str) -> Vec<Arc<[u8]>> {
+    let file = match crate::remote::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("failed to open key file: {path}\n{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let csv = path.ends_with(".csv");
+
+    let mut keys = Vec::new();
+    let mut skipped = 0;
+
+    for line in std::io::BufReader::new(file).split(b'\n') {
+        let mut line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("failed to read key file: {path}\n{e}");
+                std::process::exit(2);
+            }
+        };
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        if csv {
+            if let Some(end) = line.iter().position(|b| *b == b',') {
+                line.truncate(end);
+            }
+        }
+
+        if line.is_empty() {
+            skipped += 1;
+            continue;
+        }
+
+        keys.push(line.into());
+    }
+
+    if keys.is_empty() {
+        eprintln!("key file does not contain any keys: {path}");
+        std::process::exit(2);
+    }
+
+    if skipped > 0 {
+        warn!("skipped {skipped} lines without a key in key file: {path}");
+    }
+
+    keys
+}
+
+// the latency histograms of the value size classes of all the keyspaces,
+// which a work item refers to by its position
+static VALUE_CLASSES: OnceLock<Vec<(String, DynBoxedMetric<AtomicHistogram>)>> = OnceLock::new();
+
+// registers a latency histogram for each of the value size classes. A class
+// name which is used by several keyspaces shares the histogram
+fn value_classes(config: &Config) -> &'static [(String, DynBoxedMetric<AtomicHistogram>)] {
+    VALUE_CLASSES.get_or_init(|| {
+        let mut names: Vec<String> = Vec::new();
+
+        for keyspace in config.workload().keyspaces() {
+            if let Some(config::ValueLength::Classes { classes }) = keyspace.vlen_distribution() {
+                for class in classes {
+                    if !names.contains(&class.name()) {
+                        names.push(class.name());
+                    }
+                }
+            }
+        }
+
+        let (grouping_power, max_value_power) = histogram_parameters();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let histogram = MetricBuilder::new(format!("{VALUE_CLASS_LATENCY_PREFIX}{name}"))
+                    .description("distribution of response latencies for the value class")
+                    .build(AtomicHistogram::new(grouping_power, max_value_power));
+
+                (name, histogram)
+            })
+            .collect()
+    })
+}
+
+/// Returns the latency histogram for the value size class of a work item.
+pub fn value_class_latency(class: Option<usize>) -> Option<&'static AtomicHistogram> {
+    let classes = VALUE_CLASSES.get()?;
+    classes.get(class?).map(|(_, histogram)| &**histogram)
+}
+
+// the length of each generated key
+enum KeyLength {
+    Fixed(usize),
+    Uniform(Uniform<usize>),
+    Weighted {
+        sizes: Vec<usize>,
+        dist: WeightedAliasIndex<usize>,
+    },
+}
+
+impl KeyLength {
+    fn new(keyspace: &config::Keyspace) -> Self {
+        match keyspace.klen_distribution() {
+            None => Self::Fixed(keyspace.klen()),
+            Some(config::KeyLength::Uniform { min, max }) => {
+                Self::Uniform(Uniform::new_inclusive(*min, *max))
+            }
+            Some(config::KeyLength::Weighted { sizes, weights }) => {
+                let weights = if weights.is_empty() {
+                    vec![1; sizes.len()]
+                } else {
+                    weights.clone()
+                };
+
+                Self::Weighted {
+                    sizes: sizes.clone(),
+                    dist: WeightedAliasIndex::new(weights).unwrap(),
+                }
+            }
+        }
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> usize {
+        match self {
+            Self::Fixed(klen) => *klen,
+            Self::Uniform(dist) => dist.sample(rng),
+            Self::Weighted { sizes, dist } => sizes[dist.sample(rng)],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ValueLength {
+    Fixed(usize),
+    Uniform(Uniform<usize>),
+    Normal {
+        dist: rand_distr::Normal<f64>,
+        max: usize,
+    },
+    Weighted {
+        sizes: Vec<usize>,
+        dist: WeightedAliasIndex<usize>,
+    },
+    Classes {
+        sizes: Vec<usize>,
+        dist: WeightedAliasIndex<usize>,
+        /// the running total of the class weights, which a key is assigned to
+        /// a class with
+        bounds: Vec<u64>,
+        /// the position of each class in the registered classes
+        ids: Vec<usize>,
+    },
+}
+
+impl ValueLength {
     pub fn new(config: &Config, keyspace: &config::Keyspace) -> Self {
-        let value_random_bytes = estimate_random_bytes_needed(
-            keyspace.vlen().unwrap_or(0),
-            keyspace.compression_ratio(),
-        );
+        match keyspace.vlen_distribution() {
+            None => Self::Fixed(keyspace.vlen().unwrap_or(0)),
+            Some(config::ValueLength::Uniform { min, max }) => {
+                Self::Uniform(Uniform::new_inclusive(*min, *max))
+            }
+            Some(vlen @ config::ValueLength::Normal { mean, stddev }) => Self::Normal {
+                dist: rand_distr::Normal::new(*mean, *stddev).unwrap(),
+                max: vlen.max(),
+            },
+            Some(config::ValueLength::Weighted { sizes, weights }) => {
+                let weights = if weights.is_empty() {
+                    vec![1; sizes.len()]
+                } else {
+                    weights.clone()
+                };
+
+                Self::Weighted {
+                    sizes: sizes.clone(),
+                    dist: WeightedAliasIndex::new(weights).unwrap(),
+                }
+            }
+            Some(config::ValueLength::Classes { classes }) => {
+                let registered = value_classes(config);
+
+                let bounds = classes
+                    .iter()
+                    .scan(0, |total, class| {
+                        *total += class.weight() as u64;
+                        Some(*total)
+                    })
+                    .collect();
+
+                Self::Classes {
+                    sizes: classes.iter().map(|class| class.size()).collect(),
+                    dist: WeightedAliasIndex::new(classes.iter().map(|c| c.weight()).collect())
+                        .unwrap(),
+                    bounds,
+                    ids: classes
+                        .iter()
+                        .map(|class| {
+                            registered
+                                .iter()
+                                .position(|(name, _)| *name == class.name())
+                                .unwrap()
+                        })
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    // the class of a key is determined by a hash of the key, so that the
+    // reads and writes of a key are attributed to the same class and each
+    // value of the key has the same size
+    fn class_of(&self, key: &[u8]) -> Option<usize> {
+        match self {
+            Self::Classes { bounds, .. } => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                key.hash(&mut hasher);
+
+                let point = hasher.finish() % bounds.last().copied().unwrap_or(1);
+
+                Some(bounds.partition_point(|bound| *bound <= point))
+            }
+            _ => None,
+        }
+    }
+
+    /// The length of a value for the key.
+    pub fn sample_for(&self, key: &[u8], rng: &mut dyn RngCore) -> usize {
+        match (self, self.class_of(key)) {
+            (Self::Classes { sizes, .. }, Some(class)) => sizes[class],
+            _ => self.sample(rng),
+        }
+    }
+
+    /// The registered value size class of the key, if the value lengths are
+    /// split into classes.
+    pub fn class_id(&self, key: &[u8]) -> Option<usize> {
+        match (self, self.class_of(key)) {
+            (Self::Classes { ids, .. }, Some(class)) => Some(ids[class]),
+            _ => None,
+        }
+    }
+
+    pub fn sample(&self, rng: &mut dyn RngCore) -> usize {
+        match self {
+            Self::Fixed(vlen) => *vlen,
+            Self::Uniform(dist) => dist.sample(rng),
+            Self::Normal { dist, max } => (dist.sample(rng).round().max(0.0) as usize).min(*max),
+            Self::Weighted { sizes, dist } => sizes[dist.sample(rng)],
+            Self::Classes { sizes, dist, .. } => sizes[dist.sample(rng)],
+        }
+    }
+}
+
+impl Keyspace {
+    pub fn new(config: &Config, keyspace: &config::Keyspace, name: &str) -> Self {
+        let vlen = ValueLength::new(config, keyspace);
+        let max_vlen = keyspace
+            .vlen_distribution()
+            .map(|vlen| vlen.max())
+            .unwrap_or(keyspace.vlen().unwrap_or(0));
+
+        // we estimate the fraction of each value that needs to be random to
+        // achieve the compression ratio using the largest possible value
+        let value_random_fraction = match keyspace.vmode() {
+            ValueMode::Random => 1.0,
+            ValueMode::Zeros => 0.0,
+            ValueMode::Compressible(_) if max_vlen == 0 => 1.0,
+            ValueMode::Compressible(fraction) => {
+                estimate_random_bytes_needed(max_vlen, 1.0 / fraction) as f64 / max_vlen as f64
+            }
+        };
 
         // nkeys must be >= 1
         let nkeys = std::cmp::max(1, keyspace.nkeys());
-        let klen = keyspace.klen();
+        let klen = KeyLength::new(keyspace);
 
         // initialize a PRNG with the default initial seed
         let mut rng = Xoshiro512PlusPlus::from_seed(config.general().initial_seed());
@@ -568,22 +1074,59 @@ impl Keyspace {
         rng.fill_bytes(&mut raw_seed);
         let inner_key_seed = Seed512(raw_seed);
 
+        // generate the seed for the value pool PRNG
+        let mut raw_seed = [0_u8; 64];
+        rng.fill_bytes(&mut raw_seed);
+        let value_seed = Seed512(raw_seed);
+
         // we use a predictable seed to generate the keys in the keyspace
         let mut rng = Xoshiro512PlusPlus::from_seed(key_seed);
-        let mut keys = HashSet::with_capacity(nkeys);
-        while keys.len() < nkeys {
-            let key = (&mut rng)
-                .sample_iter(&Alphanumeric)
-                .take(klen)
-                .collect::<Vec<u8>>();
-            let _ = keys.insert(key);
-        }
-        let keys = keys.drain().map(|k| k.into()).collect();
+        let prefix = keyspace.key_prefix().into_bytes();
+        let keys: Vec<Arc<[u8]>> = if let Some(path) = keyspace.key_file() {
+            load_keys(path)
+                .iter()
+                .map(|key| [prefix.as_slice(), &key[..]].concat().into())
+                .collect()
+        } else {
+            // the keys keep the order they were generated in, so that the
+            // keys are the same for each run with the same seed. A length
+            // which has run out of distinct keys is drawn again
+            let mut unique = HashSet::with_capacity(nkeys);
+            let mut keys = Vec::with_capacity(nkeys);
+            while keys.len() < nkeys {
+                let mut key = prefix.clone();
+                let len = klen.sample(&mut rng);
+                key.extend((&mut rng).sample_iter(&Alphanumeric).take(len));
+                let key: Arc<[u8]> = key.into();
+                if unique.insert(key.clone()) {
+                    keys.push(key);
+                }
+            }
+            keys
+        };
+        let nkeys = keys.len();
         let key_dist = match keyspace.key_distribution() {
+            // a single key cannot be split into a hot and a cold set
+            config::Distribution::Uniform if nkeys > 1 && keyspace.hot_set().is_some() => {
+                let hot_set = keyspace.hot_set().unwrap();
+                let hot = (nkeys as f64 * hot_set.fraction()).round() as usize;
+
+                Distribution::HotSet {
+                    len: nkeys,
+                    hot: hot.clamp(1, nkeys - 1),
+                    traffic: hot_set.traffic(),
+                    interval: hot_set.interval(),
+                    start: std::time::Instant::now(),
+                }
+            }
             config::Distribution::Uniform => Distribution::Uniform(Uniform::new(0, nkeys)),
             config::Distribution::Zipf => {
-                Distribution::Zipf(ZipfDistribution::new(nkeys, 1.0).unwrap())
+                Distribution::Zipf(ZipfDistribution::new(nkeys, keyspace.zipf_exponent()).unwrap())
             }
+            config::Distribution::Sequential => Distribution::Sequential {
+                next: Arc::new(AtomicUsize::new(0)),
+                len: nkeys,
+            },
         };
 
         let nkeys = keyspace.inner_keys_nkeys().unwrap_or(1);
@@ -606,16 +1149,21 @@ impl Keyspace {
         let mut command_weights = Vec::new();
 
         for command in keyspace.commands() {
-            commands.push(*command);
-            command_weights.push(command.weight());
+            // zero-weight commands would never be selected, exclude them from
+            // the command distribution entirely
+            if command.weight() > 0 {
+                commands.push(*command);
+                command_weights.push(command.weight());
+            }
 
             // validate that the keyspace is adaquately specified for the given
             // verb
 
             // commands that set generated values need a `vlen`
             if keyspace.vlen().is_none()
+                && keyspace.vlen_distribution().is_none()
                 && keyspace.vkind() == ValueKind::Bytes
-                && matches!(command.verb(), Verb::Set | Verb::HashSet)
+                && matches!(command.verb(), Verb::Set | Verb::Cas | Verb::HashSet)
             {
                 eprintln!(
                     "verb: {:?} requires that the keyspace has a `vlen` set when `vkind` is `bytes`",
@@ -624,12 +1172,6 @@ impl Keyspace {
                 std::process::exit(2);
             }
 
-            // cardinality must always be > 0
-            if command.cardinality() == 0 {
-                eprintln!("cardinality must not be zero",);
-                std::process::exit(2);
-            }
-
             // not all commands support cardinality > 1
             if command.cardinality() > 1 && !command.verb().supports_cardinality() {
                 eprintln!(
@@ -639,6 +1181,13 @@ impl Keyspace {
                 std::process::exit(2);
             }
 
+            // there is no expiration option for a multi-key set
+            if command.cardinality() > 1 && command.verb() == Verb::Set && keyspace.ttl().is_some()
+            {
+                eprintln!("verb: Set with a `cardinality` above `1` does not support a `ttl`");
+                std::process::exit(2);
+            }
+
             if command.start().is_some() && !command.verb().supports_start() {
                 eprintln!(
                     "verb: {:?} does not support the `start` argument",
@@ -660,6 +1209,24 @@ impl Keyspace {
                 );
             }
 
+            if command.count().is_some() || command.max_iterations().is_some() {
+                if command.count() == Some(0) || command.max_iterations() == Some(0) {
+                    eprintln!("count and max_iterations must be >= 1");
+                    std::process::exit(2);
+                }
+
+                if (command.count().is_some() && !command.verb().supports_count())
+                    || (command.max_iterations().is_some()
+                        && !command.verb().supports_max_iterations())
+                {
+                    eprintln!(
+                        "verb: {:?} does not support count or max_iterations",
+                        command.verb()
+                    );
+                    std::process::exit(2);
+                }
+            }
+
             if command.truncate().is_some() {
                 // truncate must be >= 1
                 if command.truncate().unwrap() == 0 {
@@ -687,6 +1254,21 @@ impl Keyspace {
 
         let command_dist = WeightedAliasIndex::new(command_weights).unwrap();
 
+        // values are produced by copying from a pool of pseudorandom bytes at a
+        // random offset, which avoids filling every value with fresh random
+        // data. The pool is larger than the largest value so that values will
+        // differ from each other.
+        let mut rng = Xoshiro512PlusPlus::from_seed(value_seed);
+        let mut value_pool = vec![0_u8; max_vlen + VALUE_POOL_PADDING];
+        rng.fill_bytes(&mut value_pool);
+
+        // if a ttl range is given, ttls are sampled in milliseconds between the
+        // `ttl` and `ttl_max` values
+        let ttl_dist = keyspace.ttl_max().map(|ttl_max| {
+            let ttl_min = keyspace.ttl().unwrap_or_default();
+            Uniform::new_inclusive(ttl_min.as_millis() as u64, ttl_max.as_millis() as u64)
+        });
+
         Self {
             keys,
             key_dist,
@@ -694,36 +1276,111 @@ impl Keyspace {
             command_dist,
             inner_keys,
             inner_key_dist,
-            vlen: keyspace.vlen().unwrap_or(0),
+            vlen,
             vkind: keyspace.vkind(),
-            value_random_bytes,
+            value_random_fraction,
+            value_pool: value_pool.into(),
             ttl: keyspace.ttl(),
+            ttl_dist,
+            verify: keyspace.verify(),
+            prefix,
+            sent: Arc::new(
+                MetricBuilder::new(keyspace_sent_counter(name))
+                    .description("requests sent for the keyspace")
+                    .build(Counter::new()),
+            ),
+            dropped: Arc::new(
+                MetricBuilder::new(keyspace_dropped_counter(name))
+                    .description("requests dropped for the keyspace")
+                    .build(Counter::new()),
+            ),
         }
     }
 
+    /// Prepends the key prefix to a key which was not generated by the
+    /// keyspace, such as a key from a trace.
+    pub fn with_prefix(&self, key: &[u8]) -> Arc<[u8]> {
+        [self.prefix.as_slice(), key].concat().into()
+    }
+
     pub fn sample(&self, rng: &mut dyn RngCore) -> Arc<[u8]> {
         let index = self.key_dist.sample(rng);
         self.keys[index].clone()
     }
 
+    pub fn keys(&self) -> &[Arc<[u8]>] {
+        &self.keys
+    }
+
     pub fn sample_inner(&self, rng: &mut dyn RngCore) -> Arc<[u8]> {
         let index = self.inner_key_dist.sample(rng);
         self.inner_keys[index].clone()
     }
 
     pub fn gen_value(&self, rng: &mut dyn RngCore) -> Vec<u8> {
-        match self.vkind {
+        let vlen = self.vlen.sample(rng);
+        self.gen_value_with_len(vlen, rng)
+    }
+
+    fn gen_value_with_len(&self, vlen: usize, rng: &mut dyn RngCore) -> Vec<u8> {
+        let value = match self.vkind {
             ValueKind::I64 => format!("{}", rng.gen::<i64>()).into_bytes(),
             ValueKind::Bytes => {
-                let mut buf = vec![0_u8; self.vlen];
-                rng.fill(&mut buf[0..self.value_random_bytes]);
+                let random_bytes = std::cmp::min(
+                    vlen,
+                    (vlen as f64 * self.value_random_fraction).round() as usize,
+                );
+
+                let mut buf = vec![0_u8; vlen];
+                let offset = rng.gen_range(0..=(self.value_pool.len() - random_bytes));
+                buf[0..random_bytes]
+                    .copy_from_slice(&self.value_pool[offset..(offset + random_bytes)]);
                 buf
             }
+        };
+
+        let _ = REQUEST_VALUE_SIZE.increment(value.len() as u64);
+
+        value
+    }
+
+    /// Generates the value to store for a key. When values are verified, the
+    /// content is derived from the key so that it can be checked on read.
+    pub fn gen_value_for(&self, key: &[u8], rng: &mut dyn RngCore) -> Vec<u8> {
+        let vlen = self.vlen.sample_for(key, rng);
+
+        if !self.verify {
+            return self.gen_value_with_len(vlen, rng);
         }
+
+        let value = verified_value(key, vlen);
+
+        let _ = REQUEST_VALUE_SIZE.increment(value.len() as u64);
+
+        value
     }
 
-    pub fn ttl(&self) -> Option<Duration> {
-        self.ttl
+    /// The value size class of a request, which is only known for the
+    /// requests for a single key.
+    pub fn value_class(&self, request: &ClientRequest) -> Option<usize> {
+        let key = match request {
+            ClientRequest::Add(r) => &r.key,
+            ClientRequest::Get(r) => &r.key,
+            ClientRequest::Delete(r) => &r.key,
+            ClientRequest::Replace(r) => &r.key,
+            ClientRequest::Set(r) => &r.key,
+            ClientRequest::Cas(r) => &r.key,
+            _ => return None,
+        };
+
+        self.vlen.class_id(key)
+    }
+
+    pub fn gen_ttl(&self, rng: &mut dyn RngCore) -> Option<Duration> {
+        match &self.ttl_dist {
+            Some(dist) => Some(Duration::from_millis(dist.sample(rng))),
+            None => self.ttl,
+        }
     }
 }
 
@@ -761,6 +1418,7 @@ pub async fn reconnect(work_sender: Sender<ClientWorkItem>, config: Config) -> R
     while RUNNING.load(Ordering::Relaxed) {
         match ratelimiter.try_wait() {
             Ok(_) => {
+                debug!(event = "reconnect", reason = "ratelimit"; "reconnecting a connection");
                 let _ = work_sender.send(ClientWorkItem::Reconnect).await;
             }
             Err(d) => {
@@ -774,7 +1432,7 @@ pub async fn reconnect(work_sender: Sender<ClientWorkItem>, config: Config) -> R
 
 #[derive(Clone)]
 pub struct Ratelimit {
-    limits: Vec<u64>,
+    limits: Vec<(u64, Duration)>,
     interval: Duration,
     ramp_completion_action: RampCompletionAction,
     current_idx: usize,
@@ -784,31 +1442,98 @@ impl Ratelimit {
     pub fn new(config: &Config) -> Option<Self> {
         let ratelimit_config = config.workload().ratelimit();
 
-        if !ratelimit_config.is_dynamic() {
+        // an adaptive ratelimit follows the latency, see `adaptive()`
+        if !ratelimit_config.is_dynamic() || ratelimit_config.adaptive().is_some() {
             return None;
         }
 
-        // Unwrapping values is safe since the structure has already been
-        // validated for dynamic ratelimit parameters
-        let start: u64 = ratelimit_config.start().unwrap().into();
-        let end = ratelimit_config.end().unwrap();
-        let step = ratelimit_config.step().unwrap() as usize;
-        let interval = ratelimit_config.interval().unwrap();
-        let ramp_type = ratelimit_config.ramp_type();
-        let ramp_completion_action = ratelimit_config.ramp_completion_action();
+        let mut ramp_completion_action = ratelimit_config.ramp_completion_action();
 
-        // Store all the ratelimits to test in a vector
-        let nsteps = ((end - start) as usize / step) + 1;
-        let mut limits: Vec<u64> = Vec::with_capacity(nsteps);
-        for i in (start..end + 1).step_by(step) {
-            limits.push(i);
-        }
+        if let Some(shape) = ratelimit_config.shape() {
+            // A shape is a single period which repeats for the whole test
+            ramp_completion_action = RampCompletionAction::Loop;
+
+            // Unwrapping is safe since a shape requires the start rate
+            let base: u64 = ratelimit_config.start().unwrap().into();
+
+            let limits = match shape {
+                Shape::Sine { amplitude, period } => {
+                    // The refill rate is recomputed at each tick
+                    let ticks = period * SHAPE_TICKS_PER_SECOND;
+                    let tick = Duration::from_secs(1) / SHAPE_TICKS_PER_SECOND as u32;
 
-        // Shuffle the order of ratelimits if specified
-        if ramp_type == RampType::Shuffled {
-            limits.shuffle(&mut thread_rng());
+                    (0..ticks)
+                        .map(|t| {
+                            let phase = 2.0 * std::f64::consts::PI * t as f64 / ticks as f64;
+                            let rate = base as f64 + amplitude as f64 * phase.sin();
+                            (rate.round() as u64, tick)
+                        })
+                        .collect()
+                }
+                Shape::Burst {
+                    rate,
+                    duration,
+                    period,
+                } => vec![
+                    (rate, Duration::from_secs(duration)),
+                    (base, Duration::from_secs(period - duration)),
+                ],
+            };
+
+            return Some(Ratelimit {
+                interval: Duration::from_secs(0),
+                limits,
+                ramp_completion_action,
+                current_idx: 0,
+            });
         }
 
+        // Store all the ratelimits to test in a vector, along with how long
+        // each one is held for
+        let mut limits: Vec<(u64, Duration)> = if !ratelimit_config.schedule().is_empty() {
+            ratelimit_config
+                .schedule()
+                .iter()
+                .map(|step| (step.rate(), step.duration()))
+                .collect()
+        } else if let Some(linear) = ratelimit_config.linear() {
+            // A linear ramp is approximated by updating the ratelimit once per
+            // second
+            let from = linear.from() as i128;
+            let to = linear.to() as i128;
+            let over = linear.over() as i128;
+
+            (0..=over)
+                .map(|t| {
+                    let rate = from + (to - from) * t / over;
+                    (rate as u64, Duration::from_secs(1))
+                })
+                .collect()
+        } else {
+            // Unwrapping values is safe since the structure has already been
+            // validated for dynamic ratelimit parameters
+            let start: u64 = ratelimit_config.start().unwrap().into();
+            let end = ratelimit_config.end().unwrap();
+            let step = ratelimit_config.step().unwrap() as usize;
+            let interval = ratelimit_config.interval().unwrap();
+            let ramp_type = ratelimit_config.ramp_type();
+
+            let nsteps = ((end - start) as usize / step) + 1;
+            let mut limits: Vec<(u64, Duration)> = Vec::with_capacity(nsteps);
+            for i in (start..end + 1).step_by(step) {
+                limits.push((i, interval));
+            }
+
+            // Shuffle the order of ratelimits if specified
+            if ramp_type == RampType::Shuffled {
+                limits.shuffle(&mut Xoshiro512PlusPlus::from_seed(
+                    config.general().initial_seed(),
+                ));
+            }
+
+            limits
+        };
+
         // If the test is to be mirrored, store the ratelimits in reverse
         // order in the vector as well
         if ramp_completion_action == RampCompletionAction::Mirror {
@@ -818,19 +1543,21 @@ impl Ratelimit {
         }
 
         Some(Ratelimit {
+            interval: limits[0].1,
             limits,
-            interval,
             ramp_completion_action,
             current_idx: 0,
         })
     }
 
+    /// The length of time the most recently returned ratelimit is held for.
     pub fn interval(&self) -> Duration {
         self.interval
     }
 
     pub fn next_ratelimit(&mut self) -> u64 {
-        let limit = self.limits[self.current_idx];
+        let (limit, interval) = self.limits[self.current_idx];
+        self.interval = interval;
         self.current_idx += 1;
 
         if self.current_idx == self.limits.len() {
@@ -850,6 +1577,34 @@ impl Ratelimit {
     }
 }
 
+/// Returns the value of the given length which is stored for a key when values
+/// are verified. The value only depends on the key and the length, so there is
+/// no need to keep track of the values which have been written.
+pub fn verified_value(key: &[u8], len: usize) -> Vec<u8> {
+    // FNV-1a, which is stable across runs unlike the hashers used for maps
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    let mut rng = Xoshiro512PlusPlus::seed_from_u64(hash);
+    let mut value = vec![0_u8; len];
+    rng.fill_bytes(&mut value);
+    value
+}
+
+/// Checks a value which was read back for a key, recording the result.
+pub fn verify_value(key: &[u8], value: &[u8]) -> bool {
+    if value == verified_value(key, value.len()) {
+        GET_VERIFIED.increment();
+        true
+    } else {
+        GET_MISMATCH.increment();
+        false
+    }
+}
+
 fn estimate_random_bytes_needed(length: usize, compression_ratio: f64) -> usize {
     // if compression ratio is low, all bytes should be random
     if compression_ratio <= 1.0 {
@@ -891,3 +1646,552 @@ fn estimate_random_bytes_needed(length: usize, compression_ratio: f64) -> usize
 
     best
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(ratelimit: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [general]
+            protocol = "ping"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            initial_seed = "0"
+
+            [debug]
+
+            [target]
+            endpoints = ["127.0.0.1:12321"]
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+            {ratelimit}
+            "#
+        ))
+        .unwrap()
+    }
+
+    // the next ratelimits from the schedule, along with how long each is held
+    fn limits(ratelimit: &mut Ratelimit, n: usize) -> Vec<(u64, Duration)> {
+        (0..n)
+            .map(|_| {
+                let limit = ratelimit.next_ratelimit();
+                (limit, ratelimit.interval())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ratelimit_static() {
+        assert!(Ratelimit::new(&config("start = 1000")).is_none());
+    }
+
+    #[test]
+    fn ratelimit_schedule() {
+        let mut ratelimit = Ratelimit::new(&config(
+            r#"
+            schedule = [
+                { rate = 100, duration = 10 },
+                { rate = 500, duration = 5 },
+            ]
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(ratelimit.interval(), Duration::from_secs(10));
+
+        // the last step is held once the schedule is complete
+        assert_eq!(
+            limits(&mut ratelimit, 4),
+            vec![
+                (100, Duration::from_secs(10)),
+                (500, Duration::from_secs(5)),
+                (500, Duration::from_secs(5)),
+                (500, Duration::from_secs(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn ratelimit_schedule_loop() {
+        let mut ratelimit = Ratelimit::new(&config(
+            r#"
+            on_ramp_completion = "loop"
+            schedule = [
+                { rate = 100, duration = 10 },
+                { rate = 500, duration = 5 },
+            ]
+            "#,
+        ))
+        .unwrap();
+
+        let rates: Vec<u64> = limits(&mut ratelimit, 5).iter().map(|l| l.0).collect();
+        assert_eq!(rates, vec![100, 500, 100, 500, 100]);
+    }
+
+    #[test]
+    fn ratelimit_linear() {
+        let mut ratelimit =
+            Ratelimit::new(&config("linear = { from = 100, to = 200, over = 4 }")).unwrap();
+
+        // the rate is updated once per second, ending on the final rate
+        let one = Duration::from_secs(1);
+        assert_eq!(
+            limits(&mut ratelimit, 6),
+            vec![
+                (100, one),
+                (125, one),
+                (150, one),
+                (175, one),
+                (200, one),
+                (200, one),
+            ]
+        );
+    }
+
+    #[test]
+    fn ratelimit_linear_down() {
+        let mut ratelimit =
+            Ratelimit::new(&config("linear = { from = 300, to = 100, over = 2 }")).unwrap();
+
+        let rates: Vec<u64> = limits(&mut ratelimit, 3).iter().map(|l| l.0).collect();
+        assert_eq!(rates, vec![300, 200, 100]);
+    }
+
+    #[test]
+    fn ratelimit_linear_mirror() {
+        let mut ratelimit = Ratelimit::new(&config(
+            r#"
+            on_ramp_completion = "mirror"
+            linear = { from = 100, to = 300, over = 2 }
+            "#,
+        ))
+        .unwrap();
+
+        let rates: Vec<u64> = limits(&mut ratelimit, 7).iter().map(|l| l.0).collect();
+        assert_eq!(rates, vec![100, 200, 300, 300, 200, 100, 100]);
+    }
+
+    #[test]
+    fn ratelimit_sine() {
+        let mut ratelimit = Ratelimit::new(&config(
+            r#"
+            start = 1000
+            shape = { type = "sine", amplitude = 100, period = 2 }
+            "#,
+        ))
+        .unwrap();
+
+        let limits = limits(&mut ratelimit, 21);
+        let tick = Duration::from_secs(1) / SHAPE_TICKS_PER_SECOND as u32;
+
+        assert!(limits.iter().all(|(_, interval)| *interval == tick));
+        assert_eq!(limits[0].0, 1000);
+        assert_eq!(limits[5].0, 1100);
+        assert_eq!(limits[10].0, 1000);
+        assert_eq!(limits[15].0, 900);
+        // the shape repeats after each period
+        assert_eq!(limits[20].0, 1000);
+    }
+
+    #[test]
+    fn ratelimit_burst() {
+        let mut ratelimit = Ratelimit::new(&config(
+            r#"
+            start = 1000
+            shape = { type = "burst", rate = 5000, duration = 2, period = 10 }
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            limits(&mut ratelimit, 3),
+            vec![
+                (5000, Duration::from_secs(2)),
+                (1000, Duration::from_secs(8)),
+                (5000, Duration::from_secs(2)),
+            ]
+        );
+    }
+
+    // a hot set which has rotated the given number of times
+    fn hot_set(len: usize, hot: usize, traffic: f64, rotations: u32) -> Distribution {
+        let interval = Duration::from_secs(10);
+
+        Distribution::HotSet {
+            len,
+            hot,
+            traffic,
+            interval,
+            // halfway through the interval, so that it does not rotate while
+            // sampling
+            start: std::time::Instant::now() - interval * rotations - interval / 2,
+        }
+    }
+
+    fn samples(distribution: &Distribution) -> Vec<usize> {
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+        (0..10_000).map(|_| distribution.sample(&mut rng)).collect()
+    }
+
+    #[test]
+    fn hot_set_rotation() {
+        // the window moves by the size of the hot set with each rotation
+        for rotations in 0..3 {
+            let offset = rotations as usize * 10;
+
+            let hot = samples(&hot_set(100, 10, 1.0, rotations));
+            assert!(hot.iter().all(|key| (offset..offset + 10).contains(key)));
+
+            let cold = samples(&hot_set(100, 10, 0.0, rotations));
+            assert!(cold
+                .iter()
+                .all(|key| *key < 100 && !(offset..offset + 10).contains(key)));
+        }
+    }
+
+    #[test]
+    fn hot_set_wraps() {
+        // after two rotations the window of 4 keys starts at 8 and wraps
+        let hot = samples(&hot_set(10, 4, 1.0, 2));
+        let keys: HashSet<usize> = hot.into_iter().collect();
+
+        assert_eq!(keys, HashSet::from([8, 9, 0, 1]));
+    }
+
+    #[test]
+    fn hot_set_traffic() {
+        let keys = samples(&hot_set(100, 10, 0.9, 0));
+        let hot = keys.iter().filter(|key| **key < 10).count();
+
+        // 90% of the 10,000 samples are for the hot set
+        assert!((8_700..=9_300).contains(&hot));
+    }
+
+    // a single keyspace which generates values with the given mode
+    fn values(vmode: &str) -> Keyspace {
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [general]
+            protocol = "ping"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            initial_seed = "0"
+
+            [debug]
+
+            [target]
+            endpoints = ["127.0.0.1:12321"]
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+            start = 1000
+
+            [[workload.keyspace]]
+            nkeys = 1
+            klen = 8
+            vlen = 4096
+            vmode = {vmode}
+            commands = [{{ verb = "set" }}]
+            "#
+        ))
+        .unwrap();
+
+        Keyspace::new(&config, &config.workload().keyspaces()[0], "values")
+    }
+
+    // the fraction of the original size a value compresses to with gzip
+    fn compressed_fraction(value: &[u8]) -> f64 {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(value).unwrap();
+        encoder.finish().unwrap().len() as f64 / value.len() as f64
+    }
+
+    // the shannon entropy of a value in bits per byte
+    fn entropy(value: &[u8]) -> f64 {
+        let mut counts = [0_usize; 256];
+        for byte in value {
+            counts[*byte as usize] += 1;
+        }
+
+        counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / value.len() as f64;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn values_compressible() {
+        let keyspace = values("{ compressible = 0.5 }");
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let value = keyspace.gen_value(&mut rng);
+            assert_eq!(value.len(), 4096);
+
+            let fraction = compressed_fraction(&value);
+            assert!((fraction - 0.5).abs() < 0.05, "compressed to {fraction}");
+        }
+    }
+
+    #[test]
+    fn values_random() {
+        let keyspace = values(r#""random""#);
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+
+        let value = keyspace.gen_value(&mut rng);
+        assert_eq!(value.len(), 4096);
+
+        // close to the 8 bits per byte of uniformly random bytes
+        assert!(entropy(&value) > 7.9);
+        assert!(compressed_fraction(&value) > 0.99);
+    }
+
+    #[test]
+    fn values_zeros() {
+        let keyspace = values(r#""zeros""#);
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+
+        let value = keyspace.gen_value(&mut rng);
+        assert_eq!(value.len(), 4096);
+
+        assert!(value.iter().all(|byte| *byte == 0));
+        assert_eq!(entropy(&value), 0.0);
+        assert!(compressed_fraction(&value) < 0.05);
+    }
+
+    // a config with the extra general settings and a single keyspace
+    fn keyspace_config(general: &str, keyspace: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [general]
+            protocol = "memcache"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            {general}
+
+            [debug]
+
+            [target]
+            endpoints = ["127.0.0.1:12321"]
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+
+            [[workload.keyspace]]
+            {keyspace}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn max_requests() {
+        let config = keyspace_config(
+            "max_requests = 25",
+            r#"
+            nkeys = 100
+            klen = 8
+            commands = [{ verb = "get" }]
+            "#,
+        );
+        let generator = Generator::new(&config);
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+
+        let (client_sender, client_receiver) = async_channel::bounded(100);
+        let (pubsub_sender, _pubsub_receiver) = async_channel::bounded(100);
+
+        // the generator is done once the budget is used up
+        let mut generated = 0;
+        while generator.generate(&client_sender, &pubsub_sender, &mut rng) {
+            generated += 1;
+            assert!(generated <= 25);
+        }
+
+        assert_eq!(generated, 25);
+        assert_eq!(client_receiver.len(), 25);
+        assert!(!generator.generate(&client_sender, &pubsub_sender, &mut rng));
+    }
+
+    // the first requests generated from the seed, without their sequence
+    // numbers which are shared by all generators
+    fn first_requests(seed: &str, count: usize) -> Vec<ClientRequest> {
+        let config = keyspace_config(
+            &format!("initial_seed = {seed:?}"),
+            r#"
+            nkeys = 1000
+            klen = 16
+            vlen = 32
+            commands = [{ verb = "get", weight = 2 }, { verb = "set", weight = 1 }]
+            "#,
+        );
+        let generator = Generator::new(&config);
+
+        // each workload thread is seeded from the initial seed
+        let mut seed = [0; 64];
+        Xoshiro512PlusPlus::from_seed(config.general().initial_seed()).fill_bytes(&mut seed);
+        let mut rng = Xoshiro512PlusPlus::from_seed(Seed512(seed));
+
+        let (client_sender, client_receiver) = async_channel::bounded(count);
+        let (pubsub_sender, _pubsub_receiver) = async_channel::bounded(count);
+
+        for _ in 0..count {
+            assert!(generator.generate(&client_sender, &pubsub_sender, &mut rng));
+        }
+
+        (0..count)
+            .map(|_| match client_receiver.try_recv().unwrap() {
+                ClientWorkItem::Request { request, .. } => request,
+                ClientWorkItem::Reconnect => panic!("unexpected reconnect"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seed_reproducible() {
+        let requests = first_requests("reproducible", 100);
+
+        assert_eq!(requests, first_requests("reproducible", 100));
+        assert_ne!(requests, first_requests("different", 100));
+
+        // both commands are in the sequence
+        assert!(requests.iter().any(|r| matches!(r, ClientRequest::Get(_))));
+        assert!(requests.iter().any(|r| matches!(r, ClientRequest::Set(_))));
+    }
+
+    // a ping server which takes 50ms to respond to each request
+    fn slow_server() -> String {
+        use std::io::BufReader;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+
+                    while reader.read_line(&mut line).is_ok_and(|len| len > 0) {
+                        std::thread::sleep(Duration::from_millis(50));
+                        if stream.write_all(b"PONG\r\n").is_err() {
+                            return;
+                        }
+                        line.clear();
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    // generates requests for half a second at a rate far beyond what a single
+    // connection to the slow server sustains
+    fn overload(overflow: &str) {
+        let endpoint = slow_server();
+        let config: Config = toml::from_str(
+            &config::testing::config(
+                "ping",
+                &endpoint,
+                r#"
+                [[workload.keyspace]]
+                commands = [{ verb = "ping" }]
+                "#,
+            )
+            .replace(
+                "[workload.ratelimit]",
+                &format!("queue_depth = 2\noverflow = {overflow:?}\n\n[workload.ratelimit]"),
+            ),
+        )
+        .unwrap();
+
+        let generator = Generator::new(&config);
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+
+        let (client_sender, client_receiver) =
+            async_channel::bounded(config.workload().queue_depth());
+        let (pubsub_sender, _pubsub_receiver) = async_channel::bounded(1);
+        let client_runtime = crate::clients::launch_clients(&config, client_receiver).unwrap();
+
+        let start = std::time::Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            assert!(generator.generate(&client_sender, &pubsub_sender, &mut rng));
+            assert!(client_sender.len() <= 2);
+        }
+
+        drop(client_sender);
+        client_runtime.shutdown_timeout(Duration::from_millis(100));
+    }
+
+    #[test]
+    fn overflow_drop() {
+        let dropped = REQUEST_DROPPED.value();
+        overload("drop");
+
+        // the ratelimit allows hundreds of requests, but only about ten are
+        // serviced
+        assert!(REQUEST_DROPPED.value() >= dropped + 100);
+    }
+
+    #[test]
+    fn overflow_block() {
+        let blocked = REQUEST_BLOCKED.value();
+        overload("block");
+
+        assert!(REQUEST_BLOCKED.value() > blocked);
+    }
+
+    // the lengths of the keys generated for the keyspace
+    fn key_lengths(klen_distribution: &str) -> Vec<usize> {
+        let config = keyspace_config(
+            "",
+            &format!(
+                r#"
+                nkeys = 1000
+                klen_distribution = {klen_distribution}
+                commands = [{{ verb = "get" }}]
+                "#
+            ),
+        );
+
+        match &Generator::new(&config).components()[0] {
+            Component::Keyspace(keyspace) => keyspace.keys().iter().map(|key| key.len()).collect(),
+            _ => panic!("expected a keyspace"),
+        }
+    }
+
+    #[test]
+    fn klen_distribution() {
+        let lengths: HashSet<usize> = key_lengths(r#"{ type = "uniform", min = 4, max = 8 }"#)
+            .into_iter()
+            .collect();
+        assert_eq!(lengths, (4..=8).collect());
+
+        // a size without any weight is never picked
+        let lengths =
+            key_lengths(r#"{ type = "weighted", sizes = [6, 12, 20], weights = [3, 1, 0] }"#);
+        assert_eq!(lengths.len(), 1000);
+        assert!(lengths.iter().all(|len| *len == 6 || *len == 12));
+
+        // the sizes are picked in proportion to their weights
+        let short = lengths.iter().filter(|len| **len == 6).count();
+        assert!((650..=850).contains(&short), "{short}");
+    }
+}
@@ -0,0 +1,256 @@
+use super::*;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+/// Set while the keyspaces are preloaded, so that the latencies of the
+/// preload are not recorded.
+pub static PRELOADING: AtomicBool = AtomicBool::new(false);
+
+// how often the preload progress is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes the keys of each keyspace which has a preload configured, using
+/// clients which are only used for the preload. Each request waits for a
+/// client, so the preload runs as fast as the backend accepts writes without
+/// dropping any. Returns `false` if the preload was interrupted, after
+/// reporting where each keyspace can be resumed from.
+pub fn preload(config: &Config, generator: &Generator) -> bool {
+    // the keyspace name, the keyspace, and the range of keys to write
+    let mut ranges = Vec::new();
+
+    let keyspaces = generator.components().iter().filter_map(|c| match c {
+        Component::Keyspace(keyspace) => Some(keyspace),
+        _ => None,
+    });
+
+    for (id, (keyspace, component)) in config
+        .workload()
+        .keyspaces()
+        .iter()
+        .zip(keyspaces)
+        .enumerate()
+    {
+        if let Some(preload) = keyspace.preload() {
+            let len = component.keys().len();
+            let start = preload.start().min(len);
+            let end = preload
+                .count()
+                .map(|count| start.saturating_add(count).min(len))
+                .unwrap_or(len);
+
+            ranges.push((
+                config.workload().keyspace_name(id),
+                component.clone(),
+                start,
+                end,
+            ));
+        }
+    }
+
+    let total: usize = ranges.iter().map(|(_, _, start, end)| end - start).sum();
+
+    output!("Preload: writing {total} keys");
+
+    let (client_sender, client_receiver) = bounded(config.workload().queue_depth());
+
+    let client_runtime = match launch_clients(config, client_receiver) {
+        Some(runtime) => runtime,
+        None => return true,
+    };
+
+    PRELOADING.store(true, Ordering::Relaxed);
+
+    let sent = Arc::new(AtomicUsize::new(0));
+
+    // the position of each keyspace, so an interrupted preload can be resumed
+    let positions: Arc<Vec<AtomicUsize>> = Arc::new(
+        ranges
+            .iter()
+            .map(|(_, _, start, _)| AtomicUsize::new(*start))
+            .collect(),
+    );
+
+    let writer = {
+        let client_sender = client_sender.clone();
+        let ranges = ranges.clone();
+        let sent = sent.clone();
+        let positions = positions.clone();
+        let mut rng = Xoshiro512PlusPlus::from_seed(config.general().initial_seed());
+
+        std::thread::spawn(move || {
+            for (position, (_, keyspace, start, end)) in positions.iter().zip(ranges.iter()) {
+                for index in *start..*end {
+                    if SHUTDOWN.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let key = keyspace.keys()[index].clone();
+
+                    let work_item = ClientWorkItem::Request {
+                        request: ClientRequest::Set(client::Set {
+                            value: keyspace.gen_value_for(&key, &mut rng),
+                            key,
+                            ttl: keyspace.gen_ttl(&mut rng),
+                        }),
+                        sequence: SEQUENCE_NUMBER.fetch_add(1, Ordering::Relaxed),
+                        intended: None,
+                        // the latencies of the preload are not recorded
+                        class: None,
+                    };
+
+                    // the send only fails once the clients have shutdown
+                    if client_sender.send_blocking(work_item).is_err() {
+                        return;
+                    }
+
+                    position.store(index + 1, Ordering::Relaxed);
+                    sent.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        })
+    };
+
+    let started = Instant::now();
+
+    while !writer.is_finished() {
+        std::thread::sleep(PROGRESS_INTERVAL);
+
+        let sent = sent.load(Ordering::Relaxed);
+
+        output!(
+            "Preload: {sent}/{total} keys ({:.1} %) Rate (/s): {:.2}",
+            100.0 * sent as f64 / total.max(1) as f64,
+            sent as f64 / started.elapsed().as_secs_f64()
+        );
+    }
+
+    let _ = writer.join();
+
+    // give the outstanding requests time to complete
+    while !client_sender.is_empty() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    if let Some(client) = config.client() {
+        std::thread::sleep(client.request_timeout());
+    }
+
+    client_runtime.shutdown_timeout(Duration::from_millis(100));
+
+    // the connections were dropped without being closed by the clients
+    CONNECT_CURR.set(0);
+
+    PRELOADING.store(false, Ordering::Relaxed);
+
+    if SHUTDOWN.load(Ordering::Relaxed) {
+        for (position, (name, _, _, end)) in positions.iter().zip(ranges.iter()) {
+            let position = position.load(Ordering::Relaxed);
+
+            if position < *end {
+                output!(
+                    "Preload: Keyspace {name} interrupted, resume with `preload = {{ start = {position} }}`"
+                );
+            }
+        }
+
+        return false;
+    }
+
+    output!(
+        "Preload: complete, wrote {total} keys in {:.2}s",
+        started.elapsed().as_secs_f64()
+    );
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::{BufReader, Read};
+    use std::sync::Mutex;
+
+    // an in-process memcache store which keeps the keys that were set
+    fn store() -> (String, Arc<Mutex<HashSet<Vec<u8>>>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let keys = Arc::new(Mutex::new(HashSet::new()));
+
+        let stored = keys.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let keys = stored.clone();
+
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+
+                    while reader.read_line(&mut line).is_ok_and(|len| len > 0) {
+                        let fields: Vec<&str> = line.split_whitespace().collect();
+                        assert_eq!(fields[0], "set");
+
+                        let mut value = vec![0; fields[4].parse::<usize>().unwrap() + 2];
+                        reader.read_exact(&mut value).unwrap();
+                        keys.lock().unwrap().insert(fields[1].as_bytes().to_vec());
+
+                        if stream.write_all(b"STORED\r\n").is_err() {
+                            return;
+                        }
+                        line.clear();
+                    }
+                });
+            }
+        });
+
+        (addr, keys)
+    }
+
+    // preloads the keyspace into a store, returning the keys of the keyspace
+    // and the keys which were stored
+    fn run(preload: &str) -> (Vec<Vec<u8>>, HashSet<Vec<u8>>) {
+        let (endpoint, stored) = store();
+        let config: Config = toml::from_str(&config::testing::config(
+            "memcache",
+            &endpoint,
+            &format!(
+                r#"
+                [[workload.keyspace]]
+                nkeys = 100
+                klen = 16
+                vlen = 32
+                preload = {preload}
+                commands = [{{ verb = "get" }}]
+                "#
+            ),
+        ))
+        .unwrap();
+
+        let generator = Generator::new(&config);
+        assert!(super::preload(&config, &generator));
+
+        let keys = match &generator.components()[0] {
+            Component::Keyspace(keyspace) => keyspace.keys().iter().map(|k| k.to_vec()).collect(),
+            _ => panic!("expected a keyspace"),
+        };
+        let stored = stored.lock().unwrap().clone();
+
+        (keys, stored)
+    }
+
+    #[test]
+    fn all_keys() {
+        let (keys, stored) = run("{}");
+
+        assert_eq!(stored.len(), 100);
+        assert_eq!(stored, keys.into_iter().collect());
+    }
+
+    #[test]
+    fn resume() {
+        let (keys, stored) = run("{ start = 10, count = 20 }");
+
+        assert_eq!(stored, keys[10..30].iter().cloned().collect());
+    }
+}
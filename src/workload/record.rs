@@ -0,0 +1,116 @@
+use super::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// the recording is shared by the generator threads
+static RECORDING: OnceLock<Recording> = OnceLock::new();
+
+struct Recording {
+    start: Instant,
+    skipped: AtomicU64,
+    // the writer is taken once the recording is finished
+    writer: Mutex<Option<BufWriter<File>>>,
+}
+
+/// Creates the file which the generated requests are recorded to, if one is
+/// configured. Each request is written as a line of a trace, so that the run
+/// can be replayed with the same requests and timing.
+pub fn start(config: &Config) {
+    let path = match config.workload().record() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let file = match File::create(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("failed to create the request recording: {path}\n{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let _ = RECORDING.set(Recording {
+        start: Instant::now(),
+        skipped: AtomicU64::new(0),
+        writer: Mutex::new(Some(BufWriter::new(file))),
+    });
+}
+
+/// Returns the trace line for a request, when the requests are recorded. The
+/// line is only written with `write()` once the request has been queued, so
+/// that dropped requests are not replayed. Requests which cannot be expressed
+/// in a trace are counted instead.
+pub fn line(keyspace: &Keyspace, work_item: &ClientWorkItem) -> Option<String> {
+    let recording = RECORDING.get()?;
+
+    let (verb, key) = match work_item {
+        ClientWorkItem::Request { request, .. } => match request {
+            ClientRequest::Get(r) => ("get", &r.key),
+            ClientRequest::Set(r) => ("set", &r.key),
+            ClientRequest::Add(r) => ("add", &r.key),
+            ClientRequest::Replace(r) => ("replace", &r.key),
+            ClientRequest::Delete(r) => ("delete", &r.key),
+            _ => {
+                recording.skipped.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        },
+        ClientWorkItem::Reconnect => return None,
+    };
+
+    // the replay adds the key prefix back, and the key must fit on one line
+    let key: &[u8] = key;
+    let key = key.strip_prefix(keyspace.prefix.as_slice()).unwrap_or(key);
+    let key = match std::str::from_utf8(key) {
+        Ok(key) if !key.is_empty() && !key.contains(['\n', '\r']) => key,
+        _ => {
+            recording.skipped.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+    };
+
+    Some(format!(
+        "{:.6},{verb},{key}\n",
+        recording.start.elapsed().as_secs_f64()
+    ))
+}
+
+/// Writes the line for a request which was queued.
+pub fn write(line: Option<String>) {
+    if let (Some(line), Some(recording)) = (line, RECORDING.get()) {
+        let mut writer = recording.writer.lock().unwrap();
+
+        if let Some(w) = writer.as_mut() {
+            if let Err(e) = w.write_all(line.as_bytes()) {
+                error!("failed to write to the request recording: {e}");
+                *writer = None;
+            }
+        }
+    }
+}
+
+/// Flushes the recording once the test ends. Any requests which are generated
+/// afterwards are not recorded.
+pub fn finish() {
+    let recording = match RECORDING.get() {
+        Some(recording) => recording,
+        None => return,
+    };
+
+    let writer = recording.writer.lock().unwrap().take();
+
+    if let Some(Err(e)) = writer.map(|mut writer| writer.flush()) {
+        error!("failed to write to the request recording: {e}");
+    }
+
+    let skipped = recording.skipped.load(Ordering::Relaxed);
+
+    if skipped > 0 {
+        warn!(
+            "{skipped} requests could not be recorded, as a trace only has single key get, set, add, replace, and delete requests with utf-8 keys"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use super::*;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::net::SocketAddr;
+
+/// Periodically resolves the hostnames of the endpoints. New connections
+/// always resolve the endpoint, so they pick up any change on their own. When
+/// rebalancing is enabled and the addresses for an endpoint change, the
+/// existing connections are also moved to the new addresses by asking them to
+/// reconnect, spread over one resolve interval.
+///
+/// NOTE: the system resolver does not expose the record TTLs, so the
+/// configured interval is used instead.
+pub async fn resolve(work_sender: Sender<ClientWorkItem>, config: Config) {
+    resolve_with(work_sender, config, |endpoint| async move {
+        tokio::net::lookup_host(endpoint)
+            .await
+            .map(|addrs| addrs.collect())
+    })
+    .await
+}
+
+/// Periodically resolves the hostnames of the endpoints using the provided
+/// lookup function.
+async fn resolve_with<F, Fut>(work_sender: Sender<ClientWorkItem>, config: Config, lookup: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = std::io::Result<BTreeSet<SocketAddr>>>,
+{
+    let interval = match config.target().resolve_interval() {
+        Some(interval) => interval,
+        None => return,
+    };
+
+    // IP literals and unix domain sockets never change
+    let hostnames: Vec<&String> = config
+        .target()
+        .endpoints()
+        .iter()
+        .filter(|endpoint| {
+            !endpoint.starts_with("unix://") && endpoint.parse::<SocketAddr>().is_err()
+        })
+        .collect();
+
+    if hostnames.is_empty() {
+        return;
+    }
+
+    let connections = config
+        .client()
+        .map(|client| client.poolsize() * config.target().endpoints().len())
+        .unwrap_or(0);
+
+    let mut known: HashMap<&String, BTreeSet<SocketAddr>> = HashMap::new();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        let mut changed = false;
+
+        for endpoint in &hostnames {
+            let addrs = match lookup(endpoint.to_string()).await {
+                Ok(addrs) => addrs,
+                Err(e) => {
+                    warn!("failed to resolve endpoint: {endpoint}: {e}");
+                    continue;
+                }
+            };
+
+            if let Some(previous) = known.insert(endpoint, addrs.clone()) {
+                if previous != addrs {
+                    info!("endpoint {endpoint} now resolves to: {addrs:?}");
+                    RESOLVE_CHANGED.increment();
+                    changed = true;
+                }
+            }
+        }
+
+        if changed && config.target().resolve_rebalance() && connections > 0 {
+            // reconnect the connections gradually so that the load on the
+            // backend does not drop all at once
+            let delay = interval / connections as u32;
+
+            info!(
+                event = "reconnect",
+                reason = "resolve",
+                connections = connections;
+                "reconnecting {connections} connections to rebalance them"
+            );
+
+            for _ in 0..connections {
+                if !RUNNING.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let _ = work_sender.send(ClientWorkItem::Reconnect).await;
+                tokio::time::sleep(delay).await;
+            }
+        } else {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rebalance: bool) -> Config {
+        let config = config::testing::config("ping", "backend.test:12321", "")
+            .replace("poolsize = 1", "poolsize = 2")
+            .replace(
+                r#"endpoints = ["backend.test:12321"]"#,
+                &format!(
+                    r#"endpoints = ["backend.test:12321", "127.0.0.1:12321"]
+                    resolve_interval = 1
+                    resolve_rebalance = {rebalance}"#
+                ),
+            );
+
+        toml::from_str(&config).unwrap()
+    }
+
+    // resolves the endpoint to a different address on every lookup, counting
+    // the lookups
+    fn changing(
+        lookups: Arc<AtomicUsize>,
+    ) -> impl Fn(String) -> std::future::Ready<std::io::Result<BTreeSet<SocketAddr>>> {
+        move |endpoint| {
+            assert_eq!(endpoint, "backend.test:12321");
+            let n = lookups.fetch_add(1, Ordering::Relaxed) as u8;
+            let addr = SocketAddr::from(([10, 0, 0, n], 12321));
+            std::future::ready(Ok(BTreeSet::from([addr])))
+        }
+    }
+
+    #[tokio::test]
+    async fn changed() {
+        let (sender, receiver) = async_channel::unbounded();
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let changed = RESOLVE_CHANGED.value();
+
+        let task = tokio::spawn(resolve_with(
+            sender,
+            config(false),
+            changing(lookups.clone()),
+        ));
+
+        // only the hostname is resolved, once when starting and then once for
+        // each interval
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        task.abort();
+
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+        assert!(RESOLVE_CHANGED.value() > changed);
+
+        // without rebalancing the existing connections are left alone
+        assert!(receiver.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rebalance() {
+        let (sender, receiver) = async_channel::unbounded();
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let task = tokio::spawn(resolve_with(
+            sender,
+            config(true),
+            changing(lookups.clone()),
+        ));
+
+        // every connection in the pool for each endpoint is reconnected, spread
+        // over the following resolve interval
+        let start = std::time::Instant::now();
+        for _ in 0..4 {
+            let item = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(matches!(item, ClientWorkItem::Reconnect));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(1750));
+
+        task.abort();
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+        assert!(receiver.is_empty());
+    }
+}
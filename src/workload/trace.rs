@@ -0,0 +1,222 @@
+use super::*;
+use std::io::BufReader;
+use std::time::Instant;
+
+/// Replays the requests in the trace file, waiting until each request is due
+/// before sending it. Requests wait for space in the client queue, so requests
+/// fall behind the trace timing when the clients cannot keep up instead of
+/// being dropped. Returns once the trace has been replayed or the test ends.
+pub fn replay(
+    config: &Config,
+    generator: &Generator,
+    client_sender: &Sender<ClientWorkItem>,
+    rng: &mut dyn RngCore,
+) {
+    let trace = config.workload().trace().unwrap();
+
+    // values and ttls are produced by the first keyspace
+    let keyspace = generator
+        .components()
+        .iter()
+        .find_map(|component| match component {
+            Component::Keyspace(keyspace) => Some(keyspace),
+            _ => None,
+        })
+        .unwrap();
+
+    let file = match crate::remote::open(trace.file()) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("failed to open trace file: {}\n{e}", trace.file());
+            std::process::exit(2);
+        }
+    };
+
+    let start = Instant::now();
+    let mut first = None;
+    let mut skipped = 0;
+
+    for line in BufReader::new(file).split(b'\n') {
+        if !RUNNING.load(Ordering::Relaxed) || SHUTDOWN.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!("failed to read trace file: {e}");
+                break;
+            }
+        };
+
+        let (timestamp, request) = match parse(&line, keyspace, rng) {
+            Some(parsed) => parsed,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        // the trace timing is relative to the first request
+        let offset = timestamp - *first.get_or_insert(timestamp);
+        let due = start + Duration::from_secs_f64(offset.max(0.0) / trace.speed());
+
+        let now = Instant::now();
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+
+        let work_item = ClientWorkItem::Request {
+            class: keyspace.value_class(&request),
+            request,
+            sequence: SEQUENCE_NUMBER.fetch_add(1, Ordering::Relaxed),
+            intended: config.workload().corrected_latency().then_some(due),
+        };
+
+        // the send only fails once the clients have shutdown
+        if client_sender.send_blocking(work_item).is_err() {
+            return;
+        }
+    }
+
+    if skipped > 0 {
+        warn!("skipped {skipped} lines of the trace file which could not be parsed");
+    }
+}
+
+/// Parses a line of the trace into the timestamp, in seconds, and the request.
+fn parse(line: &[u8], keyspace: &Keyspace, rng: &mut dyn RngCore) -> Option<(f64, ClientRequest)> {
+    let line = std::str::from_utf8(line).ok()?.trim_end_matches('\r');
+
+    let mut fields = line.splitn(3, ',');
+
+    let timestamp: f64 = fields.next()?.trim().parse().ok()?;
+    let verb = fields.next()?.trim();
+    let key = fields.next()?.as_bytes();
+
+    if !timestamp.is_finite() || key.is_empty() {
+        return None;
+    }
+
+    let key = keyspace.with_prefix(key);
+
+    let request = match verb {
+        "get" => ClientRequest::Get(client::Get {
+            key,
+            verify: keyspace.verify,
+        }),
+        "set" => ClientRequest::Set(client::Set {
+            value: keyspace.gen_value_for(&key, rng),
+            key,
+            ttl: keyspace.gen_ttl(rng),
+        }),
+        "add" => ClientRequest::Add(client::Add {
+            value: keyspace.gen_value_for(&key, rng),
+            key,
+            ttl: keyspace.gen_ttl(rng),
+        }),
+        "replace" => ClientRequest::Replace(client::Replace {
+            value: keyspace.gen_value_for(&key, rng),
+            key,
+            ttl: keyspace.gen_ttl(rng),
+        }),
+        "delete" | "del" => ClientRequest::Delete(client::Delete { key }),
+        _ => return None,
+    };
+
+    Some((timestamp, request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_timing() {
+        let mut trace = tempfile::NamedTempFile::new().unwrap();
+        trace
+            .write_all(b"10.0,get,a\n10.1,set,b\nnot a request\n10.2,delete,c\n10.3,get,d\n")
+            .unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [general]
+            protocol = "memcache"
+            interval = 60
+            duration = 300
+            admin = "127.0.0.1:9090"
+            initial_seed = "0"
+
+            [debug]
+
+            [target]
+            endpoints = ["127.0.0.1:12321"]
+
+            [workload]
+            threads = 1
+            corrected_latency = true
+
+            [workload.ratelimit]
+
+            [workload.trace]
+            file = {:?}
+            speed = 2.0
+
+            [[workload.keyspace]]
+            nkeys = 1
+            klen = 8
+            vlen = 16
+            commands = [{{ verb = "get" }}]
+            "#,
+            trace.path().to_str().unwrap()
+        ))
+        .unwrap();
+
+        let generator = Generator::new(&config);
+        let mut rng = Xoshiro512PlusPlus::seed_from_u64(0);
+        let (sender, receiver) = async_channel::bounded(16);
+
+        let start = Instant::now();
+        replay(&config, &generator, &sender, &mut rng);
+
+        // the trace is replayed at twice the speed it was captured
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+
+        // the requests keep the order of the trace, skipping the line which
+        // could not be parsed, and were due at the scaled offsets
+        let mut first = None;
+        let requests: Vec<(String, u64)> = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|work_item| match work_item {
+                ClientWorkItem::Request {
+                    request, intended, ..
+                } => {
+                    let intended = intended.unwrap();
+                    let offset = intended - *first.get_or_insert(intended);
+                    let (verb, key) = match request {
+                        ClientRequest::Get(r) => ("get", r.key),
+                        ClientRequest::Set(r) => ("set", r.key),
+                        ClientRequest::Delete(r) => ("delete", r.key),
+                        _ => panic!("unexpected request"),
+                    };
+                    (
+                        format!("{verb} {}", std::str::from_utf8(&key).unwrap()),
+                        (offset.as_secs_f64() * 1000.0).round() as u64,
+                    )
+                }
+                ClientWorkItem::Reconnect => panic!("unexpected reconnect"),
+            })
+            .collect();
+
+        assert_eq!(
+            requests,
+            [
+                ("get a".to_string(), 0),
+                ("set b".to_string(), 50),
+                ("delete c".to_string(), 100),
+                ("get d".to_string(), 150),
+            ]
+        );
+    }
+}
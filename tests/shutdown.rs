@@ -0,0 +1,115 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// answers each `PING` with a `PONG` until the client disconnects
+fn ping_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            std::thread::spawn(move || serve(stream));
+        }
+    });
+
+    port
+}
+
+fn serve(mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+
+    while reader.read_line(&mut line).is_ok_and(|len| len > 0) {
+        if stream.write_all(b"PONG\r\n").is_err() {
+            return;
+        }
+        line.clear();
+    }
+}
+
+#[test]
+fn sigint_writes_summary() {
+    let port = ping_server();
+    let dir = tempfile::tempdir().unwrap();
+    let summary = dir.path().join("summary.json");
+
+    let config = dir.path().join("ping.toml");
+    std::fs::write(
+        &config,
+        format!(
+            r#"
+            [general]
+            protocol = "ping"
+            interval = 1
+            duration = 300
+            admin = "127.0.0.1:0"
+            summary_output = "{}"
+
+            [debug]
+            log_level = "info"
+
+            [target]
+            endpoints = ["127.0.0.1:{port}"]
+
+            [client]
+            threads = 1
+            poolsize = 1
+            connect_timeout = 1000
+            request_timeout = 1000
+
+            [workload]
+            threads = 1
+
+            [workload.ratelimit]
+            start = 100
+
+            [[workload.keyspace]]
+            commands = [{{ verb = "ping", weight = 100 }}]
+            "#,
+            summary.display()
+        ),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rpc-perf"))
+        .arg(&config)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_secs(2));
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+
+    // the test ends long before its duration once the outstanding requests
+    // have completed
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+
+        if start.elapsed() > Duration::from_secs(30) {
+            child.kill().unwrap();
+            panic!("rpc-perf did not exit after SIGINT");
+        }
+
+        std::thread::sleep(Duration::from_millis(100));
+    };
+
+    assert!(status.success(), "rpc-perf exited with: {status}");
+
+    let summary: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&summary).unwrap()).unwrap();
+
+    let requests = summary["requests"].as_u64().unwrap();
+    assert!(requests > 0);
+    assert_eq!(summary["responses"].as_u64(), Some(requests));
+    assert!(summary["duration"].as_f64().unwrap() < 30.0);
+}